@@ -0,0 +1,109 @@
+//! Adapter for the bundled mock lending program. Targets are the pool, the pool
+//! vault and the strategy's position in the pool.
+
+use anchor_lang::prelude::*;
+use mock_lending::cpi::accounts::{Deposit, OpenPosition, Withdraw};
+use mock_lending::{Pool, Position};
+
+use super::{AdapterAccounts, AdapterError};
+use crate::state::strategy::Strategy;
+
+/// Expected targets for a strategy lending into `pool`
+pub fn targets(pool: &Pubkey, strategy: &Pubkey) -> [Pubkey; Strategy::MAX_ADAPTER_TARGETS] {
+    let (pool_vault, _) =
+        Pubkey::find_program_address(&[b"pool_vault", pool.as_ref()], &mock_lending::ID);
+    let (position, _) = Pubkey::find_program_address(
+        &[b"position", pool.as_ref(), strategy.as_ref()],
+        &mock_lending::ID,
+    );
+    [*pool, pool_vault, position]
+}
+
+pub(super) fn open_position<'info>(
+    strategy: &Account<'info, Strategy>,
+    targets: &[AccountInfo<'info>],
+    signer_seeds: &[&[u8]],
+    payer: AccountInfo<'info>,
+    system_program: AccountInfo<'info>,
+) -> Result<()> {
+    // Re-attaching a pool the strategy already lent to reuses its position
+    if !targets[3].data_is_empty() {
+        return Ok(());
+    }
+
+    let seeds: &[&[&[u8]]] = &[signer_seeds];
+    let cpi_ctx = CpiContext::new_with_signer(
+        program(targets)?,
+        OpenPosition {
+            payer,
+            owner: strategy.to_account_info(),
+            pool: targets[1].clone(),
+            position: targets[3].clone(),
+            system_program,
+        },
+        seeds,
+    );
+    mock_lending::cpi::open_position(cpi_ctx)
+}
+
+pub(super) fn deposit<'info>(
+    strategy: &Account<'info, Strategy>,
+    accounts: &AdapterAccounts<'_, 'info>,
+    targets: &[AccountInfo<'info>],
+    signer_seeds: &[&[u8]],
+    amount: u64,
+) -> Result<()> {
+    let seeds: &[&[&[u8]]] = &[signer_seeds];
+    let cpi_ctx = CpiContext::new_with_signer(
+        program(targets)?,
+        Deposit {
+            owner: strategy.to_account_info(),
+            pool: targets[1].clone(),
+            pool_vault: targets[2].clone(),
+            position: targets[3].clone(),
+            mint: accounts.mint.clone(),
+            source: accounts.vault.clone(),
+            token_program: accounts.token_program.clone(),
+        },
+        seeds,
+    );
+    mock_lending::cpi::deposit(cpi_ctx, amount)
+}
+
+pub(super) fn withdraw<'info>(
+    strategy: &Account<'info, Strategy>,
+    accounts: &AdapterAccounts<'_, 'info>,
+    targets: &[AccountInfo<'info>],
+    signer_seeds: &[&[u8]],
+    destination: AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    let seeds: &[&[&[u8]]] = &[signer_seeds];
+    let cpi_ctx = CpiContext::new_with_signer(
+        program(targets)?,
+        Withdraw {
+            owner: strategy.to_account_info(),
+            pool: targets[1].clone(),
+            pool_vault: targets[2].clone(),
+            position: targets[3].clone(),
+            mint: accounts.mint.clone(),
+            destination,
+            token_program: accounts.token_program.clone(),
+        },
+        seeds,
+    );
+    mock_lending::cpi::withdraw(cpi_ctx, amount)
+}
+
+pub(super) fn balance(targets: &[AccountInfo]) -> Result<u64> {
+    require_keys_eq!(*targets[1].owner, mock_lending::ID, AdapterError::WrongAdapterAccount);
+    require_keys_eq!(*targets[3].owner, mock_lending::ID, AdapterError::WrongAdapterAccount);
+    let pool = Pool::try_deserialize(&mut &targets[1].try_borrow_data()?[..])?;
+    let position = Position::try_deserialize(&mut &targets[3].try_borrow_data()?[..])?;
+    Ok(position.balance_at(pool.rate_bps, Clock::get()?.unix_timestamp))
+}
+
+fn program<'info>(targets: &[AccountInfo<'info>]) -> Result<AccountInfo<'info>> {
+    require_keys_eq!(targets[0].key(), mock_lending::ID, AdapterError::WrongAdapterProgram);
+    Ok(targets[0].clone())
+}
@@ -0,0 +1,336 @@
+//! Yield-source adapters. A strategy names an adapter kind, the external program
+//! and its target accounts; deposits are deployed into that program and pulled
+//! back on withdrawal, while anything the source earns above the deployed
+//! principal is harvested into the strategy's reward reserve.
+//!
+//! Instructions pass the adapter accounts as remaining accounts: the adapter
+//! program first, then the strategy's `adapter_targets` in order.
+//!
+//! Principal queued for withdrawal is owed to its ticket holders: it is kept in
+//! the vault rather than deployed, and no other exit counts it as liquidity.
+
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::TokenAccount;
+
+use crate::state::strategy::Strategy;
+
+pub mod mock_lending;
+
+/// Accounts shared by every adapter call
+pub struct AdapterAccounts<'a, 'info> {
+    /// Strategy-owned underlying token account funds are deployed from and pulled back
+    /// into, the strategy vault or, when harvesting, the reward reserve
+    pub vault: AccountInfo<'info>,
+    /// Underlying token mint
+    pub mint: AccountInfo<'info>,
+    /// Token program of the underlying mint
+    pub token_program: AccountInfo<'info>,
+    /// Adapter program followed by the strategy's target accounts
+    pub remaining_accounts: &'a [AccountInfo<'info>],
+}
+
+/// Set up the strategy's position in the yield source when an adapter is attached
+pub fn initialize<'info>(
+    strategy: &Account<'info, Strategy>,
+    remaining_accounts: &[AccountInfo<'info>],
+    signer_seeds: &[&[u8]],
+    payer: AccountInfo<'info>,
+    system_program: AccountInfo<'info>,
+) -> Result<()> {
+    match strategy.adapter_kind {
+        Strategy::ADAPTER_NONE => Ok(()),
+        Strategy::ADAPTER_MOCK_LENDING => {
+            let targets = target_accounts(strategy, remaining_accounts)?;
+            mock_lending::open_position(strategy, targets, signer_seeds, payer, system_program)
+        }
+        _ => err!(AdapterError::UnknownAdapter),
+    }
+}
+
+/// Deploy up to `amount` from the strategy vault into the yield source, keeping
+/// enough in the vault to cover queued withdrawals
+pub fn deposit<'info>(
+    strategy: &mut Account<'info, Strategy>,
+    accounts: &AdapterAccounts<'_, 'info>,
+    signer_seeds: &[&[u8]],
+    amount: u64,
+) -> Result<()> {
+    if !strategy.has_adapter() {
+        return Ok(());
+    }
+
+    let amount = amount.min(free_balance(strategy, vault_balance(&accounts.vault)?));
+    if amount == 0 {
+        return Ok(());
+    }
+
+    match strategy.adapter_kind {
+        Strategy::ADAPTER_MOCK_LENDING => {
+            let targets = target_accounts(strategy, accounts.remaining_accounts)?;
+            mock_lending::deposit(strategy, accounts, targets, signer_seeds, amount)?;
+        }
+        _ => return err!(AdapterError::UnknownAdapter),
+    }
+
+    strategy.adapter_deposits += amount;
+    Ok(())
+}
+
+/// Make sure the strategy vault holds `amount` on top of the queued withdrawals,
+/// pulling the shortfall out of the yield source. Whatever leaves the source is
+/// taken off the deployed principal.
+pub fn withdraw_to_vault<'info>(
+    strategy: &mut Account<'info, Strategy>,
+    accounts: &AdapterAccounts<'_, 'info>,
+    signer_seeds: &[&[u8]],
+    vault_balance: u64,
+    amount: u64,
+) -> Result<()> {
+    let shortfall = amount.saturating_sub(free_balance(strategy, vault_balance));
+    if shortfall == 0 || !strategy.has_adapter() {
+        return Ok(());
+    }
+
+    withdraw(strategy, accounts, signer_seeds, accounts.vault.clone(), shortfall)?;
+    strategy.adapter_deposits = strategy.adapter_deposits.saturating_sub(shortfall);
+    Ok(())
+}
+
+/// Current value of the strategy's position in the yield source, accrued yield included
+pub fn balance(strategy: &Account<Strategy>, remaining_accounts: &[AccountInfo]) -> Result<u64> {
+    match strategy.adapter_kind {
+        Strategy::ADAPTER_NONE => Ok(0),
+        Strategy::ADAPTER_MOCK_LENDING => {
+            let targets = target_accounts(strategy, remaining_accounts)?;
+            mock_lending::balance(targets)
+        }
+        _ => err!(AdapterError::UnknownAdapter),
+    }
+}
+
+/// Move `amount` out of the yield source into `destination`
+pub fn withdraw<'info>(
+    strategy: &Account<'info, Strategy>,
+    accounts: &AdapterAccounts<'_, 'info>,
+    signer_seeds: &[&[u8]],
+    destination: AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    match strategy.adapter_kind {
+        Strategy::ADAPTER_NONE => err!(AdapterError::NoAdapter),
+        Strategy::ADAPTER_MOCK_LENDING => {
+            let targets = target_accounts(strategy, accounts.remaining_accounts)?;
+            mock_lending::withdraw(strategy, accounts, targets, signer_seeds, destination, amount)
+        }
+        _ => err!(AdapterError::UnknownAdapter),
+    }
+}
+
+/// Part of the vault balance not reserved for queued withdrawals
+fn free_balance(strategy: &Strategy, vault_balance: u64) -> u64 {
+    vault_balance.saturating_sub(strategy.pending_withdrawals)
+}
+
+/// Balance of the vault as of now, CPIs earlier in the instruction included
+fn vault_balance(vault: &AccountInfo) -> Result<u64> {
+    Ok(TokenAccount::try_deserialize(&mut &vault.try_borrow_data()?[..])?.amount)
+}
+
+/// Check the adapter program and target accounts passed by the caller against the strategy
+fn target_accounts<'a, 'info>(
+    strategy: &Strategy,
+    remaining_accounts: &'a [AccountInfo<'info>],
+) -> Result<&'a [AccountInfo<'info>]> {
+    let count = 1 + Strategy::MAX_ADAPTER_TARGETS;
+    require!(remaining_accounts.len() >= count, AdapterError::MissingAdapterAccounts);
+
+    let accounts = &remaining_accounts[..count];
+    require_keys_eq!(accounts[0].key(), strategy.adapter_program, AdapterError::WrongAdapterAccount);
+    for (account, target) in accounts[1..].iter().zip(strategy.adapter_targets.iter()) {
+        require_keys_eq!(account.key(), *target, AdapterError::WrongAdapterAccount);
+    }
+
+    Ok(accounts)
+}
+
+#[error_code]
+pub enum AdapterError {
+    #[msg("Unknown yield source adapter")]
+    UnknownAdapter,
+    #[msg("Strategy has no yield source adapter")]
+    NoAdapter,
+    #[msg("Yield source adapter accounts are missing")]
+    MissingAdapterAccounts,
+    #[msg("Yield source adapter account does not match the strategy")]
+    WrongAdapterAccount,
+    #[msg("Yield source adapter program is not supported")]
+    WrongAdapterProgram,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_utils::{LendingStrategy, balance, program_info, set_clock, signer_info};
+
+    const YEAR: i64 = 365 * 24 * 60 * 60;
+
+    fn lending(vault: u64, deployed: u64, pending_withdrawals: u64) -> LendingStrategy {
+        let mut lending = LendingStrategy::new(vault, deployed, 0);
+        lending.strategy.pending_withdrawals = pending_withdrawals;
+        lending
+    }
+
+    impl LendingStrategy {
+        fn deposit(&mut self, amount: u64) -> Result<()> {
+            let seeds = self.signer_seeds();
+            let seeds = seeds.iter().map(Vec::as_slice).collect::<Vec<_>>();
+            let accounts = self.adapter_accounts();
+            deposit(&mut self.strategy, &accounts, &seeds, amount)
+        }
+
+        fn withdraw_to_vault(&mut self, amount: u64) -> Result<()> {
+            let seeds = self.signer_seeds();
+            let seeds = seeds.iter().map(Vec::as_slice).collect::<Vec<_>>();
+            let accounts = self.adapter_accounts();
+            let vault_balance = balance(&self.vault);
+            withdraw_to_vault(&mut self.strategy, &accounts, &seeds, vault_balance, amount)
+        }
+
+        fn deployed(&self) -> u64 {
+            balance(&self.pool_vault)
+        }
+    }
+
+    #[test]
+    fn deposits_are_deployed() {
+        let mut lending = lending(500, 0, 0);
+
+        lending.deposit(500).unwrap();
+
+        assert_eq!(balance(&lending.vault), 0);
+        assert_eq!(lending.deployed(), 500);
+        assert_eq!(lending.strategy.adapter_deposits, 500);
+    }
+
+    #[test]
+    fn deposits_top_up_the_queued_withdrawals_first() {
+        // 200 idle plus a 500 deposit, with 600 queued for withdrawal
+        let mut lending = lending(700, 1_000, 600);
+
+        lending.deposit(500).unwrap();
+
+        assert_eq!(balance(&lending.vault), 600);
+        assert_eq!(lending.deployed(), 1_100);
+        assert_eq!(lending.strategy.adapter_deposits, 1_100);
+    }
+
+    #[test]
+    fn deposits_stay_in_the_vault_while_the_queue_is_short() {
+        let mut lending = lending(500, 1_000, 600);
+
+        lending.deposit(500).unwrap();
+
+        assert_eq!(balance(&lending.vault), 500);
+        assert_eq!(lending.strategy.adapter_deposits, 1_000);
+    }
+
+    #[test]
+    fn withdrawals_leave_queued_principal_in_the_vault() {
+        let mut lending = lending(600, 1_000, 600);
+
+        lending.withdraw_to_vault(300).unwrap();
+
+        assert_eq!(balance(&lending.vault), 900);
+        assert_eq!(lending.deployed(), 700);
+        assert_eq!(lending.strategy.adapter_deposits, 700);
+    }
+
+    #[test]
+    fn completed_tickets_are_paid_from_their_reserve() {
+        // The ticket's amount leaves the queue before its payout is pulled
+        let mut lending = lending(600, 1_000, 0);
+
+        lending.withdraw_to_vault(600).unwrap();
+
+        assert_eq!(balance(&lending.vault), 600);
+        assert_eq!(lending.deployed(), 1_000);
+    }
+
+    #[test]
+    fn target_accounts_follow_the_adapter_program() {
+        let lending = lending(0, 1_000, 0);
+
+        assert_eq!(target_accounts(&lending.strategy, lending.remaining_accounts).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn target_accounts_must_all_be_passed() {
+        let lending = lending(0, 1_000, 0);
+
+        assert_eq!(
+            target_accounts(&lending.strategy, &lending.remaining_accounts[..3]).unwrap_err(),
+            AdapterError::MissingAdapterAccounts.into()
+        );
+    }
+
+    #[test]
+    fn target_accounts_must_match_the_strategy() {
+        let lending = lending(0, 1_000, 0);
+
+        let mut wrong_program = lending.remaining_accounts.to_vec();
+        wrong_program[0] = program_info(Pubkey::new_unique()).clone();
+        assert_eq!(
+            target_accounts(&lending.strategy, &wrong_program).unwrap_err(),
+            AdapterError::WrongAdapterAccount.into()
+        );
+
+        let mut wrong_position = lending.remaining_accounts.to_vec();
+        wrong_position[3] = signer_info(Pubkey::new_unique()).clone();
+        assert_eq!(
+            target_accounts(&lending.strategy, &wrong_position).unwrap_err(),
+            AdapterError::WrongAdapterAccount.into()
+        );
+    }
+
+    #[test]
+    fn withdrawals_pull_the_vault_shortfall() {
+        let mut lending = lending(100, 1_000, 0);
+
+        lending.withdraw_to_vault(400).unwrap();
+
+        assert_eq!(balance(&lending.vault), 400);
+        assert_eq!(lending.deployed(), 700);
+        assert_eq!(lending.strategy.adapter_deposits, 700);
+    }
+
+    #[test]
+    fn withdrawals_the_vault_covers_stay_out_of_the_source() {
+        let mut lending = lending(500, 1_000, 0);
+
+        lending.withdraw_to_vault(400).unwrap();
+
+        assert_eq!(balance(&lending.vault), 500);
+        assert_eq!(lending.deployed(), 1_000);
+    }
+
+    #[test]
+    fn strategies_without_adapter_never_pull() {
+        let mut lending = lending(100, 1_000, 0);
+        lending.strategy.adapter_kind = Strategy::ADAPTER_NONE;
+
+        lending.withdraw_to_vault(400).unwrap();
+        lending.deposit(100).unwrap();
+
+        assert_eq!(balance(&lending.vault), 100);
+        assert_eq!(lending.strategy.adapter_deposits, 1_000);
+    }
+
+    #[test]
+    fn source_balance_includes_accrued_yield() {
+        let lending = LendingStrategy::new(0, 1_000_000, 1000);
+
+        set_clock(YEAR);
+        assert_eq!(super::balance(&lending.strategy, lending.remaining_accounts).unwrap(), 1_100_000);
+    }
+}
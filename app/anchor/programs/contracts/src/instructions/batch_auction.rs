@@ -0,0 +1,1146 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked};
+
+use crate::instructions::{candles::record_candles, token_fees::{amount_with_fee, amount_with_fees}};
+use crate::state::{candles::CandleSeries, marketplace::{Marketplace, TradeOrder}};
+
+/// Maximum number of orders settled by a single `clear_auction`, and so the most
+/// orders an auction-mode marketplace will hold open. Its 12 fixed accounts plus 4
+/// per order fit a legacy transaction (1232 bytes) without lookup tables, even with
+/// separate token programs for the two mints and a compute budget instruction.
+pub const MAX_AUCTION_ORDERS: usize = 4;
+
+/// Accounts passed per order in `clear_auction` remaining accounts:
+/// order, order escrow, owner's yield token account, owner's quote token account
+pub const AUCTION_ACCOUNTS_PER_ORDER: usize = 4;
+
+/// Accounts following a full book's open orders when a new order evicts one: the
+/// evicted order's escrow, escrow mint, token program and its owner's token account
+pub const EVICTION_ACCOUNTS: usize = 4;
+
+#[derive(Accounts)]
+pub struct SetAuctionMode<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = admin @ AuctionError::Unauthorized
+    )]
+    pub marketplace: Box<Account<'info, Marketplace>>,
+
+    #[account(
+        address = marketplace.yield_token_mint @ AuctionError::WrongMint
+    )]
+    pub yield_token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        address = marketplace.quote_mint @ AuctionError::WrongMint
+    )]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+
+    /// Pool collecting yield tokens from sell orders during clearing
+    #[account(
+        init_if_needed,
+        payer = admin,
+        seeds = [b"auction_yield_vault", marketplace.key().as_ref()],
+        bump,
+        token::mint = yield_token_mint,
+        token::authority = marketplace,
+        token::token_program = yield_token_program,
+    )]
+    pub auction_yield_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Pool collecting quote tokens from buy orders during clearing
+    #[account(
+        init_if_needed,
+        payer = admin,
+        seeds = [b"auction_quote_vault", marketplace.key().as_ref()],
+        bump,
+        token::mint = quote_mint,
+        token::authority = marketplace,
+        token::token_program = token_program,
+    )]
+    pub auction_quote_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Token program of the quote mint
+    pub token_program: Interface<'info, TokenInterface>,
+    /// Token program of the yield token mint
+    pub yield_token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct ClearAuction<'info> {
+    /// Anyone can clear an auction once its epoch has ended
+    pub cranker: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"marketplace", marketplace.strategy.as_ref(), marketplace.quote_mint.as_ref()],
+        bump,
+        constraint = marketplace.is_active @ AuctionError::MarketplaceNotActive,
+        constraint = marketplace.is_auction_mode() @ AuctionError::AuctionModeDisabled
+    )]
+    pub marketplace: Box<Account<'info, Marketplace>>,
+
+    #[account(
+        mut,
+        seeds = [b"auction_yield_vault", marketplace.key().as_ref()],
+        bump
+    )]
+    pub auction_yield_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"auction_quote_vault", marketplace.key().as_ref()],
+        bump
+    )]
+    pub auction_quote_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Marketplace fee collection account (quote tokens owned by the marketplace admin)
+    #[account(
+        mut,
+        constraint = fee_collection_account.owner == marketplace.admin @ AuctionError::WrongTokenAccount,
+        constraint = fee_collection_account.mint == marketplace.quote_mint @ AuctionError::WrongTokenAccount
+    )]
+    pub fee_collection_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        address = marketplace.yield_token_mint @ AuctionError::WrongMint
+    )]
+    pub yield_token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        address = marketplace.quote_mint @ AuctionError::WrongMint
+    )]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+
+    /// One-minute candle series of the marketplace
+    #[account(
+        mut,
+        seeds = [b"candles", marketplace.key().as_ref(), CandleSeries::MINUTE.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub minute_candles: AccountLoader<'info, CandleSeries>,
+
+    /// One-hour candle series of the marketplace
+    #[account(
+        mut,
+        seeds = [b"candles", marketplace.key().as_ref(), CandleSeries::HOUR.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub hour_candles: AccountLoader<'info, CandleSeries>,
+
+    /// One-day candle series of the marketplace
+    #[account(
+        mut,
+        seeds = [b"candles", marketplace.key().as_ref(), CandleSeries::DAY.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub day_candles: AccountLoader<'info, CandleSeries>,
+
+    /// Token program of the quote mint
+    pub token_program: Interface<'info, TokenInterface>,
+    /// Token program of the yield token mint
+    pub yield_token_program: Interface<'info, TokenInterface>,
+}
+
+/// An order taking part in a batch auction clearing
+struct AuctionParticipant<'info> {
+    order: Account<'info, TradeOrder>,
+    order_bump: u8,
+    escrow: InterfaceAccount<'info, TokenAccount>,
+    yield_destination: InterfaceAccount<'info, TokenAccount>,
+    quote_destination: InterfaceAccount<'info, TokenAccount>,
+    fill: u64,
+}
+
+pub fn handle_set_auction_mode(
+    ctx: Context<SetAuctionMode>,
+    auction_interval: i64,
+) -> Result<()> {
+    require!(auction_interval >= 0, AuctionError::InvalidInterval);
+
+    let marketplace = &mut ctx.accounts.marketplace;
+    // Every open order takes part in clearing, so they must fit in one transaction
+    require!(
+        auction_interval == 0 || marketplace.open_orders <= MAX_AUCTION_ORDERS as u64,
+        AuctionError::TooManyOrders
+    );
+    marketplace.auction_interval = auction_interval;
+    marketplace.auction_epoch_start = Clock::get()?.unix_timestamp;
+
+    if marketplace.is_auction_mode() {
+        msg!(
+            "Marketplace {} switched to batch auctions every {} seconds",
+            marketplace.marketplace_id,
+            auction_interval
+        );
+    } else {
+        msg!(
+            "Marketplace {} switched to continuous matching",
+            marketplace.marketplace_id
+        );
+    }
+
+    Ok(())
+}
+
+pub fn handle_clear_auction<'info>(
+    ctx: Context<'_, '_, 'info, 'info, ClearAuction<'info>>,
+) -> Result<()> {
+    let current_time = Clock::get()?.unix_timestamp;
+    let epoch_end = ctx.accounts.marketplace.auction_epoch_end();
+    require!(current_time >= epoch_end, AuctionError::AuctionStillOpen);
+
+    let remaining_accounts = ctx.remaining_accounts;
+    let order_count = remaining_accounts.len() / AUCTION_ACCOUNTS_PER_ORDER;
+    require!(
+        order_count * AUCTION_ACCOUNTS_PER_ORDER == remaining_accounts.len(),
+        AuctionError::InvalidRemainingAccounts
+    );
+    require!(order_count <= MAX_AUCTION_ORDERS, AuctionError::TooManyOrders);
+
+    let marketplace_key = ctx.accounts.marketplace.key();
+    let yield_token_mint = ctx.accounts.marketplace.yield_token_mint;
+    let quote_mint = ctx.accounts.marketplace.quote_mint;
+
+    // Load and validate every order collected during the epoch
+    let mut participants: Vec<AuctionParticipant<'info>> = Vec::new();
+    let mut seen_orders: Vec<Pubkey> = Vec::with_capacity(order_count);
+    let mut active_orders: u64 = 0;
+    for accounts in remaining_accounts.chunks(AUCTION_ACCOUNTS_PER_ORDER) {
+        let order = Account::<TradeOrder>::try_from(&accounts[0])?;
+        require_keys_eq!(order.marketplace, marketplace_key, AuctionError::OrderMarketplaceMismatch);
+        require!(!seen_orders.contains(&order.key()), AuctionError::DuplicateOrder);
+        seen_orders.push(order.key());
+        if order.is_active {
+            active_orders += 1;
+        }
+
+        let order_id_bytes = order.order_id.to_le_bytes();
+        let (order_address, order_bump) = Pubkey::find_program_address(
+            &[b"order", order.user.as_ref(), order_id_bytes.as_ref()],
+            ctx.program_id,
+        );
+        require_keys_eq!(order_address, order.key(), AuctionError::InvalidOrderAccount);
+
+        let (escrow_address, _) = Pubkey::find_program_address(
+            &[b"escrow", order.key().as_ref()],
+            ctx.program_id,
+        );
+        require_keys_eq!(escrow_address, accounts[1].key(), AuctionError::WrongTokenAccount);
+        let escrow = InterfaceAccount::<TokenAccount>::try_from(&accounts[1])?;
+
+        let yield_destination = InterfaceAccount::<TokenAccount>::try_from(&accounts[2])?;
+        require!(
+            yield_destination.owner == order.user && yield_destination.mint == yield_token_mint,
+            AuctionError::WrongTokenAccount
+        );
+
+        let quote_destination = InterfaceAccount::<TokenAccount>::try_from(&accounts[3])?;
+        require!(
+            quote_destination.owner == order.user && quote_destination.mint == quote_mint,
+            AuctionError::WrongTokenAccount
+        );
+
+        // Orders placed after the epoch closed wait for the next auction
+        if !order.is_fillable() || order.created_at >= epoch_end {
+            continue;
+        }
+
+        participants.push(AuctionParticipant {
+            order,
+            order_bump,
+            escrow,
+            yield_destination,
+            quote_destination,
+            fill: 0,
+        });
+    }
+
+    // The cranker may not pick which orders clear: every open order must be passed,
+    // including those that arrived too late for this epoch
+    require!(
+        active_orders == ctx.accounts.marketplace.open_orders,
+        AuctionError::MissingOrders
+    );
+
+    let clearing = compute_clearing_price(&participants);
+
+    let marketplace = &mut ctx.accounts.marketplace;
+    marketplace.auction_epoch_start = current_time;
+
+    let Some((clearing_price, volume)) = clearing else {
+        msg!(
+            "Auction epoch {} closed without crossing orders",
+            marketplace.auction_epoch
+        );
+        marketplace.auction_epoch += 1;
+        return Ok(());
+    };
+
+    allocate_fills(&mut participants, clearing_price, volume);
+
+    let yield_vault = ctx.accounts.auction_yield_vault.to_account_info();
+    let quote_vault = ctx.accounts.auction_quote_vault.to_account_info();
+    let yield_side = (&ctx.accounts.yield_token_mint, ctx.accounts.yield_token_program.to_account_info());
+    let quote_side = (&ctx.accounts.quote_mint, ctx.accounts.token_program.to_account_info());
+
+    let yield_vault_before = ctx.accounts.auction_yield_vault.amount;
+    let quote_vault_before = ctx.accounts.auction_quote_vault.amount;
+
+    // Collect yield tokens from sellers and payment from buyers into the auction vaults,
+    // grossed up for the fee on the way in and on the way out to the counterparty
+    let mut total_payment: u64 = 0;
+    for participant in participants.iter_mut().filter(|p| p.fill > 0) {
+        let order_id_bytes = participant.order.order_id.to_le_bytes();
+        let order_seeds = &[
+            b"order",
+            participant.order.user.as_ref(),
+            order_id_bytes.as_ref(),
+            &[participant.order_bump],
+        ];
+        let signer_seeds = &[order_seeds.as_slice()];
+
+        let (vault, (mint, token_program), amount) = if participant.order.order_type == TradeOrder::SELL_ORDER {
+            let amount = amount_with_fees(&ctx.accounts.yield_token_mint, participant.fill, 2)?;
+            (yield_vault.clone(), yield_side.clone(), amount.min(participant.escrow.amount))
+        } else {
+            // Buyers pay the clearing price, rounded up but never above what they escrowed
+            let payment = (participant.fill as u128 * clearing_price as u128).div_ceil(1_000_000) as u64;
+            let payment = payment.min(participant.escrow.amount);
+            total_payment += payment;
+            let amount = amount_with_fees(&ctx.accounts.quote_mint, payment, 2)?;
+            (quote_vault.clone(), quote_side.clone(), amount.min(participant.escrow.amount))
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            token_program,
+            TransferChecked {
+                from: participant.escrow.to_account_info(),
+                mint: mint.to_account_info(),
+                to: vault,
+                authority: participant.order.to_account_info(),
+            },
+            signer_seeds,
+        );
+        transfer_checked(cpi_ctx, amount, mint.decimals)?;
+        participant.escrow.reload()?;
+    }
+
+    // Pay out only what actually reached the vaults, orders escrowed before the marketplace
+    // switched to auctions were not grossed up for the extra hop
+    ctx.accounts.auction_yield_vault.reload()?;
+    ctx.accounts.auction_quote_vault.reload()?;
+    let mut yield_available = ctx.accounts.auction_yield_vault.amount - yield_vault_before;
+    let mut quote_available = ctx.accounts.auction_quote_vault.amount - quote_vault_before;
+
+    let marketplace_seeds = &[
+        b"marketplace",
+        marketplace.strategy.as_ref(),
+        marketplace.quote_mint.as_ref(),
+        &[ctx.bumps.marketplace],
+    ];
+    let marketplace_signer_seeds = &[marketplace_seeds.as_slice()];
+
+    // Pay out: buyers get yield tokens, sellers get their pro-rata share of the payments less fees,
+    // each grossed up for the transfer fee while the vault still covers it
+    for participant in participants.iter().filter(|p| p.fill > 0) {
+        let (vault, destination, (mint, token_program), amount) = if participant.order.order_type == TradeOrder::BUY_ORDER {
+            let amount = amount_with_fee(&ctx.accounts.yield_token_mint, participant.fill)?.min(yield_available);
+            yield_available -= amount;
+            (yield_vault.clone(), participant.yield_destination.to_account_info(), yield_side.clone(), amount)
+        } else {
+            let gross = (total_payment as u128 * participant.fill as u128 / volume as u128) as u64;
+            let fee_amount = (gross as u128 * marketplace.trading_fee_bps as u128 / 10000) as u64;
+            let net_payment = gross - fee_amount;
+            let amount = amount_with_fee(&ctx.accounts.quote_mint, net_payment)?.min(quote_available);
+            quote_available -= amount;
+            (quote_vault.clone(), participant.quote_destination.to_account_info(), quote_side.clone(), amount)
+        };
+
+        if amount > 0 {
+            let cpi_ctx = CpiContext::new_with_signer(
+                token_program,
+                TransferChecked {
+                    from: vault,
+                    mint: mint.to_account_info(),
+                    to: destination,
+                    authority: marketplace.to_account_info(),
+                },
+                marketplace_signer_seeds,
+            );
+            transfer_checked(cpi_ctx, amount, mint.decimals)?;
+        }
+    }
+
+    // Fees and rounding dust go to the marketplace fee account, yield token dust stays
+    // in the vault and is not credited to later auctions
+    let fee_total = quote_available;
+    if fee_total > 0 {
+        let cpi_ctx = CpiContext::new_with_signer(
+            quote_side.1.clone(),
+            TransferChecked {
+                from: quote_vault.clone(),
+                mint: ctx.accounts.quote_mint.to_account_info(),
+                to: ctx.accounts.fee_collection_account.to_account_info(),
+                authority: marketplace.to_account_info(),
+            },
+            marketplace_signer_seeds,
+        );
+        transfer_checked(cpi_ctx, fee_total, ctx.accounts.quote_mint.decimals)?;
+    }
+
+    // Update order states, refunding leftover escrow of completed orders
+    for participant in participants.iter_mut().filter(|p| p.fill > 0) {
+        let order = &mut participant.order;
+        order.filled_amount += participant.fill;
+
+        if order.filled_amount >= order.yield_token_amount {
+            order.is_active = false;
+            marketplace.open_orders -= 1;
+
+            let leftover = participant.escrow.amount;
+            if leftover > 0 {
+                let (destination, (mint, token_program)) = if order.order_type == TradeOrder::BUY_ORDER {
+                    (participant.quote_destination.to_account_info(), quote_side.clone())
+                } else {
+                    (participant.yield_destination.to_account_info(), yield_side.clone())
+                };
+                let order_id_bytes = order.order_id.to_le_bytes();
+                let order_seeds = &[
+                    b"order",
+                    order.user.as_ref(),
+                    order_id_bytes.as_ref(),
+                    &[participant.order_bump],
+                ];
+                let signer_seeds = &[order_seeds.as_slice()];
+
+                let cpi_ctx = CpiContext::new_with_signer(
+                    token_program,
+                    TransferChecked {
+                        from: participant.escrow.to_account_info(),
+                        mint: mint.to_account_info(),
+                        to: destination,
+                        authority: order.to_account_info(),
+                    },
+                    signer_seeds,
+                );
+                transfer_checked(cpi_ctx, leftover, mint.decimals)?;
+            }
+        }
+
+        order.exit(ctx.program_id)?;
+    }
+
+    // A cleared auction counts as a single uniform-price trade
+    marketplace.total_volume += total_payment;
+    marketplace.total_trades += 1;
+    marketplace.record_trade(clearing_price, current_time);
+    record_candles(
+        [&ctx.accounts.minute_candles, &ctx.accounts.hour_candles, &ctx.accounts.day_candles],
+        clearing_price,
+        volume,
+        current_time,
+    )?;
+
+    msg!(
+        "Auction epoch {} cleared: {} yield tokens at {} per token (Total: {}, Fees: {})",
+        marketplace.auction_epoch,
+        volume,
+        clearing_price as f64 / 1_000_000.0,
+        total_payment,
+        fee_total
+    );
+
+    marketplace.auction_epoch += 1;
+
+    Ok(())
+}
+
+/// Make room in a full auction book for an order of `order_type` at `price_per_token`.
+/// `remaining_accounts` hold every open order of the marketplace followed by the
+/// EVICTION_ACCOUNTS of the order to evict, whose escrow is refunded to its owner.
+pub(crate) fn evict_from_full_book<'info>(
+    program_id: &Pubkey,
+    marketplace: &mut Account<'_, Marketplace>,
+    remaining_accounts: &'info [AccountInfo<'info>],
+    order_type: u8,
+    price_per_token: u64,
+) -> Result<()> {
+    let book_size = marketplace.open_orders as usize;
+    require!(
+        remaining_accounts.len() == book_size + EVICTION_ACCOUNTS,
+        AuctionError::BookFull
+    );
+    let (order_accounts, eviction_accounts) = remaining_accounts.split_at(book_size);
+
+    // Distinct active orders of this marketplace, as many as it has open, are all of them
+    let mut book: Vec<Account<'info, TradeOrder>> = Vec::with_capacity(book_size);
+    for account in order_accounts {
+        let order = Account::<TradeOrder>::try_from(account)?;
+        require_keys_eq!(order.marketplace, marketplace.key(), AuctionError::OrderMarketplaceMismatch);
+        require!(order.is_active, AuctionError::MissingOrders);
+        require!(book.iter().all(|o| o.key() != order.key()), AuctionError::DuplicateOrder);
+        book.push(order);
+    }
+
+    let evicted_index = order_to_evict(&book, order_type, price_per_token).ok_or(AuctionError::NotCompetitive)?;
+    let mut evicted = book.swap_remove(evicted_index);
+
+    let order_id_bytes = evicted.order_id.to_le_bytes();
+    let (order_address, order_bump) = Pubkey::find_program_address(
+        &[b"order", evicted.user.as_ref(), order_id_bytes.as_ref()],
+        program_id,
+    );
+    require_keys_eq!(order_address, evicted.key(), AuctionError::InvalidOrderAccount);
+
+    let (escrow_address, _) = Pubkey::find_program_address(&[b"escrow", evicted.key().as_ref()], program_id);
+    require_keys_eq!(escrow_address, eviction_accounts[0].key(), AuctionError::WrongTokenAccount);
+    let escrow = InterfaceAccount::<TokenAccount>::try_from(&eviction_accounts[0])?;
+
+    let escrow_mint = InterfaceAccount::<Mint>::try_from(&eviction_accounts[1])?;
+    let side_mint = if evicted.order_type == TradeOrder::SELL_ORDER {
+        marketplace.yield_token_mint
+    } else {
+        marketplace.quote_mint
+    };
+    require!(
+        escrow_mint.key() == escrow.mint && escrow_mint.key() == side_mint,
+        AuctionError::WrongMint
+    );
+    let token_program = Interface::<TokenInterface>::try_from(&eviction_accounts[2])?;
+
+    let refund_account = InterfaceAccount::<TokenAccount>::try_from(&eviction_accounts[3])?;
+    require!(
+        refund_account.owner == evicted.user && refund_account.mint == escrow.mint,
+        AuctionError::WrongTokenAccount
+    );
+
+    // Nothing is owed out of an auction order's escrow between clearings
+    let refund = escrow.amount;
+    if refund > 0 {
+        let order_seeds = &[
+            b"order",
+            evicted.user.as_ref(),
+            order_id_bytes.as_ref(),
+            &[order_bump],
+        ];
+        let signer_seeds = &[order_seeds.as_slice()];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            TransferChecked {
+                from: escrow.to_account_info(),
+                mint: escrow_mint.to_account_info(),
+                to: refund_account.to_account_info(),
+                authority: evicted.to_account_info(),
+            },
+            signer_seeds,
+        );
+        transfer_checked(cpi_ctx, refund, escrow_mint.decimals)?;
+    }
+
+    evicted.is_active = false;
+    marketplace.open_orders -= 1;
+    if evicted.order_type == TradeOrder::BUY_ORDER && evicted.price_per_token == marketplace.best_bid_price {
+        marketplace.best_bid_price = 0; // Should recalculate from remaining orders
+    } else if evicted.order_type == TradeOrder::SELL_ORDER && evicted.price_per_token == marketplace.best_ask_price {
+        marketplace.best_ask_price = 0; // Should recalculate from remaining orders
+    }
+    evicted.exit(program_id)?;
+
+    msg!(
+        "Auction book full, order {} of {} evicted and refunded {} tokens",
+        evicted.order_id,
+        evicted.user,
+        refund
+    );
+
+    Ok(())
+}
+
+/// Index of the order a new order of `order_type` at `price_per_token` evicts from a
+/// full book, None when it doesn't get in. A side holding at least half of the book
+/// only takes orders beating its worst price, an order for the other side evicts that
+/// worst order, so non-crossing orders can't keep anyone out.
+fn order_to_evict(book: &[Account<TradeOrder>], order_type: u8, price_per_token: u64) -> Option<usize> {
+    let own_side = book.iter().filter(|o| o.order_type == order_type).count();
+    let larger_side = if own_side * 2 >= book.len() {
+        order_type
+    } else if order_type == TradeOrder::BUY_ORDER {
+        TradeOrder::SELL_ORDER
+    } else {
+        TradeOrder::BUY_ORDER
+    };
+
+    // Lowest bid or highest ask, the newest first among equal prices
+    let (index, worst) = book
+        .iter()
+        .enumerate()
+        .filter(|(_, o)| o.order_type == larger_side)
+        .max_by_key(|(_, o)| {
+            let worseness = if larger_side == TradeOrder::BUY_ORDER { u64::MAX - o.price_per_token } else { o.price_per_token };
+            (worseness, o.created_at)
+        })?;
+
+    let beats_worst = if order_type == TradeOrder::BUY_ORDER {
+        price_per_token > worst.price_per_token
+    } else {
+        price_per_token < worst.price_per_token
+    };
+    (larger_side != order_type || beats_worst).then_some(index)
+}
+
+/// Find the uniform price that maximises matched volume.
+/// Ties are broken by the smallest imbalance, then by market pressure:
+/// the highest tied price when demand exceeds supply, the lowest otherwise.
+/// Returns the clearing price and matched volume, or None if nothing crosses.
+fn compute_clearing_price(participants: &[AuctionParticipant]) -> Option<(u64, u64)> {
+    let mut best: Option<(u64, u64, u64, bool)> = None; // (price, volume, imbalance, excess demand)
+
+    for candidate in participants.iter().map(|p| p.order.price_per_token) {
+        let (demand, supply) = demand_and_supply(participants, candidate);
+        let volume = demand.min(supply);
+        if volume == 0 {
+            continue;
+        }
+        let imbalance = demand.abs_diff(supply);
+
+        let replace = match best {
+            None => true,
+            Some((price, best_volume, best_imbalance, excess_demand)) => {
+                volume > best_volume
+                    || (volume == best_volume && imbalance < best_imbalance)
+                    || (volume == best_volume
+                        && imbalance == best_imbalance
+                        && if excess_demand { candidate > price } else { candidate < price })
+            }
+        };
+        if replace {
+            best = Some((candidate, volume, imbalance, demand > supply));
+        }
+    }
+
+    best.map(|(price, volume, _, _)| (price, volume))
+}
+
+/// Total buy size willing to pay `price` and total sell size willing to accept it
+fn demand_and_supply(participants: &[AuctionParticipant], price: u64) -> (u64, u64) {
+    participants.iter().fold((0, 0), |(demand, supply), p| {
+        if p.order.order_type == TradeOrder::BUY_ORDER && p.order.price_per_token >= price {
+            (demand + p.order.remaining_amount(), supply)
+        } else if p.order.order_type == TradeOrder::SELL_ORDER && p.order.price_per_token <= price {
+            (demand, supply + p.order.remaining_amount())
+        } else {
+            (demand, supply)
+        }
+    })
+}
+
+/// Fill every crossing order pro-rata so each side trades exactly `volume`
+fn allocate_fills(participants: &mut [AuctionParticipant], clearing_price: u64, volume: u64) {
+    let (demand, supply) = demand_and_supply(participants, clearing_price);
+
+    for (side, side_total) in [(TradeOrder::BUY_ORDER, demand), (TradeOrder::SELL_ORDER, supply)] {
+        let crosses = |p: &AuctionParticipant| {
+            p.order.order_type == side
+                && if side == TradeOrder::BUY_ORDER {
+                    p.order.price_per_token >= clearing_price
+                } else {
+                    p.order.price_per_token <= clearing_price
+                }
+        };
+
+        let mut allocated: u64 = 0;
+        for participant in participants.iter_mut().filter(|p| crosses(p)) {
+            participant.fill = (participant.order.remaining_amount() as u128 * volume as u128
+                / side_total as u128) as u64;
+            allocated += participant.fill;
+        }
+
+        // Hand out rounding remainders in account order
+        let mut leftover = volume - allocated;
+        for participant in participants.iter_mut().filter(|p| crosses(p)) {
+            if leftover == 0 {
+                break;
+            }
+            let extra = (participant.order.remaining_amount() - participant.fill).min(leftover);
+            participant.fill += extra;
+            leftover -= extra;
+        }
+    }
+}
+
+#[error_code]
+pub enum AuctionError {
+    #[msg("Only the marketplace admin can perform this action")]
+    Unauthorized,
+    #[msg("Wrong mint for this marketplace")]
+    WrongMint,
+    #[msg("Invalid auction interval")]
+    InvalidInterval,
+    #[msg("Marketplace is not active")]
+    MarketplaceNotActive,
+    #[msg("Batch auction mode is not enabled")]
+    AuctionModeDisabled,
+    #[msg("Auction epoch has not ended yet")]
+    AuctionStillOpen,
+    #[msg("Remaining accounts must be (order, escrow, yield account, quote account) tuples")]
+    InvalidRemainingAccounts,
+    #[msg("Too many orders for a single auction clearing")]
+    TooManyOrders,
+    #[msg("Order marketplace mismatch")]
+    OrderMarketplaceMismatch,
+    #[msg("Order passed more than once")]
+    DuplicateOrder,
+    #[msg("Every open order of the marketplace must take part in the auction")]
+    MissingOrders,
+    #[msg("Invalid order account")]
+    InvalidOrderAccount,
+    #[msg("Wrong token account")]
+    WrongTokenAccount,
+    #[msg("Auction book is full, pass its open orders and the order to evict")]
+    BookFull,
+    #[msg("Order does not beat the worst order on its side of the full auction book")]
+    NotCompetitive,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use anchor_spl::token::spl_token;
+
+    use crate::test_utils::{
+        balance, candle_series, mint_account, program_account, program_account_at, program_info, set_clock,
+        signer_info, stub_syscalls, token_account, token_account_at, zeroed,
+    };
+
+    fn participant(order_type: u8, amount: u64, price: u64) -> AuctionParticipant<'static> {
+        let user = Pubkey::new_unique();
+        let mut order: TradeOrder = zeroed();
+        order.user = user;
+        order.order_type = order_type;
+        order.yield_token_amount = amount;
+        order.price_per_token = price;
+        order.is_active = true;
+        AuctionParticipant {
+            order: program_account(&order),
+            order_bump: 0,
+            escrow: token_account(Pubkey::default(), user, 0),
+            yield_destination: token_account(Pubkey::default(), user, 0),
+            quote_destination: token_account(Pubkey::default(), user, 0),
+            fill: 0,
+        }
+    }
+
+    fn buy(amount: u64, price: u64) -> AuctionParticipant<'static> {
+        participant(TradeOrder::BUY_ORDER, amount, price)
+    }
+
+    fn sell(amount: u64, price: u64) -> AuctionParticipant<'static> {
+        participant(TradeOrder::SELL_ORDER, amount, price)
+    }
+
+    #[test]
+    fn clears_at_the_price_matching_the_most_volume() {
+        let participants = [
+            buy(100, 1_200_000),
+            buy(50, 1_000_000),
+            sell(80, 900_000),
+            sell(60, 1_100_000),
+        ];
+
+        // 1.1 and 1.2 both match 100 with 40 left over on the sell side,
+        // excess supply settles the tie at the lower price
+        assert_eq!(compute_clearing_price(&participants), Some((1_100_000, 100)));
+    }
+
+    #[test]
+    fn excess_demand_settles_ties_at_the_higher_price() {
+        let participants = [buy(100, 1_200_000), sell(50, 1_000_000)];
+
+        assert_eq!(compute_clearing_price(&participants), Some((1_200_000, 50)));
+    }
+
+    #[test]
+    fn smaller_imbalance_wins_at_equal_volume() {
+        let participants = [
+            buy(100, 1_200_000),
+            buy(100, 1_000_000),
+            sell(100, 1_000_000),
+        ];
+
+        // Both prices match 100, only 1.2 leaves no unmatched demand
+        assert_eq!(compute_clearing_price(&participants), Some((1_200_000, 100)));
+    }
+
+    #[test]
+    fn nothing_clears_when_orders_do_not_cross() {
+        let participants = [buy(100, 900_000), sell(100, 1_000_000)];
+
+        assert_eq!(compute_clearing_price(&participants), None);
+    }
+
+    #[test]
+    fn demand_and_supply_count_only_the_unfilled_remainder() {
+        let mut participants = [buy(150, 1_000_000), sell(80, 1_000_000)];
+        participants[0].order.filled_amount = 50;
+
+        assert_eq!(demand_and_supply(&participants, 1_000_000), (100, 80));
+    }
+
+    #[test]
+    fn fills_crossing_orders_pro_rata_with_remainders_in_account_order() {
+        let mut participants = [
+            buy(100, 1_200_000),
+            buy(50, 1_100_000),
+            buy(40, 900_000),
+            sell(100, 1_000_000),
+            sell(30, 1_300_000),
+        ];
+
+        allocate_fills(&mut participants, 1_000_000, 100);
+
+        // 100 of 150 demand: 66.6 and 33.3 round down, the spare unit goes to the first buyer
+        let fills: Vec<u64> = participants.iter().map(|p| p.fill).collect();
+        assert_eq!(fills, [67, 33, 0, 100, 0]);
+    }
+
+    #[test]
+    fn both_sides_trade_exactly_the_cleared_volume() {
+        let mut participants = [
+            buy(70, 1_050_000),
+            buy(70, 1_000_000),
+            buy(70, 1_100_000),
+            sell(45, 950_000),
+            sell(45, 1_000_000),
+        ];
+
+        let (price, volume) = compute_clearing_price(&participants).unwrap();
+        allocate_fills(&mut participants, price, volume);
+
+        let filled = |side: u8| -> u64 {
+            participants.iter().filter(|p| p.order.order_type == side).map(|p| p.fill).sum()
+        };
+        assert_eq!(volume, 90);
+        assert_eq!(filled(TradeOrder::BUY_ORDER), volume);
+        assert_eq!(filled(TradeOrder::SELL_ORDER), volume);
+        assert!(participants.iter().all(|p| p.fill <= p.order.remaining_amount()));
+    }
+
+    /// Open order at its PDA
+    fn book_order(marketplace: Pubkey, order_type: u8, price: u64, created_at: i64) -> Account<'static, TradeOrder> {
+        let mut order: TradeOrder = zeroed();
+        order.user = Pubkey::new_unique();
+        order.marketplace = marketplace;
+        order.order_type = order_type;
+        order.yield_token_amount = 1_000;
+        order.price_per_token = price;
+        order.is_active = true;
+        order.created_at = created_at;
+        order.order_id = 1;
+        let (address, _) = Pubkey::find_program_address(
+            &[b"order", order.user.as_ref(), 1u64.to_le_bytes().as_ref()],
+            &crate::ID,
+        );
+        program_account_at(address, &order)
+    }
+
+    fn bid(price: u64) -> Account<'static, TradeOrder> {
+        book_order(Pubkey::default(), TradeOrder::BUY_ORDER, price, 0)
+    }
+
+    fn ask(price: u64) -> Account<'static, TradeOrder> {
+        book_order(Pubkey::default(), TradeOrder::SELL_ORDER, price, 0)
+    }
+
+    #[test]
+    fn orders_for_a_half_full_side_must_beat_its_worst_price() {
+        let book = [bid(900_000), bid(800_000), ask(1_100_000), ask(1_200_000)];
+
+        assert_eq!(order_to_evict(&book, TradeOrder::BUY_ORDER, 850_000), Some(1));
+        assert_eq!(order_to_evict(&book, TradeOrder::BUY_ORDER, 800_000), None);
+        assert_eq!(order_to_evict(&book, TradeOrder::SELL_ORDER, 1_150_000), Some(3));
+        assert_eq!(order_to_evict(&book, TradeOrder::SELL_ORDER, 1_200_000), None);
+    }
+
+    #[test]
+    fn one_sided_books_give_way_to_the_other_side() {
+        // Asks nobody will take can't keep buyers out
+        let book = [ask(5_000_000), ask(9_000_000), ask(7_000_000), ask(6_000_000)];
+        assert_eq!(order_to_evict(&book, TradeOrder::BUY_ORDER, 1), Some(1));
+
+        // Nor keep the smaller side from growing back to half
+        let book = [bid(100_000), ask(5_000_000), ask(9_000_000), ask(7_000_000)];
+        assert_eq!(order_to_evict(&book, TradeOrder::BUY_ORDER, 1), Some(2));
+        assert_eq!(order_to_evict(&book, TradeOrder::SELL_ORDER, 9_000_000), None);
+        assert_eq!(order_to_evict(&book, TradeOrder::SELL_ORDER, 8_000_000), Some(2));
+    }
+
+    #[test]
+    fn newest_order_goes_first_among_equal_prices() {
+        let book = [
+            book_order(Pubkey::default(), TradeOrder::BUY_ORDER, 800_000, 20),
+            book_order(Pubkey::default(), TradeOrder::BUY_ORDER, 800_000, 30),
+            book_order(Pubkey::default(), TradeOrder::BUY_ORDER, 800_000, 10),
+            ask(1_000_000),
+        ];
+
+        assert_eq!(order_to_evict(&book, TradeOrder::BUY_ORDER, 810_000), Some(1));
+    }
+
+    /// Full auction book of two bids and two asks, with remaining accounts evicting
+    /// the 0.8 bid, whose escrow holds 800 quote tokens
+    fn full_book() -> (Account<'static, Marketplace>, Vec<AccountInfo<'static>>) {
+        stub_syscalls();
+        let quote_mint = mint_account(Pubkey::new_unique(), 6);
+        let mut marketplace: Marketplace = zeroed();
+        marketplace.auction_interval = 60;
+        marketplace.quote_mint = quote_mint.key();
+        marketplace.yield_token_mint = Pubkey::new_unique();
+        marketplace.open_orders = MAX_AUCTION_ORDERS as u64;
+        marketplace.best_bid_price = 900_000;
+        let marketplace = program_account(&marketplace);
+
+        let book = [
+            book_order(marketplace.key(), TradeOrder::BUY_ORDER, 900_000, 0),
+            book_order(marketplace.key(), TradeOrder::BUY_ORDER, 800_000, 0),
+            book_order(marketplace.key(), TradeOrder::SELL_ORDER, 1_100_000, 0),
+            book_order(marketplace.key(), TradeOrder::SELL_ORDER, 1_200_000, 0),
+        ];
+        let evicted = &book[1];
+        let (escrow_address, _) = Pubkey::find_program_address(&[b"escrow", evicted.key().as_ref()], &crate::ID);
+        let escrow = token_account_at(escrow_address, quote_mint.key(), evicted.key(), 800);
+        let refund_account = token_account(quote_mint.key(), evicted.user, 0);
+
+        let mut accounts: Vec<AccountInfo<'static>> = book.iter().map(|o| o.to_account_info()).collect();
+        accounts.extend([
+            escrow.to_account_info(),
+            quote_mint.to_account_info(),
+            program_info(spl_token::ID).clone(),
+            refund_account.to_account_info(),
+        ]);
+        (marketplace, accounts)
+    }
+
+    #[test]
+    fn eviction_refunds_and_closes_the_worst_order() {
+        let (mut marketplace, accounts) = full_book();
+        let accounts = accounts.leak();
+
+        evict_from_full_book(&crate::ID, &mut marketplace, accounts, TradeOrder::BUY_ORDER, 850_000).unwrap();
+
+        let evicted = Account::<TradeOrder>::try_from(&accounts[1]).unwrap();
+        assert!(!evicted.is_active);
+        assert_eq!(marketplace.open_orders, MAX_AUCTION_ORDERS as u64 - 1);
+        let escrow = InterfaceAccount::<TokenAccount>::try_from(&accounts[4]).unwrap();
+        let refund_account = InterfaceAccount::<TokenAccount>::try_from(&accounts[7]).unwrap();
+        assert_eq!(balance(&escrow), 0);
+        assert_eq!(balance(&refund_account), 800);
+    }
+
+    #[test]
+    fn eviction_needs_the_whole_book() {
+        let (mut marketplace, accounts) = full_book();
+
+        // Nothing passed
+        assert_eq!(
+            evict_from_full_book(&crate::ID, &mut marketplace, &[], TradeOrder::BUY_ORDER, 850_000).unwrap_err(),
+            AuctionError::BookFull.into()
+        );
+
+        // An order passed twice in place of another
+        let mut repeated = accounts.clone();
+        repeated[0] = repeated[2].clone();
+        assert_eq!(
+            evict_from_full_book(&crate::ID, &mut marketplace, repeated.leak(), TradeOrder::BUY_ORDER, 850_000)
+                .unwrap_err(),
+            AuctionError::DuplicateOrder.into()
+        );
+
+        // An order that isn't open in place of one that is
+        let mut closed = accounts.clone();
+        let mut filled = book_order(marketplace.key(), TradeOrder::BUY_ORDER, 950_000, 0);
+        filled.is_active = false;
+        filled.exit(&crate::ID).unwrap();
+        closed[0] = filled.to_account_info();
+        assert_eq!(
+            evict_from_full_book(&crate::ID, &mut marketplace, closed.leak(), TradeOrder::BUY_ORDER, 850_000)
+                .unwrap_err(),
+            AuctionError::MissingOrders.into()
+        );
+
+        // A bid that doesn't beat the worst bid waits
+        assert_eq!(
+            evict_from_full_book(&crate::ID, &mut marketplace, accounts.leak(), TradeOrder::BUY_ORDER, 800_000)
+                .unwrap_err(),
+            AuctionError::NotCompetitive.into()
+        );
+        assert_eq!(marketplace.open_orders, MAX_AUCTION_ORDERS as u64);
+    }
+
+    /// Order of a fresh trader resting in an auction, its escrow funded and its
+    /// token accounts passed for the payout
+    struct Resting {
+        accounts: [AccountInfo<'static>; AUCTION_ACCOUNTS_PER_ORDER],
+    }
+
+    impl Resting {
+        fn new(marketplace: &Marketplace, order_type: u8, amount: u64, price: u64, escrowed: u64) -> Self {
+            let user = Pubkey::new_unique();
+            let mut order: TradeOrder = zeroed();
+            order.user = user;
+            order.marketplace = Pubkey::find_program_address(
+                &[b"marketplace", marketplace.strategy.as_ref(), marketplace.quote_mint.as_ref()],
+                &crate::ID,
+            )
+            .0;
+            order.order_type = order_type;
+            order.yield_token_amount = amount;
+            order.price_per_token = price;
+            order.is_active = true;
+            let (address, _) =
+                Pubkey::find_program_address(&[b"order", user.as_ref(), 0u64.to_le_bytes().as_ref()], &crate::ID);
+            let (escrow_address, _) = Pubkey::find_program_address(&[b"escrow", address.as_ref()], &crate::ID);
+            let escrow_mint = match order_type {
+                TradeOrder::BUY_ORDER => marketplace.quote_mint,
+                _ => marketplace.yield_token_mint,
+            };
+
+            Self {
+                accounts: [
+                    program_account_at(address, &order).to_account_info(),
+                    token_account_at(escrow_address, escrow_mint, address, escrowed).to_account_info(),
+                    token_account(marketplace.yield_token_mint, user, 0).to_account_info(),
+                    token_account(marketplace.quote_mint, user, 0).to_account_info(),
+                ],
+            }
+        }
+
+        fn order(&self) -> Account<'static, TradeOrder> {
+            Account::try_from(Box::leak(Box::new(self.accounts[0].clone()))).unwrap()
+        }
+
+        fn balance(&self, index: usize) -> u64 {
+            balance(&InterfaceAccount::try_from(Box::leak(Box::new(self.accounts[index].clone()))).unwrap())
+        }
+
+        fn escrow(&self) -> u64 {
+            self.balance(1)
+        }
+
+        fn yield_tokens(&self) -> u64 {
+            self.balance(2)
+        }
+
+        fn quote_tokens(&self) -> u64 {
+            self.balance(3)
+        }
+    }
+
+    /// Auction marketplace with a 1% fee and a 10 second epoch started at time 0,
+    /// holding a 3 token bid at 1.2, a 1 token bid at 1.0 and a 2 token ask at 0.9
+    fn auction() -> (ClearAuction<'static>, ClearAuctionBumps, [Resting; 3]) {
+        stub_syscalls();
+        let mut marketplace: Marketplace = zeroed();
+        marketplace.admin = Pubkey::new_unique();
+        marketplace.strategy = Pubkey::new_unique();
+        marketplace.quote_mint = Pubkey::new_unique();
+        marketplace.yield_token_mint = Pubkey::new_unique();
+        marketplace.trading_fee_bps = 100;
+        marketplace.is_active = true;
+        marketplace.auction_interval = 10;
+        marketplace.open_orders = 3;
+        let (address, bump) = Pubkey::find_program_address(
+            &[b"marketplace", marketplace.strategy.as_ref(), marketplace.quote_mint.as_ref()],
+            &crate::ID,
+        );
+
+        let orders = [
+            Resting::new(&marketplace, TradeOrder::BUY_ORDER, 3_000_000, 1_200_000, 3_600_000),
+            Resting::new(&marketplace, TradeOrder::BUY_ORDER, 1_000_000, 1_000_000, 1_000_000),
+            Resting::new(&marketplace, TradeOrder::SELL_ORDER, 2_000_000, 900_000, 2_000_000),
+        ];
+        let accounts = ClearAuction {
+            cranker: Signer::try_from(signer_info(Pubkey::new_unique())).unwrap(),
+            auction_yield_vault: token_account(marketplace.yield_token_mint, address, 0),
+            auction_quote_vault: token_account(marketplace.quote_mint, address, 0),
+            fee_collection_account: token_account(marketplace.quote_mint, marketplace.admin, 0),
+            yield_token_mint: mint_account(marketplace.yield_token_mint, 6),
+            quote_mint: mint_account(marketplace.quote_mint, 6),
+            minute_candles: candle_series(CandleSeries::MINUTE),
+            hour_candles: candle_series(CandleSeries::HOUR),
+            day_candles: candle_series(CandleSeries::DAY),
+            token_program: Interface::try_from(program_info(spl_token::ID)).unwrap(),
+            yield_token_program: Interface::try_from(program_info(spl_token::ID)).unwrap(),
+            marketplace: Box::new(program_account_at(address, &marketplace)),
+        };
+        let bumps = ClearAuctionBumps { marketplace: bump, ..Default::default() };
+        (accounts, bumps, orders)
+    }
+
+    fn clear(
+        accounts: &mut ClearAuction<'static>,
+        bumps: ClearAuctionBumps,
+        orders: &[Resting],
+    ) -> Result<()> {
+        let remaining_accounts = orders.iter().flat_map(|o| o.accounts.clone()).collect::<Vec<_>>().leak();
+        handle_clear_auction(Context::new(&crate::ID, accounts, remaining_accounts, bumps))
+    }
+
+    #[test]
+    fn auctions_clear_once_the_epoch_ends() {
+        let (mut accounts, bumps, orders) = auction();
+        set_clock(9);
+
+        assert_eq!(clear(&mut accounts, bumps, &orders).unwrap_err(), AuctionError::AuctionStillOpen.into());
+    }
+
+    #[test]
+    fn every_open_order_takes_part() {
+        let (mut accounts, bumps, orders) = auction();
+        set_clock(11);
+
+        assert_eq!(clear(&mut accounts, bumps, &orders[..2]).unwrap_err(), AuctionError::MissingOrders.into());
+    }
+
+    #[test]
+    fn crossing_orders_clear_at_one_price() {
+        let (mut accounts, bumps, orders) = auction();
+        let [big_buyer, small_buyer, seller] = &orders;
+        set_clock(11);
+
+        clear(&mut accounts, bumps, &orders).unwrap();
+
+        // Every crossing price matches 2 tokens, 1.2 leaves the least unmatched demand
+        assert_eq!(big_buyer.yield_tokens(), 2_000_000);
+        assert_eq!(small_buyer.yield_tokens(), 0);
+        // 2 tokens at 1.2 = 2.4 quote, 1% fee
+        assert_eq!(seller.quote_tokens(), 2_376_000);
+        assert_eq!(balance(&accounts.fee_collection_account), 24_000);
+
+        // The big buyer's unfilled token stays escrowed at its own limit price
+        assert_eq!(big_buyer.escrow(), 1_200_000);
+        assert_eq!(small_buyer.escrow(), 1_000_000);
+        assert_eq!(seller.escrow(), 0);
+        assert_eq!(balance(&accounts.auction_yield_vault), 0);
+        assert_eq!(balance(&accounts.auction_quote_vault), 0);
+
+        let big_order = big_buyer.order();
+        assert_eq!(big_order.filled_amount, 2_000_000);
+        assert!(big_order.is_active);
+        assert!(!seller.order().is_active);
+
+        let marketplace = &accounts.marketplace;
+        assert_eq!(marketplace.last_trade_price, 1_200_000);
+        assert_eq!(marketplace.auction_epoch, 1);
+        assert_eq!(marketplace.auction_epoch_start, 11);
+        assert_eq!(marketplace.open_orders, 2);
+        assert_eq!(accounts.minute_candles.load().unwrap().count, 1);
+    }
+}
@@ -1,16 +1,31 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{TokenAccount, Token, Transfer, transfer};
+use anchor_spl::token_interface::{
+    Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked, CloseAccount, close_account,
+};
 
-use crate::state::marketplace::{Marketplace, TradeOrder};
+use crate::instructions::trading_delegate::authorize_order_authority;
+use crate::state::{marketplace::{Marketplace, TradeOrder}, trading_delegate::TradingDelegate};
 
 #[derive(Accounts)]
 #[instruction(order_id: u64)]
 pub struct CancelOrder<'info> {
+    /// Order owner, or a session key authorised through `trading_delegate`
     #[account(mut)]
-    pub user: Signer<'info>,
+    pub authority: Signer<'info>,
+
+    /// CHECK: Order owner, checked against `authority` and `trading_delegate` in the handler
+    pub user: UncheckedAccount<'info>,
+
+    /// Session key authorisation, required when `authority` is not `user`
+    #[account(
+        mut,
+        seeds = [b"trading_delegate", user.key().as_ref(), authority.key().as_ref()],
+        bump = trading_delegate.bump
+    )]
+    pub trading_delegate: Option<Account<'info, TradingDelegate>>,
 
     #[account(mut)]
-    pub marketplace: Account<'info, Marketplace>,
+    pub marketplace: Box<Account<'info, Marketplace>>,
 
     #[account(
         mut,
@@ -28,19 +43,82 @@ pub struct CancelOrder<'info> {
         seeds = [b"escrow", order.key().as_ref()],
         bump
     )]
-    pub escrow_account: Account<'info, TokenAccount>,
+    pub escrow_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Mint held by the escrow
+    #[account(
+        address = escrow_account.mint @ CancelError::WrongTokenAccount
+    )]
+    pub escrow_mint: InterfaceAccount<'info, Mint>,
 
     /// User's token account to receive refund
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key() @ CancelError::WrongTokenAccount,
+        constraint = user_token_account.mint == escrow_account.mint @ CancelError::WrongTokenAccount
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Token program of the escrow mint
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(order_id: u64)]
+pub struct CloseOrder<'info> {
     #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
+    pub user: Signer<'info>,
 
-    pub token_program: Program<'info, Token>,
+    #[account(
+        mut,
+        seeds = [b"order", user.key().as_ref(), order_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = order.user == user.key() @ CancelError::UnauthorizedUser,
+        constraint = !order.is_active @ CancelError::OrderStillActive,
+        close = user
+    )]
+    pub order: Account<'info, TradeOrder>,
+
+    /// Escrow of the order, swept and closed
+    #[account(
+        mut,
+        seeds = [b"escrow", order.key().as_ref()],
+        bump
+    )]
+    pub escrow_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Mint held by the escrow
+    #[account(
+        address = escrow_account.mint @ CancelError::WrongTokenAccount
+    )]
+    pub escrow_mint: InterfaceAccount<'info, Mint>,
+
+    /// User's token account receiving what is left in the escrow
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key() @ CancelError::WrongTokenAccount,
+        constraint = user_token_account.mint == escrow_account.mint @ CancelError::WrongTokenAccount
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Token program of the escrow mint
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
 pub fn handle_cancel_order(
     ctx: Context<CancelOrder>,
     order_id: u64,
 ) -> Result<()> {
+    // Refunds always go to the user, so a session key only needs a valid authorisation
+    authorize_order_authority(
+        &ctx.accounts.authority.key(),
+        &ctx.accounts.user.key(),
+        ctx.accounts.trading_delegate.as_mut(),
+        0,
+        None,
+        Clock::get()?.unix_timestamp,
+    )?;
+
     let order = &mut ctx.accounts.order;
     let marketplace = &mut ctx.accounts.marketplace;
 
@@ -48,16 +126,9 @@ pub fn handle_cancel_order(
     let refund_amount = order.remaining_amount();
     require!(refund_amount > 0, CancelError::NoRefundAvailable);
 
-    // Determine refund amount based on order type
-    let actual_refund = if order.order_type == TradeOrder::BUY_ORDER {
-        // For buy orders, refund underlying tokens
-        let total_locked = order.total_value;
-        let filled_value = (order.filled_amount as u128 * order.price_per_token as u128 / 1_000_000) as u64;
-        total_locked - filled_value
-    } else {
-        // For sell orders, refund yield tokens
-        refund_amount
-    };
+    // Every fill paid out of the escrow as it settled, so whatever is left is the user's,
+    // including surplus from fills below a buy order's limit price
+    let actual_refund = ctx.accounts.escrow_account.amount;
 
     // Transfer tokens back to user
     let order_id_bytes = order_id.to_le_bytes();
@@ -71,17 +142,19 @@ pub fn handle_cancel_order(
 
     let cpi_ctx = CpiContext::new_with_signer(
         ctx.accounts.token_program.to_account_info(),
-        Transfer {
+        TransferChecked {
             from: ctx.accounts.escrow_account.to_account_info(),
+            mint: ctx.accounts.escrow_mint.to_account_info(),
             to: ctx.accounts.user_token_account.to_account_info(),
             authority: order.to_account_info(),
         },
         signer_seeds,
     );
-    transfer(cpi_ctx, actual_refund)?;
+    transfer_checked(cpi_ctx, actual_refund, ctx.accounts.escrow_mint.decimals)?;
 
     // Deactivate order
     order.is_active = false;
+    marketplace.open_orders -= 1;
 
     // Update marketplace best prices if this was the best order
     if order.order_type == TradeOrder::BUY_ORDER && order.price_per_token == marketplace.best_bid_price {
@@ -99,14 +172,195 @@ pub fn handle_cancel_order(
     Ok(())
 }
 
+/// Close a filled or cancelled order, sweeping what its escrow still holds (surplus
+/// from fills below a buy order's limit, transfer-fee rounding) to the user and
+/// returning the rent of both accounts
+pub fn handle_close_order(ctx: Context<CloseOrder>, order_id: u64) -> Result<()> {
+    let order = &ctx.accounts.order;
+    let order_id_bytes = order_id.to_le_bytes();
+    let order_seeds = &[
+        b"order",
+        order.user.as_ref(),
+        order_id_bytes.as_ref(),
+        &[ctx.bumps.order],
+    ];
+    let signer_seeds = &[order_seeds.as_slice()];
+
+    let swept = ctx.accounts.escrow_account.amount;
+    if swept > 0 {
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.escrow_account.to_account_info(),
+                mint: ctx.accounts.escrow_mint.to_account_info(),
+                to: ctx.accounts.user_token_account.to_account_info(),
+                authority: order.to_account_info(),
+            },
+            signer_seeds,
+        );
+        transfer_checked(cpi_ctx, swept, ctx.accounts.escrow_mint.decimals)?;
+    }
+
+    let cpi_ctx = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        CloseAccount {
+            account: ctx.accounts.escrow_account.to_account_info(),
+            destination: ctx.accounts.user.to_account_info(),
+            authority: order.to_account_info(),
+        },
+        signer_seeds,
+    );
+    close_account(cpi_ctx)?;
+
+    // The order account's rent is returned by the `close` constraint
+    msg!("Order {} closed, swept {} tokens to user", order_id, swept);
+
+    Ok(())
+}
+
 #[error_code]
 pub enum CancelError {
     #[msg("Unauthorized to cancel this order")]
     UnauthorizedUser,
     #[msg("Order is not active")]
     OrderNotActive,
+    #[msg("Order is still active, cancel it first")]
+    OrderStillActive,
     #[msg("Order marketplace mismatch")]
     OrderMarketplaceMismatch,
     #[msg("No refund available")]
     NoRefundAvailable,
-} 
\ No newline at end of file
+    #[msg("Wrong token account")]
+    WrongTokenAccount,
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use anchor_spl::token::spl_token;
+
+    use crate::test_utils::{
+        balance, mint_account, program_account, program_account_at, program_info, session_key, set_clock,
+        signer_info, stub_syscalls, token_account, zeroed,
+    };
+
+    /// Buy order for 100 yield tokens at 1.2 with 50 filled, its escrow holding `escrowed`
+    fn partly_filled_buy(escrowed: u64) -> CancelOrder<'static> {
+        stub_syscalls();
+        let user = Pubkey::new_unique();
+        let quote_mint = Pubkey::new_unique();
+        let marketplace = program_account(&{
+            let mut marketplace: Marketplace = zeroed();
+            marketplace.open_orders = 1;
+            marketplace
+        });
+
+        let mut order: TradeOrder = zeroed();
+        order.user = user;
+        order.marketplace = marketplace.key();
+        order.order_type = TradeOrder::BUY_ORDER;
+        order.yield_token_amount = 100;
+        order.price_per_token = 1_200_000;
+        order.total_value = 120;
+        order.filled_amount = 50;
+        order.is_active = true;
+        order.order_id = 7;
+        let (order_address, _) = Pubkey::find_program_address(
+            &[b"order", user.as_ref(), 7u64.to_le_bytes().as_ref()],
+            &crate::ID,
+        );
+
+        CancelOrder {
+            authority: Signer::try_from(signer_info(user)).unwrap(),
+            user: UncheckedAccount::try_from(signer_info(user)),
+            trading_delegate: None,
+            marketplace: Box::new(marketplace),
+            order: program_account_at(order_address, &order),
+            escrow_account: token_account(quote_mint, order_address, escrowed),
+            escrow_mint: mint_account(quote_mint, 6),
+            user_token_account: token_account(quote_mint, user, 0),
+            token_program: Interface::try_from(program_info(spl_token::ID)).unwrap(),
+        }
+    }
+
+    fn cancel(accounts: &mut CancelOrder<'static>) -> Result<()> {
+        let (_, bump) = Pubkey::find_program_address(
+            &[b"order", accounts.user.key().as_ref(), 7u64.to_le_bytes().as_ref()],
+            &crate::ID,
+        );
+        let bumps = CancelOrderBumps { order: bump, ..Default::default() };
+        handle_cancel_order(Context::new(&crate::ID, accounts, &[], bumps), 7)
+    }
+
+    #[test]
+    fn cancel_refunds_the_whole_escrow() {
+        // Half filled at an auction clearing price of 1.0: 50 paid out of 120
+        let mut accounts = partly_filled_buy(70);
+
+        cancel(&mut accounts).unwrap();
+
+        // Recomputing from the limit price would have refunded 60 and left 10 behind
+        assert_eq!(balance(&accounts.user_token_account), 70);
+        assert_eq!(balance(&accounts.escrow_account), 0);
+        assert!(!accounts.order.is_active);
+        assert_eq!(accounts.marketplace.open_orders, 0);
+    }
+
+    #[test]
+    fn cancel_needs_the_owner_or_a_session_key() {
+        let mut accounts = partly_filled_buy(60);
+        accounts.authority = Signer::try_from(signer_info(Pubkey::new_unique())).unwrap();
+
+        assert_eq!(
+            cancel(&mut accounts).unwrap_err(),
+            crate::instructions::trading_delegate::DelegateError::MissingDelegate.into()
+        );
+        assert_eq!(balance(&accounts.escrow_account), 60);
+    }
+
+    #[test]
+    fn session_keys_cancel_back_to_the_user() {
+        let mut accounts = partly_filled_buy(60);
+        set_clock(100);
+        let key = Pubkey::new_unique();
+        accounts.trading_delegate = Some(session_key(accounts.user.key(), key, 0));
+        accounts.authority = Signer::try_from(signer_info(key)).unwrap();
+
+        cancel(&mut accounts).unwrap();
+
+        assert_eq!(balance(&accounts.user_token_account), 60);
+        assert!(!accounts.order.is_active);
+    }
+
+    #[test]
+    fn closing_a_filled_order_sweeps_its_escrow() {
+        // Fully filled at 1.0 against a 1.2 limit, 20 of the 120 escrowed are left over
+        let cancelled = partly_filled_buy(20);
+        let mut order = TradeOrder::clone(&cancelled.order);
+        order.filled_amount = order.yield_token_amount;
+        order.is_active = false;
+        let user = cancelled.user.key();
+        let (order_address, bump) = Pubkey::find_program_address(
+            &[b"order", user.as_ref(), 7u64.to_le_bytes().as_ref()],
+            &crate::ID,
+        );
+
+        let mut accounts = CloseOrder {
+            user: Signer::try_from(signer_info(user)).unwrap(),
+            order: program_account_at(order_address, &order),
+            escrow_account: token_account(cancelled.escrow_mint.key(), order_address, 20),
+            escrow_mint: cancelled.escrow_mint,
+            user_token_account: cancelled.user_token_account,
+            token_program: cancelled.token_program,
+        };
+        let escrow_rent = accounts.escrow_account.to_account_info().lamports();
+        let user_lamports = accounts.user.lamports();
+        let bumps = CloseOrderBumps { order: bump, ..Default::default() };
+
+        handle_close_order(Context::new(&crate::ID, &mut accounts, &[], bumps), 7).unwrap();
+
+        assert_eq!(balance(&accounts.user_token_account), 20);
+        assert_eq!(accounts.escrow_account.to_account_info().lamports(), 0);
+        assert_eq!(accounts.user.lamports(), user_lamports + escrow_rent);
+    }
+}
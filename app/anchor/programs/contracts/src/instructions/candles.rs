@@ -0,0 +1,128 @@
+use anchor_lang::prelude::*;
+
+use crate::state::{candles::CandleSeries, marketplace::Marketplace};
+
+#[derive(Accounts)]
+#[instruction(interval: i64)]
+pub struct InitCandleSeries<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub marketplace: Box<Account<'info, Marketplace>>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"candles", marketplace.key().as_ref(), interval.to_le_bytes().as_ref()],
+        bump,
+        space = 8 + CandleSeries::INIT_SPACE
+    )]
+    pub candle_series: AccountLoader<'info, CandleSeries>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handle_init_candle_series(ctx: Context<InitCandleSeries>, interval: i64) -> Result<()> {
+    require!(
+        CandleSeries::INTERVALS.contains(&interval),
+        CandleError::UnsupportedInterval
+    );
+
+    let mut candle_series = ctx.accounts.candle_series.load_init()?;
+    candle_series.marketplace = ctx.accounts.marketplace.key();
+    candle_series.interval = interval;
+    candle_series.head = 0;
+    candle_series.count = 0;
+
+    msg!(
+        "Candle series created for marketplace {} with {}s interval",
+        ctx.accounts.marketplace.marketplace_id,
+        interval
+    );
+
+    Ok(())
+}
+
+/// Fold a trade into the marketplace's 1m, 1h and 1d series.
+/// Callers check each series against its PDA, so every trade reaches every series.
+pub(crate) fn record_candles(
+    candle_series: [&AccountLoader<CandleSeries>; 3],
+    price: u64,
+    volume: u64,
+    timestamp: i64,
+) -> Result<()> {
+    for series in candle_series {
+        series.load_mut()?.record_trade(price, volume, timestamp);
+    }
+
+    Ok(())
+}
+
+#[error_code]
+pub enum CandleError {
+    #[msg("Candle interval must be 60, 3600 or 86400 seconds")]
+    UnsupportedInterval,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_utils::{account_info, candle_series, program_account, program_info, signer_info, zeroed};
+
+    #[test]
+    fn every_trade_reaches_every_series() {
+        let series = CandleSeries::INTERVALS.map(candle_series);
+        // Three trades a minute apart, inside one hour of one day
+        let trades = [(1_000_000, 1_000_000, 36_010), (1_200_000, 2_000_000, 36_070), (900_000, 500_000, 36_130)];
+        for (price, volume, timestamp) in trades {
+            record_candles([&series[0], &series[1], &series[2]], price, volume, timestamp).unwrap();
+        }
+
+        let minutes = series[0].load().unwrap();
+        assert_eq!(minutes.count, 3);
+        assert_eq!(minutes.candles[minutes.head as usize].close, 900_000);
+        for (series, start_time) in [(&series[1], 36_000), (&series[2], 0)] {
+            let series = series.load().unwrap();
+            let candle = series.candles[series.head as usize];
+            assert_eq!(series.count, 1);
+            assert_eq!(candle.start_time, start_time);
+            assert_eq!(
+                (candle.open, candle.high, candle.low, candle.close, candle.volume),
+                (1_000_000, 1_200_000, 900_000, 900_000, 3_500_000)
+            );
+        }
+    }
+
+    #[test]
+    fn only_supported_intervals_get_a_series() {
+        for (interval, supported) in [(CandleSeries::HOUR, true), (300, false)] {
+            let data = vec![0; 8 + CandleSeries::INIT_SPACE];
+            let mut accounts = InitCandleSeries {
+                payer: Signer::try_from(signer_info(Pubkey::new_unique())).unwrap(),
+                marketplace: Box::new(program_account(&zeroed::<Marketplace>())),
+                candle_series: AccountLoader::try_from_unchecked(
+                    &crate::ID,
+                    account_info(Pubkey::new_unique(), crate::ID, data),
+                )
+                .unwrap(),
+                system_program: Program::try_from(program_info(System::id())).unwrap(),
+            };
+
+            let result = handle_init_candle_series(
+                Context::new(&crate::ID, &mut accounts, &[], InitCandleSeriesBumps::default()),
+                interval,
+            );
+
+            if supported {
+                result.unwrap();
+                accounts.exit(&crate::ID).unwrap();
+                let series = accounts.candle_series.load().unwrap();
+                assert_eq!(series.interval, interval);
+                assert_eq!(series.marketplace, accounts.marketplace.key());
+            } else {
+                assert_eq!(result.unwrap_err(), CandleError::UnsupportedInterval.into());
+            }
+        }
+    }
+}
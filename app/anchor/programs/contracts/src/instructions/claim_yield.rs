@@ -1,7 +1,19 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Mint, TokenAccount, Token, MintTo, mint_to};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, MintTo, mint_to, TransferChecked, transfer_checked};
 
-use crate::state::{strategy::Strategy, user_position::UserPosition};
+use crate::instructions::rate_feed::accrue_strategy;
+use crate::state::{rate_feed::RateFeed, strategy::Strategy, user_position::UserPosition};
+
+/// Accounts passed per position in `claim_all` remaining accounts: strategy, user position,
+/// yield token mint, user's yield token account and the strategy's rate feed (this program's
+/// id for fixed-rate strategies)
+pub const CLAIM_ALL_ACCOUNTS_PER_POSITION: usize = 5;
+
+/// Maximum number of positions settled by a single `claim_all`
+pub const MAX_CLAIM_ALL_POSITIONS: usize = 10;
+
+/// Compute units kept in hand before settling the next position in `claim_all`
+pub const CLAIM_ALL_MIN_COMPUTE_UNITS: u64 = 30_000;
 
 #[derive(Accounts)]
 #[instruction(strategy_id: u64)]
@@ -30,65 +42,254 @@ pub struct ClaimYield<'info> {
         mut,
         address = strategy.yield_token_mint @ ClaimError::WrongYieldTokenMint
     )]
-    pub yield_token_mint: Account<'info, Mint>,
+    pub yield_token_mint: InterfaceAccount<'info, Mint>,
 
-    /// User's yield token account
-    #[account(mut)]
-    pub user_yield_token_account: Account<'info, TokenAccount>,
+    /// CHECK: Only used as the expected owner of the recipient token account
+    pub recipient: UncheckedAccount<'info>,
+
+    /// Token account receiving the yield, a yield token account or, when claiming
+    /// as underlying, an underlying token account
+    #[account(
+        mut,
+        constraint = recipient_token_account.owner == recipient.key() @ ClaimError::WrongRecipient
+    )]
+    pub recipient_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Token program of the yield token mint
+    pub token_program: Interface<'info, TokenInterface>,
+
+    /// Rate feed of oracle-rate strategies, omitted for fixed-rate strategies
+    pub rate_feed: Option<Account<'info, RateFeed>>,
+
+    /// The underlying token mint, only needed when claiming as underlying
+    #[account(
+        address = strategy.underlying_token @ ClaimError::WrongUnderlyingToken
+    )]
+    pub underlying_token_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    /// Reserve paying out yield claimed as underlying
+    #[account(
+        mut,
+        seeds = [b"reward_reserve", strategy_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub reward_reserve: Option<InterfaceAccount<'info, TokenAccount>>,
 
-    pub token_program: Program<'info, Token>,
+    /// Token program of the underlying token, only needed when claiming as underlying
+    pub underlying_token_program: Option<Interface<'info, TokenInterface>>,
 }
 
+/// Claim yield to `recipient`, minted as yield tokens or, with `as_underlying`, paid in
+/// the underlying token out of the reward reserve
 pub fn handle_claim_yield(
     ctx: Context<ClaimYield>,
     strategy_id: u64,
+    as_underlying: bool,
 ) -> Result<()> {
-    let strategy = &ctx.accounts.strategy;
+    let strategy = &mut ctx.accounts.strategy;
     let user_position = &mut ctx.accounts.user_position;
     let current_time = Clock::get()?.unix_timestamp;
+    accrue_strategy(strategy, ctx.accounts.rate_feed.as_ref(), current_time)?;
 
-    // Calculate yield since last claim
-    let time_elapsed = current_time - user_position.last_yield_claim;
-    require!(time_elapsed > 0, ClaimError::NoYieldToClaim);
-
-    let yield_amount = strategy.calculate_yield(
-        user_position.deposited_amount,
-        time_elapsed,
-    );
+    let yield_amount = settle_claim(strategy, user_position, current_time)?;
 
-    require!(yield_amount > 0, ClaimError::NoYieldToClaim);
-
-    // Mint additional yield tokens
     let strategy_id_bytes = strategy_id.to_le_bytes();
     let signer_seeds: &[&[u8]] = &[b"strategy", strategy_id_bytes.as_ref(), &[ctx.bumps.strategy]];
     let seeds: &[&[&[u8]]] = &[signer_seeds];
+    let recipient_token_account = &ctx.accounts.recipient_token_account;
 
-    let cpi_ctx = CpiContext::new_with_signer(
-        ctx.accounts.token_program.to_account_info(),
-        MintTo {
-            mint: ctx.accounts.yield_token_mint.to_account_info(),
-            to: ctx.accounts.user_yield_token_account.to_account_info(),
-            authority: strategy.to_account_info(),
-        },
-        seeds,
-    );
-    mint_to(cpi_ctx, yield_amount)?;
+    if as_underlying {
+        let (Some(underlying_token_mint), Some(reward_reserve), Some(underlying_token_program)) = (
+            ctx.accounts.underlying_token_mint.as_ref(),
+            ctx.accounts.reward_reserve.as_ref(),
+            ctx.accounts.underlying_token_program.as_ref(),
+        ) else {
+            return err!(ClaimError::MissingUnderlyingAccounts);
+        };
+        require_keys_eq!(
+            recipient_token_account.mint,
+            underlying_token_mint.key(),
+            ClaimError::WrongRecipientMint
+        );
+        require!(reward_reserve.amount >= yield_amount, ClaimError::InsufficientReserve);
 
-    // Update user position
-    user_position.last_yield_claim = current_time;
-    user_position.total_yield_claimed += yield_amount;
-    user_position.yield_tokens_minted += yield_amount;
+        // Pay the yield in underlying tokens out of the reward reserve
+        let cpi_ctx = CpiContext::new_with_signer(
+            underlying_token_program.to_account_info(),
+            TransferChecked {
+                from: reward_reserve.to_account_info(),
+                mint: underlying_token_mint.to_account_info(),
+                to: recipient_token_account.to_account_info(),
+                authority: strategy.to_account_info(),
+            },
+            seeds,
+        );
+        transfer_checked(cpi_ctx, yield_amount, underlying_token_mint.decimals)?;
+    } else {
+        require_keys_eq!(
+            recipient_token_account.mint,
+            ctx.accounts.yield_token_mint.key(),
+            ClaimError::WrongRecipientMint
+        );
+
+        // Mint additional yield tokens
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            MintTo {
+                mint: ctx.accounts.yield_token_mint.to_account_info(),
+                to: recipient_token_account.to_account_info(),
+                authority: strategy.to_account_info(),
+            },
+            seeds,
+        );
+        mint_to(cpi_ctx, yield_amount)?;
+        user_position.yield_tokens_minted += yield_amount;
+    }
 
     msg!(
-        "User {} claimed {} yield tokens from strategy '{}'",
+        "User {} claimed {} {} from strategy '{}' to {}",
         ctx.accounts.user.key(),
         yield_amount,
-        strategy.name
+        if as_underlying { "underlying tokens" } else { "yield tokens" },
+        strategy.name,
+        ctx.accounts.recipient.key()
     );
 
     Ok(())
 }
 
+#[derive(Accounts)]
+pub struct ClaimAll<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// Token program of the yield token mints
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Claim yield from every position passed in remaining accounts, stopping early when
+/// compute runs low. Returns the number of positions processed so the client can resume
+/// from the next one.
+pub fn handle_claim_all<'info>(
+    ctx: Context<'_, '_, 'info, 'info, ClaimAll<'info>>,
+) -> Result<u32> {
+    let remaining_accounts = ctx.remaining_accounts;
+    let position_count = remaining_accounts.len() / CLAIM_ALL_ACCOUNTS_PER_POSITION;
+    require!(
+        position_count > 0 && position_count * CLAIM_ALL_ACCOUNTS_PER_POSITION == remaining_accounts.len(),
+        ClaimError::InvalidRemainingAccounts
+    );
+    require!(position_count <= MAX_CLAIM_ALL_POSITIONS, ClaimError::TooManyPositions);
+
+    let user_key = ctx.accounts.user.key();
+    let token_program = &ctx.accounts.token_program;
+    let current_time = Clock::get()?.unix_timestamp;
+
+    let mut processed: u32 = 0;
+    let mut total_claimed: u64 = 0;
+    for accounts in remaining_accounts.chunks(CLAIM_ALL_ACCOUNTS_PER_POSITION) {
+        if anchor_lang::solana_program::compute_units::sol_remaining_compute_units() < CLAIM_ALL_MIN_COMPUTE_UNITS {
+            msg!("Compute budget running low, stopping after {} positions", processed);
+            break;
+        }
+
+        let mut strategy = Account::<Strategy>::try_from(&accounts[0])?;
+        let strategy_id_bytes = strategy.strategy_id.to_le_bytes();
+        let (strategy_address, strategy_bump) = Pubkey::find_program_address(
+            &[b"strategy", strategy_id_bytes.as_ref()],
+            ctx.program_id,
+        );
+        require_keys_eq!(strategy_address, strategy.key(), ClaimError::InvalidStrategyAccount);
+        require!(strategy.is_operational(), ClaimError::StrategyNotActive);
+
+        let mut user_position = Account::<UserPosition>::try_from(&accounts[1])?;
+        let (position_address, _) = Pubkey::find_program_address(
+            &[b"user_position", user_key.as_ref(), strategy.key().as_ref()],
+            ctx.program_id,
+        );
+        require_keys_eq!(position_address, user_position.key(), ClaimError::InvalidPositionAccount);
+        require_keys_eq!(user_position.user, user_key, ClaimError::UnauthorizedUser);
+
+        let yield_token_mint = InterfaceAccount::<Mint>::try_from(&accounts[2])?;
+        require_keys_eq!(yield_token_mint.key(), strategy.yield_token_mint, ClaimError::WrongYieldTokenMint);
+        require_keys_eq!(*accounts[2].owner, token_program.key(), ClaimError::WrongTokenProgram);
+
+        let destination = InterfaceAccount::<TokenAccount>::try_from(&accounts[3])?;
+        require_keys_eq!(destination.owner, user_key, ClaimError::WrongRecipient);
+        require_keys_eq!(destination.mint, yield_token_mint.key(), ClaimError::WrongRecipientMint);
+
+        let rate_feed = if accounts[4].key() == crate::ID {
+            None
+        } else {
+            Some(Account::<RateFeed>::try_from(&accounts[4])?)
+        };
+        accrue_strategy(&mut strategy, rate_feed.as_ref(), current_time)?;
+        processed += 1;
+
+        // Positions with nothing accrued are skipped rather than failing the batch
+        if current_time <= user_position.last_yield_claim
+            || user_position.claimable_yield(&strategy, current_time) == 0
+        {
+            strategy.exit(&crate::ID)?;
+            continue;
+        }
+
+        let yield_amount = settle_claim(&strategy, &mut user_position, current_time)?;
+
+        let signer_seeds: &[&[u8]] = &[b"strategy", strategy_id_bytes.as_ref(), &[strategy_bump]];
+        let seeds: &[&[&[u8]]] = &[signer_seeds];
+        let cpi_ctx = CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            MintTo {
+                mint: yield_token_mint.to_account_info(),
+                to: destination.to_account_info(),
+                authority: strategy.to_account_info(),
+            },
+            seeds,
+        );
+        mint_to(cpi_ctx, yield_amount)?;
+        user_position.yield_tokens_minted += yield_amount;
+        total_claimed += yield_amount;
+
+        strategy.exit(&crate::ID)?;
+        user_position.exit(&crate::ID)?;
+    }
+
+    msg!(
+        "User {} claimed {} yield tokens across {} of {} positions",
+        user_key,
+        total_claimed,
+        processed,
+        position_count
+    );
+
+    Ok(processed)
+}
+
+/// Settle the yield accrued since the last claim, returns the amount to pay out.
+/// The strategy's reward index has to be accrued first.
+pub(crate) fn settle_claim(
+    strategy: &Strategy,
+    user_position: &mut UserPosition,
+    current_time: i64,
+) -> Result<u64> {
+    // Calculate yield since last claim
+    let time_elapsed = current_time - user_position.last_yield_claim;
+    require!(time_elapsed > 0, ClaimError::NoYieldToClaim);
+
+    let yield_amount = user_position.claimable_yield(strategy, current_time);
+
+    require!(yield_amount > 0, ClaimError::NoYieldToClaim);
+
+    // Update user position
+    user_position.last_yield_claim = current_time;
+    user_position.settle_yield(strategy);
+    user_position.pending_yield = 0;
+    user_position.total_yield_claimed += yield_amount;
+
+    Ok(yield_amount)
+}
+
 #[error_code]
 pub enum ClaimError {
     #[msg("Strategy is not active")]
@@ -99,4 +300,326 @@ pub enum ClaimError {
     UnauthorizedUser,
     #[msg("No yield to claim")]
     NoYieldToClaim,
-} 
\ No newline at end of file
+    #[msg("Recipient token account is not owned by the recipient")]
+    WrongRecipient,
+    #[msg("Recipient token account has the wrong mint")]
+    WrongRecipientMint,
+    #[msg("Wrong underlying token for this strategy")]
+    WrongUnderlyingToken,
+    #[msg("Underlying mint, reward reserve and token program are required to claim as underlying")]
+    MissingUnderlyingAccounts,
+    #[msg("Reward reserve cannot cover the claim")]
+    InsufficientReserve,
+    #[msg("Remaining accounts must come in strategy, position, mint, destination, rate feed tuples")]
+    InvalidRemainingAccounts,
+    #[msg("Too many positions in one batch claim")]
+    TooManyPositions,
+    #[msg("Strategy account does not match its PDA")]
+    InvalidStrategyAccount,
+    #[msg("User position account does not match its PDA")]
+    InvalidPositionAccount,
+    #[msg("Yield token mint is not owned by the given token program")]
+    WrongTokenProgram,
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use anchor_spl::token::spl_token;
+
+    use crate::test_utils::{
+        account_info, balance, program_account, program_account_at, program_info, set_clock, signer_info,
+        stub_syscalls, token_account, yield_mint, zeroed, LendingStrategy,
+    };
+
+    const YEAR: i64 = 365 * 24 * 60 * 60;
+    const STRATEGY_ID: u64 = 7;
+
+    fn fixed_strategy() -> Strategy {
+        let mut strategy: Strategy = zeroed();
+        strategy.apy = 1000;
+        strategy
+    }
+
+    fn position() -> UserPosition {
+        let mut position: UserPosition = zeroed();
+        position.deposited_amount = 1_000_000;
+        position.yield_tokens_minted = 1_000_000;
+        position
+    }
+
+    #[test]
+    fn claim_pays_the_yield_since_the_last_claim() {
+        let strategy = fixed_strategy();
+        let mut position = position();
+
+        assert_eq!(settle_claim(&strategy, &mut position, YEAR).unwrap(), 100_000);
+        assert_eq!(position.last_yield_claim, YEAR);
+        assert_eq!(position.total_yield_claimed, 100_000);
+
+        // Half a year later only the second half accrues
+        assert_eq!(settle_claim(&strategy, &mut position, YEAR + YEAR / 2).unwrap(), 50_000);
+        assert_eq!(position.total_yield_claimed, 150_000);
+    }
+
+    #[test]
+    fn claim_includes_settled_top_up_yield() {
+        let strategy = fixed_strategy();
+        let mut position = position();
+        position.pending_yield = 7_000;
+
+        assert_eq!(settle_claim(&strategy, &mut position, YEAR).unwrap(), 107_000);
+        assert_eq!(position.pending_yield, 0);
+    }
+
+    #[test]
+    fn nothing_to_claim_twice_in_the_same_second() {
+        let strategy = fixed_strategy();
+        let mut position = position();
+        settle_claim(&strategy, &mut position, YEAR).unwrap();
+
+        assert_eq!(
+            settle_claim(&strategy, &mut position, YEAR).unwrap_err(),
+            ClaimError::NoYieldToClaim.into()
+        );
+        assert_eq!(position.total_yield_claimed, 100_000);
+    }
+
+    #[test]
+    fn oracle_claim_moves_the_snapshot_to_the_index() {
+        let mut strategy = fixed_strategy();
+        strategy.rate_mode = Strategy::RATE_MODE_ORACLE;
+        strategy.reward_index = Strategy::REWARD_INDEX_SCALE / 20;
+        let mut position = position();
+
+        assert_eq!(settle_claim(&strategy, &mut position, 1).unwrap(), 50_000);
+        assert_eq!(position.reward_index_snapshot, strategy.reward_index);
+        assert_eq!(position.claimable_yield(&strategy, 2), 0);
+    }
+
+    /// A user claiming a year of their 1 token position at 10% to another recipient's
+    /// yield token account, with the underlying accounts when the reserve holds `reserve`
+    fn claim_accounts(reserve: Option<u64>) -> (ClaimYield<'static>, u8) {
+        set_clock(YEAR);
+        let mut lending = LendingStrategy::new(0, 0, 0);
+        let yield_token_mint = yield_mint(Pubkey::new_unique(), lending.strategy.key(), 1_000_000, 6);
+        let mut strategy = fixed_strategy();
+        strategy.is_active = true;
+        strategy.underlying_token = lending.mint.key();
+        strategy.yield_token_mint = yield_token_mint.key();
+        lending.strategy = program_account_at(lending.strategy.key(), &strategy);
+        let user = Pubkey::new_unique();
+        let mut user_position = position();
+        user_position.user = user;
+        let recipient = Pubkey::new_unique();
+
+        let accounts = ClaimYield {
+            user: Signer::try_from(signer_info(user)).unwrap(),
+            strategy: lending.strategy.clone(),
+            user_position: program_account(&user_position),
+            recipient: UncheckedAccount::try_from(account_info(recipient, System::id(), Vec::new())),
+            recipient_token_account: token_account(yield_token_mint.key(), recipient, 0),
+            yield_token_mint,
+            token_program: Interface::try_from(program_info(spl_token::ID)).unwrap(),
+            rate_feed: None,
+            underlying_token_mint: reserve.map(|_| lending.mint.clone()),
+            reward_reserve: reserve.map(|reserve| token_account(lending.mint.key(), lending.strategy.key(), reserve)),
+            underlying_token_program: reserve.map(|_| Interface::try_from(program_info(spl_token::ID)).unwrap()),
+        };
+        (accounts, lending.bump)
+    }
+
+    /// Point the claim at the recipient's account of the underlying token
+    fn to_underlying_account(accounts: &mut ClaimYield<'static>) {
+        let underlying_token = accounts.strategy.underlying_token;
+        accounts.recipient_token_account = token_account(underlying_token, accounts.recipient.key(), 0);
+    }
+
+    fn claim(accounts: &mut ClaimYield<'static>, bump: u8, as_underlying: bool) -> Result<()> {
+        let bumps = ClaimYieldBumps { strategy: bump, ..Default::default() };
+        handle_claim_yield(Context::new(&crate::ID, accounts, &[], bumps), LendingStrategy::STRATEGY_ID, as_underlying)
+    }
+
+    #[test]
+    fn yield_tokens_are_minted_to_the_recipient() {
+        let (mut accounts, bump) = claim_accounts(None);
+        to_underlying_account(&mut accounts);
+        assert_eq!(claim(&mut accounts, bump, false).unwrap_err(), ClaimError::WrongRecipientMint.into());
+
+        let (mut accounts, bump) = claim_accounts(None);
+        claim(&mut accounts, bump, false).unwrap();
+        assert_eq!(balance(&accounts.recipient_token_account), 100_000);
+        assert_eq!(accounts.user_position.total_yield_claimed, 100_000);
+        assert_eq!(accounts.user_position.yield_tokens_minted, 1_100_000);
+    }
+
+    #[test]
+    fn underlying_claims_are_paid_out_of_the_reserve() {
+        let (mut accounts, bump) = claim_accounts(None);
+        to_underlying_account(&mut accounts);
+        assert_eq!(claim(&mut accounts, bump, true).unwrap_err(), ClaimError::MissingUnderlyingAccounts.into());
+
+        let (mut accounts, bump) = claim_accounts(Some(1_000_000));
+        assert_eq!(claim(&mut accounts, bump, true).unwrap_err(), ClaimError::WrongRecipientMint.into());
+
+        let (mut accounts, bump) = claim_accounts(Some(99_999));
+        to_underlying_account(&mut accounts);
+        assert_eq!(claim(&mut accounts, bump, true).unwrap_err(), ClaimError::InsufficientReserve.into());
+
+        let (mut accounts, bump) = claim_accounts(Some(1_000_000));
+        to_underlying_account(&mut accounts);
+        claim(&mut accounts, bump, true).unwrap();
+        assert_eq!(balance(&accounts.recipient_token_account), 100_000);
+        assert_eq!(balance(accounts.reward_reserve.as_ref().unwrap()), 900_000);
+        // No yield tokens are minted for an underlying claim
+        assert_eq!(accounts.user_position.yield_tokens_minted, 1_000_000);
+    }
+
+    /// Accounts of a fixed-rate position of `user` opened at time 0, its strategy at `strategy_key`
+    fn claim_tuple(user: Pubkey, strategy_key: Pubkey) -> Vec<AccountInfo<'static>> {
+        strategy_tuple(user, strategy_key, STRATEGY_ID, 1000)
+    }
+
+    /// Accounts of a position of `user` opened at time 0 in strategy `strategy_id` at `apy`
+    fn strategy_tuple(user: Pubkey, strategy_key: Pubkey, strategy_id: u64, apy: u64) -> Vec<AccountInfo<'static>> {
+        let mut strategy = fixed_strategy();
+        strategy.strategy_id = strategy_id;
+        strategy.apy = apy;
+        strategy.is_active = true;
+        strategy.yield_token_mint = Pubkey::new_unique();
+        let strategy = program_account_at(strategy_key, &strategy);
+
+        let mut position = position();
+        position.user = user;
+        position.strategy = strategy_key;
+        let (position_key, _) = Pubkey::find_program_address(
+            &[b"user_position", user.as_ref(), strategy_key.as_ref()],
+            &crate::ID,
+        );
+        let position = program_account_at(position_key, &position);
+
+        let mint = yield_mint(strategy.yield_token_mint, strategy_key, 1_000_000, 6);
+        let destination = token_account(strategy.yield_token_mint, user, 1_000_000);
+
+        vec![
+            strategy.to_account_info(),
+            position.to_account_info(),
+            mint.to_account_info(),
+            destination.to_account_info(),
+            program_info(crate::ID).clone(),
+        ]
+    }
+
+    fn strategy_pda() -> Pubkey {
+        strategy_pda_of(STRATEGY_ID)
+    }
+
+    fn strategy_pda_of(strategy_id: u64) -> Pubkey {
+        Pubkey::find_program_address(&[b"strategy", strategy_id.to_le_bytes().as_ref()], &crate::ID).0
+    }
+
+    fn claim_all(user: Pubkey, remaining_accounts: Vec<AccountInfo<'static>>) -> Result<u32> {
+        stub_syscalls();
+        let remaining_accounts: &'static [AccountInfo<'static>] = Box::leak(remaining_accounts.into_boxed_slice());
+        let mut accounts = ClaimAll {
+            user: Signer::try_from(signer_info(user)).unwrap(),
+            token_program: Interface::try_from(program_info(spl_token::ID)).unwrap(),
+        };
+        handle_claim_all(Context::new(&crate::ID, &mut accounts, remaining_accounts, ClaimAllBumps::default()))
+    }
+
+    #[test]
+    fn batch_takes_whole_tuples_only() {
+        let user = Pubkey::new_unique();
+
+        assert_eq!(
+            claim_all(user, Vec::new()).unwrap_err(),
+            ClaimError::InvalidRemainingAccounts.into()
+        );
+
+        let mut accounts = claim_tuple(user, strategy_pda());
+        accounts.pop();
+        assert_eq!(
+            claim_all(user, accounts).unwrap_err(),
+            ClaimError::InvalidRemainingAccounts.into()
+        );
+    }
+
+    #[test]
+    fn batch_is_capped() {
+        let user = Pubkey::new_unique();
+        let accounts = (0..=MAX_CLAIM_ALL_POSITIONS)
+            .flat_map(|_| claim_tuple(user, strategy_pda()))
+            .collect();
+
+        assert_eq!(claim_all(user, accounts).unwrap_err(), ClaimError::TooManyPositions.into());
+    }
+
+    #[test]
+    fn strategies_must_sit_at_their_pda() {
+        let user = Pubkey::new_unique();
+
+        assert_eq!(
+            claim_all(user, claim_tuple(user, Pubkey::new_unique())).unwrap_err(),
+            ClaimError::InvalidStrategyAccount.into()
+        );
+    }
+
+    #[test]
+    fn positions_must_belong_to_the_signer() {
+        let owner = Pubkey::new_unique();
+
+        assert_eq!(
+            claim_all(Pubkey::new_unique(), claim_tuple(owner, strategy_pda())).unwrap_err(),
+            ClaimError::InvalidPositionAccount.into()
+        );
+    }
+
+    #[test]
+    fn positions_with_nothing_accrued_are_skipped() {
+        let user = Pubkey::new_unique();
+        let accounts = claim_tuple(user, strategy_pda());
+        let position = accounts[1].clone();
+
+        // The stubbed clock reads time 0, the moment the positions were opened
+        let mut batch = accounts;
+        batch.extend(claim_tuple(user, strategy_pda()));
+        assert_eq!(claim_all(user, batch).unwrap(), 2);
+
+        let position = Account::<UserPosition>::try_from(Box::leak(Box::new(position))).unwrap();
+        assert_eq!(position.total_yield_claimed, 0);
+        assert_eq!(position.yield_tokens_minted, 1_000_000);
+    }
+
+    #[test]
+    fn destinations_must_hold_the_yield_token() {
+        let user = Pubkey::new_unique();
+        let mut accounts = claim_tuple(user, strategy_pda());
+        accounts[3] = token_account(Pubkey::new_unique(), user, 0).to_account_info();
+
+        assert_eq!(claim_all(user, accounts).unwrap_err(), ClaimError::WrongRecipientMint.into());
+    }
+
+    #[test]
+    fn batch_claims_every_position() {
+        let user = Pubkey::new_unique();
+        // Three strategies at 10%, 20% and 50% a year
+        let batch: Vec<_> = [(1, 1000), (2, 2000), (3, 5000)]
+            .into_iter()
+            .flat_map(|(strategy_id, apy)| strategy_tuple(user, strategy_pda_of(strategy_id), strategy_id, apy))
+            .collect();
+        let tuples: Vec<_> = batch.chunks(CLAIM_ALL_ACCOUNTS_PER_POSITION).map(<[_]>::to_vec).collect();
+
+        set_clock(YEAR);
+        assert_eq!(claim_all(user, batch).unwrap(), 3);
+
+        for (tuple, claimed) in tuples.into_iter().zip([100_000, 200_000, 500_000]) {
+            let position = UserPosition::try_deserialize(&mut &tuple[1].data.borrow()[..]).unwrap();
+            assert_eq!(position.total_yield_claimed, claimed);
+            assert_eq!(position.yield_tokens_minted, 1_000_000 + claimed);
+            let destination = InterfaceAccount::<TokenAccount>::try_from(Box::leak(Box::new(tuple[3].clone()))).unwrap();
+            // Deposited yield tokens plus the claim
+            assert_eq!(balance(&destination), 1_000_000 + claimed);
+        }
+    }
+}
@@ -0,0 +1,403 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, MintTo, mint_to, TransferChecked, transfer_checked};
+
+use crate::instructions::{
+    deposit_to_strategy::check_deposit_caps, rate_feed::accrue_strategy, token_fees::transfer_fee,
+};
+use crate::state::{rate_feed::RateFeed, strategy::Strategy, user_position::UserPosition};
+
+#[derive(Accounts)]
+#[instruction(strategy_id: u64)]
+pub struct CompoundYield<'info> {
+    /// Position owner, or a keeper for positions that opted into auto-compounding
+    pub caller: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"strategy", strategy_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = strategy.is_operational() @ CompoundError::StrategyNotActive
+    )]
+    pub strategy: Account<'info, Strategy>,
+
+    #[account(
+        mut,
+        seeds = [b"user_position", user_position.user.as_ref(), strategy.key().as_ref()],
+        bump
+    )]
+    pub user_position: Account<'info, UserPosition>,
+
+    /// Yield token mint for this strategy, the keeper fee is minted from it
+    #[account(
+        mut,
+        address = strategy.yield_token_mint @ CompoundError::WrongYieldTokenMint
+    )]
+    pub yield_token_mint: InterfaceAccount<'info, Mint>,
+
+    /// Keeper's yield token account, only needed when a keeper fee is due
+    #[account(
+        mut,
+        constraint = keeper_yield_token_account.mint == yield_token_mint.key() @ CompoundError::WrongYieldTokenMint
+    )]
+    pub keeper_yield_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Token program of the yield token mint
+    pub token_program: Interface<'info, TokenInterface>,
+
+    /// Rate feed of oracle-rate strategies, omitted for fixed-rate strategies
+    pub rate_feed: Option<Account<'info, RateFeed>>,
+
+    /// Strategy's vault for underlying tokens, receives the compounded yield
+    #[account(
+        mut,
+        seeds = [b"strategy_vault", strategy_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub strategy_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Reserve funding the compounded yield
+    #[account(
+        mut,
+        seeds = [b"reward_reserve", strategy_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub reward_reserve: InterfaceAccount<'info, TokenAccount>,
+
+    /// The underlying token mint
+    #[account(
+        address = strategy.underlying_token @ CompoundError::WrongUnderlyingToken
+    )]
+    pub underlying_token_mint: InterfaceAccount<'info, Mint>,
+
+    /// Token program of the underlying token
+    pub underlying_token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(strategy_id: u64)]
+pub struct SetAutoCompound<'info> {
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [b"strategy", strategy_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub strategy: Account<'info, Strategy>,
+
+    #[account(
+        mut,
+        seeds = [b"user_position", user.key().as_ref(), strategy.key().as_ref()],
+        bump,
+        constraint = user_position.user == user.key() @ CompoundError::UnauthorizedUser
+    )]
+    pub user_position: Account<'info, UserPosition>,
+}
+
+/// Settle the position's accrued yield and add it to its principal instead of minting
+/// yield tokens, moving the underlying from the reward reserve into the vault. Keepers
+/// compounding an opted-in position earn the strategy's keeper fee out of the yield,
+/// minted to them as yield tokens.
+pub fn handle_compound_yield(ctx: Context<CompoundYield>, strategy_id: u64) -> Result<u64> {
+    let strategy = &mut ctx.accounts.strategy;
+    let user_position = &mut ctx.accounts.user_position;
+    let current_time = Clock::get()?.unix_timestamp;
+
+    let is_owner = ctx.accounts.caller.key() == user_position.user;
+    require!(is_owner || user_position.auto_compound, CompoundError::NotOptedIn);
+
+    accrue_strategy(strategy, ctx.accounts.rate_feed.as_ref(), current_time)?;
+
+    let yield_amount = user_position.claimable_yield(strategy, current_time);
+    require!(yield_amount > 0, CompoundError::NoYieldToCompound);
+
+    let keeper_fee = if is_owner { 0 } else { strategy.keeper_fee(yield_amount) };
+    let compounded = yield_amount - keeper_fee;
+    require!(ctx.accounts.reward_reserve.amount >= compounded, CompoundError::InsufficientReserve);
+
+    // The principal grows by what reaches the vault, like a deposit it has to fit the caps
+    let credited_amount = compounded - transfer_fee(&ctx.accounts.underlying_token_mint, compounded)?;
+    check_deposit_caps(strategy, user_position, credited_amount)?;
+
+    apply_compound(strategy, user_position, yield_amount, credited_amount, current_time);
+
+    let strategy_id_bytes = strategy_id.to_le_bytes();
+    let signer_seeds: &[&[u8]] = &[b"strategy", strategy_id_bytes.as_ref(), &[ctx.bumps.strategy]];
+    let seeds: &[&[&[u8]]] = &[signer_seeds];
+
+    // Back the new principal with underlying from the reward reserve
+    if compounded > 0 {
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.underlying_token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.reward_reserve.to_account_info(),
+                mint: ctx.accounts.underlying_token_mint.to_account_info(),
+                to: ctx.accounts.strategy_vault.to_account_info(),
+                authority: strategy.to_account_info(),
+            },
+            seeds,
+        );
+        transfer_checked(cpi_ctx, compounded, ctx.accounts.underlying_token_mint.decimals)?;
+    }
+
+    if keeper_fee > 0 {
+        let keeper_account = ctx
+            .accounts
+            .keeper_yield_token_account
+            .as_ref()
+            .ok_or(CompoundError::MissingKeeperAccount)?;
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            MintTo {
+                mint: ctx.accounts.yield_token_mint.to_account_info(),
+                to: keeper_account.to_account_info(),
+                authority: strategy.to_account_info(),
+            },
+            seeds,
+        );
+        mint_to(cpi_ctx, keeper_fee)?;
+    }
+
+    msg!(
+        "Compounded {} yield into position of {} in strategy '{}' ({} keeper fee)",
+        credited_amount,
+        user_position.user,
+        strategy.name,
+        keeper_fee
+    );
+
+    Ok(credited_amount)
+}
+
+/// Settle `yield_amount` like a claim, then grow the principal by `credited_amount`.
+/// The strategy index has to be accrued first.
+pub(crate) fn apply_compound(
+    strategy: &mut Account<Strategy>,
+    user_position: &mut Account<UserPosition>,
+    yield_amount: u64,
+    credited_amount: u64,
+    current_time: i64,
+) {
+    user_position.last_yield_claim = current_time;
+    user_position.settle_yield(strategy);
+    user_position.pending_yield = 0;
+    user_position.total_yield_claimed += yield_amount;
+    user_position.deposited_amount += credited_amount;
+    strategy.total_deposits += credited_amount;
+}
+
+pub fn handle_set_auto_compound(ctx: Context<SetAutoCompound>, _strategy_id: u64, enabled: bool) -> Result<()> {
+    let user_position = &mut ctx.accounts.user_position;
+    user_position.auto_compound = enabled;
+
+    msg!(
+        "Auto-compounding {} for {} in strategy '{}'",
+        if enabled { "enabled" } else { "disabled" },
+        user_position.user,
+        ctx.accounts.strategy.name
+    );
+
+    Ok(())
+}
+
+#[error_code]
+pub enum CompoundError {
+    #[msg("Strategy is not active")]
+    StrategyNotActive,
+    #[msg("Unauthorized user for this position")]
+    UnauthorizedUser,
+    #[msg("Wrong yield token mint")]
+    WrongYieldTokenMint,
+    #[msg("Position has not opted into auto-compounding")]
+    NotOptedIn,
+    #[msg("No yield to compound")]
+    NoYieldToCompound,
+    #[msg("Keeper yield token account is required to pay the keeper fee")]
+    MissingKeeperAccount,
+    #[msg("Wrong underlying token for this strategy")]
+    WrongUnderlyingToken,
+    #[msg("Reward reserve cannot cover the compounded yield")]
+    InsufficientReserve,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use anchor_spl::token::spl_token;
+
+    use crate::instructions::deposit_to_strategy::DepositError;
+    use crate::test_utils::{
+        balance, program_account, program_account_at, program_info, set_clock, signer_info, token_account, yield_mint,
+        zeroed, LendingStrategy,
+    };
+
+    const YEAR: i64 = 365 * 24 * 60 * 60;
+
+    fn strategy(keeper_fee_bps: u16) -> Strategy {
+        let mut strategy: Strategy = zeroed();
+        strategy.apy = 1000;
+        strategy.keeper_fee_bps = keeper_fee_bps;
+        strategy.total_deposits = 1_000_000;
+        strategy
+    }
+
+    fn position() -> UserPosition {
+        let mut position: UserPosition = zeroed();
+        position.deposited_amount = 1_000_000;
+        position.yield_tokens_minted = 1_000_000;
+        position
+    }
+
+    #[test]
+    fn compounded_yield_earns_yield() {
+        let mut strategy = program_account(&strategy(0));
+        let mut position = program_account(&position());
+
+        let yield_amount = position.claimable_yield(&strategy, YEAR);
+        assert_eq!(yield_amount, 100_000);
+        apply_compound(&mut strategy, &mut position, yield_amount, yield_amount, YEAR);
+
+        assert_eq!(position.deposited_amount, 1_100_000);
+        assert_eq!(position.total_yield_claimed, 100_000);
+        assert_eq!(position.last_yield_claim, YEAR);
+        assert_eq!(strategy.total_deposits, 1_100_000);
+        // Yield tokens are not minted for compounded principal
+        assert_eq!(position.yield_tokens_minted, 1_000_000);
+
+        // The second year earns on the grown principal
+        assert_eq!(position.claimable_yield(&strategy, 2 * YEAR), 110_000);
+    }
+
+    #[test]
+    fn compounding_clears_settled_top_up_yield() {
+        let mut strategy = program_account(&strategy(0));
+        let mut position = program_account(&position());
+        position.pending_yield = 5_000;
+
+        let yield_amount = position.claimable_yield(&strategy, YEAR);
+        assert_eq!(yield_amount, 105_000);
+        apply_compound(&mut strategy, &mut position, yield_amount, yield_amount, YEAR);
+
+        assert_eq!(position.pending_yield, 0);
+        assert_eq!(position.deposited_amount, 1_105_000);
+        assert_eq!(position.claimable_yield(&strategy, YEAR), 0);
+    }
+
+    #[test]
+    fn keeper_fee_comes_out_of_the_compounded_yield() {
+        let mut strategy = program_account(&strategy(250));
+        let mut position = program_account(&position());
+
+        let yield_amount = position.claimable_yield(&strategy, YEAR);
+        let keeper_fee = strategy.keeper_fee(yield_amount);
+        apply_compound(&mut strategy, &mut position, yield_amount, yield_amount - keeper_fee, YEAR);
+
+        assert_eq!(keeper_fee, 2_500);
+        assert_eq!(position.deposited_amount, 1_097_500);
+        assert_eq!(position.total_yield_claimed, 100_000);
+    }
+
+    /// The owner compounding a year of their 1 token position at 10% in a strategy
+    /// whose reserve holds `reserve`
+    fn compound_accounts(keeper_fee_bps: u16, max_deposit_per_user: u64, reserve: u64) -> (CompoundYield<'static>, u8) {
+        set_clock(YEAR);
+        let mut lending = LendingStrategy::new(1_000_000, 0, 0);
+        let yield_token_mint = yield_mint(Pubkey::new_unique(), lending.strategy.key(), 1_000_000, 6);
+        let mut state = strategy(keeper_fee_bps);
+        state.is_active = true;
+        state.max_deposit_per_user = max_deposit_per_user;
+        state.underlying_token = lending.mint.key();
+        state.yield_token_mint = yield_token_mint.key();
+        lending.strategy = program_account_at(lending.strategy.key(), &state);
+        let user = Pubkey::new_unique();
+        let mut user_position = position();
+        user_position.user = user;
+
+        let accounts = CompoundYield {
+            caller: Signer::try_from(signer_info(user)).unwrap(),
+            strategy: lending.strategy.clone(),
+            user_position: program_account(&user_position),
+            yield_token_mint,
+            keeper_yield_token_account: None,
+            token_program: Interface::try_from(program_info(spl_token::ID)).unwrap(),
+            rate_feed: None,
+            strategy_vault: lending.vault.clone(),
+            reward_reserve: token_account(lending.mint.key(), lending.strategy.key(), reserve),
+            underlying_token_mint: lending.mint.clone(),
+            underlying_token_program: Interface::try_from(program_info(spl_token::ID)).unwrap(),
+        };
+        (accounts, lending.bump)
+    }
+
+    fn compound(accounts: &mut CompoundYield<'static>, bump: u8) -> Result<u64> {
+        let bumps = CompoundYieldBumps { strategy: bump, ..Default::default() };
+        handle_compound_yield(Context::new(&crate::ID, accounts, &[], bumps), LendingStrategy::STRATEGY_ID)
+    }
+
+    /// Switch the caller to a keeper, with a yield token account for its fee when `with_keeper_account` is set
+    fn as_keeper(accounts: &mut CompoundYield<'static>, with_keeper_account: bool) {
+        let keeper = Pubkey::new_unique();
+        accounts.caller = Signer::try_from(signer_info(keeper)).unwrap();
+        accounts.keeper_yield_token_account =
+            with_keeper_account.then(|| token_account(accounts.yield_token_mint.key(), keeper, 0));
+    }
+
+    #[test]
+    fn owners_compound_yield_the_reserve_backs() {
+        let (mut accounts, bump) = compound_accounts(0, 0, 99_999);
+        assert_eq!(compound(&mut accounts, bump).unwrap_err(), CompoundError::InsufficientReserve.into());
+
+        let (mut accounts, bump) = compound_accounts(0, 0, 1_000_000);
+        assert_eq!(compound(&mut accounts, bump).unwrap(), 100_000);
+        assert_eq!(accounts.user_position.deposited_amount, 1_100_000);
+        assert_eq!(accounts.user_position.yield_tokens_minted, 1_000_000);
+        assert_eq!(balance(&accounts.reward_reserve), 900_000);
+        assert_eq!(balance(&accounts.strategy_vault), 1_100_000);
+    }
+
+    #[test]
+    fn keepers_compound_opted_in_positions_for_a_fee() {
+        let (mut accounts, bump) = compound_accounts(1000, 0, 1_000_000);
+        let owner = accounts.caller.clone();
+        as_keeper(&mut accounts, true);
+        assert_eq!(compound(&mut accounts, bump).unwrap_err(), CompoundError::NotOptedIn.into());
+
+        let mut opt_in = SetAutoCompound {
+            user: owner,
+            strategy: accounts.strategy.clone(),
+            user_position: accounts.user_position.clone(),
+        };
+        handle_set_auto_compound(
+            Context::new(&crate::ID, &mut opt_in, &[], SetAutoCompoundBumps::default()),
+            LendingStrategy::STRATEGY_ID,
+            true,
+        )
+        .unwrap();
+        accounts.user_position = opt_in.user_position;
+
+        // 10% of the year's 100_000 goes to the keeper as yield tokens
+        assert_eq!(compound(&mut accounts, bump).unwrap(), 90_000);
+        assert_eq!(accounts.user_position.deposited_amount, 1_090_000);
+        assert_eq!(accounts.user_position.total_yield_claimed, 100_000);
+        assert_eq!(balance(accounts.keeper_yield_token_account.as_ref().unwrap()), 10_000);
+        assert_eq!(balance(&accounts.reward_reserve), 910_000);
+    }
+
+    #[test]
+    fn keeper_fees_need_a_keeper_account() {
+        let (mut accounts, bump) = compound_accounts(1000, 0, 1_000_000);
+        accounts.user_position.auto_compound = true;
+        as_keeper(&mut accounts, false);
+
+        assert_eq!(compound(&mut accounts, bump).unwrap_err(), CompoundError::MissingKeeperAccount.into());
+    }
+
+    #[test]
+    fn compounded_principal_fits_the_deposit_caps() {
+        let (mut accounts, bump) = compound_accounts(0, 1_050_000, 1_000_000);
+
+        assert_eq!(compound(&mut accounts, bump).unwrap_err(), DepositError::UserCapExceeded.into());
+        assert_eq!(balance(&accounts.reward_reserve), 1_000_000);
+    }
+}
@@ -1,7 +1,7 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::Mint;
+use anchor_spl::token_interface::Mint;
 
-use crate::state::{strategy::Strategy, marketplace::{Marketplace, MarketplaceCounter}};
+use crate::state::{strategy::Strategy, marketplace::{Marketplace, MarketplaceCounter, PriceObservation}};
 
 #[derive(Accounts)]
 #[instruction(strategy_id: u64, marketplace_id: u64)]
@@ -20,11 +20,11 @@ pub struct CreateMarketplace<'info> {
     #[account(
         init,
         payer = admin,
-        seeds = [b"marketplace", strategy.key().as_ref()],
+        seeds = [b"marketplace", strategy.key().as_ref(), quote_mint.key().as_ref()],
         bump,
         space = 8 + Marketplace::INIT_SPACE
     )]
-    pub marketplace: Account<'info, Marketplace>,
+    pub marketplace: Box<Account<'info, Marketplace>>,
 
     #[account(
         init_if_needed,
@@ -39,13 +39,13 @@ pub struct CreateMarketplace<'info> {
     #[account(
         address = strategy.yield_token_mint @ MarketplaceError::WrongYieldTokenMint
     )]
-    pub yield_token_mint: Account<'info, Mint>,
+    pub yield_token_mint: InterfaceAccount<'info, Mint>,
 
-    /// Underlying token mint
+    /// Quote token mint the yield tokens are priced in (e.g. the underlying or USDC)
     #[account(
-        address = strategy.underlying_token @ MarketplaceError::WrongUnderlyingToken
+        constraint = quote_mint.key() != yield_token_mint.key() @ MarketplaceError::InvalidQuoteMint
     )]
-    pub underlying_token_mint: Account<'info, Mint>,
+    pub quote_mint: InterfaceAccount<'info, Mint>,
 
     pub system_program: Program<'info, System>,
     pub rent: Sysvar<'info, Rent>,
@@ -56,8 +56,14 @@ pub fn handle_create_marketplace(
     _strategy_id: u64,
     marketplace_id: u64,
     trading_fee_bps: u16,
+    tick_size: u64,
+    min_order_size: u64,
 ) -> Result<()> {
-    require!(trading_fee_bps <= 1000, MarketplaceError::FeeTooHigh); // Max 10% fee
+    require!(
+        trading_fee_bps <= Marketplace::MAX_TRADING_FEE_BPS,
+        MarketplaceError::FeeTooHigh
+    ); // Max 10% fee
+    require!(tick_size > 0, MarketplaceError::InvalidTickSize);
 
     let marketplace = &mut ctx.accounts.marketplace;
     let counter = &mut ctx.accounts.marketplace_counter;
@@ -67,7 +73,7 @@ pub fn handle_create_marketplace(
     marketplace.admin = ctx.accounts.admin.key();
     marketplace.strategy = strategy.key();
     marketplace.yield_token_mint = ctx.accounts.yield_token_mint.key();
-    marketplace.underlying_token_mint = ctx.accounts.underlying_token_mint.key();
+    marketplace.quote_mint = ctx.accounts.quote_mint.key();
     marketplace.total_volume = 0;
     marketplace.total_trades = 0;
     marketplace.best_bid_price = 0;
@@ -76,13 +82,26 @@ pub fn handle_create_marketplace(
     marketplace.is_active = true;
     marketplace.created_at = current_time;
     marketplace.marketplace_id = marketplace_id;
+    marketplace.open_orders = 0;
+    marketplace.auction_interval = 0;
+    marketplace.auction_epoch = 0;
+    marketplace.auction_epoch_start = current_time;
+    marketplace.self_trade_behavior = Marketplace::STP_CANCEL_NEWEST;
+    marketplace.tick_size = tick_size;
+    marketplace.min_order_size = min_order_size;
+    marketplace.last_trade_price = 0;
+    marketplace.last_trade_at = 0;
+    marketplace.cumulative_price = 0;
+    marketplace.observations = [PriceObservation::default(); Marketplace::MAX_OBSERVATIONS];
+    marketplace.observation_index = 0;
 
     // Increment counter
     counter.count += 1;
 
     msg!(
-        "Marketplace created for strategy '{}' with {}% trading fee",
+        "Marketplace created for strategy '{}' quoted in {} with {}% trading fee",
         strategy.name,
+        marketplace.quote_mint,
         trading_fee_bps as f64 / 100.0
     );
 
@@ -97,8 +116,88 @@ pub enum MarketplaceError {
     StrategyNotActive,
     #[msg("Wrong yield token mint")]
     WrongYieldTokenMint,
-    #[msg("Wrong underlying token mint")]
-    WrongUnderlyingToken,
+    #[msg("Quote mint cannot be the yield token mint")]
+    InvalidQuoteMint,
     #[msg("Trading fee too high (max 10%)")]
     FeeTooHigh,
-} 
\ No newline at end of file
+    #[msg("Tick size must be greater than zero")]
+    InvalidTickSize,
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_utils::{
+        mint_account, program_account, program_info, rent_sysvar, signer_info, stub_syscalls, zeroed,
+    };
+
+    fn listed_strategy() -> Account<'static, Strategy> {
+        let mut strategy: Strategy = zeroed();
+        strategy.admin = Pubkey::new_unique();
+        strategy.is_active = true;
+        strategy.yield_token_mint = Pubkey::new_unique();
+        program_account(&strategy)
+    }
+
+    /// List the yield tokens of `strategy` against `quote_mint`
+    fn list(
+        strategy: &Account<'static, Strategy>,
+        quote_mint: Pubkey,
+        tick_size: u64,
+        min_order_size: u64,
+    ) -> Result<Marketplace> {
+        stub_syscalls();
+        let mut accounts = CreateMarketplace {
+            admin: Signer::try_from(signer_info(strategy.admin)).unwrap(),
+            strategy: strategy.clone(),
+            marketplace: Box::new(program_account(&zeroed::<Marketplace>())),
+            marketplace_counter: program_account(&zeroed::<MarketplaceCounter>()),
+            yield_token_mint: mint_account(strategy.yield_token_mint, 6),
+            quote_mint: mint_account(quote_mint, 6),
+            system_program: Program::try_from(program_info(System::id())).unwrap(),
+            rent: rent_sysvar(),
+        };
+        handle_create_marketplace(
+            Context::new(&crate::ID, &mut accounts, &[], CreateMarketplaceBumps::default()),
+            1,
+            1,
+            30,
+            tick_size,
+            min_order_size,
+        )?;
+        Ok(Marketplace::clone(&accounts.marketplace))
+    }
+
+    fn create_marketplace(tick_size: u64, min_order_size: u64) -> Result<Marketplace> {
+        list(&listed_strategy(), Pubkey::new_unique(), tick_size, min_order_size)
+    }
+
+    #[test]
+    fn marketplace_starts_with_the_order_rules() {
+        let marketplace = create_marketplace(10_000, 1_000_000).unwrap();
+
+        assert_eq!(marketplace.tick_size, 10_000);
+        assert_eq!(marketplace.min_order_size, 1_000_000);
+        assert_eq!(marketplace.trading_fee_bps, 30);
+        assert!(marketplace.is_active);
+    }
+
+    #[test]
+    fn tick_size_cannot_be_zero() {
+        assert_eq!(
+            create_marketplace(0, 1_000_000).err(),
+            Some(MarketplaceError::InvalidTickSize.into())
+        );
+    }
+
+    #[test]
+    fn a_strategy_lists_against_several_quote_mints() {
+        let strategy = listed_strategy();
+        let (usdc, sol) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        let usdc_market = list(&strategy, usdc, 1, 0).unwrap();
+        let sol_market = list(&strategy, sol, 1, 0).unwrap();
+        assert_eq!((usdc_market.quote_mint, sol_market.quote_mint), (usdc, sol));
+        assert_eq!(usdc_market.yield_token_mint, sol_market.yield_token_mint);
+    }
+}
@@ -1,5 +1,14 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Mint, Token};
+use anchor_lang::system_program::{create_account, CreateAccount};
+use anchor_spl::token_2022::{self, initialize_mint2, InitializeMint2};
+use anchor_spl::token_2022::spl_token_2022::extension::ExtensionType;
+use anchor_spl::token_2022_extensions::spl_pod::optional_keys::OptionalNonZeroPubkey;
+use anchor_spl::token_2022_extensions::spl_token_metadata_interface::state::TokenMetadata;
+use anchor_spl::token_2022_extensions::{
+    metadata_pointer_initialize, token_metadata_initialize, MetadataPointerInitialize,
+    TokenMetadataInitialize,
+};
+use anchor_spl::token_interface::{find_mint_account_size, Mint, TokenInterface};
 
 use crate::state::{strategy::Strategy, strategy::StrategyCounter};
 
@@ -26,19 +35,19 @@ pub struct CreateStrategy<'info> {
     pub strategy_counter: Account<'info, StrategyCounter>,
 
     /// The underlying token mint (SOL, USDC, etc.)
-    pub underlying_token: Account<'info, Mint>,
+    pub underlying_token: InterfaceAccount<'info, Mint>,
 
+    /// CHECK: Yield token mint, created in the handler so that Token-2022 mints can
+    /// be sized for their metadata extension before initialization
     #[account(
-        init,
-        payer = admin,
+        mut,
         seeds = [b"yield_token", strategy_id.to_le_bytes().as_ref()],
-        bump,
-        mint::decimals = underlying_token.decimals,
-        mint::authority = strategy,
+        bump
     )]
-    pub yield_token_mint: Account<'info, Mint>,
+    pub yield_token_mint: UncheckedAccount<'info>,
 
-    pub token_program: Program<'info, Token>,
+    /// Token program the yield token mint is created under (SPL Token or Token-2022)
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
     pub rent: Sysvar<'info, Rent>,
 }
@@ -48,9 +57,18 @@ pub fn handle_create_strategy(
     name: String,
     apy_basis_points: u16,
     strategy_id: u64,
+    symbol: String,
+    uri: String,
 ) -> Result<()> {
     require!(name.len() <= 64, CustomError::NameTooLong);
     require!(apy_basis_points <= 50000, CustomError::ApyTooHigh); // Max 500% APY
+    require!(
+        !symbol.is_empty() && symbol.len() <= Strategy::MAX_SYMBOL_LEN,
+        CustomError::InvalidSymbol
+    );
+    require!(uri.len() <= Strategy::MAX_URI_LEN, CustomError::UriTooLong);
+
+    create_yield_token_mint(&ctx, &name, symbol, uri, strategy_id)?;
 
     let strategy = &mut ctx.accounts.strategy;
     let counter = &mut ctx.accounts.strategy_counter;
@@ -65,6 +83,11 @@ pub fn handle_create_strategy(
     strategy.created_at = Clock::get()?.unix_timestamp;
     strategy.total_yield_tokens_minted = 0;
     strategy.strategy_id = strategy_id;
+    strategy.rate_mode = Strategy::RATE_MODE_FIXED;
+    strategy.reward_index = 0;
+    strategy.last_index_update = strategy.created_at;
+    strategy.cumulative_rate_snapshot = 0;
+    strategy.accrual_mode = Strategy::ACCRUAL_SIMPLE;
 
     // Increment counter for tracking
     counter.count += 1;
@@ -105,6 +128,110 @@ pub fn handle_initialize_protocol(ctx: Context<InitializeProtocol>) -> Result<()
     Ok(())
 }
 
+/// Creates the yield token mint with the strategy as mint authority. Under Token-2022 the
+/// mint also carries its own metadata (e.g. "yUSDC-Yieldos Staking") through the metadata
+/// pointer and token metadata extensions, with the strategy as update authority.
+fn create_yield_token_mint(
+    ctx: &Context<CreateStrategy>,
+    name: &str,
+    symbol: String,
+    uri: String,
+    strategy_id: u64,
+) -> Result<()> {
+    let mint = &ctx.accounts.yield_token_mint;
+    let strategy_key = ctx.accounts.strategy.key();
+    let token_program = &ctx.accounts.token_program;
+    let with_metadata = token_program.key() == token_2022::ID;
+
+    let (mint_size, metadata) = if with_metadata {
+        let metadata = TokenMetadata {
+            update_authority: OptionalNonZeroPubkey::try_from(Some(strategy_key))?,
+            mint: mint.key(),
+            name: format!("{}-{}", symbol, name),
+            symbol,
+            uri,
+            additional_metadata: vec![],
+        };
+        let size = find_mint_account_size(Some(&vec![ExtensionType::MetadataPointer]))?;
+        (size, Some(metadata))
+    } else {
+        msg!("Yield token mint created without metadata (token program has no metadata extension)");
+        (find_mint_account_size(None)?, None)
+    };
+
+    // The metadata TLV is appended by the token program on initialization, so the
+    // account is funded for it up front but only allocated for the mint itself
+    let metadata_size = match &metadata {
+        Some(metadata) => metadata.tlv_size_of()?,
+        None => 0,
+    };
+    let lamports = Rent::get()?.minimum_balance(mint_size + metadata_size);
+
+    let id_bytes = strategy_id.to_le_bytes();
+    let mint_seeds: &[&[u8]] = &[b"yield_token", id_bytes.as_ref(), &[ctx.bumps.yield_token_mint]];
+    create_account(
+        CpiContext::new_with_signer(
+            ctx.accounts.system_program.to_account_info(),
+            CreateAccount {
+                from: ctx.accounts.admin.to_account_info(),
+                to: mint.to_account_info(),
+            },
+            &[mint_seeds],
+        ),
+        lamports,
+        mint_size as u64,
+        &token_program.key(),
+    )?;
+
+    if metadata.is_some() {
+        metadata_pointer_initialize(
+            CpiContext::new(
+                token_program.to_account_info(),
+                MetadataPointerInitialize {
+                    token_program_id: token_program.to_account_info(),
+                    mint: mint.to_account_info(),
+                },
+            ),
+            Some(strategy_key),
+            Some(mint.key()),
+        )?;
+    }
+
+    initialize_mint2(
+        CpiContext::new(
+            token_program.to_account_info(),
+            InitializeMint2 {
+                mint: mint.to_account_info(),
+            },
+        ),
+        ctx.accounts.underlying_token.decimals,
+        &strategy_key,
+        None,
+    )?;
+
+    if let Some(metadata) = metadata {
+        let strategy_seeds: &[&[u8]] = &[b"strategy", id_bytes.as_ref(), &[ctx.bumps.strategy]];
+        token_metadata_initialize(
+            CpiContext::new_with_signer(
+                token_program.to_account_info(),
+                TokenMetadataInitialize {
+                    program_id: token_program.to_account_info(),
+                    metadata: mint.to_account_info(),
+                    update_authority: ctx.accounts.strategy.to_account_info(),
+                    mint_authority: ctx.accounts.strategy.to_account_info(),
+                    mint: mint.to_account_info(),
+                },
+                &[strategy_seeds],
+            ),
+            metadata.name,
+            metadata.symbol,
+            metadata.uri,
+        )?;
+    }
+
+    Ok(())
+}
+
 // Custom errors for strategy creation
 #[error_code]
 pub enum CustomError {
@@ -112,4 +239,8 @@ pub enum CustomError {
     NameTooLong,
     #[msg("APY is too high (max 500%)")]
     ApyTooHigh,
+    #[msg("Yield token symbol must be 1 to 10 characters")]
+    InvalidSymbol,
+    #[msg("Yield token metadata URI is too long (max 200 characters)")]
+    UriTooLong,
 } 
\ No newline at end of file
@@ -1,8 +1,11 @@
 use anchor_lang::prelude::*;
 use anchor_spl::associated_token::AssociatedToken;
-use anchor_spl::token::{Mint, TokenAccount, Token, MintTo, mint_to, Transfer, transfer};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, MintTo, mint_to, TransferChecked, transfer_checked};
 
-use crate::state::{strategy::Strategy, user_position::UserPosition};
+use crate::adapters::{self, AdapterAccounts};
+use crate::instructions::token_fees::transfer_fee;
+use crate::instructions::rate_feed::accrue_strategy;
+use crate::state::{rate_feed::RateFeed, strategy::Strategy, user_position::UserPosition};
 
 #[derive(Accounts)]
 #[instruction(amount: u64, strategy_id: u64)]
@@ -18,8 +21,9 @@ pub struct DepositToStrategy<'info> {
     )]
     pub strategy: Account<'info, Strategy>,
 
+    /// Created on the first deposit, topped up by later ones
     #[account(
-        init,
+        init_if_needed,
         payer = user,
         seeds = [b"user_position", user.key().as_ref(), strategy.key().as_ref()],
         bump,
@@ -31,14 +35,14 @@ pub struct DepositToStrategy<'info> {
     #[account(
         address = strategy.underlying_token @ DepositError::WrongUnderlyingToken
     )]
-    pub underlying_token_mint: Account<'info, Mint>,
+    pub underlying_token_mint: InterfaceAccount<'info, Mint>,
 
     /// User's token account for the underlying token
     #[account(
         mut,
         constraint = user_underlying_token.mint == underlying_token_mint.key() @ DepositError::WrongUnderlyingToken
     )]
-    pub user_underlying_token: Account<'info, TokenAccount>,
+    pub user_underlying_token: InterfaceAccount<'info, TokenAccount>,
 
     /// Strategy's vault for holding underlying tokens
     #[account(
@@ -48,33 +52,41 @@ pub struct DepositToStrategy<'info> {
         bump,
         token::mint = underlying_token_mint,
         token::authority = strategy,
+        token::token_program = token_program,
     )]
-    pub strategy_vault: Account<'info, TokenAccount>,
+    pub strategy_vault: InterfaceAccount<'info, TokenAccount>,
 
     /// Yield token mint for this strategy
     #[account(
         mut,
         address = strategy.yield_token_mint @ DepositError::WrongYieldTokenMint
     )]
-    pub yield_token_mint: Account<'info, Mint>,
+    pub yield_token_mint: InterfaceAccount<'info, Mint>,
 
     /// User's yield token account (will be created if needed)
     #[account(
         init_if_needed,
         payer = user,
         associated_token::mint = yield_token_mint,
-        associated_token::authority = user
+        associated_token::authority = user,
+        associated_token::token_program = yield_token_program
     )]
-    pub user_yield_token_account: Account<'info, TokenAccount>,
+    pub user_yield_token_account: InterfaceAccount<'info, TokenAccount>,
 
-    pub token_program: Program<'info, Token>,
+    /// Token program of the underlying token
+    pub token_program: Interface<'info, TokenInterface>,
+    /// Token program of the yield token mint
+    pub yield_token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
     pub rent: Sysvar<'info, Rent>,
+
+    /// Rate feed of oracle-rate strategies, omitted for fixed-rate strategies
+    pub rate_feed: Option<Account<'info, RateFeed>>,
 }
 
-pub fn handle_deposit_to_strategy(
-    ctx: Context<DepositToStrategy>,
+pub fn handle_deposit_to_strategy<'info>(
+    ctx: Context<'_, '_, 'info, 'info, DepositToStrategy<'info>>,
     amount: u64,
     strategy_id: u64,
 ) -> Result<()> {
@@ -83,29 +95,50 @@ pub fn handle_deposit_to_strategy(
     let strategy = &mut ctx.accounts.strategy;
     let user_position = &mut ctx.accounts.user_position;
     let current_time = Clock::get()?.unix_timestamp;
+    accrue_strategy(strategy, ctx.accounts.rate_feed.as_ref(), current_time)?;
+
+    // Only what reaches the vault is credited, transfer-fee mints withhold part of it
+    let credited_amount = amount - transfer_fee(&ctx.accounts.underlying_token_mint, amount)?;
+    require!(credited_amount > 0, DepositError::InvalidAmount);
+    check_deposit_caps(strategy, user_position, credited_amount)?;
 
     // Transfer underlying tokens from user to strategy vault
     let cpi_ctx = CpiContext::new(
         ctx.accounts.token_program.to_account_info(),
-        Transfer {
+        TransferChecked {
             from: ctx.accounts.user_underlying_token.to_account_info(),
+            mint: ctx.accounts.underlying_token_mint.to_account_info(),
             to: ctx.accounts.strategy_vault.to_account_info(),
             authority: ctx.accounts.user.to_account_info(),
         },
     );
-    transfer(cpi_ctx, amount)?;
+    transfer_checked(cpi_ctx, amount, ctx.accounts.underlying_token_mint.decimals)?;
 
-    // Calculate initial yield tokens to mint based on current strategy
-    // For simplicity, we start with 1:1 ratio, but this could be more sophisticated
-    let yield_tokens_to_mint = amount;
+    let yield_tokens_to_mint = apply_deposit(
+        strategy,
+        user_position,
+        ctx.accounts.user.key(),
+        credited_amount,
+        current_time,
+    );
 
-    // Mint yield tokens to user
     let strategy_id_bytes = strategy_id.to_le_bytes();
     let signer_seeds: &[&[u8]] = &[b"strategy", strategy_id_bytes.as_ref(), &[ctx.bumps.strategy]];
     let seeds: &[&[&[u8]]] = &[signer_seeds];
 
+    // Deploy the deposit into the strategy's yield source, if it has one
+    let adapter_accounts = AdapterAccounts {
+        vault: ctx.accounts.strategy_vault.to_account_info(),
+        mint: ctx.accounts.underlying_token_mint.to_account_info(),
+        token_program: ctx.accounts.token_program.to_account_info(),
+        remaining_accounts: ctx.remaining_accounts,
+    };
+    adapters::deposit(strategy, &adapter_accounts, signer_seeds, credited_amount)?;
+
+    // Mint yield tokens to user
+
     let cpi_ctx = CpiContext::new_with_signer(
-        ctx.accounts.token_program.to_account_info(),
+        ctx.accounts.yield_token_program.to_account_info(),
         MintTo {
             mint: ctx.accounts.yield_token_mint.to_account_info(),
             to: ctx.accounts.user_yield_token_account.to_account_info(),
@@ -115,24 +148,10 @@ pub fn handle_deposit_to_strategy(
     );
     mint_to(cpi_ctx, yield_tokens_to_mint)?;
 
-    // Update user position
-    user_position.user = ctx.accounts.user.key();
-    user_position.strategy = strategy.key();
-    user_position.deposited_amount = amount;
-    user_position.yield_tokens_minted = yield_tokens_to_mint;
-    user_position.deposit_time = current_time;
-    user_position.last_yield_claim = current_time;
-    user_position.total_yield_claimed = 0;
-    user_position.position_id = strategy.total_deposits; // Simple position ID
-
-    // Update strategy stats
-    strategy.total_deposits += amount;
-    strategy.total_yield_tokens_minted += yield_tokens_to_mint;
-
     msg!(
         "User {} deposited {} tokens to strategy '{}' and received {} yield tokens",
         ctx.accounts.user.key(),
-        amount,
+        credited_amount,
         strategy.name,
         yield_tokens_to_mint
     );
@@ -140,6 +159,68 @@ pub fn handle_deposit_to_strategy(
     Ok(())
 }
 
+/// Reject deposits above the strategy-wide or per-user cap, logging the capacity left
+pub(crate) fn check_deposit_caps(
+    strategy: &Strategy,
+    user_position: &UserPosition,
+    amount: u64,
+) -> Result<()> {
+    let remaining = strategy.remaining_capacity();
+    if amount > remaining {
+        msg!("Strategy '{}' can take {} more tokens", strategy.name, remaining);
+        return err!(DepositError::StrategyCapExceeded);
+    }
+
+    let remaining = strategy.remaining_user_capacity(user_position.deposited_amount);
+    if amount > remaining {
+        msg!("User can deposit {} more tokens into strategy '{}'", remaining, strategy.name);
+        return err!(DepositError::UserCapExceeded);
+    }
+
+    Ok(())
+}
+
+/// Record a deposit on the position and strategy, returns the yield tokens to mint.
+/// The strategy index has to be accrued first.
+pub(crate) fn apply_deposit(
+    strategy: &mut Account<Strategy>,
+    user_position: &mut Account<UserPosition>,
+    user: Pubkey,
+    amount: u64,
+    current_time: i64,
+) -> u64 {
+    // Calculate initial yield tokens to mint based on current strategy
+    let yield_tokens_to_mint = strategy.yield_tokens_for_deposit(amount);
+
+    if user_position.user == Pubkey::default() {
+        // First deposit into a freshly created position
+        user_position.user = user;
+        user_position.strategy = strategy.key();
+        user_position.deposited_amount = amount;
+        user_position.yield_tokens_minted = yield_tokens_to_mint;
+        user_position.last_yield_claim = current_time;
+        user_position.total_yield_claimed = 0;
+        user_position.position_id = strategy.total_deposits; // Simple position ID
+        user_position.reward_index_snapshot = strategy.reward_index;
+        user_position.pending_yield = 0;
+        user_position.auto_compound = false;
+    } else {
+        // Top-up: yield earned so far stays owed at the old principal
+        user_position.settle_all_yield(strategy, current_time);
+        user_position.deposited_amount += amount;
+        user_position.yield_tokens_minted += yield_tokens_to_mint;
+    }
+    // The whole position is locked again from the latest deposit
+    user_position.deposit_time = current_time;
+    user_position.lock_until = current_time + strategy.lock_duration;
+
+    // Update strategy stats
+    strategy.total_deposits += amount;
+    strategy.total_yield_tokens_minted += yield_tokens_to_mint;
+
+    yield_tokens_to_mint
+}
+
 #[error_code]
 pub enum DepositError {
     #[msg("Strategy is not active")]
@@ -150,4 +231,195 @@ pub enum DepositError {
     WrongYieldTokenMint,
     #[msg("Invalid deposit amount")]
     InvalidAmount,
-} 
\ No newline at end of file
+    #[msg("Deposit exceeds the strategy's remaining capacity")]
+    StrategyCapExceeded,
+    #[msg("Deposit exceeds the user's remaining capacity in this strategy")]
+    UserCapExceeded,
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use anchor_spl::token::spl_token;
+    use anchor_spl::token_2022::spl_token_2022;
+
+    use crate::test_utils::{
+        balance, fee_mint, fee_token_account_at, mint_account, program_account, program_account_at, program_info,
+        rent_sysvar, set_clock, signer_info, token_account, yield_mint, zeroed,
+    };
+
+    const YEAR: i64 = 365 * 24 * 60 * 60;
+
+    fn capped_strategy(max_total_deposits: u64, max_deposit_per_user: u64, total_deposits: u64) -> Strategy {
+        let mut strategy: Strategy = zeroed();
+        strategy.apy = 1000;
+        strategy.max_total_deposits = max_total_deposits;
+        strategy.max_deposit_per_user = max_deposit_per_user;
+        strategy.total_deposits = total_deposits;
+        strategy
+    }
+
+    fn holding(deposited_amount: u64) -> UserPosition {
+        let mut position: UserPosition = zeroed();
+        position.deposited_amount = deposited_amount;
+        position
+    }
+
+    #[test]
+    fn deposits_fill_the_strategy_cap_exactly() {
+        let strategy = capped_strategy(1_000, 0, 600);
+
+        assert!(check_deposit_caps(&strategy, &holding(0), 400).is_ok());
+        assert_eq!(
+            check_deposit_caps(&strategy, &holding(0), 401).unwrap_err(),
+            DepositError::StrategyCapExceeded.into()
+        );
+    }
+
+    #[test]
+    fn user_cap_counts_the_existing_principal() {
+        let strategy = capped_strategy(0, 500, 10_000);
+
+        assert!(check_deposit_caps(&strategy, &holding(300), 200).is_ok());
+        assert_eq!(
+            check_deposit_caps(&strategy, &holding(300), 201).unwrap_err(),
+            DepositError::UserCapExceeded.into()
+        );
+    }
+
+    #[test]
+    fn caps_below_the_deposits_block_everything() {
+        let strategy = capped_strategy(500, 0, 600);
+        assert_eq!(
+            check_deposit_caps(&strategy, &holding(0), 1).unwrap_err(),
+            DepositError::StrategyCapExceeded.into()
+        );
+
+        let strategy = capped_strategy(0, 0, u64::MAX / 2);
+        assert!(check_deposit_caps(&strategy, &holding(u64::MAX / 2), u64::MAX / 2).is_ok());
+    }
+
+    #[test]
+    fn first_deposit_opens_the_position() {
+        let mut strategy = capped_strategy(0, 0, 0);
+        strategy.lock_duration = 3_600;
+        let mut strategy = program_account(&strategy);
+        let mut position = program_account(&zeroed::<UserPosition>());
+        let user = Pubkey::new_unique();
+
+        let minted = apply_deposit(&mut strategy, &mut position, user, 1_000_000, 100);
+
+        assert_eq!(minted, 1_000_000);
+        assert_eq!(position.user, user);
+        assert_eq!(position.strategy, strategy.key());
+        assert_eq!(position.deposited_amount, 1_000_000);
+        assert_eq!(position.yield_tokens_minted, 1_000_000);
+        assert_eq!(position.last_yield_claim, 100);
+        assert_eq!(position.lock_until, 3_700);
+        assert_eq!(strategy.total_deposits, 1_000_000);
+        assert_eq!(strategy.total_yield_tokens_minted, 1_000_000);
+    }
+
+    #[test]
+    fn top_up_settles_yield_at_the_old_principal() {
+        let mut strategy = program_account(&capped_strategy(0, 0, 0));
+        let mut position = program_account(&zeroed::<UserPosition>());
+        let user = Pubkey::new_unique();
+
+        apply_deposit(&mut strategy, &mut position, user, 1_000_000, 0);
+        apply_deposit(&mut strategy, &mut position, user, 3_000_000, YEAR);
+
+        // 10% on the first million for the year it stood alone
+        assert_eq!(position.pending_yield, 100_000);
+        assert_eq!(position.last_yield_claim, YEAR);
+        assert_eq!(position.deposited_amount, 4_000_000);
+        assert_eq!(position.yield_tokens_minted, 4_000_000);
+        assert_eq!(position.deposit_time, YEAR);
+        assert_eq!(strategy.total_deposits, 4_000_000);
+    }
+
+    /// Deposit accounts of strategy 1 in `underlying`, its depositor holding `wallet`
+    fn deposit_accounts(
+        strategy: Strategy,
+        underlying: InterfaceAccount<'static, Mint>,
+        wallet: u64,
+    ) -> (DepositToStrategy<'static>, u8) {
+        set_clock(100);
+        let user = Pubkey::new_unique();
+        let (strategy_key, strategy_bump) =
+            Pubkey::find_program_address(&[b"strategy", 1u64.to_le_bytes().as_ref()], &crate::ID);
+        let token_program = *underlying.to_account_info().owner;
+        let underlying_account = |owner, amount| match token_program {
+            spl_token_2022::ID => fee_token_account_at(Pubkey::new_unique(), underlying.key(), owner, amount),
+            _ => token_account(underlying.key(), owner, amount),
+        };
+        let yield_token_mint = yield_mint(Pubkey::new_unique(), strategy_key, 0, 6);
+        let accounts = DepositToStrategy {
+            user: Signer::try_from(signer_info(user)).unwrap(),
+            strategy: program_account_at(strategy_key, &strategy),
+            user_position: program_account(&zeroed::<UserPosition>()),
+            user_underlying_token: underlying_account(user, wallet),
+            strategy_vault: underlying_account(strategy_key, 0),
+            underlying_token_mint: underlying,
+            user_yield_token_account: token_account(yield_token_mint.key(), user, 0),
+            yield_token_mint,
+            token_program: Interface::try_from(program_info(token_program)).unwrap(),
+            yield_token_program: Interface::try_from(program_info(spl_token::ID)).unwrap(),
+            associated_token_program: Program::try_from(program_info(anchor_spl::associated_token::ID)).unwrap(),
+            system_program: Program::try_from(program_info(System::id())).unwrap(),
+            rent: rent_sysvar(),
+            rate_feed: None,
+        };
+        (accounts, strategy_bump)
+    }
+
+    fn deposit(accounts: &mut DepositToStrategy<'static>, strategy_bump: u8, amount: u64) -> Result<()> {
+        let bumps = DepositToStrategyBumps { strategy: strategy_bump, ..Default::default() };
+        handle_deposit_to_strategy(Context::new(&crate::ID, accounts, &[], bumps), amount, 1)
+    }
+
+    #[test]
+    fn fee_mint_deposits_credit_what_reached_the_vault() {
+        let underlying = fee_mint(Pubkey::new_unique(), 100, u64::MAX);
+        let (mut accounts, bump) = deposit_accounts(capped_strategy(0, 0, 0), underlying, 1_000_000);
+
+        deposit(&mut accounts, bump, 1_000_000).unwrap();
+
+        // 1% of the deposit is withheld on the way into the vault
+        assert_eq!(balance(&accounts.strategy_vault), 990_000);
+        assert_eq!(balance(&accounts.user_yield_token_account), 990_000);
+        assert_eq!(accounts.user_position.deposited_amount, 990_000);
+        assert_eq!(accounts.strategy.total_deposits, 990_000);
+    }
+
+    #[test]
+    fn deposits_over_a_cap_move_nothing() {
+        // 1500 tokens in total, 1000 per user
+        let strategy = capped_strategy(1_500_000_000, 1_000_000_000, 0);
+        let (mut accounts, bump) = deposit_accounts(strategy, mint_account(Pubkey::new_unique(), 6), 3_000_000_000);
+
+        deposit(&mut accounts, bump, 600_000_000).unwrap();
+        assert_eq!(
+            deposit(&mut accounts, bump, 400_000_001).unwrap_err(),
+            DepositError::UserCapExceeded.into()
+        );
+        deposit(&mut accounts, bump, 400_000_000).unwrap();
+        assert_eq!(accounts.user_position.deposited_amount, 1_000_000_000);
+        assert_eq!(balance(&accounts.user_yield_token_account), 1_000_000_000);
+        assert_eq!(balance(&accounts.user_underlying_token), 2_000_000_000);
+
+        // Without the user cap the strategy cap still holds
+        accounts.strategy.max_deposit_per_user = 0;
+        assert_eq!(
+            deposit(&mut accounts, bump, 500_000_001).unwrap_err(),
+            DepositError::StrategyCapExceeded.into()
+        );
+        deposit(&mut accounts, bump, 500_000_000).unwrap();
+        assert_eq!(deposit(&mut accounts, bump, 1).unwrap_err(), DepositError::StrategyCapExceeded.into());
+
+        accounts.strategy.max_total_deposits = 0;
+        deposit(&mut accounts, bump, 1_000_000_000).unwrap();
+        assert_eq!(accounts.strategy.total_deposits, 2_500_000_000);
+        assert_eq!(balance(&accounts.strategy_vault), 2_500_000_000);
+    }
+}
@@ -1,7 +1,8 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{TokenAccount, Token, Transfer, transfer};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked};
 
-use crate::state::marketplace::{Marketplace, TradeOrder};
+use crate::instructions::{candles::record_candles, token_fees::amount_with_fee};
+use crate::state::{candles::CandleSeries, marketplace::{Marketplace, TradeOrder}};
 
 #[derive(Accounts)]
 #[instruction(trade_amount: u64)]
@@ -9,12 +10,18 @@ pub struct ExecuteTrade<'info> {
     #[account(mut)]
     pub executor: Signer<'info>,
 
-    #[account(mut)]
-    pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        mut,
+        constraint = marketplace.is_active @ TradeError::MarketplaceNotActive,
+        constraint = !marketplace.is_auction_mode() @ TradeError::AuctionModeActive
+    )]
+    pub marketplace: Box<Account<'info, Marketplace>>,
 
     /// Buy order
     #[account(
         mut,
+        seeds = [b"order", buy_order.user.as_ref(), buy_order.order_id.to_le_bytes().as_ref()],
+        bump,
         constraint = buy_order.order_type == TradeOrder::BUY_ORDER @ TradeError::InvalidBuyOrder,
         constraint = buy_order.is_fillable() @ TradeError::OrderNotFillable,
         constraint = buy_order.marketplace == marketplace.key() @ TradeError::OrderMarketplaceMismatch
@@ -24,6 +31,8 @@ pub struct ExecuteTrade<'info> {
     /// Sell order  
     #[account(
         mut,
+        seeds = [b"order", sell_order.user.as_ref(), sell_order.order_id.to_le_bytes().as_ref()],
+        bump,
         constraint = sell_order.order_type == TradeOrder::SELL_ORDER @ TradeError::InvalidSellOrder,
         constraint = sell_order.is_fillable() @ TradeError::OrderNotFillable,
         constraint = sell_order.marketplace == marketplace.key() @ TradeError::OrderMarketplaceMismatch,
@@ -31,13 +40,13 @@ pub struct ExecuteTrade<'info> {
     )]
     pub sell_order: Account<'info, TradeOrder>,
 
-    /// Buy order escrow (contains underlying tokens)
+    /// Buy order escrow (contains quote tokens)
     #[account(
         mut,
         seeds = [b"escrow", buy_order.key().as_ref()],
         bump
     )]
-    pub buy_order_escrow: Account<'info, TokenAccount>,
+    pub buy_order_escrow: InterfaceAccount<'info, TokenAccount>,
 
     /// Sell order escrow (contains yield tokens)
     #[account(
@@ -45,25 +54,78 @@ pub struct ExecuteTrade<'info> {
         seeds = [b"escrow", sell_order.key().as_ref()],
         bump
     )]
-    pub sell_order_escrow: Account<'info, TokenAccount>,
+    pub sell_order_escrow: InterfaceAccount<'info, TokenAccount>,
 
     /// Buyer's yield token account (receives yield tokens)
-    #[account(mut)]
-    pub buyer_yield_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = buyer_yield_token_account.owner == buy_order.user @ TradeError::WrongTokenAccount,
+        constraint = buyer_yield_token_account.mint == marketplace.yield_token_mint @ TradeError::WrongTokenAccount
+    )]
+    pub buyer_yield_token_account: InterfaceAccount<'info, TokenAccount>,
 
-    /// Buyer's underlying token account (for fee collection)
-    #[account(mut)]
-    pub buyer_underlying_token_account: Account<'info, TokenAccount>,
+    /// Buyer's quote token account
+    #[account(
+        mut,
+        constraint = buyer_quote_token_account.owner == buy_order.user @ TradeError::WrongTokenAccount,
+        constraint = buyer_quote_token_account.mint == marketplace.quote_mint @ TradeError::WrongTokenAccount
+    )]
+    pub buyer_quote_token_account: InterfaceAccount<'info, TokenAccount>,
 
-    /// Seller's underlying token account (receives payment)
-    #[account(mut)]
-    pub seller_underlying_token_account: Account<'info, TokenAccount>,
+    /// Seller's quote token account (receives payment)
+    #[account(
+        mut,
+        constraint = seller_quote_token_account.owner == sell_order.user @ TradeError::WrongTokenAccount,
+        constraint = seller_quote_token_account.mint == marketplace.quote_mint @ TradeError::WrongTokenAccount
+    )]
+    pub seller_quote_token_account: InterfaceAccount<'info, TokenAccount>,
 
-    /// Marketplace fee collection account
-    #[account(mut)]
-    pub fee_collection_account: Account<'info, TokenAccount>,
+    /// Marketplace fee collection account (quote tokens owned by the marketplace admin)
+    #[account(
+        mut,
+        constraint = fee_collection_account.owner == marketplace.admin @ TradeError::WrongTokenAccount,
+        constraint = fee_collection_account.mint == marketplace.quote_mint @ TradeError::WrongTokenAccount
+    )]
+    pub fee_collection_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        address = marketplace.yield_token_mint @ TradeError::WrongTokenAccount
+    )]
+    pub yield_token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        address = marketplace.quote_mint @ TradeError::WrongTokenAccount
+    )]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+
+    /// One-minute candle series of the marketplace
+    #[account(
+        mut,
+        seeds = [b"candles", marketplace.key().as_ref(), CandleSeries::MINUTE.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub minute_candles: AccountLoader<'info, CandleSeries>,
 
-    pub token_program: Program<'info, Token>,
+    /// One-hour candle series of the marketplace
+    #[account(
+        mut,
+        seeds = [b"candles", marketplace.key().as_ref(), CandleSeries::HOUR.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub hour_candles: AccountLoader<'info, CandleSeries>,
+
+    /// One-day candle series of the marketplace
+    #[account(
+        mut,
+        seeds = [b"candles", marketplace.key().as_ref(), CandleSeries::DAY.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub day_candles: AccountLoader<'info, CandleSeries>,
+
+    /// Token program of the quote mint
+    pub token_program: Interface<'info, TokenInterface>,
+    /// Token program of the yield token mint
+    pub yield_token_program: Interface<'info, TokenInterface>,
 }
 
 pub fn handle_execute_trade(
@@ -83,66 +145,137 @@ pub fn handle_execute_trade(
 
     require!(actual_trade_amount > 0, TradeError::NoTradeableAmount);
 
+    // Escrow authorities are the order PDAs
+    let sell_user = sell_order.user;
+    let sell_order_id_bytes = sell_order.order_id.to_le_bytes();
+    let sell_order_seeds = &[
+        b"order",
+        sell_user.as_ref(),
+        sell_order_id_bytes.as_ref(),
+        &[ctx.bumps.sell_order],
+    ];
+    let sell_signer_seeds = &[sell_order_seeds.as_slice()];
+
+    let buy_user = buy_order.user;
+    let buy_order_id_bytes = buy_order.order_id.to_le_bytes();
+    let buy_order_seeds = &[
+        b"order",
+        buy_user.as_ref(),
+        buy_order_id_bytes.as_ref(),
+        &[ctx.bumps.buy_order],
+    ];
+    let buy_signer_seeds = &[buy_order_seeds.as_slice()];
+
+    // Self-trade prevention: a user's orders are never matched against each other
+    if buy_user == sell_user {
+        let (buy_reduction, sell_reduction) = self_trade_reductions(
+            marketplace.self_trade_behavior,
+            buy_order,
+            sell_order,
+            actual_trade_amount,
+        );
+
+        if buy_reduction > 0 {
+            let closed = reduce_order(
+                buy_order,
+                &ctx.accounts.buy_order_escrow,
+                &ctx.accounts.buyer_quote_token_account,
+                &ctx.accounts.quote_mint,
+                &ctx.accounts.token_program,
+                buy_signer_seeds,
+                buy_reduction,
+            )?;
+            if closed {
+                marketplace.open_orders -= 1;
+                if buy_order.price_per_token == marketplace.best_bid_price {
+                    marketplace.best_bid_price = 0; // Should recalculate from remaining orders
+                }
+            }
+        }
+        if sell_reduction > 0 {
+            // Buyer and seller are the same user, so their yield token account takes the refund
+            let closed = reduce_order(
+                sell_order,
+                &ctx.accounts.sell_order_escrow,
+                &ctx.accounts.buyer_yield_token_account,
+                &ctx.accounts.yield_token_mint,
+                &ctx.accounts.yield_token_program,
+                sell_signer_seeds,
+                sell_reduction,
+            )?;
+            if closed {
+                marketplace.open_orders -= 1;
+                if sell_order.price_per_token == marketplace.best_ask_price {
+                    marketplace.best_ask_price = 0; // Should recalculate from remaining orders
+                }
+            }
+        }
+
+        msg!(
+            "Self-trade prevented for user {}: buy order reduced by {}, sell order reduced by {}",
+            buy_user,
+            buy_reduction,
+            sell_reduction
+        );
+
+        return Ok(());
+    }
+
     // Use sell order price for execution (better for buyer)
     let execution_price = sell_order.price_per_token;
-    let total_payment = (actual_trade_amount as u128 * execution_price as u128 / 1_000_000) as u64;
+    let total_payment = Marketplace::trade_value(actual_trade_amount, execution_price);
 
     // Calculate trading fee
-    let fee_amount = (total_payment as u128 * marketplace.trading_fee_bps as u128 / 10000) as u64;
+    let fee_amount = marketplace.trading_fee(total_payment);
     let net_payment = total_payment - fee_amount;
 
-    // Transfer yield tokens from sell escrow to buyer (using escrow authority)
-    let sell_order_key = sell_order.key();
-    let sell_escrow_seeds = &[
-        b"escrow",
-        sell_order_key.as_ref(),
-        &[ctx.bumps.sell_order_escrow],
-    ];
-    let sell_signer_seeds = &[sell_escrow_seeds.as_slice()];
+    // Escrows were grossed up for this hop at placement, so recipients get the quoted amounts
+    let yield_transfer = amount_with_fee(&ctx.accounts.yield_token_mint, actual_trade_amount)?
+        .min(ctx.accounts.sell_order_escrow.amount);
+    let buy_escrow_amount = ctx.accounts.buy_order_escrow.amount;
+    let payment_transfer = amount_with_fee(&ctx.accounts.quote_mint, net_payment)?.min(buy_escrow_amount);
+    let fee_transfer = amount_with_fee(&ctx.accounts.quote_mint, fee_amount)?
+        .min(buy_escrow_amount - payment_transfer);
 
+    // Transfer yield tokens from sell escrow to buyer (signed by the sell order, the escrow authority)
     let cpi_ctx = CpiContext::new_with_signer(
-        ctx.accounts.token_program.to_account_info(),
-        Transfer {
+        ctx.accounts.yield_token_program.to_account_info(),
+        TransferChecked {
             from: ctx.accounts.sell_order_escrow.to_account_info(),
+            mint: ctx.accounts.yield_token_mint.to_account_info(),
             to: ctx.accounts.buyer_yield_token_account.to_account_info(),
-            authority: ctx.accounts.sell_order_escrow.to_account_info(),
+            authority: sell_order.to_account_info(),
         },
         sell_signer_seeds,
     );
-    transfer(cpi_ctx, actual_trade_amount)?;
-
-    // Transfer underlying tokens from buy escrow to seller (using escrow authority)
-    let buy_order_key = buy_order.key();
-    let buy_escrow_seeds = &[
-        b"escrow",
-        buy_order_key.as_ref(),
-        &[ctx.bumps.buy_order_escrow],
-    ];
-    let buy_signer_seeds = &[buy_escrow_seeds.as_slice()];
+    transfer_checked(cpi_ctx, yield_transfer, ctx.accounts.yield_token_mint.decimals)?;
 
+    // Transfer quote tokens from buy escrow to seller (signed by the buy order, the escrow authority)
     let cpi_ctx = CpiContext::new_with_signer(
         ctx.accounts.token_program.to_account_info(),
-        Transfer {
+        TransferChecked {
             from: ctx.accounts.buy_order_escrow.to_account_info(),
-            to: ctx.accounts.seller_underlying_token_account.to_account_info(),
-            authority: ctx.accounts.buy_order_escrow.to_account_info(),
+            mint: ctx.accounts.quote_mint.to_account_info(),
+            to: ctx.accounts.seller_quote_token_account.to_account_info(),
+            authority: buy_order.to_account_info(),
         },
         buy_signer_seeds,
     );
-    transfer(cpi_ctx, net_payment)?;
+    transfer_checked(cpi_ctx, payment_transfer, ctx.accounts.quote_mint.decimals)?;
 
     // Transfer fee to marketplace fee collection account
-    if fee_amount > 0 {
+    if fee_transfer > 0 {
         let cpi_ctx = CpiContext::new_with_signer(
             ctx.accounts.token_program.to_account_info(),
-            Transfer {
+            TransferChecked {
                 from: ctx.accounts.buy_order_escrow.to_account_info(),
+                mint: ctx.accounts.quote_mint.to_account_info(),
                 to: ctx.accounts.fee_collection_account.to_account_info(),
-                authority: ctx.accounts.buy_order_escrow.to_account_info(),
+                authority: buy_order.to_account_info(),
             },
             buy_signer_seeds,
         );
-        transfer(cpi_ctx, fee_amount)?;
+        transfer_checked(cpi_ctx, fee_transfer, ctx.accounts.quote_mint.decimals)?;
     }
 
     // Update order states
@@ -152,14 +285,24 @@ pub fn handle_execute_trade(
     // Mark orders as inactive if fully filled
     if buy_order.filled_amount >= buy_order.yield_token_amount {
         buy_order.is_active = false;
+        marketplace.open_orders -= 1;
     }
     if sell_order.filled_amount >= sell_order.yield_token_amount {
         sell_order.is_active = false;
+        marketplace.open_orders -= 1;
     }
 
     // Update marketplace statistics
     marketplace.total_volume += total_payment;
     marketplace.total_trades += 1;
+    let current_time = Clock::get()?.unix_timestamp;
+    marketplace.record_trade(execution_price, current_time);
+    record_candles(
+        [&ctx.accounts.minute_candles, &ctx.accounts.hour_candles, &ctx.accounts.day_candles],
+        execution_price,
+        actual_trade_amount,
+        current_time,
+    )?;
 
     msg!(
         "Trade executed: {} yield tokens at {} per token (Total: {}, Fee: {})",
@@ -172,6 +315,72 @@ pub fn handle_execute_trade(
     Ok(())
 }
 
+/// How much the buy and sell orders of a self-trade are reduced by under `behavior`,
+/// `trade_amount` being what they would have matched
+fn self_trade_reductions(
+    behavior: u8,
+    buy_order: &TradeOrder,
+    sell_order: &TradeOrder,
+    trade_amount: u64,
+) -> (u64, u64) {
+    let buy_is_newer = (buy_order.created_at, buy_order.order_id)
+        > (sell_order.created_at, sell_order.order_id);
+    match behavior {
+        Marketplace::STP_DECREMENT_BOTH => (trade_amount, trade_amount),
+        Marketplace::STP_CANCEL_OLDEST if buy_is_newer => (0, sell_order.remaining_amount()),
+        Marketplace::STP_CANCEL_OLDEST => (buy_order.remaining_amount(), 0),
+        _ if buy_is_newer => (buy_order.remaining_amount(), 0),
+        _ => (0, sell_order.remaining_amount()),
+    }
+}
+
+/// Shrink an order by `amount` yield tokens and refund the released escrow to its owner.
+/// Returns true if the order has nothing left to fill and was deactivated.
+fn reduce_order<'info>(
+    order: &mut Account<'info, TradeOrder>,
+    escrow: &InterfaceAccount<'info, TokenAccount>,
+    refund_account: &InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    signer_seeds: &[&[&[u8]]],
+    amount: u64,
+) -> Result<bool> {
+    order.yield_token_amount -= amount;
+    let closed = order.remaining_amount() == 0;
+
+    let refund = if closed {
+        escrow.amount
+    } else if order.order_type == TradeOrder::BUY_ORDER {
+        let released_value = (amount as u128 * order.price_per_token as u128 / 1_000_000) as u64;
+        released_value.min(escrow.amount)
+    } else {
+        amount
+    };
+
+    if order.order_type == TradeOrder::BUY_ORDER {
+        order.total_value = order.total_value.saturating_sub(refund);
+    }
+    if closed {
+        order.is_active = false;
+    }
+
+    if refund > 0 {
+        let cpi_ctx = CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            TransferChecked {
+                from: escrow.to_account_info(),
+                mint: mint.to_account_info(),
+                to: refund_account.to_account_info(),
+                authority: order.to_account_info(),
+            },
+            signer_seeds,
+        );
+        transfer_checked(cpi_ctx, refund, mint.decimals)?;
+    }
+
+    Ok(closed)
+}
+
 #[error_code]
 pub enum TradeError {
     #[msg("Invalid buy order")]
@@ -188,4 +397,254 @@ pub enum TradeError {
     InvalidTradeAmount,
     #[msg("No tradeable amount available")]
     NoTradeableAmount,
-} 
\ No newline at end of file
+    #[msg("Marketplace is not active")]
+    MarketplaceNotActive,
+    #[msg("Wrong token account")]
+    WrongTokenAccount,
+    #[msg("Marketplace matches orders in batch auctions")]
+    AuctionModeActive,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use anchor_spl::token::spl_token;
+    use anchor_spl::token_2022::spl_token_2022;
+
+    use crate::instructions::token_fees::amount_with_fees;
+    use crate::test_utils::{
+        balance, candle_series, fee_mint, fee_token_account_at, mint_account, program_account, program_account_at,
+        program_info, set_clock, signer_info, token_account, token_account_at, zeroed,
+    };
+
+    fn order(order_type: u8, amount: u64, filled_amount: u64, created_at: i64, order_id: u64) -> TradeOrder {
+        TradeOrder {
+            user: Pubkey::default(),
+            marketplace: Pubkey::default(),
+            order_type,
+            yield_token_amount: amount,
+            price_per_token: 1_000_000,
+            total_value: amount,
+            filled_amount,
+            is_active: true,
+            created_at,
+            order_id,
+        }
+    }
+
+    #[test]
+    fn cancel_newest_cancels_the_later_order() {
+        let older_buy = order(TradeOrder::BUY_ORDER, 100, 10, 1_000, 1);
+        let newer_sell = order(TradeOrder::SELL_ORDER, 50, 0, 2_000, 2);
+
+        assert_eq!(
+            self_trade_reductions(Marketplace::STP_CANCEL_NEWEST, &older_buy, &newer_sell, 50),
+            (0, 50)
+        );
+
+        let newer_buy = order(TradeOrder::BUY_ORDER, 100, 10, 3_000, 3);
+        assert_eq!(
+            self_trade_reductions(Marketplace::STP_CANCEL_NEWEST, &newer_buy, &newer_sell, 50),
+            (90, 0)
+        );
+    }
+
+    #[test]
+    fn cancel_oldest_cancels_the_earlier_order() {
+        let older_buy = order(TradeOrder::BUY_ORDER, 100, 10, 1_000, 1);
+        let newer_sell = order(TradeOrder::SELL_ORDER, 50, 0, 2_000, 2);
+
+        assert_eq!(
+            self_trade_reductions(Marketplace::STP_CANCEL_OLDEST, &older_buy, &newer_sell, 50),
+            (90, 0)
+        );
+    }
+
+    #[test]
+    fn order_id_breaks_ties_between_orders_placed_in_the_same_second() {
+        let buy = order(TradeOrder::BUY_ORDER, 100, 0, 1_000, 7);
+        let sell = order(TradeOrder::SELL_ORDER, 60, 0, 1_000, 8);
+
+        assert_eq!(
+            self_trade_reductions(Marketplace::STP_CANCEL_NEWEST, &buy, &sell, 60),
+            (0, 60)
+        );
+        assert_eq!(
+            self_trade_reductions(Marketplace::STP_CANCEL_OLDEST, &buy, &sell, 60),
+            (100, 0)
+        );
+    }
+
+    #[test]
+    fn decrement_both_reduces_each_order_by_the_matched_amount() {
+        let buy = order(TradeOrder::BUY_ORDER, 100, 10, 1_000, 1);
+        let sell = order(TradeOrder::SELL_ORDER, 50, 0, 2_000, 2);
+
+        assert_eq!(
+            self_trade_reductions(Marketplace::STP_DECREMENT_BOTH, &buy, &sell, 50),
+            (50, 50)
+        );
+    }
+
+    /// Resting order of `user` at 1.0 at its PDA, with its escrow fully funded
+    fn resting_order(
+        marketplace: &Account<Marketplace>,
+        user: Pubkey,
+        order_type: u8,
+        amount: u64,
+        order_id: u64,
+    ) -> (Account<'static, TradeOrder>, InterfaceAccount<'static, TokenAccount>, u8) {
+        let mut order = order(order_type, amount, 0, order_id as i64, order_id);
+        order.user = user;
+        order.marketplace = marketplace.key();
+        let (address, bump) =
+            Pubkey::find_program_address(&[b"order", user.as_ref(), order_id.to_le_bytes().as_ref()], &crate::ID);
+        let (escrow_address, _) = Pubkey::find_program_address(&[b"escrow", address.as_ref()], &crate::ID);
+        let escrow_mint = match order_type {
+            TradeOrder::BUY_ORDER => marketplace.quote_mint,
+            _ => marketplace.yield_token_mint,
+        };
+        (
+            program_account_at(address, &order),
+            token_account_at(escrow_address, escrow_mint, address, amount),
+            bump,
+        )
+    }
+
+    /// A 2 token bid of `buyer` placed before a 1 token ask of `seller`, both at 1.0,
+    /// on a marketplace charging 1% and preventing self-trades with `behavior`
+    fn trade_accounts(buyer: Pubkey, seller: Pubkey, behavior: u8) -> (ExecuteTrade<'static>, ExecuteTradeBumps) {
+        set_clock(10);
+        let mut marketplace: Marketplace = zeroed();
+        marketplace.admin = Pubkey::new_unique();
+        marketplace.quote_mint = Pubkey::new_unique();
+        marketplace.yield_token_mint = Pubkey::new_unique();
+        marketplace.trading_fee_bps = 100;
+        marketplace.is_active = true;
+        marketplace.open_orders = 2;
+        marketplace.self_trade_behavior = behavior;
+        let marketplace = program_account(&marketplace);
+
+        let (buy_order, buy_order_escrow, buy_bump) =
+            resting_order(&marketplace, buyer, TradeOrder::BUY_ORDER, 2_000_000, 1);
+        let (sell_order, sell_order_escrow, sell_bump) =
+            resting_order(&marketplace, seller, TradeOrder::SELL_ORDER, 1_000_000, 2);
+        let accounts = ExecuteTrade {
+            executor: Signer::try_from(signer_info(Pubkey::new_unique())).unwrap(),
+            buy_order,
+            sell_order,
+            buy_order_escrow,
+            sell_order_escrow,
+            buyer_yield_token_account: token_account(marketplace.yield_token_mint, buyer, 0),
+            buyer_quote_token_account: token_account(marketplace.quote_mint, buyer, 0),
+            seller_quote_token_account: token_account(marketplace.quote_mint, seller, 0),
+            fee_collection_account: token_account(marketplace.quote_mint, marketplace.admin, 0),
+            yield_token_mint: mint_account(marketplace.yield_token_mint, 6),
+            quote_mint: mint_account(marketplace.quote_mint, 6),
+            minute_candles: candle_series(CandleSeries::MINUTE),
+            hour_candles: candle_series(CandleSeries::HOUR),
+            day_candles: candle_series(CandleSeries::DAY),
+            token_program: Interface::try_from(program_info(spl_token::ID)).unwrap(),
+            yield_token_program: Interface::try_from(program_info(spl_token::ID)).unwrap(),
+            marketplace: Box::new(marketplace),
+        };
+        let bumps = ExecuteTradeBumps { buy_order: buy_bump, sell_order: sell_bump, ..Default::default() };
+        (accounts, bumps)
+    }
+
+    fn self_trade(behavior: u8) -> ExecuteTrade<'static> {
+        let user = Pubkey::new_unique();
+        let (mut accounts, bumps) = trade_accounts(user, user, behavior);
+        handle_execute_trade(Context::new(&crate::ID, &mut accounts, &[], bumps), 1_000_000).unwrap();
+
+        // Nothing trades
+        assert_eq!(accounts.marketplace.total_trades, 0);
+        assert_eq!(accounts.marketplace.total_volume, 0);
+        assert_eq!(balance(&accounts.fee_collection_account), 0);
+        assert_eq!(accounts.minute_candles.load().unwrap().count, 0);
+        accounts
+    }
+
+    #[test]
+    fn trades_pay_the_seller_less_the_fee() {
+        let (mut accounts, bumps) = trade_accounts(Pubkey::new_unique(), Pubkey::new_unique(), 0);
+
+        handle_execute_trade(Context::new(&crate::ID, &mut accounts, &[], bumps), 1_000_000).unwrap();
+
+        assert_eq!(balance(&accounts.buyer_yield_token_account), 1_000_000);
+        assert_eq!(balance(&accounts.seller_quote_token_account), 990_000);
+        assert_eq!(balance(&accounts.fee_collection_account), 10_000);
+        assert_eq!(balance(&accounts.buy_order_escrow), 1_000_000);
+        assert!(accounts.buy_order.is_active);
+        assert!(!accounts.sell_order.is_active);
+        assert_eq!(accounts.marketplace.open_orders, 1);
+        assert_eq!(accounts.marketplace.total_trades, 1);
+        assert_eq!(accounts.marketplace.last_trade_price, 1_000_000);
+    }
+
+    #[test]
+    fn fee_charging_quote_mints_deliver_the_quoted_amounts() {
+        let (buyer, seller) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (mut accounts, bumps) = trade_accounts(buyer, seller, 0);
+        let quote_mint = fee_mint(accounts.marketplace.quote_mint, 100, u64::MAX);
+        let fee_account = |owner, amount| fee_token_account_at(Pubkey::new_unique(), quote_mint.key(), owner, amount);
+
+        // The bid escrow holds enough for its payments to cross one more fee-charging hop
+        let escrowed = amount_with_fees(&quote_mint, 2_000_000, 1).unwrap();
+        accounts.buy_order_escrow =
+            fee_token_account_at(accounts.buy_order_escrow.key(), quote_mint.key(), accounts.buy_order.key(), escrowed);
+        accounts.buyer_quote_token_account = fee_account(buyer, 0);
+        accounts.seller_quote_token_account = fee_account(seller, 0);
+        accounts.fee_collection_account = fee_account(accounts.marketplace.admin, 0);
+        accounts.token_program = Interface::try_from(program_info(spl_token_2022::ID)).unwrap();
+        accounts.quote_mint = quote_mint;
+
+        handle_execute_trade(Context::new(&crate::ID, &mut accounts, &[], bumps), 1_000_000).unwrap();
+
+        // 1 token at 1.0 with a 1% trading fee: both recipients get their exact share
+        assert_eq!(balance(&accounts.seller_quote_token_account), 990_000);
+        assert_eq!(balance(&accounts.fee_collection_account), 10_000);
+        assert_eq!(balance(&accounts.buyer_yield_token_account), 1_000_000);
+        assert!(!accounts.sell_order.is_active);
+    }
+
+    #[test]
+    fn self_trades_decrement_both_orders_by_the_matched_size() {
+        let accounts = self_trade(Marketplace::STP_DECREMENT_BOTH);
+
+        assert_eq!(accounts.buy_order.yield_token_amount, 1_000_000);
+        assert!(accounts.buy_order.is_active);
+        assert!(!accounts.sell_order.is_active);
+        // Half the buy escrow and the whole sell escrow come back
+        assert_eq!(balance(&accounts.buy_order_escrow), 1_000_000);
+        assert_eq!(balance(&accounts.buyer_quote_token_account), 1_000_000);
+        assert_eq!(balance(&accounts.buyer_yield_token_account), 1_000_000);
+        assert_eq!(accounts.marketplace.open_orders, 1);
+    }
+
+    #[test]
+    fn self_trades_cancel_the_newer_order() {
+        let accounts = self_trade(Marketplace::STP_CANCEL_NEWEST);
+
+        assert!(accounts.buy_order.is_active);
+        assert!(!accounts.sell_order.is_active);
+        assert_eq!(balance(&accounts.buy_order_escrow), 2_000_000);
+        assert_eq!(balance(&accounts.sell_order_escrow), 0);
+        assert_eq!(balance(&accounts.buyer_yield_token_account), 1_000_000);
+        assert_eq!(accounts.marketplace.open_orders, 1);
+    }
+
+    #[test]
+    fn self_trades_cancel_the_older_order() {
+        let accounts = self_trade(Marketplace::STP_CANCEL_OLDEST);
+
+        assert!(!accounts.buy_order.is_active);
+        assert_eq!(balance(&accounts.buy_order_escrow), 0);
+        assert_eq!(balance(&accounts.buyer_quote_token_account), 2_000_000);
+        // The newer sell order keeps its escrow and stays on the book
+        assert!(accounts.sell_order.is_active);
+        assert_eq!(balance(&accounts.sell_order_escrow), 1_000_000);
+        assert_eq!(accounts.marketplace.open_orders, 1);
+    }
+}
@@ -0,0 +1,544 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::ed25519_program;
+use anchor_lang::solana_program::sysvar::instructions::{
+    self as sysvar_instructions, load_current_index_checked, load_instruction_at_checked,
+};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked};
+
+use crate::instructions::{candles::record_candles, place_order::validate_order_terms, token_fees::amount_with_fee};
+use crate::state::{
+    candles::CandleSeries,
+    marketplace::{Marketplace, TradeOrder},
+    signed_order::{MakerState, SignedOrder},
+};
+
+#[derive(Accounts)]
+#[instruction(order: SignedOrder, fill_amount: u64)]
+pub struct FillSignedOrder<'info> {
+    pub taker: Signer<'info>,
+
+    #[account(
+        mut,
+        address = order.marketplace @ SignedOrderError::OrderMarketplaceMismatch,
+        constraint = marketplace.is_active @ SignedOrderError::MarketplaceNotActive,
+        constraint = !marketplace.is_auction_mode() @ SignedOrderError::AuctionModeActive
+    )]
+    pub marketplace: Box<Account<'info, Marketplace>>,
+
+    #[account(
+        mut,
+        seeds = [b"maker_state", order.maker.as_ref()],
+        bump = maker_state.bump
+    )]
+    pub maker_state: Account<'info, MakerState>,
+
+    /// Maker's yield token account (maker_state must be its delegate for sell orders)
+    #[account(
+        mut,
+        constraint = maker_yield_token_account.owner == order.maker @ SignedOrderError::WrongTokenAccount,
+        constraint = maker_yield_token_account.mint == marketplace.yield_token_mint @ SignedOrderError::WrongTokenAccount
+    )]
+    pub maker_yield_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Maker's quote token account (maker_state must be its delegate for buy orders)
+    #[account(
+        mut,
+        constraint = maker_quote_token_account.owner == order.maker @ SignedOrderError::WrongTokenAccount,
+        constraint = maker_quote_token_account.mint == marketplace.quote_mint @ SignedOrderError::WrongTokenAccount
+    )]
+    pub maker_quote_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Taker's yield token account
+    #[account(
+        mut,
+        constraint = taker_yield_token_account.owner == taker.key() @ SignedOrderError::WrongTokenAccount,
+        constraint = taker_yield_token_account.mint == marketplace.yield_token_mint @ SignedOrderError::WrongTokenAccount
+    )]
+    pub taker_yield_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Taker's quote token account
+    #[account(
+        mut,
+        constraint = taker_quote_token_account.owner == taker.key() @ SignedOrderError::WrongTokenAccount,
+        constraint = taker_quote_token_account.mint == marketplace.quote_mint @ SignedOrderError::WrongTokenAccount
+    )]
+    pub taker_quote_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Marketplace fee collection account (quote tokens owned by the marketplace admin)
+    #[account(
+        mut,
+        constraint = fee_collection_account.owner == marketplace.admin @ SignedOrderError::WrongTokenAccount,
+        constraint = fee_collection_account.mint == marketplace.quote_mint @ SignedOrderError::WrongTokenAccount
+    )]
+    pub fee_collection_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Instructions sysvar, used to find the ed25519 signature check
+    #[account(address = sysvar_instructions::ID)]
+    pub instructions_sysvar: UncheckedAccount<'info>,
+
+    #[account(
+        address = marketplace.yield_token_mint @ SignedOrderError::WrongTokenAccount
+    )]
+    pub yield_token_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        address = marketplace.quote_mint @ SignedOrderError::WrongTokenAccount
+    )]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+
+    /// One-minute candle series of the marketplace
+    #[account(
+        mut,
+        seeds = [b"candles", marketplace.key().as_ref(), CandleSeries::MINUTE.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub minute_candles: AccountLoader<'info, CandleSeries>,
+
+    /// One-hour candle series of the marketplace
+    #[account(
+        mut,
+        seeds = [b"candles", marketplace.key().as_ref(), CandleSeries::HOUR.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub hour_candles: AccountLoader<'info, CandleSeries>,
+
+    /// One-day candle series of the marketplace
+    #[account(
+        mut,
+        seeds = [b"candles", marketplace.key().as_ref(), CandleSeries::DAY.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub day_candles: AccountLoader<'info, CandleSeries>,
+
+    /// Token program of the quote mint
+    pub token_program: Interface<'info, TokenInterface>,
+    /// Token program of the yield token mint
+    pub yield_token_program: Interface<'info, TokenInterface>,
+}
+
+pub fn handle_fill_signed_order(
+    ctx: Context<FillSignedOrder>,
+    order: SignedOrder,
+    fill_amount: u64,
+) -> Result<()> {
+    require!(fill_amount <= order.yield_token_amount, SignedOrderError::InvalidFillAmount);
+    require!(
+        order.order_type == TradeOrder::BUY_ORDER || order.order_type == TradeOrder::SELL_ORDER,
+        SignedOrderError::InvalidOrderType
+    );
+    require!(order.maker != ctx.accounts.taker.key(), SignedOrderError::SelfFill);
+
+    let current_time = Clock::get()?.unix_timestamp;
+    require!(current_time < order.expires_at, SignedOrderError::OrderExpired);
+
+    // The maker's signature must be verified by an ed25519 instruction placed
+    // immediately before this one in the same transaction
+    verify_maker_signature(
+        &ctx.accounts.instructions_sysvar.to_account_info(),
+        &order.maker,
+        &order.message()?,
+    )?;
+
+    // Each signed order settles once, a partial fill consumes the nonce
+    let maker_state = &mut ctx.accounts.maker_state;
+    require!(maker_state.use_nonce(order.nonce), SignedOrderError::NonceNotUsable);
+
+    let marketplace = &mut ctx.accounts.marketplace;
+
+    // Fills follow the same tick size and minimum size rules as placed orders
+    let total_payment = validate_order_terms(marketplace, fill_amount, order.price_per_token)?;
+
+    // Fee is taken from the payment to the seller, as in execute_trade
+    let fee_amount = marketplace.trading_fee(total_payment);
+    let net_payment = total_payment - fee_amount;
+
+    // Senders cover any transfer fee so every recipient gets the quoted amount
+    let yield_transfer = amount_with_fee(&ctx.accounts.yield_token_mint, fill_amount)?;
+    let payment_transfer = amount_with_fee(&ctx.accounts.quote_mint, net_payment)?;
+    let fee_transfer = amount_with_fee(&ctx.accounts.quote_mint, fee_amount)?;
+
+    let maker_state_seeds = &[b"maker_state", order.maker.as_ref(), &[maker_state.bump]];
+    let maker_signer_seeds = &[maker_state_seeds.as_slice()];
+
+    if order.order_type == TradeOrder::SELL_ORDER {
+        // Maker sells: yield tokens move from the maker (via delegate) to the taker
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.yield_token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.maker_yield_token_account.to_account_info(),
+                mint: ctx.accounts.yield_token_mint.to_account_info(),
+                to: ctx.accounts.taker_yield_token_account.to_account_info(),
+                authority: maker_state.to_account_info(),
+            },
+            maker_signer_seeds,
+        );
+        transfer_checked(cpi_ctx, yield_transfer, ctx.accounts.yield_token_mint.decimals)?;
+
+        // Taker pays the maker and the fee
+        let cpi_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.taker_quote_token_account.to_account_info(),
+                mint: ctx.accounts.quote_mint.to_account_info(),
+                to: ctx.accounts.maker_quote_token_account.to_account_info(),
+                authority: ctx.accounts.taker.to_account_info(),
+            },
+        );
+        transfer_checked(cpi_ctx, payment_transfer, ctx.accounts.quote_mint.decimals)?;
+
+        if fee_amount > 0 {
+            let cpi_ctx = CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.taker_quote_token_account.to_account_info(),
+                    mint: ctx.accounts.quote_mint.to_account_info(),
+                    to: ctx.accounts.fee_collection_account.to_account_info(),
+                    authority: ctx.accounts.taker.to_account_info(),
+                },
+            );
+            transfer_checked(cpi_ctx, fee_transfer, ctx.accounts.quote_mint.decimals)?;
+        }
+    } else {
+        // Maker buys: quote tokens move from the maker (via delegate) to the taker and the fee account
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.maker_quote_token_account.to_account_info(),
+                mint: ctx.accounts.quote_mint.to_account_info(),
+                to: ctx.accounts.taker_quote_token_account.to_account_info(),
+                authority: maker_state.to_account_info(),
+            },
+            maker_signer_seeds,
+        );
+        transfer_checked(cpi_ctx, payment_transfer, ctx.accounts.quote_mint.decimals)?;
+
+        if fee_amount > 0 {
+            let cpi_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.maker_quote_token_account.to_account_info(),
+                    mint: ctx.accounts.quote_mint.to_account_info(),
+                    to: ctx.accounts.fee_collection_account.to_account_info(),
+                    authority: maker_state.to_account_info(),
+                },
+                maker_signer_seeds,
+            );
+            transfer_checked(cpi_ctx, fee_transfer, ctx.accounts.quote_mint.decimals)?;
+        }
+
+        // Taker delivers the yield tokens
+        let cpi_ctx = CpiContext::new(
+            ctx.accounts.yield_token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.taker_yield_token_account.to_account_info(),
+                mint: ctx.accounts.yield_token_mint.to_account_info(),
+                to: ctx.accounts.maker_yield_token_account.to_account_info(),
+                authority: ctx.accounts.taker.to_account_info(),
+            },
+        );
+        transfer_checked(cpi_ctx, yield_transfer, ctx.accounts.yield_token_mint.decimals)?;
+    }
+
+    maker_state.total_fills += 1;
+
+    // Update marketplace statistics
+    marketplace.total_volume += total_payment;
+    marketplace.total_trades += 1;
+    marketplace.record_trade(order.price_per_token, current_time);
+    record_candles(
+        [&ctx.accounts.minute_candles, &ctx.accounts.hour_candles, &ctx.accounts.day_candles],
+        order.price_per_token,
+        fill_amount,
+        current_time,
+    )?;
+
+    msg!(
+        "Signed order filled: {} yield tokens at {} per token (Maker: {}, Nonce: {}, Fee: {})",
+        fill_amount,
+        order.price_per_token as f64 / 1_000_000.0,
+        order.maker,
+        order.nonce,
+        fee_amount
+    );
+
+    Ok(())
+}
+
+/// Check that the previous instruction is an ed25519 precompile call verifying
+/// `message` signed by `maker`, with all data inlined in that instruction
+fn verify_maker_signature(
+    instructions_sysvar: &AccountInfo,
+    maker: &Pubkey,
+    message: &[u8],
+) -> Result<()> {
+    let current_index = load_current_index_checked(instructions_sysvar)?;
+    require!(current_index > 0, SignedOrderError::MissingSignatureInstruction);
+
+    let ed25519_ix = load_instruction_at_checked((current_index - 1) as usize, instructions_sysvar)?;
+    require_keys_eq!(
+        ed25519_ix.program_id,
+        ed25519_program::ID,
+        SignedOrderError::MissingSignatureInstruction
+    );
+
+    // Layout: [num_signatures: u8, padding: u8, offsets: 7 x u16]
+    let data = &ed25519_ix.data;
+    require!(data.len() >= 16 && data[0] == 1, SignedOrderError::InvalidSignatureInstruction);
+
+    let read_u16 = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    let signature_instruction_index = read_u16(4);
+    let public_key_offset = read_u16(6) as usize;
+    let public_key_instruction_index = read_u16(8);
+    let message_data_offset = read_u16(10) as usize;
+    let message_data_size = read_u16(12) as usize;
+    let message_instruction_index = read_u16(14);
+
+    // Signature, key and message must all live in the ed25519 instruction itself
+    require!(
+        signature_instruction_index == u16::MAX
+            && public_key_instruction_index == u16::MAX
+            && message_instruction_index == u16::MAX,
+        SignedOrderError::InvalidSignatureInstruction
+    );
+
+    let signer = data
+        .get(public_key_offset..public_key_offset + 32)
+        .ok_or(SignedOrderError::InvalidSignatureInstruction)?;
+    require!(signer == maker.as_ref(), SignedOrderError::SignerMismatch);
+
+    let signed_message = data
+        .get(message_data_offset..message_data_offset + message_data_size)
+        .ok_or(SignedOrderError::InvalidSignatureInstruction)?;
+    require!(signed_message == message, SignedOrderError::MessageMismatch);
+
+    Ok(())
+}
+
+#[error_code]
+pub enum SignedOrderError {
+    #[msg("Marketplace is not active")]
+    MarketplaceNotActive,
+    #[msg("Order marketplace mismatch")]
+    OrderMarketplaceMismatch,
+    #[msg("Wrong token account")]
+    WrongTokenAccount,
+    #[msg("Invalid fill amount")]
+    InvalidFillAmount,
+    #[msg("Invalid order type")]
+    InvalidOrderType,
+    #[msg("Maker cannot fill their own order")]
+    SelfFill,
+    #[msg("Signed order has expired")]
+    OrderExpired,
+    #[msg("Nonce already used, cancelled or outside the nonce window")]
+    NonceNotUsable,
+    #[msg("Missing ed25519 signature instruction")]
+    MissingSignatureInstruction,
+    #[msg("Invalid ed25519 signature instruction")]
+    InvalidSignatureInstruction,
+    #[msg("Order was not signed by the maker")]
+    SignerMismatch,
+    #[msg("Signed message does not match the order")]
+    MessageMismatch,
+    #[msg("Marketplace matches orders in batch auctions")]
+    AuctionModeActive,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::sysvar::instructions::{
+        construct_instructions_data, store_current_index, BorrowedInstruction,
+    };
+
+    use anchor_spl::token::spl_token;
+
+    use crate::test_utils::{
+        account_info, approve, balance, candle_series, mint_account, program_account_at, program_info,
+        set_clock, signer_info, token_account, zeroed,
+    };
+
+    /// ed25519 precompile data for one signature with everything inlined
+    fn ed25519_data(signer: &Pubkey, message: &[u8]) -> Vec<u8> {
+        let public_key_offset: u16 = 16;
+        let signature_offset: u16 = public_key_offset + 32;
+        let message_offset: u16 = signature_offset + 64;
+
+        let mut data = vec![1, 0];
+        for value in [
+            signature_offset,
+            u16::MAX,
+            public_key_offset,
+            u16::MAX,
+            message_offset,
+            message.len() as u16,
+            u16::MAX,
+        ] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(signer.as_ref());
+        data.extend_from_slice(&[7; 64]); // checked by the precompile, not by the program
+        data.extend_from_slice(message);
+        data
+    }
+
+    /// Instructions sysvar holding `previous` followed by the fill instruction
+    fn instructions_sysvar(previous: (Pubkey, Vec<u8>)) -> &'static AccountInfo<'static> {
+        let fill_data = [0u8; 8];
+        let instructions = [
+            BorrowedInstruction { program_id: &previous.0, accounts: vec![], data: &previous.1 },
+            BorrowedInstruction { program_id: &crate::ID, accounts: vec![], data: &fill_data },
+        ];
+        let mut data = construct_instructions_data(&instructions);
+        store_current_index(&mut data, 1);
+        account_info(sysvar_instructions::ID, Pubkey::default(), data)
+    }
+
+    fn order(maker: Pubkey) -> SignedOrder {
+        SignedOrder {
+            marketplace: Pubkey::new_unique(),
+            maker,
+            order_type: TradeOrder::SELL_ORDER,
+            yield_token_amount: 1_000_000,
+            price_per_token: 950_000,
+            nonce: 3,
+            expires_at: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn accepts_the_maker_signature_over_the_order() {
+        let maker = Pubkey::new_unique();
+        let message = order(maker).message().unwrap();
+        let sysvar = instructions_sysvar((ed25519_program::ID, ed25519_data(&maker, &message)));
+
+        assert!(verify_maker_signature(sysvar, &maker, &message).is_ok());
+    }
+
+    #[test]
+    fn rejects_another_signer() {
+        let maker = Pubkey::new_unique();
+        let message = order(maker).message().unwrap();
+        let sysvar = instructions_sysvar((ed25519_program::ID, ed25519_data(&Pubkey::new_unique(), &message)));
+
+        assert_eq!(
+            verify_maker_signature(sysvar, &maker, &message).unwrap_err(),
+            SignedOrderError::SignerMismatch.into()
+        );
+    }
+
+    #[test]
+    fn rejects_a_signature_over_different_terms() {
+        let maker = Pubkey::new_unique();
+        let signed = order(maker);
+        let tampered = SignedOrder { price_per_token: 1_000_000, ..signed.clone() };
+        let sysvar = instructions_sysvar((ed25519_program::ID, ed25519_data(&maker, &signed.message().unwrap())));
+
+        assert_eq!(
+            verify_maker_signature(sysvar, &maker, &tampered.message().unwrap()).unwrap_err(),
+            SignedOrderError::MessageMismatch.into()
+        );
+    }
+
+    #[test]
+    fn rejects_a_missing_signature_instruction() {
+        let maker = Pubkey::new_unique();
+        let message = order(maker).message().unwrap();
+        let sysvar = instructions_sysvar((Pubkey::new_unique(), ed25519_data(&maker, &message)));
+
+        assert_eq!(
+            verify_maker_signature(sysvar, &maker, &message).unwrap_err(),
+            SignedOrderError::MissingSignatureInstruction.into()
+        );
+    }
+
+    #[test]
+    fn rejects_data_taken_from_other_instructions() {
+        let maker = Pubkey::new_unique();
+        let message = order(maker).message().unwrap();
+        let mut data = ed25519_data(&maker, &message);
+        // Point the public key at instruction 0 instead of the precompile's own data
+        data[8..10].copy_from_slice(&0u16.to_le_bytes());
+        let sysvar = instructions_sysvar((ed25519_program::ID, data));
+
+        assert_eq!(
+            verify_maker_signature(sysvar, &maker, &message).unwrap_err(),
+            SignedOrderError::InvalidSignatureInstruction.into()
+        );
+    }
+
+    /// A taker filling `order`, signed by its maker, on a marketplace charging 1%. The maker
+    /// holds 10 yield tokens delegated to their maker state, the taker 20 quote tokens.
+    fn fill_accounts(order: &SignedOrder) -> FillSignedOrder<'static> {
+        set_clock(100);
+        let mut marketplace: Marketplace = zeroed();
+        marketplace.admin = Pubkey::new_unique();
+        marketplace.quote_mint = Pubkey::new_unique();
+        marketplace.yield_token_mint = Pubkey::new_unique();
+        marketplace.trading_fee_bps = 100;
+        marketplace.tick_size = 1;
+        marketplace.is_active = true;
+        let marketplace = program_account_at(order.marketplace, &marketplace);
+
+        let (maker_state_address, bump) =
+            Pubkey::find_program_address(&[b"maker_state", order.maker.as_ref()], &crate::ID);
+        let mut maker_state: MakerState = zeroed();
+        maker_state.maker = order.maker;
+        maker_state.bump = bump;
+        let maker_yield_token_account = token_account(marketplace.yield_token_mint, order.maker, 10_000_000);
+        approve(&maker_yield_token_account, maker_state_address, 10_000_000);
+
+        let taker = Pubkey::new_unique();
+        let signature = ed25519_data(&order.maker, &order.message().unwrap());
+        FillSignedOrder {
+            taker: Signer::try_from(signer_info(taker)).unwrap(),
+            maker_state: program_account_at(maker_state_address, &maker_state),
+            maker_yield_token_account,
+            maker_quote_token_account: token_account(marketplace.quote_mint, order.maker, 0),
+            taker_yield_token_account: token_account(marketplace.yield_token_mint, taker, 0),
+            taker_quote_token_account: token_account(marketplace.quote_mint, taker, 20_000_000),
+            fee_collection_account: token_account(marketplace.quote_mint, marketplace.admin, 0),
+            instructions_sysvar: UncheckedAccount::try_from(instructions_sysvar((ed25519_program::ID, signature))),
+            yield_token_mint: mint_account(marketplace.yield_token_mint, 6),
+            quote_mint: mint_account(marketplace.quote_mint, 6),
+            minute_candles: candle_series(CandleSeries::MINUTE),
+            hour_candles: candle_series(CandleSeries::HOUR),
+            day_candles: candle_series(CandleSeries::DAY),
+            token_program: Interface::try_from(program_info(spl_token::ID)).unwrap(),
+            yield_token_program: Interface::try_from(program_info(spl_token::ID)).unwrap(),
+            marketplace: Box::new(marketplace),
+        }
+    }
+
+    fn fill(accounts: &mut FillSignedOrder<'static>, order: &SignedOrder, fill_amount: u64) -> Result<()> {
+        let bumps = FillSignedOrderBumps::default();
+        handle_fill_signed_order(Context::new(&crate::ID, accounts, &[], bumps), order.clone(), fill_amount)
+    }
+
+    #[test]
+    fn signed_sell_orders_settle_once_and_charge_the_fee_on_the_payment() {
+        let order = SignedOrder {
+            marketplace: Pubkey::new_unique(),
+            yield_token_amount: 4_000_000,
+            price_per_token: 1_500_000,
+            nonce: 0,
+            ..order(Pubkey::new_unique())
+        };
+        let mut accounts = fill_accounts(&order);
+
+        fill(&mut accounts, &order, 3_000_000).unwrap();
+
+        // 3 tokens at 1.5 = 4.5 quote, 1% fee
+        assert_eq!(balance(&accounts.taker_yield_token_account), 3_000_000);
+        assert_eq!(balance(&accounts.maker_yield_token_account), 7_000_000);
+        assert_eq!(balance(&accounts.taker_quote_token_account), 15_500_000);
+        assert_eq!(balance(&accounts.maker_quote_token_account), 4_455_000);
+        assert_eq!(balance(&accounts.fee_collection_account), 45_000);
+        assert_eq!((accounts.maker_state.total_fills, accounts.maker_state.nonce_floor), (1, 1));
+        assert_eq!(accounts.marketplace.last_trade_price, 1_500_000);
+
+        // A partial fill consumes the nonce, the remainder can't be filled
+        assert_eq!(fill(&mut accounts, &order, 1_000_000).unwrap_err(), SignedOrderError::NonceNotUsable.into());
+        assert_eq!(balance(&accounts.taker_yield_token_account), 3_000_000);
+    }
+}
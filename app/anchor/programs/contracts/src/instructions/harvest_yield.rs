@@ -0,0 +1,142 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::adapters::{self, AdapterAccounts};
+use crate::state::strategy::Strategy;
+
+#[derive(Accounts)]
+#[instruction(strategy_id: u64)]
+pub struct HarvestYield<'info> {
+    /// Anyone can crank a harvest, the yield only ever moves to the reward reserve
+    pub harvester: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"strategy", strategy_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = strategy.has_adapter() @ HarvestError::NoAdapter
+    )]
+    pub strategy: Account<'info, Strategy>,
+
+    /// The underlying token mint
+    #[account(
+        address = strategy.underlying_token @ HarvestError::WrongUnderlyingToken
+    )]
+    pub underlying_token_mint: InterfaceAccount<'info, Mint>,
+
+    /// Reserve receiving the harvested yield
+    #[account(
+        mut,
+        seeds = [b"reward_reserve", strategy_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub reward_reserve: InterfaceAccount<'info, TokenAccount>,
+
+    /// Token program of the underlying token
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+pub fn handle_harvest_yield<'info>(
+    ctx: Context<'_, '_, 'info, 'info, HarvestYield<'info>>,
+    strategy_id: u64,
+) -> Result<u64> {
+    let strategy = &mut ctx.accounts.strategy;
+
+    // Everything the yield source holds above the deployed principal is yield
+    let source_balance = adapters::balance(strategy, ctx.remaining_accounts)?;
+    let harvestable = source_balance.saturating_sub(strategy.adapter_deposits);
+    require!(harvestable > 0, HarvestError::NothingToHarvest);
+
+    let strategy_id_bytes = strategy_id.to_le_bytes();
+    let signer_seeds: &[&[u8]] = &[b"strategy", strategy_id_bytes.as_ref(), &[ctx.bumps.strategy]];
+
+    let reserve_before = ctx.accounts.reward_reserve.amount;
+    let adapter_accounts = AdapterAccounts {
+        vault: ctx.accounts.reward_reserve.to_account_info(),
+        mint: ctx.accounts.underlying_token_mint.to_account_info(),
+        token_program: ctx.accounts.token_program.to_account_info(),
+        remaining_accounts: ctx.remaining_accounts,
+    };
+    adapters::withdraw(
+        strategy,
+        &adapter_accounts,
+        signer_seeds,
+        ctx.accounts.reward_reserve.to_account_info(),
+        harvestable,
+    )?;
+
+    // Credit what actually reached the reserve, transfer-fee mints withhold part of it
+    ctx.accounts.reward_reserve.reload()?;
+    let harvested = ctx.accounts.reward_reserve.amount - reserve_before;
+    strategy.total_harvested += harvested;
+
+    msg!(
+        "Harvested {} tokens of yield from strategy '{}' into the reward reserve",
+        harvested,
+        strategy.name
+    );
+
+    Ok(harvested)
+}
+
+#[error_code]
+pub enum HarvestError {
+    #[msg("Strategy has no yield source adapter")]
+    NoAdapter,
+    #[msg("Wrong underlying token for this strategy")]
+    WrongUnderlyingToken,
+    #[msg("No yield to harvest")]
+    NothingToHarvest,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use anchor_spl::token::spl_token;
+
+    use crate::test_utils::{LendingStrategy, balance, program_info, set_clock, signer_info, token_account_at};
+
+    const YEAR: i64 = 365 * 24 * 60 * 60;
+
+    fn harvest(lending: &LendingStrategy) -> Result<(u64, HarvestYield<'static>)> {
+        let strategy_id = LendingStrategy::STRATEGY_ID.to_le_bytes();
+        let (reserve_key, reserve_bump) =
+            Pubkey::find_program_address(&[b"reward_reserve", strategy_id.as_ref()], &crate::ID);
+        let mut accounts = HarvestYield {
+            harvester: Signer::try_from(signer_info(Pubkey::new_unique())).unwrap(),
+            strategy: lending.strategy.clone(),
+            underlying_token_mint: lending.mint.clone(),
+            reward_reserve: token_account_at(reserve_key, lending.mint.key(), lending.strategy.key(), 0),
+            token_program: Interface::try_from(program_info(spl_token::ID)).unwrap(),
+        };
+        let bumps = HarvestYieldBumps { strategy: lending.bump, reward_reserve: reserve_bump };
+        let harvested = handle_harvest_yield(
+            Context::new(&crate::ID, &mut accounts, lending.remaining_accounts, bumps),
+            LendingStrategy::STRATEGY_ID,
+        )?;
+        Ok((harvested, accounts))
+    }
+
+    #[test]
+    fn harvest_takes_what_the_source_holds_above_the_principal() {
+        let lending = LendingStrategy::new(0, 1_000_000, 1000);
+        set_clock(YEAR);
+
+        let (harvested, accounts) = harvest(&lending).unwrap();
+
+        assert_eq!(harvested, 100_000);
+        assert_eq!(balance(&accounts.reward_reserve), 100_000);
+        assert_eq!(balance(&lending.pool_vault), 1_000_000);
+        assert_eq!(accounts.strategy.adapter_deposits, 1_000_000);
+        assert_eq!(accounts.strategy.total_harvested, 100_000);
+    }
+
+    #[test]
+    fn principal_is_never_harvested() {
+        let lending = LendingStrategy::new(0, 1_000_000, 1000);
+        set_clock(0);
+
+        assert_eq!(harvest(&lending).err(), Some(HarvestError::NothingToHarvest.into()));
+    }
+}
@@ -0,0 +1,156 @@
+use anchor_lang::prelude::*;
+
+use crate::instructions::rate_feed::RateFeedError;
+use crate::state::{marketplace::Marketplace, rate_feed::RateFeed, strategy::Strategy};
+
+#[derive(Accounts)]
+pub struct GetImpliedApy<'info> {
+    #[account(
+        address = marketplace.strategy @ ImpliedApyError::WrongStrategy
+    )]
+    pub strategy: Account<'info, Strategy>,
+
+    /// Marketplace must quote in the strategy's underlying token so the price
+    /// compares against the redemption value
+    #[account(
+        constraint = marketplace.quote_mint == strategy.underlying_token @ ImpliedApyError::QuoteNotUnderlying
+    )]
+    pub marketplace: Box<Account<'info, Marketplace>>,
+
+    /// Rate feed of oracle-rate strategies, omitted for fixed-rate strategies
+    pub rate_feed: Option<Account<'info, RateFeed>>,
+}
+
+pub fn handle_get_implied_apy(ctx: Context<GetImpliedApy>, price_source: u8) -> Result<i64> {
+    let marketplace = &ctx.accounts.marketplace;
+    let strategy = &ctx.accounts.strategy;
+
+    let price = match price_source {
+        Marketplace::PRICE_SOURCE_LAST_TRADE => marketplace.last_trade_price,
+        Marketplace::PRICE_SOURCE_MID => {
+            require!(
+                marketplace.best_bid_price > 0 && marketplace.best_ask_price > 0,
+                ImpliedApyError::NoPrice
+            );
+            ((marketplace.best_bid_price as u128 + marketplace.best_ask_price as u128) / 2) as u64
+        }
+        _ => return err!(ImpliedApyError::InvalidPriceSource),
+    };
+    require!(price > 0, ImpliedApyError::NoPrice);
+
+    let rate_bps = live_rate_bps(strategy, ctx.accounts.rate_feed.as_ref(), Clock::get()?.unix_timestamp)?;
+    let implied_apy_bps = strategy
+        .implied_apy_bps(price, rate_bps)
+        .ok_or(ImpliedApyError::MathOverflow)?;
+
+    msg!(
+        "Implied APY {}% at price {} ({}s holding period, strategy rate {}%)",
+        implied_apy_bps as f64 / 100.0,
+        price as f64 / 1_000_000.0,
+        strategy.holding_period(),
+        rate_bps as f64 / 100.0
+    );
+
+    Ok(implied_apy_bps)
+}
+
+/// Rate the strategy currently accrues at: its fixed APY, or the latest rate of
+/// its feed, which has to be fresh to mean anything
+fn live_rate_bps(strategy: &Strategy, rate_feed: Option<&Account<RateFeed>>, now: i64) -> Result<u64> {
+    if !strategy.uses_rate_feed() {
+        return Ok(strategy.apy);
+    }
+
+    let rate_feed = rate_feed.ok_or(RateFeedError::MissingRateFeed)?;
+    require_keys_eq!(rate_feed.key(), strategy.rate_feed, RateFeedError::WrongRateFeed);
+    require!(rate_feed.is_fresh(now), RateFeedError::StaleRate);
+    Ok(rate_feed.rate_bps)
+}
+
+#[error_code]
+pub enum ImpliedApyError {
+    #[msg("Strategy does not match the marketplace")]
+    WrongStrategy,
+    #[msg("Marketplace quote token is not the strategy underlying token")]
+    QuoteNotUnderlying,
+    #[msg("Invalid price source")]
+    InvalidPriceSource,
+    #[msg("Marketplace has no price for the requested source")]
+    NoPrice,
+    #[msg("Math overflow")]
+    MathOverflow,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_utils::{program_account, program_account_at, stub_syscalls, zeroed};
+
+    /// Strategy advertising 5% while its feed pays 10%, and a marketplace last
+    /// traded at par
+    fn oracle_market() -> GetImpliedApy<'static> {
+        let mut feed: RateFeed = zeroed();
+        feed.rate_bps = 1000;
+        feed.max_staleness = 3_600;
+        let rate_feed = program_account(&feed);
+
+        let mut strategy: Strategy = zeroed();
+        strategy.apy = 500;
+        strategy.rate_mode = Strategy::RATE_MODE_ORACLE;
+        strategy.rate_feed = rate_feed.key();
+
+        let mut marketplace: Marketplace = zeroed();
+        marketplace.last_trade_price = 1_000_000;
+
+        GetImpliedApy {
+            strategy: program_account(&strategy),
+            marketplace: Box::new(program_account(&marketplace)),
+            rate_feed: Some(rate_feed),
+        }
+    }
+
+    fn implied_apy(accounts: &mut GetImpliedApy<'static>) -> Result<i64> {
+        stub_syscalls();
+        handle_get_implied_apy(
+            Context::new(&crate::ID, accounts, &[], GetImpliedApyBumps::default()),
+            Marketplace::PRICE_SOURCE_LAST_TRADE,
+        )
+    }
+
+    #[test]
+    fn oracle_strategies_are_priced_at_the_feed_rate() {
+        assert_eq!(implied_apy(&mut oracle_market()).unwrap(), 1000);
+    }
+
+    #[test]
+    fn fixed_rate_strategies_are_priced_at_their_apy() {
+        let mut accounts = oracle_market();
+        let mut strategy = Strategy::clone(&accounts.strategy);
+        strategy.rate_mode = Strategy::RATE_MODE_FIXED;
+        accounts.strategy = program_account(&strategy);
+        accounts.rate_feed = None;
+
+        assert_eq!(implied_apy(&mut accounts).unwrap(), 500);
+    }
+
+    #[test]
+    fn oracle_strategies_need_their_fresh_feed() {
+        let mut accounts = oracle_market();
+        accounts.rate_feed = None;
+        assert_eq!(implied_apy(&mut accounts).err(), Some(RateFeedError::MissingRateFeed.into()));
+
+        let mut accounts = oracle_market();
+        let feed = RateFeed::clone(accounts.rate_feed.as_ref().unwrap());
+        accounts.rate_feed = Some(program_account(&feed));
+        assert_eq!(implied_apy(&mut accounts).err(), Some(RateFeedError::WrongRateFeed.into()));
+
+        // The stubbed clock sits at 0, a feed last pushed before the staleness window
+        let mut accounts = oracle_market();
+        let mut feed = RateFeed::clone(accounts.rate_feed.as_ref().unwrap());
+        feed.last_update = -3_601;
+        let stale = program_account_at(accounts.strategy.rate_feed, &feed);
+        accounts.rate_feed = Some(stale);
+        assert_eq!(implied_apy(&mut accounts).err(), Some(RateFeedError::StaleRate.into()));
+    }
+}
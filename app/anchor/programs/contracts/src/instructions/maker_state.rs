@@ -0,0 +1,80 @@
+use anchor_lang::prelude::*;
+
+use crate::state::signed_order::MakerState;
+
+#[derive(Accounts)]
+pub struct InitMakerState<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>,
+
+    /// Nonce tracker for the maker's signed orders; the maker approves this PDA
+    /// as SPL delegate on the token accounts its signed orders settle from
+    #[account(
+        init,
+        payer = maker,
+        seeds = [b"maker_state", maker.key().as_ref()],
+        bump,
+        space = 8 + MakerState::INIT_SPACE
+    )]
+    pub maker_state: Account<'info, MakerState>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelSignedOrders<'info> {
+    pub maker: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"maker_state", maker.key().as_ref()],
+        bump = maker_state.bump,
+        constraint = maker_state.maker == maker.key() @ MakerStateError::UnauthorizedMaker
+    )]
+    pub maker_state: Account<'info, MakerState>,
+}
+
+pub fn handle_init_maker_state(ctx: Context<InitMakerState>) -> Result<()> {
+    let maker_state = &mut ctx.accounts.maker_state;
+
+    maker_state.maker = ctx.accounts.maker.key();
+    maker_state.nonce_floor = 0;
+    maker_state.used_nonces = [0; 4];
+    maker_state.total_fills = 0;
+    maker_state.bump = ctx.bumps.maker_state;
+
+    msg!("Maker state initialized for {}", maker_state.maker);
+
+    Ok(())
+}
+
+pub fn handle_cancel_signed_order(ctx: Context<CancelSignedOrders>, nonce: u64) -> Result<()> {
+    let maker_state = &mut ctx.accounts.maker_state;
+    require!(maker_state.use_nonce(nonce), MakerStateError::NonceNotUsable);
+
+    msg!("Signed order with nonce {} cancelled", nonce);
+
+    Ok(())
+}
+
+pub fn handle_cancel_signed_orders_below(
+    ctx: Context<CancelSignedOrders>,
+    nonce_floor: u64,
+) -> Result<()> {
+    let maker_state = &mut ctx.accounts.maker_state;
+    require!(nonce_floor > maker_state.nonce_floor, MakerStateError::NonceNotUsable);
+
+    maker_state.raise_floor(nonce_floor);
+
+    msg!("All signed orders with nonce below {} cancelled", nonce_floor);
+
+    Ok(())
+}
+
+#[error_code]
+pub enum MakerStateError {
+    #[msg("Unauthorized maker")]
+    UnauthorizedMaker,
+    #[msg("Nonce already used, cancelled or outside the nonce window")]
+    NonceNotUsable,
+}
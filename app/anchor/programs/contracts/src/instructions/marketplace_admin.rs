@@ -0,0 +1,292 @@
+use anchor_lang::prelude::*;
+
+use crate::state::marketplace::Marketplace;
+
+#[derive(Accounts)]
+pub struct UpdateMarketplace<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = admin @ MarketplaceAdminError::Unauthorized
+    )]
+    pub marketplace: Box<Account<'info, Marketplace>>,
+}
+
+#[derive(Accounts)]
+pub struct CloseMarketplace<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = admin @ MarketplaceAdminError::Unauthorized,
+        constraint = marketplace.open_orders == 0 @ MarketplaceAdminError::OpenOrdersRemaining,
+        close = admin
+    )]
+    pub marketplace: Box<Account<'info, Marketplace>>,
+}
+
+pub fn handle_set_marketplace_active(
+    ctx: Context<UpdateMarketplace>,
+    is_active: bool,
+) -> Result<()> {
+    let marketplace = &mut ctx.accounts.marketplace;
+    marketplace.is_active = is_active;
+
+    msg!(
+        "Marketplace {} {}",
+        marketplace.marketplace_id,
+        if is_active { "resumed" } else { "paused" }
+    );
+
+    Ok(())
+}
+
+pub fn handle_update_trading_fee(
+    ctx: Context<UpdateMarketplace>,
+    trading_fee_bps: u16,
+) -> Result<()> {
+    require!(
+        trading_fee_bps <= Marketplace::MAX_TRADING_FEE_BPS,
+        MarketplaceAdminError::FeeTooHigh
+    ); // Max 10% fee
+
+    let marketplace = &mut ctx.accounts.marketplace;
+    let previous_fee_bps = marketplace.trading_fee_bps;
+    marketplace.trading_fee_bps = trading_fee_bps;
+
+    msg!(
+        "Marketplace {} trading fee changed from {}% to {}%",
+        marketplace.marketplace_id,
+        previous_fee_bps as f64 / 100.0,
+        trading_fee_bps as f64 / 100.0
+    );
+
+    Ok(())
+}
+
+pub fn handle_transfer_marketplace_admin(
+    ctx: Context<UpdateMarketplace>,
+    new_admin: Pubkey,
+) -> Result<()> {
+    require!(new_admin != Pubkey::default(), MarketplaceAdminError::InvalidAdmin);
+
+    let marketplace = &mut ctx.accounts.marketplace;
+    marketplace.admin = new_admin;
+
+    msg!(
+        "Marketplace {} admin transferred to {}",
+        marketplace.marketplace_id,
+        new_admin
+    );
+
+    Ok(())
+}
+
+pub fn handle_set_self_trade_behavior(
+    ctx: Context<UpdateMarketplace>,
+    self_trade_behavior: u8,
+) -> Result<()> {
+    require!(
+        self_trade_behavior <= Marketplace::STP_DECREMENT_BOTH,
+        MarketplaceAdminError::InvalidSelfTradeBehavior
+    );
+
+    let marketplace = &mut ctx.accounts.marketplace;
+    marketplace.self_trade_behavior = self_trade_behavior;
+
+    msg!(
+        "Marketplace {} self-trade prevention set to {}",
+        marketplace.marketplace_id,
+        match self_trade_behavior {
+            Marketplace::STP_CANCEL_NEWEST => "cancel newest",
+            Marketplace::STP_CANCEL_OLDEST => "cancel oldest",
+            _ => "decrement both",
+        }
+    );
+
+    Ok(())
+}
+
+pub fn handle_update_order_rules(
+    ctx: Context<UpdateMarketplace>,
+    tick_size: u64,
+    min_order_size: u64,
+) -> Result<()> {
+    require!(tick_size > 0, MarketplaceAdminError::InvalidTickSize);
+
+    let marketplace = &mut ctx.accounts.marketplace;
+    marketplace.tick_size = tick_size;
+    marketplace.min_order_size = min_order_size;
+
+    msg!(
+        "Marketplace {} order rules updated: tick size {}, minimum order size {}",
+        marketplace.marketplace_id,
+        tick_size,
+        min_order_size
+    );
+
+    Ok(())
+}
+
+/// Close an empty marketplace and return its rent to the admin.
+///
+/// Order escrows stay open: their authority is the order PDA and their rent was paid
+/// by whoever placed the order, so it is not the admin's to reclaim. Filled buy orders
+/// can still hold surplus from fills below their limit and transfer-fee rounding,
+/// which owners sweep with `close_order` along with the rent.
+pub fn handle_close_marketplace(ctx: Context<CloseMarketplace>) -> Result<()> {
+    // Rent is returned to the admin by the `close` constraint
+    msg!(
+        "Marketplace {} closed after {} trades",
+        ctx.accounts.marketplace.marketplace_id,
+        ctx.accounts.marketplace.total_trades
+    );
+
+    Ok(())
+}
+
+#[error_code]
+pub enum MarketplaceAdminError {
+    #[msg("Only the marketplace admin can perform this action")]
+    Unauthorized,
+    #[msg("Trading fee too high (max 10%)")]
+    FeeTooHigh,
+    #[msg("Invalid marketplace admin")]
+    InvalidAdmin,
+    #[msg("Marketplace still has open orders")]
+    OpenOrdersRemaining,
+    #[msg("Invalid self-trade prevention mode")]
+    InvalidSelfTradeBehavior,
+    #[msg("Tick size must be greater than zero")]
+    InvalidTickSize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeSet;
+
+    use crate::test_utils::{program_account, signer_info, zeroed, ACCOUNT_LAMPORTS};
+
+    fn marketplace_accounts() -> UpdateMarketplace<'static> {
+        let admin = Pubkey::new_unique();
+        let mut marketplace: Marketplace = zeroed();
+        marketplace.admin = admin;
+        marketplace.is_active = true;
+        marketplace.trading_fee_bps = 30;
+        marketplace.tick_size = 1;
+
+        UpdateMarketplace {
+            admin: Signer::try_from(signer_info(admin)).unwrap(),
+            marketplace: Box::new(program_account(&marketplace)),
+        }
+    }
+
+    fn ctx<'b>(
+        accounts: &'b mut UpdateMarketplace<'static>,
+    ) -> Context<'static, 'b, 'static, 'static, UpdateMarketplace<'static>> {
+        Context::new(&crate::ID, accounts, &[], UpdateMarketplaceBumps::default())
+    }
+
+    fn update_order_rules(tick_size: u64, min_order_size: u64) -> Result<Marketplace> {
+        let mut accounts = marketplace_accounts();
+        handle_update_order_rules(ctx(&mut accounts), tick_size, min_order_size)?;
+        Ok(Marketplace::clone(&accounts.marketplace))
+    }
+
+    #[test]
+    fn order_rules_take_effect() {
+        let marketplace = update_order_rules(10_000, 1_000_000).unwrap();
+
+        assert_eq!(marketplace.tick_size, 10_000);
+        assert_eq!(marketplace.min_order_size, 1_000_000);
+    }
+
+    #[test]
+    fn tick_size_cannot_be_zero() {
+        assert_eq!(
+            update_order_rules(0, 1_000_000).err(),
+            Some(MarketplaceAdminError::InvalidTickSize.into())
+        );
+    }
+
+    #[test]
+    fn admin_pauses_and_resumes_trading() {
+        let mut accounts = marketplace_accounts();
+
+        handle_set_marketplace_active(ctx(&mut accounts), false).unwrap();
+        assert!(!accounts.marketplace.is_active);
+        handle_set_marketplace_active(ctx(&mut accounts), true).unwrap();
+        assert!(accounts.marketplace.is_active);
+    }
+
+    #[test]
+    fn trading_fee_is_capped() {
+        let mut accounts = marketplace_accounts();
+
+        assert_eq!(
+            handle_update_trading_fee(ctx(&mut accounts), Marketplace::MAX_TRADING_FEE_BPS + 1).unwrap_err(),
+            MarketplaceAdminError::FeeTooHigh.into()
+        );
+        assert_eq!(accounts.marketplace.trading_fee_bps, 30);
+
+        handle_update_trading_fee(ctx(&mut accounts), Marketplace::MAX_TRADING_FEE_BPS).unwrap();
+        assert_eq!(accounts.marketplace.trading_fee_bps, Marketplace::MAX_TRADING_FEE_BPS);
+    }
+
+    #[test]
+    fn admin_hands_over_to_a_real_key() {
+        let mut accounts = marketplace_accounts();
+
+        assert_eq!(
+            handle_transfer_marketplace_admin(ctx(&mut accounts), Pubkey::default()).unwrap_err(),
+            MarketplaceAdminError::InvalidAdmin.into()
+        );
+
+        let new_admin = Pubkey::new_unique();
+        handle_transfer_marketplace_admin(ctx(&mut accounts), new_admin).unwrap();
+        assert_eq!(accounts.marketplace.admin, new_admin);
+    }
+
+    #[test]
+    fn self_trade_behavior_must_be_known() {
+        let mut accounts = marketplace_accounts();
+
+        assert_eq!(
+            handle_set_self_trade_behavior(ctx(&mut accounts), Marketplace::STP_DECREMENT_BOTH + 1).unwrap_err(),
+            MarketplaceAdminError::InvalidSelfTradeBehavior.into()
+        );
+        handle_set_self_trade_behavior(ctx(&mut accounts), Marketplace::STP_CANCEL_OLDEST).unwrap();
+        assert_eq!(accounts.marketplace.self_trade_behavior, Marketplace::STP_CANCEL_OLDEST);
+    }
+
+    #[test]
+    fn only_the_admin_closes_an_empty_marketplace() {
+        let accounts = marketplace_accounts();
+        let admin = accounts.admin.key();
+        let marketplace = accounts.marketplace.to_account_info();
+        let close = |admin, open_orders| {
+            let mut state = Marketplace::try_deserialize(&mut &marketplace.data.borrow()[..])?;
+            state.open_orders = open_orders;
+            state.try_serialize(&mut &mut marketplace.data.borrow_mut()[..])?;
+
+            let infos: &'static [AccountInfo<'static>] = vec![signer_info(admin).clone(), marketplace.clone()].leak();
+            let mut bumps = CloseMarketplaceBumps::default();
+            let mut accounts =
+                CloseMarketplace::try_accounts(&crate::ID, &mut &infos[..], &[], &mut bumps, &mut BTreeSet::new())?;
+            handle_close_marketplace(Context::new(&crate::ID, &mut accounts, &[], bumps))?;
+            accounts.exit(&crate::ID)?;
+            Ok::<_, Error>(infos[0].lamports())
+        };
+
+        assert_eq!(close(Pubkey::new_unique(), 0).unwrap_err(), MarketplaceAdminError::Unauthorized.into());
+        assert_eq!(close(admin, 1).unwrap_err(), MarketplaceAdminError::OpenOrdersRemaining.into());
+
+        let rent = marketplace.lamports();
+        assert_eq!(close(admin, 0).unwrap(), ACCOUNT_LAMPORTS + rent);
+        assert_eq!(marketplace.lamports(), 0);
+    }
+}
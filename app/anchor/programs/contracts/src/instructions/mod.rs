@@ -3,17 +3,50 @@ pub mod deposit_to_strategy;
 pub mod claim_yield;
 pub mod withdraw_from_strategy;
 pub mod redeem_yield_tokens;
+pub mod native_sol;
 pub mod create_marketplace;
 pub mod place_order;
 pub mod execute_trade;
 pub mod cancel_order;
+pub mod modify_order;
+pub mod trading_delegate;
+pub mod trigger_order;
+pub mod marketplace_admin;
+pub mod maker_state;
+pub mod fill_signed_order;
+pub mod batch_auction;
+pub mod candles;
+pub mod implied_apy;
+pub mod preview;
+pub mod token_fees;
+pub mod strategy_admin;
+pub mod harvest_yield;
+pub mod rate_feed;
+pub mod withdrawal_queue;
+pub mod compound_yield;
 
 pub use create_strategy::*;
 pub use deposit_to_strategy::*;
 pub use claim_yield::*;
 pub use withdraw_from_strategy::*;
 pub use redeem_yield_tokens::*;
+pub use native_sol::*;
 pub use create_marketplace::*;
 pub use place_order::*;
 pub use execute_trade::*;
-pub use cancel_order::*;
\ No newline at end of file
+pub use cancel_order::*;
+pub use modify_order::*;
+pub use trading_delegate::*;
+pub use trigger_order::*;
+pub use marketplace_admin::*;
+pub use maker_state::*;
+pub use fill_signed_order::*;
+pub use batch_auction::*;
+pub use candles::*;
+pub use implied_apy::*;
+pub use preview::*;
+pub use strategy_admin::*;
+pub use harvest_yield::*;
+pub use rate_feed::*;
+pub use withdrawal_queue::*;
+pub use compound_yield::*;
\ No newline at end of file
@@ -0,0 +1,439 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked};
+
+use crate::instructions::{
+    place_order::validate_order_terms, token_fees::{amount_with_fee, amount_with_fees}, trading_delegate::authorize_order_authority,
+};
+use crate::state::{marketplace::{Marketplace, TradeOrder}, trading_delegate::TradingDelegate};
+
+#[derive(Accounts)]
+#[instruction(order_id: u64)]
+pub struct ModifyOrder<'info> {
+    /// Order owner, or a session key authorised through `trading_delegate`
+    pub authority: Signer<'info>,
+
+    /// CHECK: Order owner, checked against `authority` and `trading_delegate` in the handler
+    pub user: UncheckedAccount<'info>,
+
+    /// Session key authorisation, required when `authority` is not `user`
+    #[account(
+        mut,
+        seeds = [b"trading_delegate", user.key().as_ref(), authority.key().as_ref()],
+        bump = trading_delegate.bump
+    )]
+    pub trading_delegate: Option<Account<'info, TradingDelegate>>,
+
+    #[account(
+        mut,
+        constraint = marketplace.is_active @ ModifyError::MarketplaceNotActive
+    )]
+    pub marketplace: Box<Account<'info, Marketplace>>,
+
+    #[account(
+        mut,
+        seeds = [b"order", user.key().as_ref(), order_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = order.user == user.key() @ ModifyError::UnauthorizedUser,
+        constraint = order.is_active @ ModifyError::OrderNotActive,
+        constraint = order.marketplace == marketplace.key() @ ModifyError::OrderMarketplaceMismatch
+    )]
+    pub order: Account<'info, TradeOrder>,
+
+    /// Escrow account holding the order's tokens
+    #[account(
+        mut,
+        seeds = [b"escrow", order.key().as_ref()],
+        bump
+    )]
+    pub escrow_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Mint held by the escrow
+    #[account(
+        address = escrow_account.mint @ ModifyError::WrongTokenAccount
+    )]
+    pub escrow_mint: InterfaceAccount<'info, Mint>,
+
+    /// User's token account that tops up or receives the escrow difference
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key() @ ModifyError::WrongTokenAccount,
+        constraint = user_token_account.mint == escrow_account.mint @ ModifyError::WrongTokenAccount
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Token program of the escrow mint
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+pub fn handle_modify_order(
+    ctx: Context<ModifyOrder>,
+    order_id: u64,
+    new_price_per_token: u64,
+    new_yield_token_amount: u64,
+) -> Result<()> {
+    let order = &mut ctx.accounts.order;
+    let marketplace = &mut ctx.accounts.marketplace;
+    let current_time = Clock::get()?.unix_timestamp;
+
+    require!(
+        new_yield_token_amount > order.filled_amount,
+        ModifyError::AmountBelowFilled
+    );
+    let total_value = validate_order_terms(marketplace, new_yield_token_amount, new_price_per_token)?;
+
+    // Session keys are charged for any increase in order value and can't reprice sells
+    // below their floor
+    let user_key = ctx.accounts.user.key();
+    let authority_key = ctx.accounts.authority.key();
+    let delegated = authorize_order_authority(
+        &authority_key,
+        &user_key,
+        ctx.accounts.trading_delegate.as_mut(),
+        total_value.saturating_sub(order.total_value),
+        (order.order_type == TradeOrder::SELL_ORDER).then_some(new_price_per_token),
+        current_time,
+    )?;
+
+    // Tokens the escrow must hold for the unfilled part of the amended order, grossed up
+    // for the transfer fee on every hop out to the counterparty
+    let new_remaining = new_yield_token_amount - order.filled_amount;
+    let required_escrow = if order.order_type == TradeOrder::BUY_ORDER {
+        (new_remaining as u128 * new_price_per_token as u128 / 1_000_000) as u64
+    } else {
+        new_remaining
+    };
+    let required_escrow = amount_with_fees(&ctx.accounts.escrow_mint, required_escrow, marketplace.settlement_hops())?;
+    let current_escrow = ctx.accounts.escrow_account.amount;
+
+    if required_escrow > current_escrow {
+        // Top up the escrow from the user (grossed up for any transfer fee)
+        let top_up = amount_with_fee(&ctx.accounts.escrow_mint, required_escrow - current_escrow)?;
+        require!(
+            ctx.accounts.user_token_account.amount >= top_up,
+            ModifyError::InsufficientBalance
+        );
+
+        let delegate_bump = [ctx.accounts.trading_delegate.as_ref().map_or(0, |d| d.bump)];
+        let delegate_seeds = &[
+            b"trading_delegate".as_ref(),
+            user_key.as_ref(),
+            authority_key.as_ref(),
+            &delegate_bump,
+        ];
+        let delegate_signer_seeds = &[delegate_seeds.as_slice()];
+        let (transfer_authority, signer_seeds): (AccountInfo, &[&[&[u8]]]) = match &ctx.accounts.trading_delegate {
+            Some(trading_delegate) if delegated => (trading_delegate.to_account_info(), delegate_signer_seeds),
+            _ => (ctx.accounts.authority.to_account_info(), &[]),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.user_token_account.to_account_info(),
+                mint: ctx.accounts.escrow_mint.to_account_info(),
+                to: ctx.accounts.escrow_account.to_account_info(),
+                authority: transfer_authority,
+            },
+            signer_seeds,
+        );
+        transfer_checked(cpi_ctx, top_up, ctx.accounts.escrow_mint.decimals)?;
+    } else if required_escrow < current_escrow {
+        // Release the excess back to the user
+        let release = current_escrow - required_escrow;
+        let order_id_bytes = order_id.to_le_bytes();
+        let order_seeds = &[
+            b"order",
+            order.user.as_ref(),
+            order_id_bytes.as_ref(),
+            &[ctx.bumps.order],
+        ];
+        let signer_seeds = &[order_seeds.as_slice()];
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.escrow_account.to_account_info(),
+                mint: ctx.accounts.escrow_mint.to_account_info(),
+                to: ctx.accounts.user_token_account.to_account_info(),
+                authority: order.to_account_info(),
+            },
+            signer_seeds,
+        );
+        transfer_checked(cpi_ctx, release, ctx.accounts.escrow_mint.decimals)?;
+    }
+
+    amend_order(
+        order,
+        marketplace,
+        new_yield_token_amount,
+        new_price_per_token,
+        total_value,
+        current_time,
+    );
+
+    msg!(
+        "Order {} modified: {} yield tokens at {} per token (escrow {} -> {})",
+        order_id,
+        new_yield_token_amount,
+        new_price_per_token as f64 / 1_000_000.0,
+        current_escrow,
+        required_escrow
+    );
+
+    Ok(())
+}
+
+/// Apply the amended terms to an order and move the marketplace's best prices with it.
+/// A new price loses time priority, a size change alone keeps it.
+fn amend_order(
+    order: &mut TradeOrder,
+    marketplace: &mut Marketplace,
+    new_yield_token_amount: u64,
+    new_price_per_token: u64,
+    total_value: u64,
+    current_time: i64,
+) {
+    let previous_price = order.price_per_token;
+    let price_changed = new_price_per_token != previous_price;
+
+    order.yield_token_amount = new_yield_token_amount;
+    order.price_per_token = new_price_per_token;
+    order.total_value = total_value;
+
+    if price_changed {
+        order.created_at = current_time;
+    }
+
+    // Update marketplace best prices
+    if order.order_type == TradeOrder::BUY_ORDER {
+        if price_changed && previous_price == marketplace.best_bid_price {
+            marketplace.best_bid_price = 0; // Should recalculate from remaining orders
+        }
+        if marketplace.best_bid_price == 0 || new_price_per_token > marketplace.best_bid_price {
+            marketplace.best_bid_price = new_price_per_token;
+        }
+    } else {
+        if price_changed && previous_price == marketplace.best_ask_price {
+            marketplace.best_ask_price = 0; // Should recalculate from remaining orders
+        }
+        if marketplace.best_ask_price == 0 || new_price_per_token < marketplace.best_ask_price {
+            marketplace.best_ask_price = new_price_per_token;
+        }
+    }
+}
+
+#[error_code]
+pub enum ModifyError {
+    #[msg("Marketplace is not active")]
+    MarketplaceNotActive,
+    #[msg("Unauthorized to modify this order")]
+    UnauthorizedUser,
+    #[msg("Order is not active")]
+    OrderNotActive,
+    #[msg("Order marketplace mismatch")]
+    OrderMarketplaceMismatch,
+    #[msg("Wrong token account")]
+    WrongTokenAccount,
+    #[msg("New amount must exceed the amount already filled")]
+    AmountBelowFilled,
+    #[msg("Insufficient balance")]
+    InsufficientBalance,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use anchor_spl::token::spl_token;
+
+    use crate::test_utils::{
+        approve, balance, mint_account, program_account, program_account_at, program_info, session_key, set_clock,
+        signer_info, token_account, token_account_at, zeroed,
+    };
+
+    fn buy_order(amount: u64, price: u64) -> TradeOrder {
+        let mut order: TradeOrder = zeroed();
+        order.order_type = TradeOrder::BUY_ORDER;
+        order.yield_token_amount = amount;
+        order.price_per_token = price;
+        order.total_value = Marketplace::trade_value(amount, price);
+        order.is_active = true;
+        order.created_at = 1_000;
+        order
+    }
+
+    #[test]
+    fn resizing_keeps_time_priority() {
+        let mut marketplace: Marketplace = zeroed();
+        marketplace.best_bid_price = 1_000_000;
+        let mut order = buy_order(100, 1_000_000);
+
+        amend_order(&mut order, &mut marketplace, 250, 1_000_000, 250, 2_000);
+
+        assert_eq!(order.yield_token_amount, 250);
+        assert_eq!(order.total_value, 250);
+        assert_eq!(order.created_at, 1_000);
+        assert_eq!(marketplace.best_bid_price, 1_000_000);
+    }
+
+    #[test]
+    fn repricing_resets_time_priority() {
+        let mut marketplace: Marketplace = zeroed();
+        let mut order = buy_order(100, 1_000_000);
+
+        amend_order(&mut order, &mut marketplace, 100, 1_200_000, 120, 2_000);
+
+        assert_eq!(order.price_per_token, 1_200_000);
+        assert_eq!(order.created_at, 2_000);
+        assert_eq!(marketplace.best_bid_price, 1_200_000);
+    }
+
+    #[test]
+    fn lowering_the_best_bid_moves_it_down() {
+        let mut marketplace: Marketplace = zeroed();
+        marketplace.best_bid_price = 1_000_000;
+        let mut order = buy_order(100, 1_000_000);
+
+        amend_order(&mut order, &mut marketplace, 100, 900_000, 90, 2_000);
+
+        assert_eq!(marketplace.best_bid_price, 900_000);
+    }
+
+    #[test]
+    fn raising_an_ask_leaves_a_better_best_ask() {
+        let mut marketplace: Marketplace = zeroed();
+        marketplace.best_ask_price = 1_100_000;
+        let mut order = buy_order(100, 1_300_000);
+        order.order_type = TradeOrder::SELL_ORDER;
+
+        amend_order(&mut order, &mut marketplace, 100, 1_400_000, 140, 2_000);
+
+        assert_eq!(marketplace.best_ask_price, 1_100_000);
+    }
+
+    const ORDER_ID: u64 = 7;
+
+    /// A 2 token bid at 1.0 placed at time 1 with `filled` filled and the rest
+    /// escrowed, its owner holding `wallet` quote tokens
+    fn bid_accounts(filled: u64, wallet: u64) -> (ModifyOrder<'static>, u8) {
+        set_clock(100);
+        let user = Pubkey::new_unique();
+        let quote_mint = mint_account(Pubkey::new_unique(), 6);
+        let mut marketplace: Marketplace = zeroed();
+        marketplace.is_active = true;
+        marketplace.tick_size = 1;
+        marketplace.quote_mint = quote_mint.key();
+        let marketplace = program_account(&marketplace);
+
+        let mut order = buy_order(2_000_000, 1_000_000);
+        order.user = user;
+        order.marketplace = marketplace.key();
+        order.order_id = ORDER_ID;
+        order.filled_amount = filled;
+        order.created_at = 1;
+        let (address, bump) =
+            Pubkey::find_program_address(&[b"order", user.as_ref(), ORDER_ID.to_le_bytes().as_ref()], &crate::ID);
+        let (escrow_address, _) = Pubkey::find_program_address(&[b"escrow", address.as_ref()], &crate::ID);
+
+        let user_info = signer_info(user);
+        let accounts = ModifyOrder {
+            authority: Signer::try_from(user_info).unwrap(),
+            user: UncheckedAccount::try_from(user_info),
+            trading_delegate: None,
+            marketplace: Box::new(marketplace),
+            order: program_account_at(address, &order),
+            escrow_account: token_account_at(escrow_address, quote_mint.key(), address, 2_000_000 - filled),
+            user_token_account: token_account(quote_mint.key(), user, wallet),
+            escrow_mint: quote_mint,
+            token_program: Interface::try_from(program_info(spl_token::ID)).unwrap(),
+        };
+        (accounts, bump)
+    }
+
+    fn modify(accounts: &mut ModifyOrder<'static>, order_bump: u8, price: u64, amount: u64) -> Result<()> {
+        let bumps = ModifyOrderBumps { order: order_bump, ..Default::default() };
+        handle_modify_order(Context::new(&crate::ID, accounts, &[], bumps), ORDER_ID, price, amount)
+    }
+
+    #[test]
+    fn repricing_tops_up_the_escrow() {
+        let (mut accounts, bump) = bid_accounts(0, 8_000_000);
+
+        modify(&mut accounts, bump, 1_500_000, 2_000_000).unwrap();
+
+        assert_eq!(balance(&accounts.escrow_account), 3_000_000);
+        assert_eq!(balance(&accounts.user_token_account), 7_000_000);
+        assert_eq!(accounts.order.price_per_token, 1_500_000);
+        assert_eq!(accounts.order.total_value, 3_000_000);
+        assert_eq!(accounts.order.created_at, 100);
+    }
+
+    #[test]
+    fn shrinking_releases_the_escrow() {
+        let (mut accounts, bump) = bid_accounts(0, 8_000_000);
+
+        modify(&mut accounts, bump, 1_000_000, 1_000_000).unwrap();
+
+        assert_eq!(balance(&accounts.escrow_account), 1_000_000);
+        assert_eq!(balance(&accounts.user_token_account), 9_000_000);
+        assert_eq!(accounts.order.yield_token_amount, 1_000_000);
+        assert_eq!(accounts.order.created_at, 1);
+    }
+
+    #[test]
+    fn partially_filled_orders_size_only_their_remainder() {
+        let (mut accounts, bump) = bid_accounts(500_000, 0);
+
+        assert_eq!(
+            modify(&mut accounts, bump, 1_500_000, 400_000).unwrap_err(),
+            ModifyError::AmountBelowFilled.into()
+        );
+
+        // 300k left to fill at 1.5 needs 450k of the 1.5M escrowed
+        modify(&mut accounts, bump, 1_500_000, 800_000).unwrap();
+        assert_eq!(balance(&accounts.escrow_account), 450_000);
+        assert_eq!(balance(&accounts.user_token_account), 1_050_000);
+        assert_eq!(accounts.order.filled_amount, 500_000);
+    }
+
+    #[test]
+    fn top_ups_need_the_funds() {
+        let (mut accounts, bump) = bid_accounts(0, 8_000_000);
+
+        assert_eq!(
+            modify(&mut accounts, bump, 1_500_000, 100_000_000).unwrap_err(),
+            ModifyError::InsufficientBalance.into()
+        );
+        assert_eq!(balance(&accounts.escrow_account), 2_000_000);
+    }
+
+    #[test]
+    fn session_keys_amend_for_the_user() {
+        let (mut accounts, bump) = bid_accounts(0, 8_000_000);
+        let key = Pubkey::new_unique();
+        let trading_delegate = session_key(accounts.user.key(), key, 3_000_000);
+        approve(&accounts.user_token_account, trading_delegate.key(), 8_000_000);
+        accounts.authority = Signer::try_from(signer_info(key)).unwrap();
+        accounts.trading_delegate = Some(trading_delegate);
+        accounts.trading_delegate.as_mut().unwrap().notional_used = 2_000_000;
+
+        // Going to 2 tokens at 2.0 would add 2 tokens of notional on top of the 2 used
+        assert_eq!(
+            modify(&mut accounts, bump, 2_000_000, 2_000_000).unwrap_err(),
+            crate::instructions::trading_delegate::DelegateError::NotionalLimitExceeded.into()
+        );
+
+        // Halving the price releases the escrow to the user and charges nothing
+        modify(&mut accounts, bump, 500_000, 2_000_000).unwrap();
+        assert_eq!(balance(&accounts.user_token_account), 9_000_000);
+        assert_eq!(accounts.trading_delegate.as_ref().unwrap().notional_used, 2_000_000);
+
+        // Raising it back tops up from the user through the delegate PDA, charging the increase
+        accounts.escrow_account.reload().unwrap();
+        accounts.user_token_account.reload().unwrap();
+        modify(&mut accounts, bump, 1_000_000, 2_000_000).unwrap();
+        assert_eq!(balance(&accounts.escrow_account), 2_000_000);
+        assert_eq!(balance(&accounts.user_token_account), 8_000_000);
+        assert_eq!(accounts.trading_delegate.as_ref().unwrap().notional_used, 3_000_000);
+    }
+}
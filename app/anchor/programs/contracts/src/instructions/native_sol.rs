@@ -0,0 +1,715 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token::spl_token::native_mint;
+use anchor_spl::token_interface::{
+    Mint, TokenAccount, TokenInterface, MintTo, mint_to, TransferChecked, transfer_checked,
+    Burn, burn, CloseAccount, close_account, SyncNative, sync_native,
+};
+
+use crate::instructions::{
+    deposit_to_strategy::{apply_deposit, check_deposit_caps}, redeem_yield_tokens::apply_redeem,
+    withdraw_from_strategy::{apply_withdraw, transfer_penalty},
+};
+use crate::adapters::{self, AdapterAccounts};
+use crate::instructions::rate_feed::accrue_strategy;
+use crate::state::{rate_feed::RateFeed, strategy::Strategy, user_position::UserPosition};
+
+#[derive(Accounts)]
+#[instruction(amount: u64, strategy_id: u64)]
+pub struct DepositSol<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"strategy", strategy_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = strategy.is_operational() @ NativeSolError::StrategyNotActive,
+        constraint = strategy.underlying_token == native_mint::ID @ NativeSolError::NotNativeStrategy
+    )]
+    pub strategy: Account<'info, Strategy>,
+
+    /// Created on the first deposit, topped up by later ones
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"user_position", user.key().as_ref(), strategy.key().as_ref()],
+        bump,
+        space = 8 + UserPosition::INIT_SPACE
+    )]
+    pub user_position: Account<'info, UserPosition>,
+
+    /// The wrapped SOL mint
+    #[account(
+        address = native_mint::ID @ NativeSolError::NotNativeStrategy
+    )]
+    pub native_mint: InterfaceAccount<'info, Mint>,
+
+    /// Strategy's wSOL vault, lamports sent here are wrapped with sync_native
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"strategy_vault", strategy_id.to_le_bytes().as_ref()],
+        bump,
+        token::mint = native_mint,
+        token::authority = strategy,
+        token::token_program = token_program,
+    )]
+    pub strategy_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Yield token mint for this strategy
+    #[account(
+        mut,
+        address = strategy.yield_token_mint @ NativeSolError::WrongYieldTokenMint
+    )]
+    pub yield_token_mint: InterfaceAccount<'info, Mint>,
+
+    /// User's yield token account (will be created if needed)
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = yield_token_mint,
+        associated_token::authority = user,
+        associated_token::token_program = yield_token_program
+    )]
+    pub user_yield_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// SPL Token program, owner of the native mint
+    pub token_program: Interface<'info, TokenInterface>,
+    /// Token program of the yield token mint
+    pub yield_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+
+    /// Rate feed of oracle-rate strategies, omitted for fixed-rate strategies
+    pub rate_feed: Option<Account<'info, RateFeed>>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, strategy_id: u64)]
+pub struct WithdrawSol<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"strategy", strategy_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = strategy.is_operational() @ NativeSolError::StrategyNotActive,
+        constraint = strategy.underlying_token == native_mint::ID @ NativeSolError::NotNativeStrategy,
+        constraint = strategy.withdrawal_cooldown == 0 @ NativeSolError::CooldownRequired
+    )]
+    pub strategy: Account<'info, Strategy>,
+
+    #[account(
+        mut,
+        seeds = [b"user_position", user.key().as_ref(), strategy.key().as_ref()],
+        bump,
+        constraint = user_position.user == user.key() @ NativeSolError::UnauthorizedUser
+    )]
+    pub user_position: Account<'info, UserPosition>,
+
+    /// Strategy's wSOL vault
+    #[account(
+        mut,
+        seeds = [b"strategy_vault", strategy_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub strategy_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// The wrapped SOL mint
+    #[account(
+        address = native_mint::ID @ NativeSolError::NotNativeStrategy
+    )]
+    pub native_mint: InterfaceAccount<'info, Mint>,
+
+    /// Temporary wSOL account, closed to the user within the instruction to unwrap the payout
+    #[account(
+        init,
+        payer = user,
+        seeds = [b"sol_unwrap", user.key().as_ref()],
+        bump,
+        token::mint = native_mint,
+        token::authority = user,
+        token::token_program = token_program,
+    )]
+    pub unwrap_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// SPL Token program, owner of the native mint
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+
+    /// Rate feed of oracle-rate strategies, omitted for fixed-rate strategies
+    pub rate_feed: Option<Account<'info, RateFeed>>,
+
+    /// Reserve receiving early-exit penalties, only needed while the position is locked
+    #[account(
+        mut,
+        seeds = [b"reward_reserve", strategy_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub reward_reserve: Option<InterfaceAccount<'info, TokenAccount>>,
+}
+
+#[derive(Accounts)]
+#[instruction(yield_token_amount: u64, strategy_id: u64)]
+pub struct RedeemSol<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"strategy", strategy_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = strategy.is_operational() @ NativeSolError::StrategyNotActive,
+        constraint = strategy.underlying_token == native_mint::ID @ NativeSolError::NotNativeStrategy
+    )]
+    pub strategy: Account<'info, Strategy>,
+
+    #[account(
+        mut,
+        seeds = [b"user_position", user.key().as_ref(), strategy.key().as_ref()],
+        bump,
+        constraint = user_position.user == user.key() @ NativeSolError::UnauthorizedUser
+    )]
+    pub user_position: Account<'info, UserPosition>,
+
+    /// Strategy's wSOL vault
+    #[account(
+        mut,
+        seeds = [b"strategy_vault", strategy_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub strategy_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Yield token mint for this strategy
+    #[account(
+        mut,
+        address = strategy.yield_token_mint @ NativeSolError::WrongYieldTokenMint
+    )]
+    pub yield_token_mint: InterfaceAccount<'info, Mint>,
+
+    /// User's yield token account (tokens will be burned from here)
+    #[account(
+        mut,
+        constraint = user_yield_token_account.mint == yield_token_mint.key() @ NativeSolError::WrongYieldTokenAccount
+    )]
+    pub user_yield_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// The wrapped SOL mint
+    #[account(
+        address = native_mint::ID @ NativeSolError::NotNativeStrategy
+    )]
+    pub native_mint: InterfaceAccount<'info, Mint>,
+
+    /// Temporary wSOL account, closed to the user within the instruction to unwrap the payout
+    #[account(
+        init,
+        payer = user,
+        seeds = [b"sol_unwrap", user.key().as_ref()],
+        bump,
+        token::mint = native_mint,
+        token::authority = user,
+        token::token_program = token_program,
+    )]
+    pub unwrap_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// SPL Token program, owner of the native mint
+    pub token_program: Interface<'info, TokenInterface>,
+    /// Token program of the yield token mint
+    pub yield_token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+
+    /// Rate feed of oracle-rate strategies, omitted for fixed-rate strategies
+    pub rate_feed: Option<Account<'info, RateFeed>>,
+
+    /// Reserve receiving early-exit penalties, only needed while the position is locked
+    #[account(
+        mut,
+        seeds = [b"reward_reserve", strategy_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub reward_reserve: Option<InterfaceAccount<'info, TokenAccount>>,
+}
+
+pub fn handle_deposit_sol<'info>(
+    ctx: Context<'_, '_, 'info, 'info, DepositSol<'info>>,
+    amount: u64,
+    strategy_id: u64,
+) -> Result<()> {
+    require!(amount > 0, NativeSolError::InvalidAmount);
+
+    let strategy = &mut ctx.accounts.strategy;
+    let user_position = &mut ctx.accounts.user_position;
+    let current_time = Clock::get()?.unix_timestamp;
+    accrue_strategy(strategy, ctx.accounts.rate_feed.as_ref(), current_time)?;
+    check_deposit_caps(strategy, user_position, amount)?;
+
+    // Send lamports straight into the wSOL vault and wrap them
+    let cpi_ctx = CpiContext::new(
+        ctx.accounts.system_program.to_account_info(),
+        system_program::Transfer {
+            from: ctx.accounts.user.to_account_info(),
+            to: ctx.accounts.strategy_vault.to_account_info(),
+        },
+    );
+    system_program::transfer(cpi_ctx, amount)?;
+
+    let cpi_ctx = CpiContext::new(
+        ctx.accounts.token_program.to_account_info(),
+        SyncNative {
+            account: ctx.accounts.strategy_vault.to_account_info(),
+        },
+    );
+    sync_native(cpi_ctx)?;
+
+    let yield_tokens_to_mint = apply_deposit(
+        strategy,
+        user_position,
+        ctx.accounts.user.key(),
+        amount,
+        current_time,
+    );
+
+    let strategy_id_bytes = strategy_id.to_le_bytes();
+    let signer_seeds: &[&[u8]] = &[b"strategy", strategy_id_bytes.as_ref(), &[ctx.bumps.strategy]];
+    let seeds: &[&[&[u8]]] = &[signer_seeds];
+
+    // Deploy the deposit into the strategy's yield source, if it has one
+    let adapter_accounts = AdapterAccounts {
+        vault: ctx.accounts.strategy_vault.to_account_info(),
+        mint: ctx.accounts.native_mint.to_account_info(),
+        token_program: ctx.accounts.token_program.to_account_info(),
+        remaining_accounts: ctx.remaining_accounts,
+    };
+    adapters::deposit(strategy, &adapter_accounts, signer_seeds, amount)?;
+
+    // Mint yield tokens to user
+
+    let cpi_ctx = CpiContext::new_with_signer(
+        ctx.accounts.yield_token_program.to_account_info(),
+        MintTo {
+            mint: ctx.accounts.yield_token_mint.to_account_info(),
+            to: ctx.accounts.user_yield_token_account.to_account_info(),
+            authority: strategy.to_account_info(),
+        },
+        seeds,
+    );
+    mint_to(cpi_ctx, yield_tokens_to_mint)?;
+
+    msg!(
+        "User {} deposited {} lamports to strategy '{}' and received {} yield tokens",
+        ctx.accounts.user.key(),
+        amount,
+        strategy.name,
+        yield_tokens_to_mint
+    );
+
+    Ok(())
+}
+
+pub fn handle_withdraw_sol<'info>(
+    ctx: Context<'_, '_, 'info, 'info, WithdrawSol<'info>>,
+    amount: u64,
+    strategy_id: u64,
+) -> Result<()> {
+    require!(amount > 0, NativeSolError::InvalidAmount);
+
+    let strategy = &mut ctx.accounts.strategy;
+    let current_time = Clock::get()?.unix_timestamp;
+    accrue_strategy(strategy, ctx.accounts.rate_feed.as_ref(), current_time)?;
+    let penalty = apply_withdraw(strategy, &mut ctx.accounts.user_position, amount, current_time)?;
+
+    let strategy_id_bytes = strategy_id.to_le_bytes();
+    let signer_seeds: &[&[u8]] = &[b"strategy", strategy_id_bytes.as_ref(), &[ctx.bumps.strategy]];
+    let seeds: &[&[&[u8]]] = &[signer_seeds];
+
+    // Pull what the vault is missing back out of the yield source
+    let adapter_accounts = AdapterAccounts {
+        vault: ctx.accounts.strategy_vault.to_account_info(),
+        mint: ctx.accounts.native_mint.to_account_info(),
+        token_program: ctx.accounts.token_program.to_account_info(),
+        remaining_accounts: ctx.remaining_accounts,
+    };
+    adapters::withdraw_to_vault(
+        strategy,
+        &adapter_accounts,
+        signer_seeds,
+        ctx.accounts.strategy_vault.amount,
+        amount,
+    )?;
+
+    // Early exits leave their penalty in the reward reserve
+    transfer_penalty(
+        strategy.to_account_info(),
+        ctx.accounts.strategy_vault.to_account_info(),
+        ctx.accounts.reward_reserve.as_ref(),
+        &ctx.accounts.native_mint,
+        ctx.accounts.token_program.to_account_info(),
+        signer_seeds,
+        penalty,
+    )?;
+
+    let cpi_ctx = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        TransferChecked {
+            from: ctx.accounts.strategy_vault.to_account_info(),
+            mint: ctx.accounts.native_mint.to_account_info(),
+            to: ctx.accounts.unwrap_account.to_account_info(),
+            authority: strategy.to_account_info(),
+        },
+        seeds,
+    );
+    transfer_checked(cpi_ctx, amount - penalty, ctx.accounts.native_mint.decimals)?;
+
+    unwrap_to_user(
+        &ctx.accounts.unwrap_account,
+        &ctx.accounts.user,
+        &ctx.accounts.token_program,
+    )?;
+
+    msg!(
+        "User {} withdrew {} lamports from strategy '{}' - {} early-exit penalty applied",
+        ctx.accounts.user.key(),
+        amount,
+        strategy.name,
+        penalty
+    );
+
+    Ok(())
+}
+
+pub fn handle_redeem_sol<'info>(
+    ctx: Context<'_, '_, 'info, 'info, RedeemSol<'info>>,
+    yield_token_amount: u64,
+    strategy_id: u64,
+) -> Result<()> {
+    require!(yield_token_amount > 0, NativeSolError::InvalidAmount);
+
+    let strategy = &mut ctx.accounts.strategy;
+    let current_time = Clock::get()?.unix_timestamp;
+    accrue_strategy(strategy, ctx.accounts.rate_feed.as_ref(), current_time)?;
+
+    // Check if user has sufficient yield tokens
+    require!(
+        ctx.accounts.user_yield_token_account.amount >= yield_token_amount,
+        NativeSolError::InsufficientYieldTokens
+    );
+
+    let (principal_to_return, yield_to_return, penalty) = apply_redeem(
+        strategy,
+        &mut ctx.accounts.user_position,
+        yield_token_amount,
+        current_time,
+    )?;
+    let total_to_transfer = principal_to_return + yield_to_return;
+
+    // Burn the yield tokens
+    let cpi_ctx = CpiContext::new(
+        ctx.accounts.yield_token_program.to_account_info(),
+        Burn {
+            mint: ctx.accounts.yield_token_mint.to_account_info(),
+            from: ctx.accounts.user_yield_token_account.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        },
+    );
+    burn(cpi_ctx, yield_token_amount)?;
+
+    let strategy_id_bytes = strategy_id.to_le_bytes();
+    let signer_seeds: &[&[u8]] = &[b"strategy", strategy_id_bytes.as_ref(), &[ctx.bumps.strategy]];
+    let seeds: &[&[&[u8]]] = &[signer_seeds];
+
+    // Pull what the vault is missing back out of the yield source
+    let adapter_accounts = AdapterAccounts {
+        vault: ctx.accounts.strategy_vault.to_account_info(),
+        mint: ctx.accounts.native_mint.to_account_info(),
+        token_program: ctx.accounts.token_program.to_account_info(),
+        remaining_accounts: ctx.remaining_accounts,
+    };
+    adapters::withdraw_to_vault(
+        strategy,
+        &adapter_accounts,
+        signer_seeds,
+        ctx.accounts.strategy_vault.amount,
+        total_to_transfer,
+    )?;
+
+    // Early exits leave their penalty in the reward reserve
+    transfer_penalty(
+        strategy.to_account_info(),
+        ctx.accounts.strategy_vault.to_account_info(),
+        ctx.accounts.reward_reserve.as_ref(),
+        &ctx.accounts.native_mint,
+        ctx.accounts.token_program.to_account_info(),
+        signer_seeds,
+        penalty,
+    )?;
+
+    let cpi_ctx = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        TransferChecked {
+            from: ctx.accounts.strategy_vault.to_account_info(),
+            mint: ctx.accounts.native_mint.to_account_info(),
+            to: ctx.accounts.unwrap_account.to_account_info(),
+            authority: strategy.to_account_info(),
+        },
+        seeds,
+    );
+    transfer_checked(cpi_ctx, total_to_transfer - penalty, ctx.accounts.native_mint.decimals)?;
+
+    unwrap_to_user(
+        &ctx.accounts.unwrap_account,
+        &ctx.accounts.user,
+        &ctx.accounts.token_program,
+    )?;
+
+    msg!(
+        "User {} redeemed {} yield tokens for {} principal + {} yield - {} penalty = {} total lamports",
+        ctx.accounts.user.key(),
+        yield_token_amount,
+        principal_to_return,
+        yield_to_return,
+        penalty,
+        total_to_transfer - penalty
+    );
+
+    Ok(())
+}
+
+/// Close the temporary wSOL account so its wrapped balance and rent land in the user's wallet
+fn unwrap_to_user<'info>(
+    unwrap_account: &InterfaceAccount<'info, TokenAccount>,
+    user: &Signer<'info>,
+    token_program: &Interface<'info, TokenInterface>,
+) -> Result<()> {
+    let cpi_ctx = CpiContext::new(
+        token_program.to_account_info(),
+        CloseAccount {
+            account: unwrap_account.to_account_info(),
+            destination: user.to_account_info(),
+            authority: user.to_account_info(),
+        },
+    );
+    close_account(cpi_ctx)
+}
+
+#[error_code]
+pub enum NativeSolError {
+    #[msg("Strategy is not active")]
+    StrategyNotActive,
+    #[msg("Strategy underlying token is not native SOL")]
+    NotNativeStrategy,
+    #[msg("Unauthorized user for this position")]
+    UnauthorizedUser,
+    #[msg("Wrong yield token mint")]
+    WrongYieldTokenMint,
+    #[msg("Wrong yield token account")]
+    WrongYieldTokenAccount,
+    #[msg("Invalid amount")]
+    InvalidAmount,
+    #[msg("Insufficient yield tokens")]
+    InsufficientYieldTokens,
+    #[msg("Strategy has a withdrawal cooldown, use request_withdrawal")]
+    CooldownRequired,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use anchor_spl::token::spl_token;
+
+    use crate::test_utils::{
+        ACCOUNT_LAMPORTS, LendingStrategy, balance, program_account_at, program_info, rent_sysvar, set_clock,
+        signer_info, token_account, token_account_at, yield_mint, zeroed,
+    };
+
+    const YEAR: i64 = 365 * 24 * 60 * 60;
+
+    /// SOL strategy paying 10% with a position of `user` holding `deposited`, lending
+    /// `deployed` out of the vault when `adapter` is set
+    struct SolStrategy {
+        lending: LendingStrategy,
+        user: AccountInfo<'static>,
+        position: Account<'static, UserPosition>,
+        yield_mint: InterfaceAccount<'static, Mint>,
+        user_yield: InterfaceAccount<'static, TokenAccount>,
+    }
+
+    impl SolStrategy {
+        fn new(vault: u64, deployed: u64, deposited: u64, adapter: bool) -> Self {
+            let mut lending = LendingStrategy::of_mint(native_mint::ID, vault, deployed, 0);
+            let user = signer_info(Pubkey::new_unique()).clone();
+            let strategy_key = lending.strategy.key();
+
+            let yield_mint_key = Pubkey::new_unique();
+            let yield_mint = yield_mint(yield_mint_key, strategy_key, deposited, native_mint::DECIMALS);
+
+            let strategy = &mut lending.strategy;
+            strategy.is_active = true;
+            strategy.apy = 1000;
+            strategy.yield_token_mint = yield_mint_key;
+            strategy.total_deposits = deposited;
+            strategy.total_yield_tokens_minted = deposited;
+            if !adapter {
+                strategy.adapter_kind = Strategy::ADAPTER_NONE;
+            }
+
+            let mut position: UserPosition = zeroed();
+            if deposited > 0 {
+                position.user = user.key();
+                position.strategy = strategy_key;
+                position.deposited_amount = deposited;
+                position.yield_tokens_minted = deposited;
+            }
+
+            Self {
+                user_yield: token_account(yield_mint_key, user.key(), deposited),
+                position: program_account_at(Pubkey::new_unique(), &position),
+                yield_mint,
+                user,
+                lending,
+            }
+        }
+
+        fn unwrap_account(&self) -> InterfaceAccount<'static, TokenAccount> {
+            token_account_at(Pubkey::new_unique(), native_mint::ID, self.user.key(), 0)
+        }
+
+        fn deposit(&self, amount: u64) -> Result<DepositSol<'static>> {
+            let mut accounts = DepositSol {
+                user: Signer::try_from(Box::leak(Box::new(self.user.clone()))).unwrap(),
+                strategy: self.lending.strategy.clone(),
+                user_position: self.position.clone(),
+                native_mint: self.lending.mint.clone(),
+                strategy_vault: self.lending.vault.clone(),
+                yield_token_mint: self.yield_mint.clone(),
+                user_yield_token_account: self.user_yield.clone(),
+                token_program: Interface::try_from(program_info(spl_token::ID)).unwrap(),
+                yield_token_program: Interface::try_from(program_info(spl_token::ID)).unwrap(),
+                associated_token_program: Program::try_from(program_info(anchor_spl::associated_token::ID)).unwrap(),
+                system_program: Program::try_from(program_info(System::id())).unwrap(),
+                rent: rent_sysvar(),
+                rate_feed: None,
+            };
+            let bumps = DepositSolBumps { strategy: self.lending.bump, ..Default::default() };
+            handle_deposit_sol(
+                Context::new(&crate::ID, &mut accounts, self.lending.remaining_accounts, bumps),
+                amount,
+                LendingStrategy::STRATEGY_ID,
+            )?;
+            Ok(accounts)
+        }
+
+        fn withdraw(&self, amount: u64, unwrap_account: InterfaceAccount<'static, TokenAccount>) -> Result<()> {
+            let mut accounts = WithdrawSol {
+                user: Signer::try_from(Box::leak(Box::new(self.user.clone()))).unwrap(),
+                strategy: self.lending.strategy.clone(),
+                user_position: self.position.clone(),
+                strategy_vault: self.lending.vault.clone(),
+                native_mint: self.lending.mint.clone(),
+                unwrap_account,
+                token_program: Interface::try_from(program_info(spl_token::ID)).unwrap(),
+                system_program: Program::try_from(program_info(System::id())).unwrap(),
+                rent: rent_sysvar(),
+                rate_feed: None,
+                reward_reserve: None,
+            };
+            let bumps = WithdrawSolBumps { strategy: self.lending.bump, ..Default::default() };
+            handle_withdraw_sol(
+                Context::new(&crate::ID, &mut accounts, self.lending.remaining_accounts, bumps),
+                amount,
+                LendingStrategy::STRATEGY_ID,
+            )
+        }
+
+        fn redeem(&self, yield_token_amount: u64, unwrap_account: InterfaceAccount<'static, TokenAccount>) -> Result<()> {
+            let mut accounts = RedeemSol {
+                user: Signer::try_from(Box::leak(Box::new(self.user.clone()))).unwrap(),
+                strategy: self.lending.strategy.clone(),
+                user_position: self.position.clone(),
+                strategy_vault: self.lending.vault.clone(),
+                yield_token_mint: self.yield_mint.clone(),
+                user_yield_token_account: self.user_yield.clone(),
+                native_mint: self.lending.mint.clone(),
+                unwrap_account,
+                token_program: Interface::try_from(program_info(spl_token::ID)).unwrap(),
+                yield_token_program: Interface::try_from(program_info(spl_token::ID)).unwrap(),
+                system_program: Program::try_from(program_info(System::id())).unwrap(),
+                rent: rent_sysvar(),
+                rate_feed: None,
+                reward_reserve: None,
+            };
+            let bumps = RedeemSolBumps { strategy: self.lending.bump, ..Default::default() };
+            handle_redeem_sol(
+                Context::new(&crate::ID, &mut accounts, self.lending.remaining_accounts, bumps),
+                yield_token_amount,
+                LendingStrategy::STRATEGY_ID,
+            )
+        }
+    }
+
+    #[test]
+    fn deposits_wrap_lamports_into_the_vault() {
+        let sol = SolStrategy::new(0, 0, 0, false);
+
+        let accounts = sol.deposit(400_000).unwrap();
+
+        assert_eq!(sol.user.lamports(), ACCOUNT_LAMPORTS - 400_000);
+        assert_eq!(balance(&sol.lending.vault), 400_000);
+        assert_eq!(balance(&sol.user_yield), 400_000);
+        assert_eq!(accounts.user_position.deposited_amount, 400_000);
+        assert_eq!(accounts.strategy.total_deposits, 400_000);
+    }
+
+    #[test]
+    fn wrapped_deposits_are_deployed_into_the_yield_source() {
+        let sol = SolStrategy::new(0, 0, 0, true);
+
+        let accounts = sol.deposit(400_000).unwrap();
+
+        assert_eq!(balance(&sol.lending.vault), 0);
+        assert_eq!(balance(&sol.lending.pool_vault), 400_000);
+        assert_eq!(accounts.strategy.adapter_deposits, 400_000);
+    }
+
+    #[test]
+    fn withdrawals_unwrap_into_the_wallet() {
+        let sol = SolStrategy::new(1_000_000, 0, 1_000_000, false);
+        let unwrap_account = sol.unwrap_account();
+
+        sol.withdraw(400_000, unwrap_account.clone()).unwrap();
+
+        // The temporary account is closed, its rent comes back along with the payout
+        assert_eq!(sol.user.lamports(), 2 * ACCOUNT_LAMPORTS + 400_000);
+        assert_eq!(unwrap_account.to_account_info().lamports(), 0);
+        assert_eq!(balance(&sol.lending.vault), 600_000);
+    }
+
+    #[test]
+    fn withdrawals_pull_deployed_lamports_back() {
+        let sol = SolStrategy::new(0, 1_000_000, 1_000_000, true);
+
+        sol.withdraw(400_000, sol.unwrap_account()).unwrap();
+
+        assert_eq!(sol.user.lamports(), 2 * ACCOUNT_LAMPORTS + 400_000);
+        assert_eq!(balance(&sol.lending.vault), 0);
+        assert_eq!(balance(&sol.lending.pool_vault), 600_000);
+    }
+
+    #[test]
+    fn redemptions_unwrap_principal_and_yield() {
+        let sol = SolStrategy::new(1_100_000, 0, 1_000_000, false);
+        set_clock(YEAR);
+
+        sol.redeem(1_000_000, sol.unwrap_account()).unwrap();
+
+        assert_eq!(sol.user.lamports(), 2 * ACCOUNT_LAMPORTS + 1_100_000);
+        assert_eq!(balance(&sol.user_yield), 0);
+        assert_eq!(balance(&sol.lending.vault), 0);
+    }
+}
@@ -1,23 +1,38 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Mint, TokenAccount, Token, Transfer, transfer};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked};
 
-use crate::state::marketplace::{Marketplace, TradeOrder, OrderCounter};
+use crate::instructions::{
+    batch_auction::{evict_from_full_book, MAX_AUCTION_ORDERS}, token_fees::amount_with_fees, trading_delegate::authorize_order_authority,
+};
+use crate::state::{marketplace::{Marketplace, TradeOrder, OrderCounter}, trading_delegate::TradingDelegate};
 
 #[derive(Accounts)]
 #[instruction(order_id: u64, order_type: u8, yield_token_amount: u64, price_per_token: u64)]
 pub struct PlaceOrder<'info> {
+    /// Order owner, or a session key authorised through `trading_delegate`
     #[account(mut)]
-    pub user: Signer<'info>,
+    pub authority: Signer<'info>,
+
+    /// CHECK: Order owner, checked against `authority` and `trading_delegate` in the handler
+    pub user: UncheckedAccount<'info>,
+
+    /// Session key authorisation, required when `authority` is not `user`
+    #[account(
+        mut,
+        seeds = [b"trading_delegate", user.key().as_ref(), authority.key().as_ref()],
+        bump = trading_delegate.bump
+    )]
+    pub trading_delegate: Option<Account<'info, TradingDelegate>>,
 
     #[account(
         mut,
         constraint = marketplace.is_active @ OrderError::MarketplaceNotActive
     )]
-    pub marketplace: Account<'info, Marketplace>,
+    pub marketplace: Box<Account<'info, Marketplace>>,
 
     #[account(
         init,
-        payer = user,
+        payer = authority,
         seeds = [b"order", user.key().as_ref(), order_id.to_le_bytes().as_ref()],
         bump,
         space = 8 + TradeOrder::INIT_SPACE
@@ -26,7 +41,7 @@ pub struct PlaceOrder<'info> {
 
     #[account(
         init_if_needed,
-        payer = user,
+        payer = authority,
         seeds = [b"order_counter"],
         bump,
         space = 8 + OrderCounter::INIT_SPACE
@@ -37,53 +52,65 @@ pub struct PlaceOrder<'info> {
     #[account(
         address = marketplace.yield_token_mint @ OrderError::WrongYieldTokenMint
     )]
-    pub yield_token_mint: Account<'info, Mint>,
+    pub yield_token_mint: InterfaceAccount<'info, Mint>,
 
-    /// Underlying token mint  
+    /// Quote token mint of the marketplace
     #[account(
-        address = marketplace.underlying_token_mint @ OrderError::WrongUnderlyingToken
+        address = marketplace.quote_mint @ OrderError::WrongQuoteMint
     )]
-    pub underlying_token_mint: Account<'info, Mint>,
+    pub quote_mint: InterfaceAccount<'info, Mint>,
 
     /// User's yield token account (for sell orders)
     #[account(
         mut,
+        constraint = user_yield_token_account.owner == user.key() @ OrderError::WrongTokenAccount,
         constraint = user_yield_token_account.mint == yield_token_mint.key() @ OrderError::WrongTokenAccount
     )]
-    pub user_yield_token_account: Account<'info, TokenAccount>,
+    pub user_yield_token_account: InterfaceAccount<'info, TokenAccount>,
 
-    /// User's underlying token account (for buy orders)
+    /// User's quote token account (for buy orders)
     #[account(
         mut,
-        constraint = user_underlying_token_account.mint == underlying_token_mint.key() @ OrderError::WrongTokenAccount
+        constraint = user_quote_token_account.owner == user.key() @ OrderError::WrongTokenAccount,
+        constraint = user_quote_token_account.mint == quote_mint.key() @ OrderError::WrongTokenAccount
+    )]
+    pub user_quote_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Mint locked in escrow: yield tokens for sell orders, quote tokens for buy orders
+    #[account(
+        constraint = escrow_mint.key() == if order_type == TradeOrder::SELL_ORDER {
+            marketplace.yield_token_mint
+        } else {
+            marketplace.quote_mint
+        } @ OrderError::WrongEscrowMint
     )]
-    pub user_underlying_token_account: Account<'info, TokenAccount>,
+    pub escrow_mint: InterfaceAccount<'info, Mint>,
 
     /// Escrow account for holding tokens during order
     #[account(
         init_if_needed,
-        payer = user,
+        payer = authority,
         seeds = [b"escrow", order.key().as_ref()],
         bump,
-        token::mint = yield_token_mint, // Will be reassigned based on order type
+        token::mint = escrow_mint,
         token::authority = order,
+        token::token_program = token_program,
     )]
-    pub escrow_account: Account<'info, TokenAccount>,
+    pub escrow_account: InterfaceAccount<'info, TokenAccount>,
 
-    pub token_program: Program<'info, Token>,
+    /// Token program of the escrow mint
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
     pub rent: Sysvar<'info, Rent>,
 }
 
-pub fn handle_place_order(
-    ctx: Context<PlaceOrder>,
+pub fn handle_place_order<'info>(
+    ctx: Context<'_, '_, 'info, 'info, PlaceOrder<'info>>,
     order_id: u64,
     order_type: u8,
     yield_token_amount: u64,
     price_per_token: u64,
 ) -> Result<()> {
-    require!(yield_token_amount > 0, OrderError::InvalidAmount);
-    require!(price_per_token > 0, OrderError::InvalidPrice);
     require!(
         order_type == TradeOrder::BUY_ORDER || order_type == TradeOrder::SELL_ORDER,
         OrderError::InvalidOrderType
@@ -95,10 +122,40 @@ pub fn handle_place_order(
     let current_time = Clock::get()?.unix_timestamp;
 
     // Calculate total value
-    let total_value = (yield_token_amount as u128 * price_per_token as u128 / 1_000_000) as u64;
+    let total_value = validate_order_terms(marketplace, yield_token_amount, price_per_token)?;
+
+    // An auction clears every open order at once, so a full book makes room by eviction
+    if marketplace.is_auction_mode() && marketplace.open_orders >= MAX_AUCTION_ORDERS as u64 {
+        evict_from_full_book(ctx.program_id, marketplace, ctx.remaining_accounts, order_type, price_per_token)?;
+    }
+
+    // Session keys move the user's tokens through the delegate PDA, within their notional
+    // limit and sell price floor
+    let user_key = ctx.accounts.user.key();
+    let authority_key = ctx.accounts.authority.key();
+    let delegated = authorize_order_authority(
+        &authority_key,
+        &user_key,
+        ctx.accounts.trading_delegate.as_mut(),
+        total_value,
+        (order_type == TradeOrder::SELL_ORDER).then_some(price_per_token),
+        current_time,
+    )?;
+    let delegate_bump = [ctx.accounts.trading_delegate.as_ref().map_or(0, |d| d.bump)];
+    let delegate_seeds = &[
+        b"trading_delegate".as_ref(),
+        user_key.as_ref(),
+        authority_key.as_ref(),
+        &delegate_bump,
+    ];
+    let delegate_signer_seeds = &[delegate_seeds.as_slice()];
+    let (transfer_authority, signer_seeds): (AccountInfo, &[&[&[u8]]]) = match &ctx.accounts.trading_delegate {
+        Some(trading_delegate) if delegated => (trading_delegate.to_account_info(), delegate_signer_seeds),
+        _ => (ctx.accounts.authority.to_account_info(), &[]),
+    };
 
     // Initialize order
-    order.user = ctx.accounts.user.key();
+    order.user = user_key;
     order.marketplace = marketplace.key();
     order.order_type = order_type;
     order.yield_token_amount = yield_token_amount;
@@ -109,38 +166,47 @@ pub fn handle_place_order(
     order.created_at = current_time;
     order.order_id = order_id;
 
+    // Transfer-fee mints are charged on the way into the escrow and on every hop out to the counterparty
+    let escrow_hops = 1 + marketplace.settlement_hops();
+
     if order_type == TradeOrder::SELL_ORDER {
-        // For sell orders, lock yield tokens in escrow
+        // For sell orders, lock yield tokens in escrow (grossed up for any transfer fee)
+        let escrow_deposit = amount_with_fees(&ctx.accounts.escrow_mint, yield_token_amount, escrow_hops)?;
         require!(
-            ctx.accounts.user_yield_token_account.amount >= yield_token_amount,
+            ctx.accounts.user_yield_token_account.amount >= escrow_deposit,
             OrderError::InsufficientBalance
         );
 
-        let cpi_ctx = CpiContext::new(
+        let cpi_ctx = CpiContext::new_with_signer(
             ctx.accounts.token_program.to_account_info(),
-            Transfer {
+            TransferChecked {
                 from: ctx.accounts.user_yield_token_account.to_account_info(),
+                mint: ctx.accounts.escrow_mint.to_account_info(),
                 to: ctx.accounts.escrow_account.to_account_info(),
-                authority: ctx.accounts.user.to_account_info(),
+                authority: transfer_authority,
             },
+            signer_seeds,
         );
-        transfer(cpi_ctx, yield_token_amount)?;
+        transfer_checked(cpi_ctx, escrow_deposit, ctx.accounts.escrow_mint.decimals)?;
     } else {
-        // For buy orders, lock underlying tokens in escrow
+        // For buy orders, lock quote tokens in escrow (grossed up for any transfer fee)
+        let escrow_deposit = amount_with_fees(&ctx.accounts.escrow_mint, total_value, escrow_hops)?;
         require!(
-            ctx.accounts.user_underlying_token_account.amount >= total_value,
+            ctx.accounts.user_quote_token_account.amount >= escrow_deposit,
             OrderError::InsufficientBalance
         );
 
-        let cpi_ctx = CpiContext::new(
+        let cpi_ctx = CpiContext::new_with_signer(
             ctx.accounts.token_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.user_underlying_token_account.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.user_quote_token_account.to_account_info(),
+                mint: ctx.accounts.escrow_mint.to_account_info(),
                 to: ctx.accounts.escrow_account.to_account_info(),
-                authority: ctx.accounts.user.to_account_info(),
+                authority: transfer_authority,
             },
+            signer_seeds,
         );
-        transfer(cpi_ctx, total_value)?;
+        transfer_checked(cpi_ctx, escrow_deposit, ctx.accounts.escrow_mint.decimals)?;
     }
 
     // Update marketplace best prices
@@ -154,6 +220,9 @@ pub fn handle_place_order(
         }
     }
 
+    // Track escrow-holding orders so the marketplace cannot be closed under them
+    marketplace.open_orders += 1;
+
     // Increment counter
     counter.count += 1;
 
@@ -168,14 +237,38 @@ pub fn handle_place_order(
     Ok(())
 }
 
+/// Check an order against the marketplace tick size and minimum order size.
+/// Returns the order's total value, which must not round down to zero.
+pub(crate) fn validate_order_terms(
+    marketplace: &Marketplace,
+    yield_token_amount: u64,
+    price_per_token: u64,
+) -> Result<u64> {
+    require!(yield_token_amount > 0, OrderError::InvalidAmount);
+    require!(price_per_token > 0, OrderError::InvalidPrice);
+    require!(
+        price_per_token.checked_rem(marketplace.tick_size) == Some(0),
+        OrderError::PriceOffTick
+    );
+    require!(
+        yield_token_amount >= marketplace.min_order_size,
+        OrderError::OrderTooSmall
+    );
+
+    let total_value = (yield_token_amount as u128 * price_per_token as u128 / 1_000_000) as u64;
+    require!(total_value > 0, OrderError::ZeroValueOrder);
+
+    Ok(total_value)
+}
+
 #[error_code]
 pub enum OrderError {
     #[msg("Marketplace is not active")]
     MarketplaceNotActive,
     #[msg("Wrong yield token mint")]
     WrongYieldTokenMint,
-    #[msg("Wrong underlying token mint")]
-    WrongUnderlyingToken,
+    #[msg("Wrong quote token mint")]
+    WrongQuoteMint,
     #[msg("Wrong token account")]
     WrongTokenAccount,
     #[msg("Invalid order amount")]
@@ -186,4 +279,129 @@ pub enum OrderError {
     InvalidOrderType,
     #[msg("Insufficient balance")]
     InsufficientBalance,
-} 
\ No newline at end of file
+    #[msg("Escrow mint does not match the order side")]
+    WrongEscrowMint,
+    #[msg("Order price is not a multiple of the marketplace tick size")]
+    PriceOffTick,
+    #[msg("Order is smaller than the marketplace minimum order size")]
+    OrderTooSmall,
+    #[msg("Order value rounds down to zero")]
+    ZeroValueOrder,
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use anchor_spl::token::spl_token;
+
+    use crate::instructions::trading_delegate::DelegateError;
+    use crate::test_utils::{
+        account_info, approve, balance, mint_account, program_account, program_account_at, program_info,
+        rent_sysvar, session_key, set_clock, signer_info, token_account, token_account_at, zeroed,
+    };
+
+    fn marketplace(tick_size: u64, min_order_size: u64) -> Marketplace {
+        let mut marketplace: Marketplace = zeroed();
+        marketplace.tick_size = tick_size;
+        marketplace.min_order_size = min_order_size;
+        marketplace
+    }
+
+    #[test]
+    fn prices_must_sit_on_the_tick() {
+        let marketplace = marketplace(10_000, 0);
+
+        assert_eq!(validate_order_terms(&marketplace, 1_000_000, 1_230_000).unwrap(), 1_230_000);
+        assert_eq!(
+            validate_order_terms(&marketplace, 1_000_000, 1_235_000).unwrap_err(),
+            OrderError::PriceOffTick.into()
+        );
+    }
+
+    #[test]
+    fn orders_must_reach_the_minimum_size() {
+        let marketplace = marketplace(1, 500_000);
+
+        assert!(validate_order_terms(&marketplace, 500_000, 1_000_000).is_ok());
+        assert_eq!(
+            validate_order_terms(&marketplace, 499_999, 1_000_000).unwrap_err(),
+            OrderError::OrderTooSmall.into()
+        );
+    }
+
+    #[test]
+    fn orders_worth_nothing_are_rejected() {
+        let marketplace = marketplace(1, 0);
+
+        assert_eq!(validate_order_terms(&marketplace, 0, 1_000_000).unwrap_err(), OrderError::InvalidAmount.into());
+        assert_eq!(validate_order_terms(&marketplace, 1_000_000, 0).unwrap_err(), OrderError::InvalidPrice.into());
+        // 1 base unit at 0.5 rounds down to no quote at all
+        assert_eq!(
+            validate_order_terms(&marketplace, 1, 500_000).unwrap_err(),
+            OrderError::ZeroValueOrder.into()
+        );
+    }
+
+    const ORDER_ID: u64 = 3;
+
+    /// A session key placing orders for a user holding 10 quote tokens, who approved the
+    /// key's delegate PDA for all of them
+    fn session_key_accounts(notional_limit: u64) -> PlaceOrder<'static> {
+        set_clock(100);
+        let (user, key) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (yield_mint, quote_mint) = (mint_account(Pubkey::new_unique(), 6), mint_account(Pubkey::new_unique(), 6));
+        let mut marketplace = marketplace(1, 0);
+        marketplace.is_active = true;
+        marketplace.yield_token_mint = yield_mint.key();
+        marketplace.quote_mint = quote_mint.key();
+
+        let (order, _) =
+            Pubkey::find_program_address(&[b"order", user.as_ref(), ORDER_ID.to_le_bytes().as_ref()], &crate::ID);
+        let (escrow, _) = Pubkey::find_program_address(&[b"escrow", order.as_ref()], &crate::ID);
+        let trading_delegate = session_key(user, key, notional_limit);
+        let user_quote_token_account = token_account(quote_mint.key(), user, 10_000_000);
+        approve(&user_quote_token_account, trading_delegate.key(), 10_000_000);
+
+        PlaceOrder {
+            authority: Signer::try_from(signer_info(key)).unwrap(),
+            user: UncheckedAccount::try_from(account_info(user, System::id(), Vec::new())),
+            trading_delegate: Some(trading_delegate),
+            marketplace: Box::new(program_account(&marketplace)),
+            order: program_account_at(order, &zeroed::<TradeOrder>()),
+            order_counter: program_account(&zeroed::<OrderCounter>()),
+            user_yield_token_account: token_account(yield_mint.key(), user, 0),
+            user_quote_token_account,
+            escrow_account: token_account_at(escrow, quote_mint.key(), order, 0),
+            escrow_mint: quote_mint.clone(),
+            yield_token_mint: yield_mint,
+            quote_mint,
+            token_program: Interface::try_from(program_info(spl_token::ID)).unwrap(),
+            system_program: Program::try_from(program_info(System::id())).unwrap(),
+            rent: rent_sysvar(),
+        }
+    }
+
+    fn place_bid(accounts: &mut PlaceOrder<'static>, amount: u64, price: u64) -> Result<()> {
+        let bumps = PlaceOrderBumps::default();
+        handle_place_order(Context::new(&crate::ID, accounts, &[], bumps), ORDER_ID, TradeOrder::BUY_ORDER, amount, price)
+    }
+
+    #[test]
+    fn session_keys_place_orders_from_the_users_funds() {
+        let mut accounts = session_key_accounts(3_000_000);
+
+        place_bid(&mut accounts, 2_000_000, 1_000_000).unwrap();
+
+        assert_eq!(balance(&accounts.escrow_account), 2_000_000);
+        assert_eq!(balance(&accounts.user_quote_token_account), 8_000_000);
+        assert_eq!(accounts.order.user, accounts.user.key());
+        assert_eq!(accounts.trading_delegate.as_ref().unwrap().notional_used, 2_000_000);
+
+        // Another 2 tokens at 1.0 would take the key past its 3 token limit
+        assert_eq!(
+            place_bid(&mut accounts, 2_000_000, 1_000_000).unwrap_err(),
+            DelegateError::NotionalLimitExceeded.into()
+        );
+        assert_eq!(balance(&accounts.user_quote_token_account), 8_000_000);
+    }
+}
@@ -0,0 +1,407 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::instructions::{
+    rate_feed::accrue_strategy, token_fees::transfer_fee, withdraw_from_strategy::early_exit_penalty,
+};
+use crate::state::{
+    marketplace::{Marketplace, TradeOrder}, rate_feed::RateFeed, strategy::Strategy, user_position::UserPosition,
+};
+
+/// Result of `preview_deposit`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct DepositPreview {
+    /// Yield tokens minted for the deposit
+    pub yield_tokens: u64,
+
+    /// Underlying tokens withheld by the mint's transfer fee
+    pub transfer_fee: u64,
+
+    /// Principal credited to the position
+    pub credited_amount: u64,
+
+    /// Deposits the strategy can still take, `u64::MAX` when uncapped
+    pub remaining_capacity: u64,
+
+    /// Deposits the user can still make into the strategy, `u64::MAX` when uncapped
+    pub remaining_user_capacity: u64,
+}
+
+/// Result of `preview_claim`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ClaimPreview {
+    /// Yield tokens minted by a claim now
+    pub yield_amount: u64,
+
+    /// Seconds since the last claim
+    pub time_elapsed: i64,
+}
+
+/// Result of `preview_withdraw`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct WithdrawPreview {
+    /// Underlying tokens returned
+    pub amount: u64,
+
+    /// Principal left in the position afterwards
+    pub remaining_deposit: u64,
+
+    /// Early-exit penalty withheld from the principal
+    pub penalty: u64,
+}
+
+/// Result of `preview_redeem`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct RedeemPreview {
+    /// Principal returned
+    pub principal: u64,
+
+    /// Accrued yield returned
+    pub yield_amount: u64,
+
+    /// Total underlying tokens returned
+    pub total: u64,
+
+    /// Early-exit penalty withheld from the principal
+    pub penalty: u64,
+}
+
+/// Result of `preview_trade`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct TradePreview {
+    /// Yield tokens that would change hands
+    pub trade_amount: u64,
+
+    /// Execution price (the sell order's price)
+    pub execution_price: u64,
+
+    /// Quote tokens paid by the buyer
+    pub total_payment: u64,
+
+    /// Trading fee taken from the payment
+    pub fee_amount: u64,
+
+    /// Quote tokens received by the seller
+    pub net_payment: u64,
+
+    /// Both orders belong to the same user, so self-trade prevention applies instead
+    pub self_trade: bool,
+}
+
+#[derive(Accounts)]
+pub struct PreviewDeposit<'info> {
+    pub strategy: Account<'info, Strategy>,
+
+    #[account(
+        address = strategy.underlying_token @ PreviewError::WrongUnderlyingToken
+    )]
+    pub underlying_token_mint: InterfaceAccount<'info, Mint>,
+
+    /// Depositor's existing position, omitted before their first deposit
+    #[account(
+        seeds = [b"user_position", user_position.user.as_ref(), strategy.key().as_ref()],
+        bump
+    )]
+    pub user_position: Option<Account<'info, UserPosition>>,
+}
+
+#[derive(Accounts)]
+pub struct PreviewPosition<'info> {
+    pub strategy: Account<'info, Strategy>,
+
+    #[account(
+        seeds = [b"user_position", user_position.user.as_ref(), strategy.key().as_ref()],
+        bump
+    )]
+    pub user_position: Account<'info, UserPosition>,
+
+    /// Rate feed of oracle-rate strategies, omitted for fixed-rate strategies
+    pub rate_feed: Option<Account<'info, RateFeed>>,
+}
+
+#[derive(Accounts)]
+pub struct PreviewTrade<'info> {
+    pub marketplace: Box<Account<'info, Marketplace>>,
+
+    #[account(
+        constraint = buy_order.order_type == TradeOrder::BUY_ORDER @ PreviewError::InvalidBuyOrder,
+        constraint = buy_order.marketplace == marketplace.key() @ PreviewError::OrderMarketplaceMismatch
+    )]
+    pub buy_order: Account<'info, TradeOrder>,
+
+    #[account(
+        constraint = sell_order.order_type == TradeOrder::SELL_ORDER @ PreviewError::InvalidSellOrder,
+        constraint = sell_order.marketplace == marketplace.key() @ PreviewError::OrderMarketplaceMismatch
+    )]
+    pub sell_order: Account<'info, TradeOrder>,
+}
+
+pub fn handle_preview_deposit(ctx: Context<PreviewDeposit>, amount: u64) -> Result<DepositPreview> {
+    require!(amount > 0, PreviewError::InvalidAmount);
+
+    // Only what reaches the vault is credited, as in deposit_to_strategy
+    let transfer_fee = transfer_fee(&ctx.accounts.underlying_token_mint, amount)?;
+    let credited_amount = amount - transfer_fee;
+    require!(credited_amount > 0, PreviewError::InvalidAmount);
+
+    let strategy = &ctx.accounts.strategy;
+    let deposited_amount = ctx.accounts.user_position.as_ref().map_or(0, |position| position.deposited_amount);
+
+    Ok(DepositPreview {
+        yield_tokens: strategy.yield_tokens_for_deposit(credited_amount),
+        transfer_fee,
+        credited_amount,
+        remaining_capacity: strategy.remaining_capacity(),
+        remaining_user_capacity: strategy.remaining_user_capacity(deposited_amount),
+    })
+}
+
+pub fn handle_preview_claim(ctx: Context<PreviewPosition>) -> Result<ClaimPreview> {
+    let user_position = &ctx.accounts.user_position;
+    let current_time = Clock::get()?.unix_timestamp;
+    let strategy = accrued_strategy(ctx.accounts, current_time)?;
+
+    Ok(ClaimPreview {
+        yield_amount: user_position.claimable_yield(&strategy, current_time),
+        time_elapsed: current_time - user_position.last_yield_claim,
+    })
+}
+
+pub fn handle_preview_withdraw(ctx: Context<PreviewPosition>, amount: u64) -> Result<WithdrawPreview> {
+    let user_position = &ctx.accounts.user_position;
+    require!(amount > 0, PreviewError::InvalidAmount);
+    require!(user_position.deposited_amount >= amount, PreviewError::InsufficientBalance);
+
+    let current_time = Clock::get()?.unix_timestamp;
+    let penalty = early_exit_penalty(&ctx.accounts.strategy, user_position, amount, current_time)?;
+
+    Ok(WithdrawPreview {
+        amount: amount - penalty,
+        remaining_deposit: user_position.deposited_amount - amount,
+        penalty,
+    })
+}
+
+pub fn handle_preview_redeem(ctx: Context<PreviewPosition>, yield_token_amount: u64) -> Result<RedeemPreview> {
+    require!(yield_token_amount > 0, PreviewError::InvalidAmount);
+
+    let current_time = Clock::get()?.unix_timestamp;
+    let strategy = accrued_strategy(ctx.accounts, current_time)?;
+    let (principal, yield_amount) = ctx.accounts.user_position
+        .redemption_amounts(&strategy, yield_token_amount, current_time)
+        .ok_or(PreviewError::NoPosition)?;
+    let penalty = early_exit_penalty(&strategy, &ctx.accounts.user_position, principal, current_time)?;
+
+    Ok(RedeemPreview {
+        principal,
+        yield_amount,
+        total: principal + yield_amount - penalty,
+        penalty,
+    })
+}
+
+/// Copy of the strategy with its reward index brought up to `now`, as the
+/// instruction being previewed would do before settling
+fn accrued_strategy(accounts: &PreviewPosition, now: i64) -> Result<Strategy> {
+    let mut strategy = Strategy::clone(&accounts.strategy);
+    accrue_strategy(&mut strategy, accounts.rate_feed.as_ref(), now)?;
+    Ok(strategy)
+}
+
+pub fn handle_preview_trade(ctx: Context<PreviewTrade>, trade_amount: u64) -> Result<TradePreview> {
+    let marketplace = &ctx.accounts.marketplace;
+    let buy_order = &ctx.accounts.buy_order;
+    let sell_order = &ctx.accounts.sell_order;
+    require!(trade_amount > 0, PreviewError::InvalidAmount);
+    require!(
+        buy_order.is_fillable() && sell_order.is_fillable(),
+        PreviewError::OrderNotFillable
+    );
+    require!(
+        sell_order.price_per_token <= buy_order.price_per_token,
+        PreviewError::PriceMismatch
+    );
+
+    // Same sizing and settlement maths as execute_trade
+    let trade_amount = trade_amount
+        .min(buy_order.remaining_amount())
+        .min(sell_order.remaining_amount());
+    let execution_price = sell_order.price_per_token;
+    let total_payment = Marketplace::trade_value(trade_amount, execution_price);
+    let fee_amount = marketplace.trading_fee(total_payment);
+
+    Ok(TradePreview {
+        trade_amount,
+        execution_price,
+        total_payment,
+        fee_amount,
+        net_payment: total_payment - fee_amount,
+        self_trade: buy_order.user == sell_order.user,
+    })
+}
+
+#[error_code]
+pub enum PreviewError {
+    #[msg("Invalid amount")]
+    InvalidAmount,
+    #[msg("Wrong underlying token for this strategy")]
+    WrongUnderlyingToken,
+    #[msg("Insufficient balance")]
+    InsufficientBalance,
+    #[msg("No position found")]
+    NoPosition,
+    #[msg("Invalid buy order")]
+    InvalidBuyOrder,
+    #[msg("Invalid sell order")]
+    InvalidSellOrder,
+    #[msg("Order marketplace mismatch")]
+    OrderMarketplaceMismatch,
+    #[msg("Order is not fillable")]
+    OrderNotFillable,
+    #[msg("Price mismatch between orders")]
+    PriceMismatch,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::instructions::rate_feed::RateFeedError;
+    use crate::test_utils::{mint_account, program_account, set_clock, zeroed};
+
+    const YEAR: i64 = 365 * 24 * 60 * 60;
+
+    /// Oracle strategy indexed up to t = 1000, its feed at 10% since then, and a
+    /// position of 1000 tokens opened at t = 1000
+    fn oracle_position() -> PreviewPosition<'static> {
+        let mut feed: RateFeed = zeroed();
+        feed.rate_bps = 1000;
+        feed.last_update = 1_000;
+        feed.max_staleness = 2 * YEAR;
+        let rate_feed = program_account(&feed);
+
+        let mut strategy: Strategy = zeroed();
+        strategy.rate_mode = Strategy::RATE_MODE_ORACLE;
+        strategy.rate_feed = rate_feed.key();
+        strategy.last_index_update = 1_000;
+
+        let mut position: UserPosition = zeroed();
+        position.deposited_amount = 1_000_000_000;
+        position.yield_tokens_minted = 1_000_000_000;
+        position.deposit_time = 1_000;
+        position.last_yield_claim = 1_000;
+
+        PreviewPosition {
+            strategy: program_account(&strategy),
+            user_position: program_account(&position),
+            rate_feed: Some(rate_feed),
+        }
+    }
+
+    #[test]
+    fn previews_yield_accrued_since_the_last_index_update() {
+        let accounts = oracle_position();
+        let now = 1_000 + YEAR;
+
+        let strategy = accrued_strategy(&accounts, now).unwrap();
+        let previewed = accounts.user_position.claimable_yield(&strategy, now);
+
+        // What claim_yield settles after accruing the index itself
+        let mut settled = UserPosition::clone(&accounts.user_position);
+        settled.settle_yield(&strategy);
+        assert_eq!(previewed, settled.pending_yield);
+        assert_eq!(previewed, 100_000_000);
+
+        // The stored index is untouched, so reading it alone would show nothing
+        assert_eq!(accounts.strategy.reward_index, 0);
+        assert_eq!(accounts.user_position.claimable_yield(&accounts.strategy, now), 0);
+    }
+
+    #[test]
+    fn oracle_previews_need_the_rate_feed() {
+        let mut accounts = oracle_position();
+        accounts.rate_feed = None;
+
+        assert_eq!(
+            accrued_strategy(&accounts, 2_000).err(),
+            Some(RateFeedError::MissingRateFeed.into())
+        );
+    }
+
+    #[test]
+    fn previews_a_trade_at_the_sell_price_net_of_fees() {
+        let mut marketplace: Marketplace = zeroed();
+        marketplace.trading_fee_bps = 100;
+        let order = |order_type, amount, price| {
+            let mut order: TradeOrder = zeroed();
+            order.user = Pubkey::new_unique();
+            order.order_type = order_type;
+            order.yield_token_amount = amount;
+            order.price_per_token = price;
+            order.is_active = true;
+            program_account(&order)
+        };
+        let mut accounts = PreviewTrade {
+            marketplace: Box::new(program_account(&marketplace)),
+            buy_order: order(TradeOrder::BUY_ORDER, 2_000_000, 1_200_000),
+            sell_order: order(TradeOrder::SELL_ORDER, 1_500_000, 1_000_000),
+        };
+        let ctx = Context::new(&crate::ID, &mut accounts, &[], PreviewTradeBumps::default());
+
+        let preview = handle_preview_trade(ctx, 5_000_000).unwrap();
+
+        assert_eq!(preview.trade_amount, 1_500_000);
+        assert_eq!(preview.execution_price, 1_000_000);
+        assert_eq!(preview.total_payment, 1_500_000);
+        assert_eq!(preview.fee_amount, 15_000);
+        assert_eq!(preview.net_payment, 1_485_000);
+        assert!(!preview.self_trade);
+    }
+
+    #[test]
+    fn previews_a_deposit_against_the_caps() {
+        let mut strategy: Strategy = zeroed();
+        strategy.max_total_deposits = 10_000_000;
+        strategy.total_deposits = 4_000_000;
+        strategy.max_deposit_per_user = 3_000_000;
+        let mut position: UserPosition = zeroed();
+        position.deposited_amount = 1_000_000;
+        let mut accounts = PreviewDeposit {
+            strategy: program_account(&strategy),
+            underlying_token_mint: mint_account(Pubkey::new_unique(), 6),
+            user_position: Some(program_account(&position)),
+        };
+
+        let ctx = Context::new(&crate::ID, &mut accounts, &[], PreviewDepositBumps::default());
+        let preview = handle_preview_deposit(ctx, 2_000_000).unwrap();
+
+        assert_eq!(preview.yield_tokens, 2_000_000);
+        assert_eq!(preview.transfer_fee, 0);
+        assert_eq!(preview.credited_amount, 2_000_000);
+        assert_eq!(preview.remaining_capacity, 6_000_000);
+        assert_eq!(preview.remaining_user_capacity, 2_000_000);
+    }
+
+    #[test]
+    fn previews_the_early_exit_penalty_of_a_locked_position() {
+        set_clock(1_000 + YEAR / 2);
+        let mut accounts = oracle_position();
+        accounts.strategy.early_exit_penalty_bps = 500;
+        accounts.strategy.total_yield_tokens_minted = 1_000_000_000;
+        accounts.user_position.lock_until = 1_000 + YEAR;
+
+        let ctx = Context::new(&crate::ID, &mut accounts, &[], PreviewPositionBumps::default());
+        let withdraw = handle_preview_withdraw(ctx, 400_000_000).unwrap();
+        assert_eq!(withdraw.penalty, 20_000_000);
+        assert_eq!(withdraw.amount, 380_000_000);
+        assert_eq!(withdraw.remaining_deposit, 600_000_000);
+
+        // Half the yield tokens redeem half the principal and half a year of yield at 10%
+        let ctx = Context::new(&crate::ID, &mut accounts, &[], PreviewPositionBumps::default());
+        let redeem = handle_preview_redeem(ctx, 500_000_000).unwrap();
+        assert_eq!(redeem.principal, 500_000_000);
+        assert_eq!(redeem.yield_amount, 25_000_000);
+        assert_eq!(redeem.penalty, 25_000_000);
+        assert_eq!(redeem.total, 500_000_000);
+    }
+}
@@ -0,0 +1,320 @@
+use anchor_lang::prelude::*;
+
+use crate::state::{rate_feed::RateFeed, strategy::Strategy};
+
+#[derive(Accounts)]
+#[instruction(feed_id: u64)]
+pub struct CreateRateFeed<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"rate_feed", authority.key().as_ref(), feed_id.to_le_bytes().as_ref()],
+        bump,
+        space = 8 + RateFeed::INIT_SPACE
+    )]
+    pub rate_feed: Account<'info, RateFeed>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigureRateFeed<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = authority @ RateFeedError::Unauthorized
+    )]
+    pub rate_feed: Account<'info, RateFeed>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateRateFeed<'info> {
+    pub updater: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = updater @ RateFeedError::Unauthorized
+    )]
+    pub rate_feed: Account<'info, RateFeed>,
+}
+
+#[derive(Accounts)]
+#[instruction(strategy_id: u64)]
+pub struct AccrueRewardIndex<'info> {
+    #[account(
+        mut,
+        seeds = [b"strategy", strategy_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = strategy.uses_rate_feed() @ RateFeedError::NotOracleStrategy
+    )]
+    pub strategy: Account<'info, Strategy>,
+
+    #[account(
+        address = strategy.rate_feed @ RateFeedError::WrongRateFeed
+    )]
+    pub rate_feed: Account<'info, RateFeed>,
+}
+
+pub fn handle_create_rate_feed(
+    ctx: Context<CreateRateFeed>,
+    feed_id: u64,
+    updater: Pubkey,
+    max_staleness: i64,
+    max_deviation_bps: u64,
+) -> Result<()> {
+    require!(max_staleness > 0, RateFeedError::InvalidConfig);
+
+    let rate_feed = &mut ctx.accounts.rate_feed;
+    rate_feed.authority = ctx.accounts.authority.key();
+    rate_feed.updater = updater;
+    rate_feed.feed_id = feed_id;
+    rate_feed.rate_bps = 0;
+    rate_feed.last_update = 0;
+    rate_feed.max_staleness = max_staleness;
+    rate_feed.max_deviation_bps = max_deviation_bps;
+    rate_feed.cumulative_rate = 0;
+
+    msg!(
+        "Rate feed {} created, updater {}, max staleness {}s, max deviation {}%",
+        feed_id,
+        updater,
+        max_staleness,
+        max_deviation_bps as f64 / 100.0
+    );
+
+    Ok(())
+}
+
+pub fn handle_configure_rate_feed(
+    ctx: Context<ConfigureRateFeed>,
+    updater: Pubkey,
+    max_staleness: i64,
+    max_deviation_bps: u64,
+) -> Result<()> {
+    require!(max_staleness > 0, RateFeedError::InvalidConfig);
+
+    let rate_feed = &mut ctx.accounts.rate_feed;
+    rate_feed.updater = updater;
+    rate_feed.max_staleness = max_staleness;
+    rate_feed.max_deviation_bps = max_deviation_bps;
+
+    msg!("Rate feed {} reconfigured, updater {}", rate_feed.feed_id, updater);
+
+    Ok(())
+}
+
+/// Push a new rate. The outgoing rate is folded into the feed's accumulator, so
+/// strategies accrue it for the time it stood whenever they next catch up.
+pub fn handle_update_rate_feed(ctx: Context<UpdateRateFeed>, rate_bps: u64) -> Result<()> {
+    require!(rate_bps <= RateFeed::MAX_RATE_BPS, RateFeedError::RateTooHigh);
+
+    let rate_feed = &mut ctx.accounts.rate_feed;
+    require!(rate_feed.within_deviation(rate_bps), RateFeedError::DeviationTooLarge);
+
+    let previous_rate_bps = rate_feed.rate_bps;
+    rate_feed.update(rate_bps, Clock::get()?.unix_timestamp);
+
+    msg!(
+        "Rate feed {} moved from {}% to {}%",
+        rate_feed.feed_id,
+        previous_rate_bps as f64 / 100.0,
+        rate_bps as f64 / 100.0
+    );
+
+    Ok(())
+}
+
+/// Permissionless crank bringing a strategy's reward index up to date
+pub fn handle_accrue_reward_index(ctx: Context<AccrueRewardIndex>, _strategy_id: u64) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let rate_feed = &ctx.accounts.rate_feed;
+    require!(rate_feed.is_fresh(now), RateFeedError::StaleRate);
+
+    let strategy = &mut ctx.accounts.strategy;
+    strategy.accrue_reward_index(rate_feed, now);
+
+    msg!(
+        "Strategy {} reward index accrued to {} at {}%",
+        strategy.strategy_id,
+        strategy.reward_index,
+        rate_feed.rate_bps as f64 / 100.0
+    );
+
+    Ok(())
+}
+
+/// Bring an oracle-rate strategy's reward index up to date before a position changes,
+/// fixed-rate strategies need no feed
+pub(crate) fn accrue_strategy(
+    strategy: &mut Strategy,
+    rate_feed: Option<&Account<RateFeed>>,
+    now: i64,
+) -> Result<()> {
+    if !strategy.uses_rate_feed() {
+        return Ok(());
+    }
+
+    let rate_feed = rate_feed.ok_or(RateFeedError::MissingRateFeed)?;
+    require_keys_eq!(rate_feed.key(), strategy.rate_feed, RateFeedError::WrongRateFeed);
+    strategy.accrue_reward_index(rate_feed, now);
+
+    Ok(())
+}
+
+#[error_code]
+pub enum RateFeedError {
+    #[msg("Not authorised for this rate feed")]
+    Unauthorized,
+    #[msg("Invalid rate feed configuration")]
+    InvalidConfig,
+    #[msg("Rate is too high (max 500%)")]
+    RateTooHigh,
+    #[msg("Rate change exceeds the feed's maximum deviation")]
+    DeviationTooLarge,
+    #[msg("Rate feed is stale")]
+    StaleRate,
+    #[msg("Strategy does not follow a rate feed")]
+    NotOracleStrategy,
+    #[msg("Rate feed does not belong to this strategy")]
+    WrongRateFeed,
+    #[msg("Rate feed account is required for this strategy")]
+    MissingRateFeed,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeSet;
+
+    use crate::test_utils::{program_account, program_account_at, set_clock, signer_info, zeroed};
+
+    /// Feed pushed by `updater`, moving at most 20% of its rate per update and stale
+    /// after an hour
+    fn feed(updater: Pubkey) -> AccountInfo<'static> {
+        let mut feed: RateFeed = zeroed();
+        feed.authority = Pubkey::new_unique();
+        feed.updater = updater;
+        feed.feed_id = 1;
+        feed.max_staleness = 3_600;
+        feed.max_deviation_bps = 2_000;
+        program_account(&feed).to_account_info()
+    }
+
+    fn push(rate_feed: &AccountInfo<'static>, updater: Pubkey, rate_bps: u64) -> Result<()> {
+        let infos: &'static [AccountInfo<'static>] = vec![signer_info(updater).clone(), rate_feed.clone()].leak();
+        let mut bumps = UpdateRateFeedBumps::default();
+        let mut accounts =
+            UpdateRateFeed::try_accounts(&crate::ID, &mut &infos[..], &[], &mut bumps, &mut BTreeSet::new())?;
+        handle_update_rate_feed(Context::new(&crate::ID, &mut accounts, &[], bumps), rate_bps)?;
+        accounts.exit(&crate::ID)
+    }
+
+    #[test]
+    fn only_the_updater_pushes_rates() {
+        set_clock(100);
+        let updater = Pubkey::new_unique();
+        let rate_feed = feed(updater);
+
+        assert_eq!(push(&rate_feed, Pubkey::new_unique(), 1000).unwrap_err(), RateFeedError::Unauthorized.into());
+        push(&rate_feed, updater, 1000).unwrap();
+    }
+
+    #[test]
+    fn rates_and_their_moves_are_capped() {
+        set_clock(100);
+        let updater = Pubkey::new_unique();
+        let rate_feed = feed(updater);
+
+        assert_eq!(push(&rate_feed, updater, 50_001).unwrap_err(), RateFeedError::RateTooHigh.into());
+        // Any first rate is accepted, later ones move at most 20%
+        push(&rate_feed, updater, 1000).unwrap();
+        for rate_bps in [1201, 799] {
+            assert_eq!(push(&rate_feed, updater, rate_bps).unwrap_err(), RateFeedError::DeviationTooLarge.into());
+        }
+        push(&rate_feed, updater, 1200).unwrap();
+
+        let feed = RateFeed::try_deserialize(&mut &rate_feed.data.borrow()[..]).unwrap();
+        assert_eq!(feed.rate_bps, 1200);
+        assert_eq!(feed.last_update, 100);
+    }
+
+    /// Crank accounts of strategy 1 following a 12% feed pushed at t = 1000, or of a
+    /// fixed-rate strategy when `oracle` is unset
+    fn accrue_accounts(oracle: bool) -> Result<(AccrueRewardIndex<'static>, AccrueRewardIndexBumps)> {
+        let mut feed: RateFeed = zeroed();
+        feed.max_staleness = 3_600;
+        feed.update(1200, 1_000);
+        let rate_feed = program_account(&feed);
+
+        let (strategy_key, _) = Pubkey::find_program_address(&[b"strategy", 1u64.to_le_bytes().as_ref()], &crate::ID);
+        let mut strategy: Strategy = zeroed();
+        strategy.strategy_id = 1;
+        strategy.apy = 1000;
+        if oracle {
+            strategy.rate_mode = Strategy::RATE_MODE_ORACLE;
+            strategy.rate_feed = rate_feed.key();
+            strategy.last_index_update = 1_000;
+        }
+
+        let infos: &'static [AccountInfo<'static>] =
+            vec![program_account_at(strategy_key, &strategy).to_account_info(), rate_feed.to_account_info()].leak();
+        let mut bumps = AccrueRewardIndexBumps::default();
+        let accounts = AccrueRewardIndex::try_accounts(
+            &crate::ID,
+            &mut &infos[..],
+            &1u64.to_le_bytes(),
+            &mut bumps,
+            &mut BTreeSet::new(),
+        )?;
+        Ok((accounts, bumps))
+    }
+
+    fn accrue(accounts: &mut AccrueRewardIndex<'static>, bumps: AccrueRewardIndexBumps) -> Result<()> {
+        handle_accrue_reward_index(Context::new(&crate::ID, accounts, &[], bumps), 1)
+    }
+
+    #[test]
+    fn the_crank_accrues_oracle_strategies_from_a_fresh_feed() {
+        set_clock(2_000);
+        let (mut accounts, bumps) = accrue_accounts(true).unwrap();
+
+        accrue(&mut accounts, bumps).unwrap();
+
+        assert_eq!(accounts.strategy.reward_index, accounts.rate_feed.cumulative_rate_at(2_000));
+        assert!(accounts.strategy.reward_index > 0);
+        assert_eq!(accounts.strategy.last_index_update, 2_000);
+    }
+
+    #[test]
+    fn the_crank_rejects_stale_feeds_and_fixed_rate_strategies() {
+        set_clock(1_000 + 3_601);
+        let (mut accounts, bumps) = accrue_accounts(true).unwrap();
+        assert_eq!(accrue(&mut accounts, bumps).unwrap_err(), RateFeedError::StaleRate.into());
+        assert_eq!(accounts.strategy.reward_index, 0);
+
+        assert_eq!(accrue_accounts(false).err(), Some(RateFeedError::NotOracleStrategy.into()));
+    }
+
+    #[test]
+    fn oracle_strategies_need_their_own_feed() {
+        let (accounts, _) = accrue_accounts(true).unwrap();
+        let mut strategy = Strategy::clone(&accounts.strategy);
+
+        assert_eq!(
+            accrue_strategy(&mut strategy, None, 2_000).unwrap_err(),
+            RateFeedError::MissingRateFeed.into()
+        );
+        let other_feed = program_account(&RateFeed::clone(&accounts.rate_feed));
+        assert_eq!(
+            accrue_strategy(&mut strategy, Some(&other_feed), 2_000).unwrap_err(),
+            RateFeedError::WrongRateFeed.into()
+        );
+        accrue_strategy(&mut strategy, Some(&accounts.rate_feed), 2_000).unwrap();
+        assert!(strategy.reward_index > 0);
+    }
+}
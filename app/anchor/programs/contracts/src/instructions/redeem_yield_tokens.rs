@@ -1,7 +1,10 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Mint, TokenAccount, Token, Transfer, transfer, Burn, burn};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked, Burn, burn};
 
-use crate::state::{strategy::Strategy, user_position::UserPosition};
+use crate::adapters::{self, AdapterAccounts};
+use crate::instructions::rate_feed::accrue_strategy;
+use crate::instructions::withdraw_from_strategy::{early_exit_penalty, transfer_penalty};
+use crate::state::{rate_feed::RateFeed, strategy::Strategy, user_position::UserPosition};
 
 #[derive(Accounts)]
 #[instruction(yield_token_amount: u64, strategy_id: u64)]
@@ -31,31 +34,51 @@ pub struct RedeemYieldTokens<'info> {
         seeds = [b"strategy_vault", strategy_id.to_le_bytes().as_ref()],
         bump
     )]
-    pub strategy_vault: Account<'info, TokenAccount>,
+    pub strategy_vault: InterfaceAccount<'info, TokenAccount>,
 
     /// Yield token mint for this strategy
     #[account(
         mut,
         address = strategy.yield_token_mint @ RedeemError::WrongYieldTokenMint
     )]
-    pub yield_token_mint: Account<'info, Mint>,
+    pub yield_token_mint: InterfaceAccount<'info, Mint>,
 
     /// User's yield token account (tokens will be burned from here)
     #[account(
         mut,
         constraint = user_yield_token_account.mint == yield_token_mint.key() @ RedeemError::WrongYieldTokenAccount
     )]
-    pub user_yield_token_account: Account<'info, TokenAccount>,
+    pub user_yield_token_account: InterfaceAccount<'info, TokenAccount>,
 
     /// User's underlying token account (will receive SOL + yield)
     #[account(mut)]
-    pub user_underlying_token: Account<'info, TokenAccount>,
+    pub user_underlying_token: InterfaceAccount<'info, TokenAccount>,
 
-    pub token_program: Program<'info, Token>,
+    /// The underlying token mint
+    #[account(
+        address = strategy.underlying_token @ RedeemError::WrongUnderlyingToken
+    )]
+    pub underlying_token_mint: InterfaceAccount<'info, Mint>,
+
+    /// Token program of the underlying token
+    pub token_program: Interface<'info, TokenInterface>,
+    /// Token program of the yield token mint
+    pub yield_token_program: Interface<'info, TokenInterface>,
+
+    /// Rate feed of oracle-rate strategies, omitted for fixed-rate strategies
+    pub rate_feed: Option<Account<'info, RateFeed>>,
+
+    /// Reserve receiving early-exit penalties, only needed while the position is locked
+    #[account(
+        mut,
+        seeds = [b"reward_reserve", strategy_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub reward_reserve: Option<InterfaceAccount<'info, TokenAccount>>,
 }
 
-pub fn handle_redeem_yield_tokens(
-    ctx: Context<RedeemYieldTokens>,
+pub fn handle_redeem_yield_tokens<'info>(
+    ctx: Context<'_, '_, 'info, 'info, RedeemYieldTokens<'info>>,
     yield_token_amount: u64,
     strategy_id: u64,
 ) -> Result<()> {
@@ -64,6 +87,7 @@ pub fn handle_redeem_yield_tokens(
     let strategy = &mut ctx.accounts.strategy;
     let user_position = &mut ctx.accounts.user_position;
     let current_time = Clock::get()?.unix_timestamp;
+    accrue_strategy(strategy, ctx.accounts.rate_feed.as_ref(), current_time)?;
 
     // Check if user has sufficient yield tokens
     require!(
@@ -71,33 +95,15 @@ pub fn handle_redeem_yield_tokens(
         RedeemError::InsufficientYieldTokens
     );
 
-    // Calculate the proportion of the position being redeemed
-    let total_yield_tokens = user_position.yield_tokens_minted;
-    require!(total_yield_tokens > 0, RedeemError::NoPosition);
-
-    // Calculate principal to return (proportional to yield tokens being redeemed)
-    let principal_to_return = (user_position.deposited_amount as u128 * yield_token_amount as u128) 
-        / total_yield_tokens as u128;
-    let principal_to_return = principal_to_return as u64;
-
-    // Calculate accumulated yield for this portion
-    let time_since_deposit = current_time - user_position.deposit_time;
-    let total_yield_for_position = strategy.calculate_yield(
-        user_position.deposited_amount,
-        time_since_deposit,
-    );
-    
-    // Proportional yield for the tokens being redeemed
-    let yield_to_return = (total_yield_for_position as u128 * yield_token_amount as u128) 
-        / total_yield_tokens as u128;
-    let yield_to_return = yield_to_return as u64;
+    let (principal_to_return, yield_to_return, penalty) =
+        apply_redeem(strategy, user_position, yield_token_amount, current_time)?;
 
     // Total amount to transfer = principal + accumulated yield
     let total_to_transfer = principal_to_return + yield_to_return;
 
     // Burn the yield tokens
     let cpi_ctx = CpiContext::new(
-        ctx.accounts.token_program.to_account_info(),
+        ctx.accounts.yield_token_program.to_account_info(),
         Burn {
             mint: ctx.accounts.yield_token_mint.to_account_info(),
             from: ctx.accounts.user_yield_token_account.to_account_info(),
@@ -106,26 +112,91 @@ pub fn handle_redeem_yield_tokens(
     );
     burn(cpi_ctx, yield_token_amount)?;
 
-    // Transfer underlying tokens + yield from strategy vault to user
     let strategy_id_bytes = strategy_id.to_le_bytes();
     let signer_seeds: &[&[u8]] = &[b"strategy", strategy_id_bytes.as_ref(), &[ctx.bumps.strategy]];
     let seeds: &[&[&[u8]]] = &[signer_seeds];
 
+    // Pull what the vault is missing back out of the yield source
+    let adapter_accounts = AdapterAccounts {
+        vault: ctx.accounts.strategy_vault.to_account_info(),
+        mint: ctx.accounts.underlying_token_mint.to_account_info(),
+        token_program: ctx.accounts.token_program.to_account_info(),
+        remaining_accounts: ctx.remaining_accounts,
+    };
+    adapters::withdraw_to_vault(
+        strategy,
+        &adapter_accounts,
+        signer_seeds,
+        ctx.accounts.strategy_vault.amount,
+        total_to_transfer,
+    )?;
+
+    // Early exits leave their penalty in the reward reserve
+    transfer_penalty(
+        strategy.to_account_info(),
+        ctx.accounts.strategy_vault.to_account_info(),
+        ctx.accounts.reward_reserve.as_ref(),
+        &ctx.accounts.underlying_token_mint,
+        ctx.accounts.token_program.to_account_info(),
+        signer_seeds,
+        penalty,
+    )?;
+
+    // Transfer underlying tokens + yield from strategy vault to user
     let cpi_ctx = CpiContext::new_with_signer(
         ctx.accounts.token_program.to_account_info(),
-        Transfer {
+        TransferChecked {
             from: ctx.accounts.strategy_vault.to_account_info(),
+            mint: ctx.accounts.underlying_token_mint.to_account_info(),
             to: ctx.accounts.user_underlying_token.to_account_info(),
             authority: strategy.to_account_info(),
         },
         seeds,
     );
-    transfer(cpi_ctx, total_to_transfer)?;
+    transfer_checked(cpi_ctx, total_to_transfer - penalty, ctx.accounts.underlying_token_mint.decimals)?;
+
+    msg!(
+        "User {} redeemed {} yield tokens for {} principal + {} yield - {} penalty = {} total tokens",
+        ctx.accounts.user.key(),
+        yield_token_amount,
+        principal_to_return,
+        yield_to_return,
+        penalty,
+        total_to_transfer - penalty
+    );
+
+    Ok(())
+}
+
+/// Record a redemption on the position and strategy, returns the principal and yield
+/// paid out and the early-exit penalty withheld from the principal
+pub(crate) fn apply_redeem(
+    strategy: &mut Account<Strategy>,
+    user_position: &mut Account<UserPosition>,
+    yield_token_amount: u64,
+    current_time: i64,
+) -> Result<(u64, u64, u64)> {
+    user_position.settle_yield(strategy);
+
+    // Calculate the proportion of the position being redeemed
+    let total_yield_tokens = user_position.yield_tokens_minted;
+    let (principal_to_return, yield_to_return) = user_position
+        .redemption_amounts(strategy, yield_token_amount, current_time)
+        .ok_or(RedeemError::NoPosition)?;
+    let penalty = early_exit_penalty(strategy, user_position, principal_to_return, current_time)?;
 
     // Update user position (proportional reduction)
     let remaining_yield_tokens = total_yield_tokens - yield_token_amount;
     user_position.deposited_amount -= principal_to_return;
     user_position.yield_tokens_minted = remaining_yield_tokens;
+    if strategy.uses_rate_feed() {
+        user_position.pending_yield -= yield_to_return;
+    } else {
+        // Settled top-up yield leaves with its share of the position
+        let pending_share = (user_position.pending_yield as u128 * yield_token_amount as u128
+            / total_yield_tokens as u128) as u64;
+        user_position.pending_yield -= pending_share;
+    }
 
     // Update strategy stats
     strategy.total_deposits -= principal_to_return;
@@ -138,16 +209,7 @@ pub fn handle_redeem_yield_tokens(
         user_position.last_yield_claim = current_time;
     }
 
-    msg!(
-        "User {} redeemed {} yield tokens for {} principal + {} yield = {} total tokens",
-        ctx.accounts.user.key(),
-        yield_token_amount,
-        principal_to_return,
-        yield_to_return,
-        total_to_transfer
-    );
-
-    Ok(())
+    Ok((principal_to_return, yield_to_return, penalty))
 }
 
 #[error_code]
@@ -166,4 +228,90 @@ pub enum RedeemError {
     InsufficientYieldTokens,
     #[msg("No position found")]
     NoPosition,
-} 
\ No newline at end of file
+    #[msg("Wrong underlying token for this strategy")]
+    WrongUnderlyingToken,
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::instructions::withdraw_from_strategy::{apply_withdraw, WithdrawError};
+    use crate::test_utils::{program_account, zeroed};
+
+    const YEAR: i64 = 365 * 24 * 60 * 60;
+
+    fn position_with_terms(
+        lock_duration: i64,
+        early_exit_penalty_bps: u16,
+    ) -> (Account<'static, Strategy>, Account<'static, UserPosition>) {
+        let mut strategy: Strategy = zeroed();
+        strategy.apy = 1000;
+        strategy.lock_duration = lock_duration;
+        strategy.early_exit_penalty_bps = early_exit_penalty_bps;
+        strategy.total_deposits = 1_000_000;
+        strategy.total_yield_tokens_minted = 1_000_000;
+
+        let mut position: UserPosition = zeroed();
+        position.deposited_amount = 1_000_000;
+        position.yield_tokens_minted = 1_000_000;
+        position.lock_until = lock_duration;
+
+        (program_account(&strategy), program_account(&position))
+    }
+
+    #[test]
+    fn redemption_pays_its_share_of_principal_and_yield() {
+        let (mut strategy, mut position) = position_with_terms(0, 0);
+
+        let (principal, yield_amount, penalty) = apply_redeem(&mut strategy, &mut position, 250_000, YEAR).unwrap();
+
+        assert_eq!((principal, yield_amount, penalty), (250_000, 25_000, 0));
+        assert_eq!(position.deposited_amount, 750_000);
+        assert_eq!(position.yield_tokens_minted, 750_000);
+        assert_eq!(strategy.total_deposits, 750_000);
+        assert_eq!(strategy.total_yield_tokens_minted, 750_000);
+    }
+
+    #[test]
+    fn early_redemption_charges_the_principal_only() {
+        let (mut strategy, mut position) = position_with_terms(2 * YEAR, 1000);
+
+        let (principal, yield_amount, penalty) = apply_redeem(&mut strategy, &mut position, 500_000, YEAR).unwrap();
+
+        assert_eq!((principal, yield_amount, penalty), (500_000, 50_000, 50_000));
+    }
+
+    #[test]
+    fn locked_redemption_is_rejected_without_a_penalty() {
+        let (mut strategy, mut position) = position_with_terms(2 * YEAR, 0);
+
+        assert_eq!(
+            apply_redeem(&mut strategy, &mut position, 500_000, YEAR).unwrap_err(),
+            WithdrawError::PositionLocked.into()
+        );
+        assert_eq!(position.deposited_amount, 1_000_000);
+    }
+
+    #[test]
+    fn full_redemption_resets_the_position() {
+        let (mut strategy, mut position) = position_with_terms(0, 0);
+
+        apply_redeem(&mut strategy, &mut position, 1_000_000, YEAR).unwrap();
+
+        assert_eq!(position.deposited_amount, 0);
+        assert_eq!(position.yield_tokens_minted, 0);
+        assert_eq!(position.last_yield_claim, YEAR);
+        assert_eq!(strategy.total_deposits, 0);
+    }
+
+    #[test]
+    fn redemption_after_a_withdrawal_pays_each_window_once() {
+        let (mut strategy, mut position) = position_with_terms(0, 0);
+        apply_withdraw(&mut strategy, &mut position, 600_000, YEAR).unwrap();
+
+        let (principal, yield_amount, _) = apply_redeem(&mut strategy, &mut position, 1_000_000, 2 * YEAR).unwrap();
+
+        // A year on the million settled at the withdrawal, then a year on the 400_000 left
+        assert_eq!((principal, yield_amount), (400_000, 140_000));
+    }
+}
@@ -0,0 +1,622 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+use anchor_spl::token_2022::spl_token_2022::extension::{
+    BaseStateWithExtensions, StateWithExtensions,
+};
+use anchor_spl::token_2022::spl_token_2022::state::Mint as MintState;
+use anchor_spl::token_2022::Token2022;
+use anchor_spl::token_2022_extensions::spl_token_metadata_interface::state::{
+    Field, TokenMetadata,
+};
+use anchor_spl::token_2022_extensions::{token_metadata_update_field, TokenMetadataUpdateField};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::adapters::{self, AdapterError};
+use crate::state::{rate_feed::RateFeed, strategy::Strategy};
+
+#[derive(Accounts)]
+pub struct UpdateYieldTokenUri<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"strategy", strategy.strategy_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = admin @ StrategyAdminError::Unauthorized,
+        has_one = yield_token_mint @ StrategyAdminError::WrongYieldTokenMint
+    )]
+    pub strategy: Account<'info, Strategy>,
+
+    /// CHECK: Address checked against the strategy, ownership and metadata are
+    /// validated in the handler
+    #[account(mut, owner = token_program.key() @ StrategyAdminError::NoMetadata)]
+    pub yield_token_mint: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetStrategyAdapter<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"strategy", strategy.strategy_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = admin @ StrategyAdminError::Unauthorized
+    )]
+    pub strategy: Account<'info, Strategy>,
+
+    /// The underlying token mint
+    #[account(
+        address = strategy.underlying_token @ StrategyAdminError::WrongUnderlyingToken
+    )]
+    pub underlying_token_mint: InterfaceAccount<'info, Mint>,
+
+    /// Reserve receiving the yield harvested from the yield source
+    #[account(
+        init_if_needed,
+        payer = admin,
+        seeds = [b"reward_reserve", strategy.strategy_id.to_le_bytes().as_ref()],
+        bump,
+        token::mint = underlying_token_mint,
+        token::authority = strategy,
+        token::token_program = token_program,
+    )]
+    pub reward_reserve: InterfaceAccount<'info, TokenAccount>,
+
+    /// Token program of the underlying token
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateStrategy<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = admin @ StrategyAdminError::Unauthorized
+    )]
+    pub strategy: Account<'info, Strategy>,
+}
+
+#[derive(Accounts)]
+pub struct SetLockTerms<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"strategy", strategy.strategy_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = admin @ StrategyAdminError::Unauthorized
+    )]
+    pub strategy: Account<'info, Strategy>,
+
+    /// The underlying token mint
+    #[account(
+        address = strategy.underlying_token @ StrategyAdminError::WrongUnderlyingToken
+    )]
+    pub underlying_token_mint: InterfaceAccount<'info, Mint>,
+
+    /// Reserve receiving early-exit penalties
+    #[account(
+        init_if_needed,
+        payer = admin,
+        seeds = [b"reward_reserve", strategy.strategy_id.to_le_bytes().as_ref()],
+        bump,
+        token::mint = underlying_token_mint,
+        token::authority = strategy,
+        token::token_program = token_program,
+    )]
+    pub reward_reserve: InterfaceAccount<'info, TokenAccount>,
+
+    /// Token program of the underlying token
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetStrategyRateFeed<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = admin @ StrategyAdminError::Unauthorized
+    )]
+    pub strategy: Account<'info, Strategy>,
+
+    /// Feed to follow, omitted to go back to the fixed APY
+    pub rate_feed: Option<Account<'info, RateFeed>>,
+}
+
+pub fn handle_update_yield_token_uri(ctx: Context<UpdateYieldTokenUri>, uri: String) -> Result<()> {
+    require!(uri.len() <= Strategy::MAX_URI_LEN, StrategyAdminError::UriTooLong);
+
+    let mint = &ctx.accounts.yield_token_mint;
+    let (current_len, current_uri_len) = {
+        let data = mint.try_borrow_data()?;
+        let state = StateWithExtensions::<MintState>::unpack(&data)?;
+        let metadata = state
+            .get_variable_len_extension::<TokenMetadata>()
+            .map_err(|_| StrategyAdminError::NoMetadata)?;
+        (data.len(), metadata.uri.len())
+    };
+
+    // The token program reallocates the mint to fit a longer URI but does not fund it
+    if uri.len() > current_uri_len {
+        let new_len = current_len + uri.len() - current_uri_len;
+        let required = Rent::get()?.minimum_balance(new_len);
+        let shortfall = required.saturating_sub(mint.lamports());
+        if shortfall > 0 {
+            transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.admin.to_account_info(),
+                        to: mint.to_account_info(),
+                    },
+                ),
+                shortfall,
+            )?;
+        }
+    }
+
+    let strategy = &ctx.accounts.strategy;
+    let id_bytes = strategy.strategy_id.to_le_bytes();
+    let seeds: &[&[u8]] = &[b"strategy", id_bytes.as_ref(), &[ctx.bumps.strategy]];
+    token_metadata_update_field(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TokenMetadataUpdateField {
+                program_id: ctx.accounts.token_program.to_account_info(),
+                metadata: mint.to_account_info(),
+                update_authority: strategy.to_account_info(),
+            },
+            &[seeds],
+        ),
+        Field::Uri,
+        uri.clone(),
+    )?;
+
+    msg!("Strategy {} yield token URI updated to {}", strategy.strategy_id, uri);
+
+    Ok(())
+}
+
+/// Attach a yield source adapter to a strategy, or detach it with `Strategy::ADAPTER_NONE`.
+/// The adapter program and targets are passed again as remaining accounts so the
+/// strategy's position in the yield source can be opened.
+pub fn handle_set_strategy_adapter<'info>(
+    ctx: Context<'_, '_, 'info, 'info, SetStrategyAdapter<'info>>,
+    adapter_kind: u8,
+    adapter_program: Pubkey,
+    adapter_targets: [Pubkey; Strategy::MAX_ADAPTER_TARGETS],
+) -> Result<()> {
+    let strategy = &mut ctx.accounts.strategy;
+
+    // Funds must be pulled back before the yield source can change
+    require!(strategy.adapter_deposits == 0, StrategyAdminError::AdapterInUse);
+
+    match adapter_kind {
+        Strategy::ADAPTER_NONE => {
+            require!(
+                adapter_program == Pubkey::default()
+                    && adapter_targets.iter().all(|target| *target == Pubkey::default()),
+                StrategyAdminError::WrongAdapterTargets
+            );
+        }
+        Strategy::ADAPTER_MOCK_LENDING => {
+            require_keys_eq!(adapter_program, mock_lending::ID, AdapterError::WrongAdapterProgram);
+            require!(
+                adapter_targets == adapters::mock_lending::targets(&adapter_targets[0], &strategy.key()),
+                StrategyAdminError::WrongAdapterTargets
+            );
+        }
+        _ => return err!(AdapterError::UnknownAdapter),
+    }
+
+    strategy.adapter_kind = adapter_kind;
+    strategy.adapter_program = adapter_program;
+    strategy.adapter_targets = adapter_targets;
+
+    let id_bytes = strategy.strategy_id.to_le_bytes();
+    let seeds: &[&[u8]] = &[b"strategy", id_bytes.as_ref(), &[ctx.bumps.strategy]];
+    adapters::initialize(
+        strategy,
+        ctx.remaining_accounts,
+        seeds,
+        ctx.accounts.admin.to_account_info(),
+        ctx.accounts.system_program.to_account_info(),
+    )?;
+
+    msg!(
+        "Strategy {} yield source set to adapter {} ({})",
+        strategy.strategy_id,
+        adapter_kind,
+        adapter_program
+    );
+
+    Ok(())
+}
+
+/// Switch a strategy between its fixed APY and a rate feed. Positions settle against
+/// one rate model only, so the mode can only change while nothing is deposited.
+pub fn handle_set_strategy_rate_feed(ctx: Context<SetStrategyRateFeed>) -> Result<()> {
+    let strategy = &mut ctx.accounts.strategy;
+    require!(strategy.total_deposits == 0, StrategyAdminError::DepositsOutstanding);
+
+    match &ctx.accounts.rate_feed {
+        Some(rate_feed) => {
+            require!(rate_feed.last_update > 0, StrategyAdminError::RateFeedNotLive);
+
+            strategy.rate_mode = Strategy::RATE_MODE_ORACLE;
+            strategy.rate_feed = rate_feed.key();
+            strategy.last_index_update = Clock::get()?.unix_timestamp;
+            strategy.cumulative_rate_snapshot = rate_feed.cumulative_rate_at(strategy.last_index_update);
+
+            msg!(
+                "Strategy {} now follows rate feed {} (currently {}%)",
+                strategy.strategy_id,
+                rate_feed.key(),
+                rate_feed.rate_bps as f64 / 100.0
+            );
+        }
+        None => {
+            strategy.rate_mode = Strategy::RATE_MODE_FIXED;
+            strategy.rate_feed = Pubkey::default();
+
+            msg!(
+                "Strategy {} back to its fixed APY of {}%",
+                strategy.strategy_id,
+                strategy.apy as f64 / 100.0
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Choose between simple and continuously compounded interest. Like the rate
+/// mode, it can only change while nothing is deposited.
+pub fn handle_set_accrual_mode(ctx: Context<UpdateStrategy>, accrual_mode: u8) -> Result<()> {
+    require!(
+        accrual_mode == Strategy::ACCRUAL_SIMPLE || accrual_mode == Strategy::ACCRUAL_COMPOUND,
+        StrategyAdminError::InvalidAccrualMode
+    );
+
+    let strategy = &mut ctx.accounts.strategy;
+    require!(strategy.total_deposits == 0, StrategyAdminError::DepositsOutstanding);
+    strategy.accrual_mode = accrual_mode;
+
+    msg!(
+        "Strategy {} now accrues {} interest",
+        strategy.strategy_id,
+        if accrual_mode == Strategy::ACCRUAL_COMPOUND { "compound" } else { "simple" }
+    );
+
+    Ok(())
+}
+
+/// Cap the strategy's total deposits and each user's principal, 0 lifts a cap.
+/// Lowering a cap below what is already deposited only blocks new deposits.
+pub fn handle_set_deposit_caps(
+    ctx: Context<UpdateStrategy>,
+    max_total_deposits: u64,
+    max_deposit_per_user: u64,
+) -> Result<()> {
+    let strategy = &mut ctx.accounts.strategy;
+    strategy.max_total_deposits = max_total_deposits;
+    strategy.max_deposit_per_user = max_deposit_per_user;
+
+    msg!(
+        "Strategy {} deposit caps set to {} total and {} per user (0 = uncapped)",
+        strategy.strategy_id,
+        max_total_deposits,
+        max_deposit_per_user
+    );
+
+    Ok(())
+}
+
+/// Lock new deposits for `lock_duration` seconds. Early exits pay `early_exit_penalty_bps`
+/// of the principal into the reward reserve, or are rejected when it is 0. Positions
+/// keep the lock end they were opened with.
+pub fn handle_set_lock_terms(
+    ctx: Context<SetLockTerms>,
+    lock_duration: i64,
+    early_exit_penalty_bps: u16,
+) -> Result<()> {
+    require!(lock_duration >= 0, StrategyAdminError::InvalidLockDuration);
+    require!(
+        early_exit_penalty_bps <= Strategy::MAX_EARLY_EXIT_PENALTY_BPS,
+        StrategyAdminError::PenaltyTooHigh
+    ); // Max 50% penalty
+
+    let strategy = &mut ctx.accounts.strategy;
+    strategy.lock_duration = lock_duration;
+    strategy.early_exit_penalty_bps = early_exit_penalty_bps;
+
+    msg!(
+        "Strategy {} deposits now lock for {}s with a {}% early-exit penalty",
+        strategy.strategy_id,
+        lock_duration,
+        early_exit_penalty_bps as f64 / 100.0
+    );
+
+    Ok(())
+}
+
+/// Route withdrawals through the cooldown queue, 0 restores instant withdrawals.
+/// Open tickets keep the unlock time they were requested with.
+pub fn handle_set_withdrawal_cooldown(ctx: Context<UpdateStrategy>, withdrawal_cooldown: i64) -> Result<()> {
+    require!(withdrawal_cooldown >= 0, StrategyAdminError::InvalidCooldown);
+
+    let strategy = &mut ctx.accounts.strategy;
+    strategy.withdrawal_cooldown = withdrawal_cooldown;
+
+    msg!(
+        "Strategy {} withdrawal cooldown set to {}s",
+        strategy.strategy_id,
+        withdrawal_cooldown
+    );
+
+    Ok(())
+}
+
+/// Set the share of compounded yield paid to keepers
+pub fn handle_set_keeper_fee(ctx: Context<UpdateStrategy>, keeper_fee_bps: u16) -> Result<()> {
+    require!(
+        keeper_fee_bps <= Strategy::MAX_KEEPER_FEE_BPS,
+        StrategyAdminError::KeeperFeeTooHigh
+    ); // Max 10% fee
+
+    let strategy = &mut ctx.accounts.strategy;
+    strategy.keeper_fee_bps = keeper_fee_bps;
+
+    msg!(
+        "Strategy {} keeper fee set to {}%",
+        strategy.strategy_id,
+        keeper_fee_bps as f64 / 100.0
+    );
+
+    Ok(())
+}
+
+#[error_code]
+pub enum StrategyAdminError {
+    #[msg("Only the strategy admin can perform this action")]
+    Unauthorized,
+    #[msg("Yield token mint does not belong to this strategy")]
+    WrongYieldTokenMint,
+    #[msg("Yield token mint has no Token-2022 metadata")]
+    NoMetadata,
+    #[msg("Yield token metadata URI is too long (max 200 characters)")]
+    UriTooLong,
+    #[msg("Wrong underlying token for this strategy")]
+    WrongUnderlyingToken,
+    #[msg("Funds are still deployed in the current yield source")]
+    AdapterInUse,
+    #[msg("Adapter targets do not match the adapter kind")]
+    WrongAdapterTargets,
+    #[msg("Strategy still holds deposits")]
+    DepositsOutstanding,
+    #[msg("Rate feed has no rate yet")]
+    RateFeedNotLive,
+    #[msg("Unknown accrual mode")]
+    InvalidAccrualMode,
+    #[msg("Lock duration cannot be negative")]
+    InvalidLockDuration,
+    #[msg("Early-exit penalty is too high (max 50%)")]
+    PenaltyTooHigh,
+    #[msg("Withdrawal cooldown cannot be negative")]
+    InvalidCooldown,
+    #[msg("Keeper fee is too high (max 10%)")]
+    KeeperFeeTooHigh,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeSet;
+
+    use anchor_lang::solana_program::program_option::COption;
+    use anchor_spl::token_2022::spl_token_2022::{
+        self,
+        extension::{BaseStateWithExtensionsMut, ExtensionType, StateWithExtensionsMut, metadata_pointer::MetadataPointer},
+    };
+
+    use crate::test_utils::{
+        account_info, program_account, program_account_at, program_info, set_clock, signer_info, stub_syscalls, zeroed,
+    };
+
+    const STRATEGY_ID: u64 = 7;
+
+    /// Token-2022 yield token mint at `key` whose metadata the strategy updates
+    fn metadata_mint(key: Pubkey, strategy: Pubkey, uri: &str) -> &'static AccountInfo<'static> {
+        let metadata = TokenMetadata {
+            update_authority: Some(strategy).try_into().unwrap(),
+            mint: key,
+            name: "yUSDC-Yieldos Staking".to_string(),
+            symbol: "yUSDC".to_string(),
+            uri: uri.to_string(),
+            additional_metadata: vec![],
+        };
+        let len = ExtensionType::try_calculate_account_len::<MintState>(&[ExtensionType::MetadataPointer]).unwrap()
+            + metadata.tlv_size_of().unwrap();
+        let mut data = vec![0; len];
+        let mut state = StateWithExtensionsMut::<MintState>::unpack_uninitialized(&mut data).unwrap();
+        state.init_extension::<MetadataPointer>(true).unwrap().metadata_address = Some(key).try_into().unwrap();
+        state.base = MintState {
+            mint_authority: COption::Some(strategy),
+            decimals: 6,
+            is_initialized: true,
+            ..Default::default()
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        state.init_variable_len_extension(&metadata, false).unwrap();
+        account_info(key, spl_token_2022::ID, data)
+    }
+
+    /// Accounts of `update_yield_token_uri` signed by `admin`, checked like the runtime would
+    fn update_uri_accounts(
+        admin: Pubkey,
+        yield_token_mint: Pubkey,
+    ) -> Result<(UpdateYieldTokenUri<'static>, UpdateYieldTokenUriBumps)> {
+        let (strategy_key, _) =
+            Pubkey::find_program_address(&[b"strategy", STRATEGY_ID.to_le_bytes().as_ref()], &crate::ID);
+        let mut strategy: Strategy = zeroed();
+        strategy.strategy_id = STRATEGY_ID;
+        strategy.admin = Pubkey::new_from_array([1; 32]);
+        strategy.yield_token_mint = Pubkey::new_from_array([2; 32]);
+
+        let infos: &'static [AccountInfo<'static>] = vec![
+            signer_info(admin).clone(),
+            program_account_at(strategy_key, &strategy).to_account_info(),
+            metadata_mint(yield_token_mint, strategy_key, "https://yieldos.xyz/a.json").clone(),
+            program_info(spl_token_2022::ID).clone(),
+            program_info(System::id()).clone(),
+        ]
+        .leak();
+        let mut bumps = UpdateYieldTokenUriBumps::default();
+        let accounts =
+            UpdateYieldTokenUri::try_accounts(&crate::ID, &mut &infos[..], &[], &mut bumps, &mut BTreeSet::new())?;
+        Ok((accounts, bumps))
+    }
+
+    fn uri(mint: &AccountInfo) -> String {
+        let data = mint.try_borrow_data().unwrap();
+        let state = StateWithExtensions::<MintState>::unpack(&data).unwrap();
+        state.get_variable_len_extension::<TokenMetadata>().unwrap().uri
+    }
+
+    #[test]
+    fn admin_updates_the_yield_token_uri() {
+        stub_syscalls();
+        let (mut accounts, bumps) =
+            update_uri_accounts(Pubkey::new_from_array([1; 32]), Pubkey::new_from_array([2; 32])).unwrap();
+
+        handle_update_yield_token_uri(
+            Context::new(&crate::ID, &mut accounts, &[], bumps),
+            "https://yieldos.xyz/b.json".to_string(),
+        )
+        .unwrap();
+
+        assert_eq!(uri(&accounts.yield_token_mint), "https://yieldos.xyz/b.json");
+    }
+
+    #[test]
+    fn only_the_admin_updates_the_uri() {
+        assert_eq!(
+            update_uri_accounts(Pubkey::new_unique(), Pubkey::new_from_array([2; 32])).err(),
+            Some(StrategyAdminError::Unauthorized.into())
+        );
+    }
+
+    #[test]
+    fn uri_updates_target_the_strategy_mint() {
+        assert_eq!(
+            update_uri_accounts(Pubkey::new_from_array([1; 32]), Pubkey::new_unique()).err(),
+            Some(StrategyAdminError::WrongYieldTokenMint.into())
+        );
+    }
+
+    #[test]
+    fn uri_length_is_capped() {
+        let (mut accounts, bumps) =
+            update_uri_accounts(Pubkey::new_from_array([1; 32]), Pubkey::new_from_array([2; 32])).unwrap();
+
+        assert_eq!(
+            handle_update_yield_token_uri(
+                Context::new(&crate::ID, &mut accounts, &[], bumps),
+                "x".repeat(Strategy::MAX_URI_LEN + 1),
+            )
+            .unwrap_err(),
+            StrategyAdminError::UriTooLong.into()
+        );
+        assert_eq!(uri(&accounts.yield_token_mint), "https://yieldos.xyz/a.json");
+    }
+
+    #[test]
+    fn strategies_follow_only_live_feeds() {
+        set_clock(100);
+        let admin = Pubkey::new_unique();
+        let mut strategy: Strategy = zeroed();
+        strategy.admin = admin;
+        let mut feed: RateFeed = zeroed();
+        feed.max_staleness = 3_600;
+        let mut accounts = SetStrategyRateFeed {
+            admin: Signer::try_from(signer_info(admin)).unwrap(),
+            strategy: program_account(&strategy),
+            rate_feed: Some(program_account(&feed)),
+        };
+        let set_feed = |accounts: &mut SetStrategyRateFeed<'static>| {
+            handle_set_strategy_rate_feed(Context::new(&crate::ID, accounts, &[], SetStrategyRateFeedBumps::default()))
+        };
+
+        // A feed that never had a rate pushed
+        assert_eq!(set_feed(&mut accounts).unwrap_err(), StrategyAdminError::RateFeedNotLive.into());
+        assert_eq!(accounts.strategy.rate_mode, Strategy::RATE_MODE_FIXED);
+
+        feed.update(1200, 50);
+        accounts.rate_feed = Some(program_account(&feed));
+        set_feed(&mut accounts).unwrap();
+        assert_eq!(accounts.strategy.rate_mode, Strategy::RATE_MODE_ORACLE);
+        assert_eq!(accounts.strategy.rate_feed, accounts.rate_feed.as_ref().unwrap().key());
+        assert_eq!(accounts.strategy.last_index_update, 100);
+        assert_eq!(accounts.strategy.cumulative_rate_snapshot, feed.cumulative_rate_at(100));
+    }
+
+    #[test]
+    fn only_the_admin_sets_deposit_caps() {
+        let strategy = program_account(&{
+            let mut strategy: Strategy = zeroed();
+            strategy.admin = Pubkey::new_unique();
+            strategy
+        });
+        let set_caps = |admin| {
+            let infos: &'static [AccountInfo<'static>] =
+                vec![signer_info(admin).clone(), strategy.to_account_info()].leak();
+            let mut bumps = UpdateStrategyBumps::default();
+            let mut accounts =
+                UpdateStrategy::try_accounts(&crate::ID, &mut &infos[..], &[], &mut bumps, &mut BTreeSet::new())?;
+            handle_set_deposit_caps(Context::new(&crate::ID, &mut accounts, &[], bumps), 1_500, 1_000)?;
+            accounts.exit(&crate::ID)
+        };
+
+        assert_eq!(set_caps(Pubkey::new_unique()).unwrap_err(), StrategyAdminError::Unauthorized.into());
+        set_caps(strategy.admin).unwrap();
+
+        let strategy = Strategy::try_deserialize(&mut &strategy.to_account_info().data.borrow()[..]).unwrap();
+        assert_eq!((strategy.max_total_deposits, strategy.max_deposit_per_user), (1_500, 1_000));
+    }
+
+    #[test]
+    fn accrual_mode_changes_only_before_deposits() {
+        let set_mode = |total_deposits, accrual_mode| {
+            let admin = Pubkey::new_unique();
+            let mut strategy: Strategy = zeroed();
+            strategy.admin = admin;
+            strategy.total_deposits = total_deposits;
+            let mut accounts = UpdateStrategy {
+                admin: Signer::try_from(signer_info(admin)).unwrap(),
+                strategy: program_account(&strategy),
+            };
+            let ctx = Context::new(&crate::ID, &mut accounts, &[], UpdateStrategyBumps::default());
+            handle_set_accrual_mode(ctx, accrual_mode).map(|()| accounts.strategy.accrual_mode)
+        };
+
+        assert_eq!(set_mode(0, 2).unwrap_err(), StrategyAdminError::InvalidAccrualMode.into());
+        assert_eq!(set_mode(0, Strategy::ACCRUAL_COMPOUND).unwrap(), Strategy::ACCRUAL_COMPOUND);
+        assert_eq!(
+            set_mode(1, Strategy::ACCRUAL_COMPOUND).unwrap_err(),
+            StrategyAdminError::DepositsOutstanding.into()
+        );
+    }
+}
+
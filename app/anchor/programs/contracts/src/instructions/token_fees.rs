@@ -0,0 +1,110 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_2022::spl_token_2022::extension::transfer_fee::TransferFeeConfig;
+use anchor_spl::token_interface::{get_mint_extension_data, Mint};
+
+/// Tokens withheld by a Token-2022 transfer fee when `amount` of `mint` is sent
+pub(crate) fn transfer_fee(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    let Some(fee_config) = transfer_fee_config(mint) else {
+        return Ok(0);
+    };
+    fee_config
+        .calculate_epoch_fee(Clock::get()?.epoch, amount)
+        .ok_or_else(|| error!(TokenFeeError::FeeCalculationFailed))
+}
+
+/// Amount to send so that the recipient receives exactly `net_amount` of `mint`
+pub(crate) fn amount_with_fee(mint: &InterfaceAccount<Mint>, net_amount: u64) -> Result<u64> {
+    let Some(fee_config) = transfer_fee_config(mint) else {
+        return Ok(net_amount);
+    };
+    let fee = fee_config
+        .calculate_inverse_epoch_fee(Clock::get()?.epoch, net_amount)
+        .ok_or(TokenFeeError::FeeCalculationFailed)?;
+    net_amount
+        .checked_add(fee)
+        .ok_or_else(|| error!(TokenFeeError::FeeCalculationFailed))
+}
+
+/// Amount to send so that `net_amount` of `mint` is left after `hops` chained transfers
+pub(crate) fn amount_with_fees(mint: &InterfaceAccount<Mint>, net_amount: u64, hops: u32) -> Result<u64> {
+    let mut amount = net_amount;
+    for _ in 0..hops {
+        amount = amount_with_fee(mint, amount)?;
+    }
+    Ok(amount)
+}
+
+fn transfer_fee_config(mint: &InterfaceAccount<Mint>) -> Option<TransferFeeConfig> {
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner != anchor_spl::token_2022::ID {
+        return None;
+    }
+    get_mint_extension_data::<TransferFeeConfig>(&mint_info).ok()
+}
+
+#[error_code]
+pub enum TokenFeeError {
+    #[msg("Transfer fee calculation failed")]
+    FeeCalculationFailed,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::program_pack::Pack;
+    use anchor_spl::token::spl_token;
+
+    use crate::test_utils::{self, account_info};
+
+    fn spl_mint() -> InterfaceAccount<'static, Mint> {
+        let mut data = vec![0; spl_token::state::Mint::LEN];
+        let mint = spl_token::state::Mint { decimals: 6, is_initialized: true, ..Default::default() };
+        spl_token::state::Mint::pack(mint, &mut data).unwrap();
+        InterfaceAccount::try_from(account_info(Pubkey::new_unique(), spl_token::ID, data)).unwrap()
+    }
+
+    fn fee_mint(basis_points: u16, maximum_fee: u64) -> InterfaceAccount<'static, Mint> {
+        test_utils::fee_mint(Pubkey::new_unique(), basis_points, maximum_fee)
+    }
+
+    #[test]
+    fn mints_without_a_fee_send_the_net_amount() {
+        let mint = spl_mint();
+
+        assert_eq!(transfer_fee(&mint, 1_000_000).unwrap(), 0);
+        assert_eq!(amount_with_fees(&mint, 1_000_000, 3).unwrap(), 1_000_000);
+    }
+
+    #[test]
+    fn grossed_up_amounts_arrive_in_full() {
+        let mint = fee_mint(100, u64::MAX);
+
+        assert_eq!(transfer_fee(&mint, 1_000_000).unwrap(), 10_000);
+        assert_eq!(amount_with_fee(&mint, 990_000).unwrap(), 1_000_000);
+        for net in [1, 99, 101, 12_345, 990_001, 7_777_777] {
+            let sent = amount_with_fee(&mint, net).unwrap();
+            assert_eq!(sent - transfer_fee(&mint, sent).unwrap(), net, "net {net}");
+        }
+    }
+
+    #[test]
+    fn every_hop_is_grossed_up() {
+        let mint = fee_mint(250, u64::MAX);
+
+        for net in [1_000, 123_457, 5_000_000] {
+            let mut amount = amount_with_fees(&mint, net, 3).unwrap();
+            for _ in 0..3 {
+                amount -= transfer_fee(&mint, amount).unwrap();
+            }
+            assert_eq!(amount, net, "net {net}");
+        }
+    }
+
+    #[test]
+    fn the_fee_cap_limits_the_gross_up() {
+        let mint = fee_mint(100, 500);
+
+        assert_eq!(amount_with_fee(&mint, 1_000_000).unwrap(), 1_000_500);
+        assert_eq!(amount_with_fees(&mint, 1_000_000, 2).unwrap(), 1_001_000);
+    }
+}
@@ -0,0 +1,236 @@
+use anchor_lang::prelude::*;
+
+use crate::state::trading_delegate::TradingDelegate;
+
+#[derive(Accounts)]
+#[instruction(delegate: Pubkey)]
+pub struct AuthorizeTradingDelegate<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// Authorisation for the session key; the user approves this PDA as SPL
+    /// delegate on the token accounts its orders should draw from
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"trading_delegate", user.key().as_ref(), delegate.as_ref()],
+        bump,
+        space = 8 + TradingDelegate::INIT_SPACE
+    )]
+    pub trading_delegate: Account<'info, TradingDelegate>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeTradingDelegate<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"trading_delegate", user.key().as_ref(), trading_delegate.delegate.as_ref()],
+        bump = trading_delegate.bump,
+        has_one = user @ DelegateError::UnauthorizedUser,
+        close = user
+    )]
+    pub trading_delegate: Account<'info, TradingDelegate>,
+}
+
+pub fn handle_authorize_trading_delegate(
+    ctx: Context<AuthorizeTradingDelegate>,
+    delegate: Pubkey,
+    expires_at: i64,
+    notional_limit: u64,
+    min_sell_price: u64,
+) -> Result<()> {
+    require!(delegate != ctx.accounts.user.key(), DelegateError::InvalidDelegate);
+    require!(
+        expires_at > Clock::get()?.unix_timestamp,
+        DelegateError::InvalidExpiry
+    );
+
+    // Re-authorising the same key renews it and resets the notional used
+    let trading_delegate = &mut ctx.accounts.trading_delegate;
+    trading_delegate.user = ctx.accounts.user.key();
+    trading_delegate.delegate = delegate;
+    trading_delegate.expires_at = expires_at;
+    trading_delegate.notional_limit = notional_limit;
+    trading_delegate.notional_used = 0;
+    trading_delegate.min_sell_price = min_sell_price;
+    trading_delegate.bump = ctx.bumps.trading_delegate;
+
+    msg!(
+        "User {} authorised session key {} until {} with notional limit {} and minimum sell price {}",
+        trading_delegate.user,
+        delegate,
+        expires_at,
+        notional_limit,
+        min_sell_price
+    );
+
+    Ok(())
+}
+
+pub fn handle_revoke_trading_delegate(ctx: Context<RevokeTradingDelegate>) -> Result<()> {
+    msg!(
+        "User {} revoked session key {}",
+        ctx.accounts.user.key(),
+        ctx.accounts.trading_delegate.delegate
+    );
+
+    Ok(())
+}
+
+/// Check that `authority` may manage `user`'s orders, charging `notional` against
+/// the session key's limit and holding sells at `sell_price` to its price floor.
+/// Returns true when acting through a trading delegate.
+pub(crate) fn authorize_order_authority(
+    authority: &Pubkey,
+    user: &Pubkey,
+    trading_delegate: Option<&mut Account<TradingDelegate>>,
+    notional: u64,
+    sell_price: Option<u64>,
+    current_time: i64,
+) -> Result<bool> {
+    if authority == user {
+        return Ok(false);
+    }
+
+    let trading_delegate = trading_delegate.ok_or(DelegateError::MissingDelegate)?;
+    require_keys_eq!(trading_delegate.user, *user, DelegateError::UnauthorizedUser);
+    require_keys_eq!(trading_delegate.delegate, *authority, DelegateError::InvalidDelegate);
+    require!(
+        current_time < trading_delegate.expires_at,
+        DelegateError::DelegateExpired
+    );
+    require!(
+        notional <= trading_delegate.remaining_notional(),
+        DelegateError::NotionalLimitExceeded
+    );
+    if let Some(price_per_token) = sell_price {
+        require!(
+            trading_delegate.allows_sell_price(price_per_token),
+            DelegateError::SellPriceTooLow
+        );
+    }
+
+    trading_delegate.notional_used += notional;
+
+    Ok(true)
+}
+
+#[error_code]
+pub enum DelegateError {
+    #[msg("Unauthorized user for this trading delegate")]
+    UnauthorizedUser,
+    #[msg("Invalid session key")]
+    InvalidDelegate,
+    #[msg("Expiry must be in the future")]
+    InvalidExpiry,
+    #[msg("Signer is not the order owner and no trading delegate was provided")]
+    MissingDelegate,
+    #[msg("Trading delegate has expired")]
+    DelegateExpired,
+    #[msg("Trading delegate notional limit exceeded")]
+    NotionalLimitExceeded,
+    #[msg("Sell price is below the trading delegate's minimum")]
+    SellPriceTooLow,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_utils::program_account;
+
+    fn session(user: Pubkey, delegate: Pubkey) -> Account<'static, TradingDelegate> {
+        program_account(&TradingDelegate {
+            user,
+            delegate,
+            expires_at: 2_000,
+            notional_limit: 1_000,
+            notional_used: 0,
+            min_sell_price: 500_000,
+            bump: 255,
+        })
+    }
+
+    #[test]
+    fn owner_needs_no_delegate() {
+        let user = Pubkey::new_unique();
+
+        assert!(!authorize_order_authority(&user, &user, None, u64::MAX, None, 1_000).unwrap());
+    }
+
+    #[test]
+    fn session_key_is_charged_against_its_limit() {
+        let (user, key) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut delegate = session(user, key);
+
+        assert!(authorize_order_authority(&key, &user, Some(&mut delegate), 600, None, 1_000).unwrap());
+        assert_eq!(delegate.notional_used, 600);
+        assert_eq!(
+            authorize_order_authority(&key, &user, Some(&mut delegate), 500, None, 1_000).unwrap_err(),
+            DelegateError::NotionalLimitExceeded.into()
+        );
+        assert!(authorize_order_authority(&key, &user, Some(&mut delegate), 400, None, 1_000).unwrap());
+        assert_eq!(delegate.remaining_notional(), 0);
+    }
+
+    #[test]
+    fn expired_session_key_is_rejected() {
+        let (user, key) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut delegate = session(user, key);
+
+        assert_eq!(
+            authorize_order_authority(&key, &user, Some(&mut delegate), 0, None, 2_000).unwrap_err(),
+            DelegateError::DelegateExpired.into()
+        );
+    }
+
+    #[test]
+    fn other_signers_are_rejected() {
+        let (user, key) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let stranger = Pubkey::new_unique();
+        let mut delegate = session(user, key);
+
+        assert_eq!(
+            authorize_order_authority(&stranger, &user, None, 0, None, 1_000).unwrap_err(),
+            DelegateError::MissingDelegate.into()
+        );
+        assert!(authorize_order_authority(&stranger, &user, Some(&mut delegate), 0, None, 1_000).is_err());
+        assert!(authorize_order_authority(&key, &stranger, Some(&mut delegate), 0, None, 1_000).is_err());
+        assert_eq!(delegate.notional_used, 0);
+    }
+
+    #[test]
+    fn session_key_sells_only_above_its_floor() {
+        let (user, key) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut delegate = session(user, key);
+
+        // Dumping at one tick stays well inside the notional limit, but not the floor
+        assert_eq!(
+            authorize_order_authority(&key, &user, Some(&mut delegate), 1, Some(1), 1_000).unwrap_err(),
+            DelegateError::SellPriceTooLow.into()
+        );
+        assert_eq!(delegate.notional_used, 0);
+        assert!(authorize_order_authority(&key, &user, Some(&mut delegate), 500, Some(500_000), 1_000).unwrap());
+
+        // The owner sells at any price
+        assert!(!authorize_order_authority(&user, &user, None, 1, Some(1), 1_000).unwrap());
+    }
+
+    #[test]
+    fn session_key_without_a_floor_cannot_sell() {
+        let (user, key) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut delegate = session(user, key);
+        delegate.min_sell_price = 0;
+
+        assert_eq!(
+            authorize_order_authority(&key, &user, Some(&mut delegate), 100, Some(u64::MAX), 1_000).unwrap_err(),
+            DelegateError::SellPriceTooLow.into()
+        );
+        assert!(authorize_order_authority(&key, &user, Some(&mut delegate), 100, None, 1_000).unwrap());
+    }
+}
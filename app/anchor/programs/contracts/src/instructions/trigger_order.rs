@@ -0,0 +1,693 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked, CloseAccount, close_account};
+
+use crate::instructions::{
+    batch_auction::{evict_from_full_book, MAX_AUCTION_ORDERS}, place_order::validate_order_terms, token_fees::amount_with_fees,
+};
+use crate::state::{marketplace::{Marketplace, TradeOrder, OrderCounter}, trigger_order::TriggerOrder};
+
+#[derive(Accounts)]
+#[instruction(trigger_id: u64, order_id: u64, order_type: u8)]
+pub struct PlaceTriggerOrder<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        constraint = marketplace.is_active @ TriggerError::MarketplaceNotActive
+    )]
+    pub marketplace: Box<Account<'info, Marketplace>>,
+
+    #[account(
+        init,
+        payer = user,
+        seeds = [b"trigger_order", user.key().as_ref(), trigger_id.to_le_bytes().as_ref()],
+        bump,
+        space = 8 + TriggerOrder::INIT_SPACE
+    )]
+    pub trigger_order: Account<'info, TriggerOrder>,
+
+    /// Mint locked in escrow: yield tokens for sell orders, quote tokens for buy orders
+    #[account(
+        constraint = escrow_mint.key() == if order_type == TradeOrder::SELL_ORDER {
+            marketplace.yield_token_mint
+        } else {
+            marketplace.quote_mint
+        } @ TriggerError::WrongEscrowMint
+    )]
+    pub escrow_mint: InterfaceAccount<'info, Mint>,
+
+    /// User's token account funding the escrow
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key() @ TriggerError::WrongTokenAccount,
+        constraint = user_token_account.mint == escrow_mint.key() @ TriggerError::WrongTokenAccount
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Escrow holding the funds until the order is triggered
+    #[account(
+        init,
+        payer = user,
+        seeds = [b"trigger_escrow", trigger_order.key().as_ref()],
+        bump,
+        token::mint = escrow_mint,
+        token::authority = trigger_order,
+        token::token_program = token_program,
+    )]
+    pub trigger_escrow: InterfaceAccount<'info, TokenAccount>,
+
+    /// Token program of the escrow mint
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(trigger_id: u64)]
+pub struct CancelTriggerOrder<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"trigger_order", user.key().as_ref(), trigger_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = user @ TriggerError::UnauthorizedUser,
+        close = user
+    )]
+    pub trigger_order: Account<'info, TriggerOrder>,
+
+    #[account(
+        mut,
+        seeds = [b"trigger_escrow", trigger_order.key().as_ref()],
+        bump
+    )]
+    pub trigger_escrow: InterfaceAccount<'info, TokenAccount>,
+
+    /// User's token account to receive the refund
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key() @ TriggerError::WrongTokenAccount,
+        constraint = user_token_account.mint == trigger_escrow.mint @ TriggerError::WrongTokenAccount
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Mint held by the trigger escrow
+    #[account(
+        address = trigger_escrow.mint @ TriggerError::WrongEscrowMint
+    )]
+    pub escrow_mint: InterfaceAccount<'info, Mint>,
+
+    /// Token program of the escrow mint
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteTriggerOrder<'info> {
+    /// Anyone can convert a triggered order and collect its bounty
+    #[account(mut)]
+    pub keeper: Signer<'info>,
+
+    /// CHECK: Owner of the trigger order, receives its rent and the escrow rent
+    #[account(
+        mut,
+        address = trigger_order.user @ TriggerError::UnauthorizedUser
+    )]
+    pub user: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = marketplace.is_active @ TriggerError::MarketplaceNotActive
+    )]
+    pub marketplace: Box<Account<'info, Marketplace>>,
+
+    #[account(
+        mut,
+        seeds = [b"trigger_order", trigger_order.user.as_ref(), trigger_order.trigger_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = marketplace @ TriggerError::OrderMarketplaceMismatch,
+        close = user
+    )]
+    pub trigger_order: Box<Account<'info, TriggerOrder>>,
+
+    #[account(
+        mut,
+        seeds = [b"trigger_escrow", trigger_order.key().as_ref()],
+        bump
+    )]
+    pub trigger_escrow: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// The live order created from the trigger order
+    #[account(
+        init,
+        payer = keeper,
+        seeds = [b"order", trigger_order.user.as_ref(), trigger_order.order_id.to_le_bytes().as_ref()],
+        bump,
+        space = 8 + TradeOrder::INIT_SPACE
+    )]
+    pub order: Box<Account<'info, TradeOrder>>,
+
+    #[account(
+        init_if_needed,
+        payer = keeper,
+        seeds = [b"order_counter"],
+        bump,
+        space = 8 + OrderCounter::INIT_SPACE
+    )]
+    pub order_counter: Box<Account<'info, OrderCounter>>,
+
+    #[account(
+        address = trigger_escrow.mint @ TriggerError::WrongEscrowMint
+    )]
+    pub escrow_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    /// Escrow of the live order
+    #[account(
+        init,
+        payer = keeper,
+        seeds = [b"escrow", order.key().as_ref()],
+        bump,
+        token::mint = escrow_mint,
+        token::authority = order,
+        token::token_program = token_program,
+    )]
+    pub escrow_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Token program of the escrow mint
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn handle_place_trigger_order(
+    ctx: Context<PlaceTriggerOrder>,
+    trigger_id: u64,
+    order_id: u64,
+    order_type: u8,
+    yield_token_amount: u64,
+    price_per_token: u64,
+    trigger_price: u64,
+    trigger_condition: u8,
+    price_source: u8,
+    twap_window: i64,
+    keeper_bounty: u64,
+) -> Result<()> {
+    require!(
+        order_type == TradeOrder::BUY_ORDER || order_type == TradeOrder::SELL_ORDER,
+        TriggerError::InvalidOrderType
+    );
+    require!(
+        trigger_condition == TriggerOrder::TRIGGER_AT_OR_ABOVE
+            || trigger_condition == TriggerOrder::TRIGGER_AT_OR_BELOW,
+        TriggerError::InvalidTriggerCondition
+    );
+    require!(trigger_price > 0, TriggerError::InvalidTriggerPrice);
+    validate_price_source(price_source, twap_window)?;
+
+    let total_value = validate_order_terms(&ctx.accounts.marketplace, yield_token_amount, price_per_token)?;
+    let order_escrow_amount = if order_type == TradeOrder::SELL_ORDER {
+        yield_token_amount
+    } else {
+        total_value
+    };
+    // Transfer-fee mints are charged into the trigger escrow, on to the order escrow and
+    // on every hop out to the counterparty
+    let escrow_amount = amount_with_fees(
+        &ctx.accounts.escrow_mint,
+        order_escrow_amount,
+        2 + ctx.accounts.marketplace.settlement_hops(),
+    )?;
+    require!(
+        ctx.accounts.user_token_account.amount >= escrow_amount,
+        TriggerError::InsufficientBalance
+    );
+
+    // Lock the order funds in the trigger escrow
+    let cpi_ctx = CpiContext::new(
+        ctx.accounts.token_program.to_account_info(),
+        TransferChecked {
+            from: ctx.accounts.user_token_account.to_account_info(),
+            mint: ctx.accounts.escrow_mint.to_account_info(),
+            to: ctx.accounts.trigger_escrow.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        },
+    );
+    transfer_checked(cpi_ctx, escrow_amount, ctx.accounts.escrow_mint.decimals)?;
+
+    // Deposit the keeper bounty on the trigger order account
+    if keeper_bounty > 0 {
+        let cpi_ctx = CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            system_program::Transfer {
+                from: ctx.accounts.user.to_account_info(),
+                to: ctx.accounts.trigger_order.to_account_info(),
+            },
+        );
+        system_program::transfer(cpi_ctx, keeper_bounty)?;
+    }
+
+    let trigger_order = &mut ctx.accounts.trigger_order;
+    trigger_order.user = ctx.accounts.user.key();
+    trigger_order.marketplace = ctx.accounts.marketplace.key();
+    trigger_order.order_type = order_type;
+    trigger_order.yield_token_amount = yield_token_amount;
+    trigger_order.price_per_token = price_per_token;
+    trigger_order.trigger_price = trigger_price;
+    trigger_order.trigger_condition = trigger_condition;
+    trigger_order.price_source = price_source;
+    trigger_order.twap_window = if price_source == Marketplace::PRICE_SOURCE_TWAP { twap_window } else { 0 };
+    trigger_order.keeper_bounty = keeper_bounty;
+    trigger_order.order_id = order_id;
+    trigger_order.created_at = Clock::get()?.unix_timestamp;
+    trigger_order.trigger_id = trigger_id;
+
+    msg!(
+        "Trigger order {} placed: {} {} yield tokens at {} when {} is {} {} (bounty {} lamports)",
+        trigger_id,
+        if order_type == TradeOrder::BUY_ORDER { "BUY" } else { "SELL" },
+        yield_token_amount,
+        price_per_token as f64 / 1_000_000.0,
+        if price_source == Marketplace::PRICE_SOURCE_TWAP { "TWAP" } else { "last trade price" },
+        if trigger_condition == TriggerOrder::TRIGGER_AT_OR_ABOVE { "at or above" } else { "at or below" },
+        trigger_price as f64 / 1_000_000.0,
+        keeper_bounty
+    );
+
+    Ok(())
+}
+
+pub fn handle_cancel_trigger_order(
+    ctx: Context<CancelTriggerOrder>,
+    trigger_id: u64,
+) -> Result<()> {
+    let trigger_order = &ctx.accounts.trigger_order;
+    let trigger_id_bytes = trigger_id.to_le_bytes();
+    let trigger_seeds = &[
+        b"trigger_order",
+        trigger_order.user.as_ref(),
+        trigger_id_bytes.as_ref(),
+        &[ctx.bumps.trigger_order],
+    ];
+    let signer_seeds = &[trigger_seeds.as_slice()];
+
+    // Refund the escrow and close it, the bounty returns with the trigger order rent
+    let refund = ctx.accounts.trigger_escrow.amount;
+    if refund > 0 {
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.trigger_escrow.to_account_info(),
+                mint: ctx.accounts.escrow_mint.to_account_info(),
+                to: ctx.accounts.user_token_account.to_account_info(),
+                authority: trigger_order.to_account_info(),
+            },
+            signer_seeds,
+        );
+        transfer_checked(cpi_ctx, refund, ctx.accounts.escrow_mint.decimals)?;
+    }
+
+    let cpi_ctx = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        CloseAccount {
+            account: ctx.accounts.trigger_escrow.to_account_info(),
+            destination: ctx.accounts.user.to_account_info(),
+            authority: trigger_order.to_account_info(),
+        },
+        signer_seeds,
+    );
+    close_account(cpi_ctx)?;
+
+    msg!("Trigger order {} cancelled, refunded {} tokens to user", trigger_id, refund);
+
+    Ok(())
+}
+
+pub fn handle_execute_trigger_order<'info>(
+    ctx: Context<'_, '_, 'info, 'info, ExecuteTriggerOrder<'info>>,
+) -> Result<()> {
+    let trigger_order = &ctx.accounts.trigger_order;
+    let marketplace = &mut ctx.accounts.marketplace;
+    let current_time = Clock::get()?.unix_timestamp;
+
+    let observed_price = trigger_order.observed_price(marketplace, current_time);
+    require!(trigger_order.is_triggered(observed_price), TriggerError::NotTriggered);
+
+    // The resulting order follows place_order rules as they stand now
+    let total_value = validate_order_terms(
+        marketplace,
+        trigger_order.yield_token_amount,
+        trigger_order.price_per_token,
+    )?;
+    if marketplace.is_auction_mode() && marketplace.open_orders >= MAX_AUCTION_ORDERS as u64 {
+        evict_from_full_book(
+            ctx.program_id,
+            marketplace,
+            ctx.remaining_accounts,
+            trigger_order.order_type,
+            trigger_order.price_per_token,
+        )?;
+    }
+
+    let trigger_id_bytes = trigger_order.trigger_id.to_le_bytes();
+    let trigger_seeds = &[
+        b"trigger_order",
+        trigger_order.user.as_ref(),
+        trigger_id_bytes.as_ref(),
+        &[ctx.bumps.trigger_order],
+    ];
+    let signer_seeds = &[trigger_seeds.as_slice()];
+
+    // Move the escrowed funds to the live order's escrow and close the trigger escrow
+    let escrow_amount = ctx.accounts.trigger_escrow.amount;
+    let cpi_ctx = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        TransferChecked {
+            from: ctx.accounts.trigger_escrow.to_account_info(),
+            mint: ctx.accounts.escrow_mint.to_account_info(),
+            to: ctx.accounts.escrow_account.to_account_info(),
+            authority: trigger_order.to_account_info(),
+        },
+        signer_seeds,
+    );
+    transfer_checked(cpi_ctx, escrow_amount, ctx.accounts.escrow_mint.decimals)?;
+
+    let cpi_ctx = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        CloseAccount {
+            account: ctx.accounts.trigger_escrow.to_account_info(),
+            destination: ctx.accounts.user.to_account_info(),
+            authority: trigger_order.to_account_info(),
+        },
+        signer_seeds,
+    );
+    close_account(cpi_ctx)?;
+
+    // Initialize the live order
+    let order = &mut ctx.accounts.order;
+    order.user = trigger_order.user;
+    order.marketplace = marketplace.key();
+    order.order_type = trigger_order.order_type;
+    order.yield_token_amount = trigger_order.yield_token_amount;
+    order.price_per_token = trigger_order.price_per_token;
+    order.total_value = total_value;
+    order.filled_amount = 0;
+    order.is_active = true;
+    order.created_at = current_time;
+    order.order_id = trigger_order.order_id;
+
+    // Update marketplace best prices
+    if order.order_type == TradeOrder::BUY_ORDER {
+        if marketplace.best_bid_price == 0 || order.price_per_token > marketplace.best_bid_price {
+            marketplace.best_bid_price = order.price_per_token;
+        }
+    } else if marketplace.best_ask_price == 0 || order.price_per_token < marketplace.best_ask_price {
+        marketplace.best_ask_price = order.price_per_token;
+    }
+
+    marketplace.open_orders += 1;
+    ctx.accounts.order_counter.count += 1;
+
+    // Pay the keeper bounty, the rest of the trigger order lamports go back to the user on close
+    let bounty = trigger_order.keeper_bounty;
+    if bounty > 0 {
+        **ctx.accounts.trigger_order.to_account_info().try_borrow_mut_lamports()? -= bounty;
+        **ctx.accounts.keeper.to_account_info().try_borrow_mut_lamports()? += bounty;
+    }
+
+    msg!(
+        "Trigger order {} executed at price {} as order {} (bounty {} lamports)",
+        ctx.accounts.trigger_order.trigger_id,
+        observed_price as f64 / 1_000_000.0,
+        order.order_id,
+        bounty
+    );
+
+    Ok(())
+}
+
+/// Mid prices move with resting orders alone, so triggers only follow trades. TWAP
+/// windows have to fit in the marketplace's observation history.
+pub(crate) fn validate_price_source(price_source: u8, twap_window: i64) -> Result<()> {
+    match price_source {
+        Marketplace::PRICE_SOURCE_LAST_TRADE => Ok(()),
+        Marketplace::PRICE_SOURCE_TWAP => {
+            require!(
+                twap_window > 0 && twap_window <= Marketplace::MAX_TWAP_WINDOW,
+                TriggerError::InvalidTwapWindow
+            );
+            Ok(())
+        }
+        _ => err!(TriggerError::InvalidPriceSource),
+    }
+}
+
+#[error_code]
+pub enum TriggerError {
+    #[msg("Marketplace is not active")]
+    MarketplaceNotActive,
+    #[msg("Unauthorized user for this trigger order")]
+    UnauthorizedUser,
+    #[msg("Trigger order marketplace mismatch")]
+    OrderMarketplaceMismatch,
+    #[msg("Escrow mint does not match the order side")]
+    WrongEscrowMint,
+    #[msg("Wrong token account")]
+    WrongTokenAccount,
+    #[msg("Invalid order type")]
+    InvalidOrderType,
+    #[msg("Invalid trigger condition")]
+    InvalidTriggerCondition,
+    #[msg("Invalid trigger price")]
+    InvalidTriggerPrice,
+    #[msg("Trigger orders follow the last trade price or its TWAP")]
+    InvalidPriceSource,
+    #[msg("TWAP window must be between one second and 15 minutes")]
+    InvalidTwapWindow,
+    #[msg("Insufficient balance")]
+    InsufficientBalance,
+    #[msg("Marketplace price has not reached the trigger price")]
+    NotTriggered,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use anchor_spl::token::spl_token;
+
+    use crate::test_utils::{
+        account_info, balance, mint_account, program_account, program_account_at, program_info, rent_sysvar,
+        set_clock, signer_info, stub_syscalls, token_account, token_account_at, zeroed, ACCOUNT_LAMPORTS,
+    };
+
+    #[test]
+    fn last_trade_triggers_ignore_the_window() {
+        assert!(validate_price_source(Marketplace::PRICE_SOURCE_LAST_TRADE, 0).is_ok());
+    }
+
+    #[test]
+    fn twap_windows_must_fit_the_observation_history() {
+        let twap = Marketplace::PRICE_SOURCE_TWAP;
+        assert!(validate_price_source(twap, 1).is_ok());
+        assert!(validate_price_source(twap, Marketplace::MAX_TWAP_WINDOW).is_ok());
+        assert_eq!(
+            validate_price_source(twap, 0).unwrap_err(),
+            TriggerError::InvalidTwapWindow.into()
+        );
+        assert_eq!(
+            validate_price_source(twap, Marketplace::MAX_TWAP_WINDOW + 1).unwrap_err(),
+            TriggerError::InvalidTwapWindow.into()
+        );
+    }
+
+    #[test]
+    fn unknown_price_sources_are_rejected() {
+        assert_eq!(
+            validate_price_source(7, 60).unwrap_err(),
+            TriggerError::InvalidPriceSource.into()
+        );
+    }
+
+    const TRIGGER_ID: u64 = 5;
+    const ORDER_ID: u64 = 6;
+    const BOUNTY: u64 = 10_000_000;
+
+    fn yield_marketplace(yield_mint: Pubkey, last_trade_price: u64) -> Marketplace {
+        let mut marketplace: Marketplace = zeroed();
+        marketplace.is_active = true;
+        marketplace.tick_size = 1;
+        marketplace.yield_token_mint = yield_mint;
+        marketplace.last_trade_price = last_trade_price;
+        marketplace
+    }
+
+    /// Stop-loss selling `amount` at 0.75 once the last trade is at or below 0.9
+    fn stop_loss(user: Pubkey, marketplace: Pubkey, amount: u64) -> TriggerOrder {
+        let mut trigger_order: TriggerOrder = zeroed();
+        trigger_order.user = user;
+        trigger_order.marketplace = marketplace;
+        trigger_order.order_type = TradeOrder::SELL_ORDER;
+        trigger_order.yield_token_amount = amount;
+        trigger_order.price_per_token = 750_000;
+        trigger_order.trigger_price = 900_000;
+        trigger_order.trigger_condition = TriggerOrder::TRIGGER_AT_OR_BELOW;
+        trigger_order.price_source = Marketplace::PRICE_SOURCE_LAST_TRADE;
+        trigger_order.keeper_bounty = BOUNTY;
+        trigger_order.order_id = ORDER_ID;
+        trigger_order.trigger_id = TRIGGER_ID;
+        trigger_order
+    }
+
+    fn trigger_address(user: Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"trigger_order", user.as_ref(), TRIGGER_ID.to_le_bytes().as_ref()], &crate::ID)
+    }
+
+    fn escrow_address(seed: &[u8], owner: Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[seed, owner.as_ref()], &crate::ID).0
+    }
+
+    #[test]
+    fn placing_locks_the_funds_and_the_bounty() {
+        set_clock(100);
+        let user = Pubkey::new_unique();
+        let yield_mint = mint_account(Pubkey::new_unique(), 6);
+        let marketplace = program_account(&yield_marketplace(yield_mint.key(), 0));
+        let (trigger_order, _) = trigger_address(user);
+        let mut accounts = PlaceTriggerOrder {
+            user: Signer::try_from(signer_info(user)).unwrap(),
+            marketplace: Box::new(marketplace),
+            trigger_order: program_account_at(trigger_order, &zeroed::<TriggerOrder>()),
+            user_token_account: token_account(yield_mint.key(), user, 5_000_000),
+            trigger_escrow: token_account_at(
+                escrow_address(b"trigger_escrow", trigger_order),
+                yield_mint.key(),
+                trigger_order,
+                0,
+            ),
+            escrow_mint: yield_mint,
+            token_program: Interface::try_from(program_info(spl_token::ID)).unwrap(),
+            system_program: Program::try_from(program_info(System::id())).unwrap(),
+            rent: rent_sysvar(),
+        };
+
+        handle_place_trigger_order(
+            Context::new(&crate::ID, &mut accounts, &[], PlaceTriggerOrderBumps::default()),
+            TRIGGER_ID,
+            ORDER_ID,
+            TradeOrder::SELL_ORDER,
+            2_000_000,
+            750_000,
+            900_000,
+            TriggerOrder::TRIGGER_AT_OR_BELOW,
+            Marketplace::PRICE_SOURCE_LAST_TRADE,
+            0,
+            BOUNTY,
+        )
+        .unwrap();
+
+        assert_eq!(balance(&accounts.trigger_escrow), 2_000_000);
+        assert_eq!(balance(&accounts.user_token_account), 3_000_000);
+        assert_eq!(accounts.trigger_order.get_lamports(), ACCOUNT_LAMPORTS + BOUNTY);
+        assert_eq!(accounts.trigger_order.created_at, 100);
+    }
+
+    /// A placed 1 token stop-loss on a marketplace last traded at `last_trade_price`
+    fn execute_accounts(last_trade_price: u64) -> (ExecuteTriggerOrder<'static>, u8) {
+        set_clock(100);
+        let (user, keeper) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let yield_mint = mint_account(Pubkey::new_unique(), 6);
+        let marketplace = program_account(&yield_marketplace(yield_mint.key(), last_trade_price));
+        let (trigger_order, bump) = trigger_address(user);
+        let (order, _) =
+            Pubkey::find_program_address(&[b"order", user.as_ref(), ORDER_ID.to_le_bytes().as_ref()], &crate::ID);
+
+        let accounts = ExecuteTriggerOrder {
+            keeper: Signer::try_from(signer_info(keeper)).unwrap(),
+            user: UncheckedAccount::try_from(account_info(user, System::id(), Vec::new())),
+            trigger_order: Box::new(program_account_at(trigger_order, &stop_loss(user, marketplace.key(), 1_000_000))),
+            marketplace: Box::new(marketplace),
+            trigger_escrow: Box::new(token_account_at(
+                escrow_address(b"trigger_escrow", trigger_order),
+                yield_mint.key(),
+                trigger_order,
+                1_000_000,
+            )),
+            order: Box::new(program_account_at(order, &zeroed::<TradeOrder>())),
+            order_counter: Box::new(program_account(&zeroed::<OrderCounter>())),
+            escrow_account: Box::new(token_account_at(escrow_address(b"escrow", order), yield_mint.key(), order, 0)),
+            escrow_mint: Box::new(yield_mint),
+            token_program: Interface::try_from(program_info(spl_token::ID)).unwrap(),
+            system_program: Program::try_from(program_info(System::id())).unwrap(),
+            rent: rent_sysvar(),
+        };
+        (accounts, bump)
+    }
+
+    fn execute(accounts: &mut ExecuteTriggerOrder<'static>, trigger_bump: u8) -> Result<()> {
+        let bumps = ExecuteTriggerOrderBumps { trigger_order: trigger_bump, ..Default::default() };
+        handle_execute_trigger_order(Context::new(&crate::ID, accounts, &[], bumps))
+    }
+
+    #[test]
+    fn untriggered_orders_stay_in_escrow() {
+        // No trade yet, then a trade above the trigger price
+        for last_trade_price in [0, 1_000_000] {
+            let (mut accounts, bump) = execute_accounts(last_trade_price);
+
+            assert_eq!(execute(&mut accounts, bump).unwrap_err(), TriggerError::NotTriggered.into());
+            assert_eq!(balance(&accounts.trigger_escrow), 1_000_000);
+        }
+    }
+
+    #[test]
+    fn triggered_orders_go_live_and_pay_the_keeper() {
+        let (mut accounts, bump) = execute_accounts(800_000);
+
+        execute(&mut accounts, bump).unwrap();
+
+        let order = &accounts.order;
+        assert_eq!(order.order_type, TradeOrder::SELL_ORDER);
+        assert_eq!(order.yield_token_amount, 1_000_000);
+        assert_eq!(order.price_per_token, 750_000);
+        assert!(order.is_active);
+        assert_eq!(balance(&accounts.escrow_account), 1_000_000);
+        assert_eq!(accounts.marketplace.best_ask_price, 750_000);
+        assert_eq!(accounts.marketplace.open_orders, 1);
+
+        // The trigger escrow rent goes back to the user, the bounty to the keeper
+        assert_eq!(accounts.trigger_escrow.get_lamports(), 0);
+        assert_eq!(accounts.user.get_lamports(), 2 * ACCOUNT_LAMPORTS);
+        assert_eq!(accounts.keeper.get_lamports(), ACCOUNT_LAMPORTS + BOUNTY);
+        assert_eq!(accounts.trigger_order.get_lamports(), ACCOUNT_LAMPORTS - BOUNTY);
+    }
+
+    #[test]
+    fn cancelling_refunds_the_escrow() {
+        stub_syscalls();
+        let user = Pubkey::new_unique();
+        let yield_mint = mint_account(Pubkey::new_unique(), 6);
+        let (trigger_order, bump) = trigger_address(user);
+        let mut accounts = CancelTriggerOrder {
+            user: Signer::try_from(signer_info(user)).unwrap(),
+            trigger_order: program_account_at(trigger_order, &stop_loss(user, Pubkey::new_unique(), 1_000_000)),
+            trigger_escrow: token_account_at(
+                escrow_address(b"trigger_escrow", trigger_order),
+                yield_mint.key(),
+                trigger_order,
+                1_000_000,
+            ),
+            user_token_account: token_account(yield_mint.key(), user, 0),
+            escrow_mint: yield_mint,
+            token_program: Interface::try_from(program_info(spl_token::ID)).unwrap(),
+        };
+
+        let bumps = CancelTriggerOrderBumps { trigger_order: bump, ..Default::default() };
+        handle_cancel_trigger_order(Context::new(&crate::ID, &mut accounts, &[], bumps), TRIGGER_ID).unwrap();
+
+        assert_eq!(balance(&accounts.user_token_account), 1_000_000);
+        assert_eq!(accounts.trigger_escrow.get_lamports(), 0);
+        assert_eq!(accounts.user.get_lamports(), 2 * ACCOUNT_LAMPORTS);
+    }
+}
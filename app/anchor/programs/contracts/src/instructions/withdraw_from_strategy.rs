@@ -1,7 +1,9 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{TokenAccount, Token, Transfer, transfer};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked};
 
-use crate::state::{strategy::Strategy, user_position::UserPosition};
+use crate::adapters::{self, AdapterAccounts};
+use crate::instructions::rate_feed::accrue_strategy;
+use crate::state::{rate_feed::RateFeed, strategy::Strategy, user_position::UserPosition};
 
 #[derive(Accounts)]
 #[instruction(amount: u64, strategy_id: u64)]
@@ -13,7 +15,8 @@ pub struct WithdrawFromStrategy<'info> {
         mut,
         seeds = [b"strategy", strategy_id.to_le_bytes().as_ref()],
         bump,
-        constraint = strategy.is_operational() @ WithdrawError::StrategyNotActive
+        constraint = strategy.is_operational() @ WithdrawError::StrategyNotActive,
+        constraint = strategy.withdrawal_cooldown == 0 @ WithdrawError::CooldownRequired
     )]
     pub strategy: Account<'info, Strategy>,
 
@@ -31,17 +34,34 @@ pub struct WithdrawFromStrategy<'info> {
         seeds = [b"strategy_vault", strategy_id.to_le_bytes().as_ref()],
         bump
     )]
-    pub strategy_vault: Account<'info, TokenAccount>,
+    pub strategy_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// The underlying token mint
+    #[account(
+        address = strategy.underlying_token @ WithdrawError::WrongUnderlyingToken
+    )]
+    pub underlying_token_mint: InterfaceAccount<'info, Mint>,
 
     /// User's token account for receiving underlying tokens
     #[account(mut)]
-    pub user_underlying_token: Account<'info, TokenAccount>,
+    pub user_underlying_token: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    /// Rate feed of oracle-rate strategies, omitted for fixed-rate strategies
+    pub rate_feed: Option<Account<'info, RateFeed>>,
 
-    pub token_program: Program<'info, Token>,
+    /// Reserve receiving early-exit penalties, only needed while the position is locked
+    #[account(
+        mut,
+        seeds = [b"reward_reserve", strategy_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub reward_reserve: Option<InterfaceAccount<'info, TokenAccount>>,
 }
 
-pub fn handle_withdraw_from_strategy(
-    ctx: Context<WithdrawFromStrategy>,
+pub fn handle_withdraw_from_strategy<'info>(
+    ctx: Context<'_, '_, 'info, 'info, WithdrawFromStrategy<'info>>,
     amount: u64,
     strategy_id: u64,
 ) -> Result<()> {
@@ -49,28 +69,81 @@ pub fn handle_withdraw_from_strategy(
 
     let user_position = &mut ctx.accounts.user_position;
     let strategy = &mut ctx.accounts.strategy;
+    let current_time = Clock::get()?.unix_timestamp;
+    accrue_strategy(strategy, ctx.accounts.rate_feed.as_ref(), current_time)?;
 
-    // Check if user has sufficient balance
-    require!(user_position.deposited_amount >= amount, WithdrawError::InsufficientBalance);
+    let penalty = apply_withdraw(strategy, user_position, amount, current_time)?;
 
-    // Users can now withdraw anytime without maturity restrictions
-    // No penalties applied - full flexibility for users
-    
-    // Transfer tokens from strategy vault to user
     let strategy_id_bytes = strategy_id.to_le_bytes();
     let signer_seeds: &[&[u8]] = &[b"strategy", strategy_id_bytes.as_ref(), &[ctx.bumps.strategy]];
     let seeds: &[&[&[u8]]] = &[signer_seeds];
 
+    // Pull what the vault is missing back out of the yield source
+    let adapter_accounts = AdapterAccounts {
+        vault: ctx.accounts.strategy_vault.to_account_info(),
+        mint: ctx.accounts.underlying_token_mint.to_account_info(),
+        token_program: ctx.accounts.token_program.to_account_info(),
+        remaining_accounts: ctx.remaining_accounts,
+    };
+    adapters::withdraw_to_vault(
+        strategy,
+        &adapter_accounts,
+        signer_seeds,
+        ctx.accounts.strategy_vault.amount,
+        amount,
+    )?;
+
+    // Early exits leave their penalty in the reward reserve
+    transfer_penalty(
+        strategy.to_account_info(),
+        ctx.accounts.strategy_vault.to_account_info(),
+        ctx.accounts.reward_reserve.as_ref(),
+        &ctx.accounts.underlying_token_mint,
+        ctx.accounts.token_program.to_account_info(),
+        signer_seeds,
+        penalty,
+    )?;
+
+    // Transfer tokens from strategy vault to user
     let cpi_ctx = CpiContext::new_with_signer(
         ctx.accounts.token_program.to_account_info(),
-        Transfer {
+        TransferChecked {
             from: ctx.accounts.strategy_vault.to_account_info(),
+            mint: ctx.accounts.underlying_token_mint.to_account_info(),
             to: ctx.accounts.user_underlying_token.to_account_info(),
             authority: strategy.to_account_info(),
         },
         seeds,
     );
-    transfer(cpi_ctx, amount)?;
+    transfer_checked(cpi_ctx, amount - penalty, ctx.accounts.underlying_token_mint.decimals)?;
+
+    msg!(
+        "User {} withdrew {} tokens from strategy '{}' - {} early-exit penalty applied",
+        ctx.accounts.user.key(),
+        amount,
+        strategy.name,
+        penalty
+    );
+
+    Ok(())
+}
+
+/// Check and record a principal withdrawal on the position and strategy, returns
+/// the early-exit penalty withheld from it
+pub(crate) fn apply_withdraw(
+    strategy: &mut Account<Strategy>,
+    user_position: &mut Account<UserPosition>,
+    amount: u64,
+    current_time: i64,
+) -> Result<u64> {
+    // Check if user has sufficient balance
+    require!(user_position.deposited_amount >= amount, WithdrawError::InsufficientBalance);
+
+    // Yield earned on the principal being withdrawn stays claimable
+    user_position.settle_all_yield(strategy, current_time);
+
+    // Locked principal is either held back or charged the early-exit penalty
+    let penalty = early_exit_penalty(strategy, user_position, amount, current_time)?;
 
     // Update user position
     user_position.deposited_amount -= amount;
@@ -78,14 +151,55 @@ pub fn handle_withdraw_from_strategy(
     // Update strategy stats
     strategy.total_deposits -= amount;
 
-    msg!(
-        "User {} withdrew {} tokens from strategy '{}' - No penalties applied",
-        ctx.accounts.user.key(),
-        amount,
-        strategy.name
-    );
+    Ok(penalty)
+}
 
-    Ok(())
+/// Penalty on `principal` leaving a position, zero once the lock has ended
+pub(crate) fn early_exit_penalty(
+    strategy: &Strategy,
+    user_position: &UserPosition,
+    principal: u64,
+    current_time: i64,
+) -> Result<u64> {
+    if user_position.is_unlocked(current_time) {
+        return Ok(0);
+    }
+
+    if strategy.early_exit_penalty_bps == 0 {
+        msg!("Position is locked until {}", user_position.lock_until);
+        return err!(WithdrawError::PositionLocked);
+    }
+
+    Ok((principal as u128 * strategy.early_exit_penalty_bps as u128 / 10000) as u64)
+}
+
+/// Move an early-exit penalty from the strategy vault into the reward reserve
+pub(crate) fn transfer_penalty<'info>(
+    strategy: AccountInfo<'info>,
+    strategy_vault: AccountInfo<'info>,
+    reward_reserve: Option<&InterfaceAccount<'info, TokenAccount>>,
+    mint: &InterfaceAccount<'info, Mint>,
+    token_program: AccountInfo<'info>,
+    signer_seeds: &[&[u8]],
+    penalty: u64,
+) -> Result<()> {
+    if penalty == 0 {
+        return Ok(());
+    }
+
+    let reward_reserve = reward_reserve.ok_or(WithdrawError::MissingRewardReserve)?;
+    let seeds: &[&[&[u8]]] = &[signer_seeds];
+    let cpi_ctx = CpiContext::new_with_signer(
+        token_program,
+        TransferChecked {
+            from: strategy_vault,
+            mint: mint.to_account_info(),
+            to: reward_reserve.to_account_info(),
+            authority: strategy,
+        },
+        seeds,
+    );
+    transfer_checked(cpi_ctx, penalty, mint.decimals)
 }
 
 #[error_code]
@@ -98,4 +212,173 @@ pub enum WithdrawError {
     InsufficientBalance,
     #[msg("Invalid withdrawal amount")]
     InvalidAmount,
-} 
\ No newline at end of file
+    #[msg("Wrong underlying token for this strategy")]
+    WrongUnderlyingToken,
+    #[msg("Position is still locked")]
+    PositionLocked,
+    #[msg("Reward reserve is required to charge the early-exit penalty")]
+    MissingRewardReserve,
+    #[msg("Strategy has a withdrawal cooldown, use request_withdrawal")]
+    CooldownRequired,
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use anchor_spl::token::spl_token;
+
+    use crate::test_utils::{
+        balance, program_account, program_account_at, program_info, set_clock, signer_info, token_account,
+        token_account_at, zeroed, LendingStrategy,
+    };
+
+    const YEAR: i64 = 365 * 24 * 60 * 60;
+
+    fn locked_strategy(early_exit_penalty_bps: u16) -> Strategy {
+        let mut strategy: Strategy = zeroed();
+        strategy.apy = 1000;
+        strategy.lock_duration = 3_600;
+        strategy.early_exit_penalty_bps = early_exit_penalty_bps;
+        strategy.total_deposits = 1_000_000;
+        strategy
+    }
+
+    fn locked_position() -> UserPosition {
+        let mut position: UserPosition = zeroed();
+        position.deposited_amount = 1_000_000;
+        position.yield_tokens_minted = 1_000_000;
+        position.lock_until = 3_600;
+        position
+    }
+
+    #[test]
+    fn locked_principal_pays_the_penalty() {
+        let strategy = locked_strategy(500);
+
+        assert_eq!(early_exit_penalty(&strategy, &locked_position(), 400_000, 3_599).unwrap(), 20_000);
+        assert_eq!(early_exit_penalty(&strategy, &locked_position(), 400_000, 3_600).unwrap(), 0);
+    }
+
+    #[test]
+    fn locks_without_a_penalty_hold_the_principal() {
+        let strategy = locked_strategy(0);
+
+        assert_eq!(
+            early_exit_penalty(&strategy, &locked_position(), 1, 3_599).unwrap_err(),
+            WithdrawError::PositionLocked.into()
+        );
+        assert_eq!(early_exit_penalty(&strategy, &locked_position(), 1, 3_600).unwrap(), 0);
+    }
+
+    #[test]
+    fn early_withdrawal_reduces_the_principal_by_the_full_amount() {
+        let mut strategy = program_account(&locked_strategy(500));
+        let mut position = program_account(&locked_position());
+
+        let penalty = apply_withdraw(&mut strategy, &mut position, 400_000, 60).unwrap();
+
+        // The user receives 380_000, the penalty stays behind for the reserve
+        assert_eq!(penalty, 20_000);
+        assert_eq!(position.deposited_amount, 600_000);
+        assert_eq!(strategy.total_deposits, 600_000);
+    }
+
+    #[test]
+    fn withdrawn_principal_keeps_its_fixed_rate_yield() {
+        let mut strategy = locked_strategy(500);
+        strategy.lock_duration = 0;
+        let mut strategy = program_account(&strategy);
+        let mut position = locked_position();
+        position.lock_until = 0;
+        let mut position = program_account(&position);
+
+        apply_withdraw(&mut strategy, &mut position, 600_000, YEAR).unwrap();
+
+        // 10% on the full million for the year before the withdrawal
+        assert_eq!(position.pending_yield, 100_000);
+        assert_eq!(position.last_yield_claim, YEAR);
+        assert_eq!(position.claimable_yield(&strategy, YEAR), 100_000);
+        // Only the 400_000 left keeps accruing
+        assert_eq!(position.claimable_yield(&strategy, 2 * YEAR), 140_000);
+    }
+
+    #[test]
+    fn withdrawals_cannot_exceed_the_principal() {
+        let mut strategy = program_account(&locked_strategy(500));
+        let mut position = program_account(&locked_position());
+
+        assert_eq!(
+            apply_withdraw(&mut strategy, &mut position, 1_000_001, 3_600).unwrap_err(),
+            WithdrawError::InsufficientBalance.into()
+        );
+        assert_eq!(position.deposited_amount, 1_000_000);
+    }
+
+    /// Withdrawal of a user's locked 1 token position out of a vault holding it, the
+    /// penalty going to the reward reserve when `with_reserve` is set
+    fn withdraw_accounts(early_exit_penalty_bps: u16, with_reserve: bool) -> (WithdrawFromStrategy<'static>, u8) {
+        let lending = LendingStrategy::new(1_000_000, 0, 0);
+        let mut strategy = locked_strategy(early_exit_penalty_bps);
+        strategy.underlying_token = lending.mint.key();
+        let user = Pubkey::new_unique();
+        let mut position = locked_position();
+        position.user = user;
+        let (reserve_key, _) = Pubkey::find_program_address(
+            &[b"reward_reserve", LendingStrategy::STRATEGY_ID.to_le_bytes().as_ref()],
+            &crate::ID,
+        );
+
+        let accounts = WithdrawFromStrategy {
+            user: Signer::try_from(signer_info(user)).unwrap(),
+            strategy: program_account_at(lending.strategy.key(), &strategy),
+            user_position: program_account(&position),
+            strategy_vault: lending.vault.clone(),
+            user_underlying_token: token_account(lending.mint.key(), user, 0),
+            token_program: Interface::try_from(program_info(spl_token::ID)).unwrap(),
+            rate_feed: None,
+            reward_reserve: with_reserve
+                .then(|| token_account_at(reserve_key, lending.mint.key(), lending.strategy.key(), 0)),
+            underlying_token_mint: lending.mint,
+        };
+        (accounts, lending.bump)
+    }
+
+    fn withdraw(accounts: &mut WithdrawFromStrategy<'static>, strategy_bump: u8, amount: u64) -> Result<()> {
+        let bumps = WithdrawFromStrategyBumps { strategy: strategy_bump, ..Default::default() };
+        handle_withdraw_from_strategy(
+            Context::new(&crate::ID, accounts, &[], bumps),
+            amount,
+            LendingStrategy::STRATEGY_ID,
+        )
+    }
+
+    #[test]
+    fn early_withdrawals_leave_the_penalty_in_the_reserve() {
+        set_clock(100);
+        let (mut accounts, bump) = withdraw_accounts(500, false);
+        assert_eq!(
+            withdraw(&mut accounts, bump, 400_000).unwrap_err(),
+            WithdrawError::MissingRewardReserve.into()
+        );
+
+        let (mut accounts, bump) = withdraw_accounts(500, true);
+        withdraw(&mut accounts, bump, 400_000).unwrap();
+
+        assert_eq!(balance(&accounts.user_underlying_token), 380_000);
+        assert_eq!(balance(accounts.reward_reserve.as_ref().unwrap()), 20_000);
+        assert_eq!(balance(&accounts.strategy_vault), 600_000);
+        assert_eq!(accounts.user_position.deposited_amount, 600_000);
+    }
+
+    #[test]
+    fn unbreakable_locks_release_the_principal_when_they_end() {
+        set_clock(100);
+        let (mut accounts, bump) = withdraw_accounts(0, false);
+        assert_eq!(withdraw(&mut accounts, bump, 1).unwrap_err(), WithdrawError::PositionLocked.into());
+
+        set_clock(3_600);
+        withdraw(&mut accounts, bump, 1_000_000).unwrap();
+        assert_eq!(balance(&accounts.user_underlying_token), 1_000_000);
+        assert_eq!(balance(&accounts.strategy_vault), 0);
+    }
+}
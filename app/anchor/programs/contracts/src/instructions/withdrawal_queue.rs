@@ -0,0 +1,374 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked};
+
+use crate::adapters::{self, AdapterAccounts};
+use crate::instructions::rate_feed::accrue_strategy;
+use crate::instructions::withdraw_from_strategy::{apply_withdraw, transfer_penalty};
+use crate::state::{
+    rate_feed::RateFeed, strategy::Strategy, user_position::UserPosition,
+    withdrawal_ticket::WithdrawalTicket,
+};
+
+#[derive(Accounts)]
+#[instruction(amount: u64, strategy_id: u64, ticket_id: u64)]
+pub struct RequestWithdrawal<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"strategy", strategy_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = strategy.is_operational() @ WithdrawalQueueError::StrategyNotActive
+    )]
+    pub strategy: Account<'info, Strategy>,
+
+    #[account(
+        mut,
+        seeds = [b"user_position", user.key().as_ref(), strategy.key().as_ref()],
+        bump,
+        constraint = user_position.user == user.key() @ WithdrawalQueueError::UnauthorizedUser
+    )]
+    pub user_position: Account<'info, UserPosition>,
+
+    #[account(
+        init,
+        payer = user,
+        seeds = [b"withdrawal_ticket", user_position.key().as_ref(), ticket_id.to_le_bytes().as_ref()],
+        bump,
+        space = 8 + WithdrawalTicket::INIT_SPACE
+    )]
+    pub withdrawal_ticket: Account<'info, WithdrawalTicket>,
+
+    /// Rate feed of oracle-rate strategies, omitted for fixed-rate strategies
+    pub rate_feed: Option<Account<'info, RateFeed>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(strategy_id: u64)]
+pub struct CompleteWithdrawal<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"strategy", strategy_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub strategy: Account<'info, Strategy>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"withdrawal_ticket",
+            withdrawal_ticket.user_position.as_ref(),
+            withdrawal_ticket.ticket_id.to_le_bytes().as_ref()
+        ],
+        bump,
+        has_one = user @ WithdrawalQueueError::UnauthorizedUser,
+        has_one = strategy @ WithdrawalQueueError::WrongStrategy,
+        close = user
+    )]
+    pub withdrawal_ticket: Account<'info, WithdrawalTicket>,
+
+    /// Strategy's vault for underlying tokens
+    #[account(
+        mut,
+        seeds = [b"strategy_vault", strategy_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub strategy_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// The underlying token mint
+    #[account(
+        address = strategy.underlying_token @ WithdrawalQueueError::WrongUnderlyingToken
+    )]
+    pub underlying_token_mint: InterfaceAccount<'info, Mint>,
+
+    /// User's token account for receiving underlying tokens
+    #[account(
+        mut,
+        constraint = user_underlying_token.mint == underlying_token_mint.key() @ WithdrawalQueueError::WrongUnderlyingToken
+    )]
+    pub user_underlying_token: InterfaceAccount<'info, TokenAccount>,
+
+    /// Reserve receiving early-exit penalties, only needed when the ticket carries one
+    #[account(
+        mut,
+        seeds = [b"reward_reserve", strategy_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub reward_reserve: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Token program of the underlying token
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Take principal out of the position into a ticket redeemable after the strategy's
+/// cooldown. The amount stops accruing yield as soon as it is requested.
+pub fn handle_request_withdrawal(
+    ctx: Context<RequestWithdrawal>,
+    amount: u64,
+    _strategy_id: u64,
+    ticket_id: u64,
+) -> Result<()> {
+    require!(amount > 0, WithdrawalQueueError::InvalidAmount);
+
+    let strategy = &mut ctx.accounts.strategy;
+    require!(strategy.withdrawal_cooldown > 0, WithdrawalQueueError::NoCooldown);
+
+    let current_time = Clock::get()?.unix_timestamp;
+    accrue_strategy(strategy, ctx.accounts.rate_feed.as_ref(), current_time)?;
+
+    let penalty = apply_withdraw(strategy, &mut ctx.accounts.user_position, amount, current_time)?;
+    strategy.pending_withdrawals += amount;
+
+    let ticket = &mut ctx.accounts.withdrawal_ticket;
+    ticket.user = ctx.accounts.user.key();
+    ticket.strategy = strategy.key();
+    ticket.user_position = ctx.accounts.user_position.key();
+    ticket.amount = amount;
+    ticket.penalty = penalty;
+    ticket.requested_at = current_time;
+    ticket.unlock_at = current_time + strategy.withdrawal_cooldown;
+    ticket.ticket_id = ticket_id;
+
+    msg!(
+        "User {} requested withdrawal of {} tokens from strategy '{}', claimable at {}",
+        ctx.accounts.user.key(),
+        amount,
+        strategy.name,
+        ticket.unlock_at
+    );
+
+    Ok(())
+}
+
+pub fn handle_complete_withdrawal<'info>(
+    ctx: Context<'_, '_, 'info, 'info, CompleteWithdrawal<'info>>,
+    strategy_id: u64,
+) -> Result<()> {
+    let ticket = &ctx.accounts.withdrawal_ticket;
+    let current_time = Clock::get()?.unix_timestamp;
+    if !ticket.is_claimable(current_time) {
+        msg!("Withdrawal can be completed at {}", ticket.unlock_at);
+        return err!(WithdrawalQueueError::CooldownActive);
+    }
+
+    let amount = ticket.amount;
+    let penalty = ticket.penalty;
+    let strategy = &mut ctx.accounts.strategy;
+    strategy.pending_withdrawals -= amount;
+
+    let strategy_id_bytes = strategy_id.to_le_bytes();
+    let signer_seeds: &[&[u8]] = &[b"strategy", strategy_id_bytes.as_ref(), &[ctx.bumps.strategy]];
+    let seeds: &[&[&[u8]]] = &[signer_seeds];
+
+    // Pull what the vault is missing back out of the yield source, the ticket has
+    // left the queue so its own reserve counts towards the payout
+    let adapter_accounts = AdapterAccounts {
+        vault: ctx.accounts.strategy_vault.to_account_info(),
+        mint: ctx.accounts.underlying_token_mint.to_account_info(),
+        token_program: ctx.accounts.token_program.to_account_info(),
+        remaining_accounts: ctx.remaining_accounts,
+    };
+    adapters::withdraw_to_vault(
+        strategy,
+        &adapter_accounts,
+        signer_seeds,
+        ctx.accounts.strategy_vault.amount,
+        amount,
+    )?;
+
+    // Early exits leave their penalty in the reward reserve
+    transfer_penalty(
+        strategy.to_account_info(),
+        ctx.accounts.strategy_vault.to_account_info(),
+        ctx.accounts.reward_reserve.as_ref(),
+        &ctx.accounts.underlying_token_mint,
+        ctx.accounts.token_program.to_account_info(),
+        signer_seeds,
+        penalty,
+    )?;
+
+    // Transfer tokens from strategy vault to user
+    let cpi_ctx = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        TransferChecked {
+            from: ctx.accounts.strategy_vault.to_account_info(),
+            mint: ctx.accounts.underlying_token_mint.to_account_info(),
+            to: ctx.accounts.user_underlying_token.to_account_info(),
+            authority: strategy.to_account_info(),
+        },
+        seeds,
+    );
+    transfer_checked(cpi_ctx, amount - penalty, ctx.accounts.underlying_token_mint.decimals)?;
+
+    msg!(
+        "User {} completed withdrawal of {} tokens from strategy '{}' - {} early-exit penalty applied",
+        ctx.accounts.user.key(),
+        amount,
+        strategy.name,
+        penalty
+    );
+
+    Ok(())
+}
+
+#[error_code]
+pub enum WithdrawalQueueError {
+    #[msg("Strategy is not active")]
+    StrategyNotActive,
+    #[msg("Unauthorized user for this withdrawal")]
+    UnauthorizedUser,
+    #[msg("Withdrawal ticket belongs to another strategy")]
+    WrongStrategy,
+    #[msg("Wrong underlying token for this strategy")]
+    WrongUnderlyingToken,
+    #[msg("Invalid withdrawal amount")]
+    InvalidAmount,
+    #[msg("Strategy has no withdrawal cooldown, withdraw directly")]
+    NoCooldown,
+    #[msg("Withdrawal cooldown has not ended")]
+    CooldownActive,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use anchor_spl::token::spl_token;
+
+    use crate::instructions::withdraw_from_strategy::WithdrawError;
+    use crate::test_utils::{
+        balance, program_account, program_account_at, program_info, set_clock, signer_info, token_account, zeroed,
+        LendingStrategy,
+    };
+
+    const YEAR: i64 = 365 * 24 * 60 * 60;
+
+    fn cooldown_strategy() -> Strategy {
+        let mut strategy: Strategy = zeroed();
+        strategy.apy = 1000;
+        strategy.withdrawal_cooldown = 86_400;
+        strategy.total_deposits = 1_000_000;
+        strategy
+    }
+
+    fn position() -> UserPosition {
+        let mut position: UserPosition = zeroed();
+        position.deposited_amount = 1_000_000;
+        position.yield_tokens_minted = 1_000_000;
+        position
+    }
+
+    #[test]
+    fn requested_principal_stops_accruing() {
+        let mut strategy = program_account(&cooldown_strategy());
+        let mut position = program_account(&position());
+
+        // What a request does to the position before the ticket is written
+        let penalty = apply_withdraw(&mut strategy, &mut position, 600_000, 0).unwrap();
+        strategy.pending_withdrawals += 600_000;
+
+        assert_eq!(penalty, 0);
+        assert_eq!(position.deposited_amount, 400_000);
+        assert_eq!(strategy.total_deposits, 400_000);
+        assert_eq!(strategy.pending_withdrawals, 600_000);
+        // A year later only the 400_000 left in the position has earned its 10%
+        assert_eq!(position.claimable_yield(&strategy, YEAR), 40_000);
+    }
+
+    #[test]
+    fn requests_cannot_exceed_the_principal() {
+        let mut strategy = program_account(&cooldown_strategy());
+        let mut position = program_account(&position());
+
+        assert_eq!(
+            apply_withdraw(&mut strategy, &mut position, 1_000_001, 0).unwrap_err(),
+            WithdrawError::InsufficientBalance.into()
+        );
+    }
+
+    /// A user's 1 token position in a cooldown strategy whose vault holds it all, and
+    /// their request for 0.6 of it at t = 100
+    fn requested_withdrawal() -> (LendingStrategy, CompleteWithdrawal<'static>) {
+        set_clock(100);
+        let mut lending = LendingStrategy::new(1_000_000, 0, 0);
+        let mut strategy = cooldown_strategy();
+        strategy.underlying_token = lending.mint.key();
+        lending.strategy = program_account_at(lending.strategy.key(), &strategy);
+        let user = Pubkey::new_unique();
+        let mut user_position = position();
+        user_position.user = user;
+
+        let mut accounts = RequestWithdrawal {
+            user: Signer::try_from(signer_info(user)).unwrap(),
+            strategy: lending.strategy.clone(),
+            user_position: program_account(&user_position),
+            withdrawal_ticket: program_account(&zeroed::<WithdrawalTicket>()),
+            rate_feed: None,
+            system_program: Program::try_from(program_info(System::id())).unwrap(),
+        };
+        let ctx = Context::new(&crate::ID, &mut accounts, &[], RequestWithdrawalBumps::default());
+        handle_request_withdrawal(ctx, 600_000, LendingStrategy::STRATEGY_ID, 1).unwrap();
+
+        assert_eq!(accounts.user_position.deposited_amount, 400_000);
+        assert_eq!(
+            handle_request_withdrawal(
+                Context::new(&crate::ID, &mut accounts, &[], RequestWithdrawalBumps::default()),
+                400_001,
+                LendingStrategy::STRATEGY_ID,
+                2,
+            )
+            .unwrap_err(),
+            WithdrawError::InsufficientBalance.into()
+        );
+
+        let complete = CompleteWithdrawal {
+            user: accounts.user,
+            strategy: accounts.strategy,
+            withdrawal_ticket: accounts.withdrawal_ticket,
+            strategy_vault: lending.vault.clone(),
+            underlying_token_mint: lending.mint.clone(),
+            user_underlying_token: token_account(lending.mint.key(), user, 0),
+            reward_reserve: None,
+            token_program: Interface::try_from(program_info(spl_token::ID)).unwrap(),
+        };
+        (lending, complete)
+    }
+
+    fn complete(lending: &LendingStrategy, accounts: &mut CompleteWithdrawal<'static>) -> Result<()> {
+        let bumps = CompleteWithdrawalBumps { strategy: lending.bump, ..Default::default() };
+        handle_complete_withdrawal(Context::new(&crate::ID, accounts, &[], bumps), LendingStrategy::STRATEGY_ID)
+    }
+
+    #[test]
+    fn requests_open_a_ticket_for_the_cooldown() {
+        let (_, accounts) = requested_withdrawal();
+
+        let ticket = &accounts.withdrawal_ticket;
+        assert_eq!((ticket.amount, ticket.penalty), (600_000, 0));
+        assert_eq!((ticket.requested_at, ticket.unlock_at), (100, 100 + 86_400));
+        assert_eq!(ticket.ticket_id, 1);
+        assert_eq!(accounts.strategy.total_deposits, 400_000);
+        assert_eq!(accounts.strategy.pending_withdrawals, 600_000);
+    }
+
+    #[test]
+    fn tickets_pay_out_once_the_cooldown_ends() {
+        let (lending, mut accounts) = requested_withdrawal();
+
+        set_clock(100 + 86_399);
+        assert_eq!(complete(&lending, &mut accounts).unwrap_err(), WithdrawalQueueError::CooldownActive.into());
+        assert_eq!(balance(&accounts.user_underlying_token), 0);
+
+        set_clock(100 + 86_400);
+        complete(&lending, &mut accounts).unwrap();
+        assert_eq!(balance(&accounts.user_underlying_token), 600_000);
+        assert_eq!(balance(&accounts.strategy_vault), 400_000);
+        assert_eq!(accounts.strategy.pending_withdrawals, 0);
+    }
+}
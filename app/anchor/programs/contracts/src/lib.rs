@@ -7,8 +7,14 @@ declare_id!("9dEwdrEo7Tu9eTW3S3opbJa1fyyppRGPpdn8CqBxJX27");
 pub mod instructions;
 pub mod state;
 pub mod error;
+pub mod adapters;
+pub mod math;
+
+#[cfg(test)]
+mod test_utils;
 
 use instructions::*;
+use state::signed_order::SignedOrder;
 
 #[program]
 pub mod contracts {
@@ -19,50 +25,188 @@ pub mod contracts {
         instructions::handle_initialize_protocol(ctx)
     }
 
-    /// Create a new yield strategy (admin only)
+    /// Create a new yield strategy (admin only), its yield mint is created under the
+    /// passed token program so it can be SPL Token or Token-2022. Token-2022 yield
+    /// mints carry their own name, symbol and URI metadata
     pub fn create_strategy(
         ctx: Context<CreateStrategy>,
         name: String,
         apy_basis_points: u16,
         strategy_id: u64,
+        symbol: String,
+        uri: String,
+    ) -> Result<()> {
+        instructions::handle_create_strategy(ctx, name, apy_basis_points, strategy_id, symbol, uri)
+    }
+
+    /// Update the metadata URI of a strategy's Token-2022 yield mint (admin only)
+    pub fn update_yield_token_uri(ctx: Context<UpdateYieldTokenUri>, uri: String) -> Result<()> {
+        instructions::handle_update_yield_token_uri(ctx, uri)
+    }
+
+    /// Attach a yield source adapter to a strategy (admin only), deposits are then
+    /// deployed into the adapter's program and pulled back on withdrawal
+    pub fn set_strategy_adapter<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SetStrategyAdapter<'info>>,
+        adapter_kind: u8,
+        adapter_program: Pubkey,
+        adapter_targets: [Pubkey; 3],
+    ) -> Result<()> {
+        instructions::handle_set_strategy_adapter(ctx, adapter_kind, adapter_program, adapter_targets)
+    }
+
+    /// Make a strategy follow a rate feed instead of its fixed APY, or switch it back
+    /// when no feed is passed (admin only, only while nothing is deposited)
+    pub fn set_strategy_rate_feed(ctx: Context<SetStrategyRateFeed>) -> Result<()> {
+        instructions::handle_set_strategy_rate_feed(ctx)
+    }
+
+    /// Choose simple or continuously compounded interest for a strategy
+    /// (admin only, only while nothing is deposited)
+    pub fn set_accrual_mode(ctx: Context<UpdateStrategy>, accrual_mode: u8) -> Result<()> {
+        instructions::handle_set_accrual_mode(ctx, accrual_mode)
+    }
+
+    /// Set the strategy-wide and per-user deposit caps, 0 for uncapped (admin only)
+    pub fn set_deposit_caps(
+        ctx: Context<UpdateStrategy>,
+        max_total_deposits: u64,
+        max_deposit_per_user: u64,
     ) -> Result<()> {
-        instructions::handle_create_strategy(ctx, name, apy_basis_points, strategy_id)
+        instructions::handle_set_deposit_caps(ctx, max_total_deposits, max_deposit_per_user)
+    }
+
+    /// Set the lock duration of new deposits and the early-exit penalty paid into
+    /// the reward reserve, 0 penalty rejects early exits (admin only)
+    pub fn set_lock_terms(
+        ctx: Context<SetLockTerms>,
+        lock_duration: i64,
+        early_exit_penalty_bps: u16,
+    ) -> Result<()> {
+        instructions::handle_set_lock_terms(ctx, lock_duration, early_exit_penalty_bps)
+    }
+
+    /// Set the cooldown between requesting and completing a withdrawal, 0 for
+    /// instant withdrawals (admin only)
+    pub fn set_withdrawal_cooldown(ctx: Context<UpdateStrategy>, withdrawal_cooldown: i64) -> Result<()> {
+        instructions::handle_set_withdrawal_cooldown(ctx, withdrawal_cooldown)
+    }
+
+    /// Set the share of compounded yield paid to keepers (admin only)
+    pub fn set_keeper_fee(ctx: Context<UpdateStrategy>, keeper_fee_bps: u16) -> Result<()> {
+        instructions::handle_set_keeper_fee(ctx, keeper_fee_bps)
+    }
+
+    /// Move the yield earned by a strategy's yield source into its reward reserve
+    pub fn harvest_yield<'info>(
+        ctx: Context<'_, '_, 'info, 'info, HarvestYield<'info>>,
+        strategy_id: u64,
+    ) -> Result<u64> {
+        instructions::handle_harvest_yield(ctx, strategy_id)
     }
 
     /// Deposit tokens into a specific strategy
-    pub fn deposit_to_strategy(
-        ctx: Context<DepositToStrategy>,
+    pub fn deposit_to_strategy<'info>(
+        ctx: Context<'_, '_, 'info, 'info, DepositToStrategy<'info>>,
         amount: u64,
         strategy_id: u64,
     ) -> Result<()> {
         instructions::handle_deposit_to_strategy(ctx, amount, strategy_id)
     }
 
-    /// Claim accumulated yield from a strategy
+    /// Claim accumulated yield from a strategy to a recipient, as yield tokens or,
+    /// with `as_underlying`, in the underlying token from the reward reserve
     pub fn claim_yield(
         ctx: Context<ClaimYield>,
         strategy_id: u64,
+        as_underlying: bool,
+    ) -> Result<()> {
+        instructions::handle_claim_yield(ctx, strategy_id, as_underlying)
+    }
+
+    /// Claim yield from several strategies at once, positions are passed in remaining
+    /// accounts and the number processed is returned
+    pub fn claim_all<'info>(ctx: Context<'_, '_, 'info, 'info, ClaimAll<'info>>) -> Result<u32> {
+        instructions::handle_claim_all(ctx)
+    }
+
+    /// Add a position's accrued yield to its principal, callable by the owner or by a
+    /// keeper for positions that opted in, keepers earn the strategy's keeper fee
+    pub fn compound_yield(ctx: Context<CompoundYield>, strategy_id: u64) -> Result<u64> {
+        instructions::handle_compound_yield(ctx, strategy_id)
+    }
+
+    /// Let keepers compound the caller's position
+    pub fn set_auto_compound(
+        ctx: Context<SetAutoCompound>,
+        strategy_id: u64,
+        enabled: bool,
     ) -> Result<()> {
-        instructions::handle_claim_yield(ctx, strategy_id)
+        instructions::handle_set_auto_compound(ctx, strategy_id, enabled)
     }
 
     /// Withdraw principal from a strategy
-    pub fn withdraw_from_strategy(
-        ctx: Context<WithdrawFromStrategy>,
+    pub fn withdraw_from_strategy<'info>(
+        ctx: Context<'_, '_, 'info, 'info, WithdrawFromStrategy<'info>>,
         amount: u64,
         strategy_id: u64,
     ) -> Result<()> {
         instructions::handle_withdraw_from_strategy(ctx, amount, strategy_id)
     }
 
-    pub fn redeem_yield_tokens(
-        ctx: Context<RedeemYieldTokens>,
+    pub fn redeem_yield_tokens<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RedeemYieldTokens<'info>>,
         yield_token_amount: u64,
         strategy_id: u64,
     ) -> Result<()> {
         instructions::redeem_yield_tokens::handle_redeem_yield_tokens(ctx, yield_token_amount, strategy_id)
     }
 
+    /// Queue a withdrawal from a strategy with a cooldown, the amount stops earning yield
+    pub fn request_withdrawal(
+        ctx: Context<RequestWithdrawal>,
+        amount: u64,
+        strategy_id: u64,
+        ticket_id: u64,
+    ) -> Result<()> {
+        instructions::handle_request_withdrawal(ctx, amount, strategy_id, ticket_id)
+    }
+
+    /// Pay out a queued withdrawal once its cooldown has ended
+    pub fn complete_withdrawal<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CompleteWithdrawal<'info>>,
+        strategy_id: u64,
+    ) -> Result<()> {
+        instructions::handle_complete_withdrawal(ctx, strategy_id)
+    }
+
+    /// Deposit lamports into a native SOL strategy, wrapped into the vault
+    pub fn deposit_sol<'info>(
+        ctx: Context<'_, '_, 'info, 'info, DepositSol<'info>>,
+        amount: u64,
+        strategy_id: u64,
+    ) -> Result<()> {
+        instructions::handle_deposit_sol(ctx, amount, strategy_id)
+    }
+
+    /// Withdraw principal from a native SOL strategy, unwrapped to the user's wallet
+    pub fn withdraw_sol<'info>(
+        ctx: Context<'_, '_, 'info, 'info, WithdrawSol<'info>>,
+        amount: u64,
+        strategy_id: u64,
+    ) -> Result<()> {
+        instructions::handle_withdraw_sol(ctx, amount, strategy_id)
+    }
+
+    /// Redeem yield tokens of a native SOL strategy, unwrapped to the user's wallet
+    pub fn redeem_sol<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RedeemSol<'info>>,
+        yield_token_amount: u64,
+        strategy_id: u64,
+    ) -> Result<()> {
+        instructions::handle_redeem_sol(ctx, yield_token_amount, strategy_id)
+    }
+
     // === MARKETPLACE INSTRUCTIONS ===
 
     pub fn create_marketplace(
@@ -70,12 +214,21 @@ pub mod contracts {
         strategy_id: u64,
         marketplace_id: u64,
         trading_fee_bps: u16,
+        tick_size: u64,
+        min_order_size: u64,
     ) -> Result<()> {
-        instructions::handle_create_marketplace(ctx, strategy_id, marketplace_id, trading_fee_bps)
+        instructions::handle_create_marketplace(
+            ctx,
+            strategy_id,
+            marketplace_id,
+            trading_fee_bps,
+            tick_size,
+            min_order_size,
+        )
     }
 
-    pub fn place_order(
-        ctx: Context<PlaceOrder>,
+    pub fn place_order<'info>(
+        ctx: Context<'_, '_, 'info, 'info, PlaceOrder<'info>>,
         order_id: u64,
         order_type: u8,
         yield_token_amount: u64,
@@ -84,6 +237,7 @@ pub mod contracts {
         instructions::handle_place_order(ctx, order_id, order_type, yield_token_amount, price_per_token)
     }
 
+    /// Match a buy and a sell order and update the marketplace candle series
     pub fn execute_trade(
         ctx: Context<ExecuteTrade>,
         trade_amount: u64,
@@ -97,4 +251,246 @@ pub mod contracts {
     ) -> Result<()> {
         instructions::handle_cancel_order(ctx, order_id)
     }
+
+    /// Close a filled or cancelled order, sweeping its escrow and returning the rent
+    pub fn close_order(ctx: Context<CloseOrder>, order_id: u64) -> Result<()> {
+        instructions::handle_close_order(ctx, order_id)
+    }
+
+    /// Change the price and/or size of an active order in place
+    pub fn modify_order(
+        ctx: Context<ModifyOrder>,
+        order_id: u64,
+        new_price_per_token: u64,
+        new_yield_token_amount: u64,
+    ) -> Result<()> {
+        instructions::handle_modify_order(ctx, order_id, new_price_per_token, new_yield_token_amount)
+    }
+
+    /// Place a dormant order that a keeper converts once the marketplace price crosses a threshold
+    #[allow(clippy::too_many_arguments)]
+    pub fn place_trigger_order(
+        ctx: Context<PlaceTriggerOrder>,
+        trigger_id: u64,
+        order_id: u64,
+        order_type: u8,
+        yield_token_amount: u64,
+        price_per_token: u64,
+        trigger_price: u64,
+        trigger_condition: u8,
+        price_source: u8,
+        twap_window: i64,
+        keeper_bounty: u64,
+    ) -> Result<()> {
+        instructions::handle_place_trigger_order(
+            ctx,
+            trigger_id,
+            order_id,
+            order_type,
+            yield_token_amount,
+            price_per_token,
+            trigger_price,
+            trigger_condition,
+            price_source,
+            twap_window,
+            keeper_bounty,
+        )
+    }
+
+    /// Cancel a trigger order and refund its escrow and bounty
+    pub fn cancel_trigger_order(
+        ctx: Context<CancelTriggerOrder>,
+        trigger_id: u64,
+    ) -> Result<()> {
+        instructions::handle_cancel_trigger_order(ctx, trigger_id)
+    }
+
+    /// Convert a triggered order into a live order (permissionless, pays the keeper bounty)
+    pub fn execute_trigger_order<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ExecuteTriggerOrder<'info>>,
+    ) -> Result<()> {
+        instructions::handle_execute_trigger_order(ctx)
+    }
+
+    /// Authorise a session key to manage the user's orders
+    pub fn authorize_trading_delegate(
+        ctx: Context<AuthorizeTradingDelegate>,
+        delegate: Pubkey,
+        expires_at: i64,
+        notional_limit: u64,
+        min_sell_price: u64,
+    ) -> Result<()> {
+        instructions::handle_authorize_trading_delegate(ctx, delegate, expires_at, notional_limit, min_sell_price)
+    }
+
+    /// Revoke a session key
+    pub fn revoke_trading_delegate(ctx: Context<RevokeTradingDelegate>) -> Result<()> {
+        instructions::handle_revoke_trading_delegate(ctx)
+    }
+
+    /// Pause or resume trading on a marketplace (admin only)
+    pub fn set_marketplace_active(
+        ctx: Context<UpdateMarketplace>,
+        is_active: bool,
+    ) -> Result<()> {
+        instructions::handle_set_marketplace_active(ctx, is_active)
+    }
+
+    /// Change the marketplace trading fee (admin only)
+    pub fn update_trading_fee(
+        ctx: Context<UpdateMarketplace>,
+        trading_fee_bps: u16,
+    ) -> Result<()> {
+        instructions::handle_update_trading_fee(ctx, trading_fee_bps)
+    }
+
+    /// Hand marketplace administration to another key (admin only)
+    pub fn transfer_marketplace_admin(
+        ctx: Context<UpdateMarketplace>,
+        new_admin: Pubkey,
+    ) -> Result<()> {
+        instructions::handle_transfer_marketplace_admin(ctx, new_admin)
+    }
+
+    /// Change the marketplace tick size and minimum order size (admin only)
+    pub fn update_order_rules(
+        ctx: Context<UpdateMarketplace>,
+        tick_size: u64,
+        min_order_size: u64,
+    ) -> Result<()> {
+        instructions::handle_update_order_rules(ctx, tick_size, min_order_size)
+    }
+
+    /// Choose how execute_trade handles a user's crossing orders (admin only)
+    pub fn set_self_trade_behavior(
+        ctx: Context<UpdateMarketplace>,
+        self_trade_behavior: u8,
+    ) -> Result<()> {
+        instructions::handle_set_self_trade_behavior(ctx, self_trade_behavior)
+    }
+
+    /// Close a marketplace with no open orders (admin only)
+    pub fn close_marketplace(ctx: Context<CloseMarketplace>) -> Result<()> {
+        instructions::handle_close_marketplace(ctx)
+    }
+
+    /// Switch a marketplace between continuous matching and batch auctions (admin only)
+    pub fn set_auction_mode(
+        ctx: Context<SetAuctionMode>,
+        auction_interval: i64,
+    ) -> Result<()> {
+        instructions::handle_set_auction_mode(ctx, auction_interval)
+    }
+
+    /// Clear the ended auction epoch at one uniform price (permissionless)
+    pub fn clear_auction<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClearAuction<'info>>,
+    ) -> Result<()> {
+        instructions::handle_clear_auction(ctx)
+    }
+
+    /// Create the marketplace's 1m, 1h or 1d candle series; trading needs all three
+    pub fn init_candle_series(ctx: Context<InitCandleSeries>, interval: i64) -> Result<()> {
+        instructions::handle_init_candle_series(ctx, interval)
+    }
+
+    /// Implied APY in basis points of the marketplace price (0 = last trade, 1 = mid)
+    /// over the strategy's holding period, returned through return data for CPI
+    /// callers and simulation
+    pub fn get_implied_apy(ctx: Context<GetImpliedApy>, price_source: u8) -> Result<i64> {
+        instructions::handle_get_implied_apy(ctx, price_source)
+    }
+
+    // === RATE FEED INSTRUCTIONS ===
+
+    /// Create a rate feed pushed by an authorised updater
+    pub fn create_rate_feed(
+        ctx: Context<CreateRateFeed>,
+        feed_id: u64,
+        updater: Pubkey,
+        max_staleness: i64,
+        max_deviation_bps: u64,
+    ) -> Result<()> {
+        instructions::handle_create_rate_feed(ctx, feed_id, updater, max_staleness, max_deviation_bps)
+    }
+
+    /// Change a rate feed's updater, staleness window and deviation limit (feed authority only)
+    pub fn configure_rate_feed(
+        ctx: Context<ConfigureRateFeed>,
+        updater: Pubkey,
+        max_staleness: i64,
+        max_deviation_bps: u64,
+    ) -> Result<()> {
+        instructions::handle_configure_rate_feed(ctx, updater, max_staleness, max_deviation_bps)
+    }
+
+    /// Push a new rate (updater only)
+    pub fn update_rate_feed(ctx: Context<UpdateRateFeed>, rate_bps: u64) -> Result<()> {
+        instructions::handle_update_rate_feed(ctx, rate_bps)
+    }
+
+    /// Bring an oracle-rate strategy's reward index up to date (permissionless)
+    pub fn accrue_reward_index(ctx: Context<AccrueRewardIndex>, strategy_id: u64) -> Result<()> {
+        instructions::handle_accrue_reward_index(ctx, strategy_id)
+    }
+
+    // === PREVIEW INSTRUCTIONS ===
+    // Read-only; results are Borsh-encoded in return data for simulation and CPI
+
+    /// Yield tokens minted for a deposit of `amount`, and the caps it has to fit in
+    pub fn preview_deposit(ctx: Context<PreviewDeposit>, amount: u64) -> Result<DepositPreview> {
+        instructions::handle_preview_deposit(ctx, amount)
+    }
+
+    /// Yield a claim would mint right now
+    pub fn preview_claim(ctx: Context<PreviewPosition>) -> Result<ClaimPreview> {
+        instructions::handle_preview_claim(ctx)
+    }
+
+    /// Outcome of withdrawing `amount` of principal
+    pub fn preview_withdraw(ctx: Context<PreviewPosition>, amount: u64) -> Result<WithdrawPreview> {
+        instructions::handle_preview_withdraw(ctx, amount)
+    }
+
+    /// Principal and yield returned for redeeming `yield_token_amount`
+    pub fn preview_redeem(ctx: Context<PreviewPosition>, yield_token_amount: u64) -> Result<RedeemPreview> {
+        instructions::handle_preview_redeem(ctx, yield_token_amount)
+    }
+
+    /// Settlement of matching two orders for up to `trade_amount`
+    pub fn preview_trade(ctx: Context<PreviewTrade>, trade_amount: u64) -> Result<TradePreview> {
+        instructions::handle_preview_trade(ctx, trade_amount)
+    }
+
+    // === SIGNED ORDER INSTRUCTIONS ===
+
+    /// Create the nonce tracker for a maker's off-chain signed orders
+    pub fn init_maker_state(ctx: Context<InitMakerState>) -> Result<()> {
+        instructions::handle_init_maker_state(ctx)
+    }
+
+    /// Cancel a single signed order by nonce
+    pub fn cancel_signed_order(
+        ctx: Context<CancelSignedOrders>,
+        nonce: u64,
+    ) -> Result<()> {
+        instructions::handle_cancel_signed_order(ctx, nonce)
+    }
+
+    /// Cancel every signed order with a nonce below `nonce_floor`
+    pub fn cancel_signed_orders_below(
+        ctx: Context<CancelSignedOrders>,
+        nonce_floor: u64,
+    ) -> Result<()> {
+        instructions::handle_cancel_signed_orders_below(ctx, nonce_floor)
+    }
+
+    /// Settle a maker's ed25519-signed order against the taker and update the marketplace candle series
+    pub fn fill_signed_order(
+        ctx: Context<FillSignedOrder>,
+        order: SignedOrder,
+        fill_amount: u64,
+    ) -> Result<()> {
+        instructions::handle_fill_signed_order(ctx, order, fill_amount)
+    }
 }
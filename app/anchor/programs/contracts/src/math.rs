@@ -0,0 +1,180 @@
+//! Deterministic fixed-point maths shared by the yield accrual code.
+//!
+//! Values are unsigned fixed-point numbers with 12 decimals (`SCALE` = 1.0).
+//! `exp` reduces its argument by multiples of ln 2, evaluates the Taylor series
+//! of the remainder with 18 decimals of working precision and rounds the result
+//! down to 12 decimals. Over its whole domain (0 to `EXP_MAX_INPUT`, i.e. 20.0)
+//! the relative error stays below 1e-11, see the reference values in the tests.
+//! `ln` splits off powers of two and sums the atanh series of the rest, within a
+//! couple of units in the last decimal.
+
+/// Fixed-point one
+pub const SCALE: u128 = 1_000_000_000_000;
+
+/// Largest accepted exponent (20.0), e^20 still fits comfortably in a u128 at `SCALE`
+pub const EXP_MAX_INPUT: u128 = 20 * SCALE;
+
+/// Working precision of `exp`
+const PRECISE_SCALE: u128 = 1_000_000_000_000_000_000;
+const PRECISION_FACTOR: u128 = PRECISE_SCALE / SCALE;
+
+/// ln 2 at `PRECISE_SCALE`
+const LN_2: u128 = 693_147_180_559_945_309;
+
+/// rate * t at `SCALE` for an annual rate in basis points over `seconds`
+pub fn rate_exponent(rate_bps: u64, seconds: i64) -> u128 {
+    let seconds_per_year: u128 = 365 * 24 * 60 * 60;
+    rate_bps as u128 * seconds as u128 * SCALE / (10000 * seconds_per_year)
+}
+
+/// e^x for `x` at `SCALE`, `None` above `EXP_MAX_INPUT`
+pub fn exp(x: u128) -> Option<u128> {
+    if x > EXP_MAX_INPUT {
+        return None;
+    }
+
+    // e^x = 2^k * e^r with 0 <= r < ln 2
+    let x = x * PRECISION_FACTOR;
+    let k = x / LN_2;
+    let r = x - k * LN_2;
+
+    // Taylor series of e^r, the terms shrink by at least ln 2 / n each step
+    let mut sum = PRECISE_SCALE;
+    let mut term = PRECISE_SCALE;
+    let mut n = 1;
+    while term > 0 {
+        term = term * r / PRECISE_SCALE / n;
+        sum += term;
+        n += 1;
+    }
+
+    Some((sum << k) / PRECISION_FACTOR)
+}
+
+/// ln(x) for `x` at `SCALE`, `None` below 1.0
+pub fn ln(x: u128) -> Option<u128> {
+    if x < SCALE {
+        return None;
+    }
+
+    // x = 2^k * m with 1 <= m < 2
+    let k = (x / SCALE).ilog2();
+    let m = x.checked_mul(PRECISION_FACTOR)? >> k;
+
+    // ln m = 2 * atanh(z) with z = (m - 1) / (m + 1) <= 1/3
+    let z = (m - PRECISE_SCALE) * PRECISE_SCALE / (m + PRECISE_SCALE);
+    let z_squared = z * z / PRECISE_SCALE;
+    let mut sum = 0;
+    let mut power = z;
+    let mut n = 1;
+    while power > 0 {
+        sum += power / n;
+        power = power * z_squared / PRECISE_SCALE;
+        n += 2;
+    }
+
+    Some((k as u128 * LN_2 + 2 * sum) / PRECISION_FACTOR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Relative error of `actual` against `expected`, both at `SCALE`
+    fn relative_error(actual: u128, expected: u128) -> f64 {
+        (actual as f64 - expected as f64).abs() / expected as f64
+    }
+
+    #[test]
+    fn rate_exponent_of_a_full_year() {
+        assert_eq!(rate_exponent(10000, 365 * 24 * 60 * 60), SCALE);
+        assert_eq!(rate_exponent(500, 365 * 24 * 60 * 60), SCALE / 20);
+        assert_eq!(rate_exponent(500, 0), 0);
+    }
+
+    #[test]
+    fn exp_of_zero_is_one() {
+        assert_eq!(exp(0), Some(SCALE));
+    }
+
+    #[test]
+    fn exp_matches_reference_values() {
+        // (x, e^x) at 12 decimals, reference values rounded down
+        let cases: [(u128, u128); 8] = [
+            (1_000_000, 1_000_001_000_000),                 // 0.000001
+            (50_000_000_000, 1_051_271_096_376),            // 0.05
+            (100_000_000_000, 1_105_170_918_075),           // 0.1
+            (500_000_000_000, 1_648_721_270_700),           // 0.5
+            (693_147_180_560, 2_000_000_000_000),           // ln 2
+            (1_000_000_000_000, 2_718_281_828_459),         // 1
+            (5_000_000_000_000, 148_413_159_102_576),       // 5
+            (10_000_000_000_000, 22_026_465_794_806_716),   // 10
+        ];
+
+        for (x, expected) in cases {
+            let actual = exp(x).unwrap();
+            assert!(
+                relative_error(actual, expected) < 1e-11,
+                "exp({}) = {}, expected {}",
+                x,
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn exp_at_domain_limit() {
+        // e^20 = 485165195.40979027796910683...
+        let actual = exp(EXP_MAX_INPUT).unwrap();
+        assert!(relative_error(actual, 485_165_195_409_790_277_969) < 1e-11);
+        assert_eq!(exp(EXP_MAX_INPUT + 1), None);
+    }
+
+    #[test]
+    fn exp_is_monotonic() {
+        let mut previous = exp(0).unwrap();
+        for step in 1..=1000u128 {
+            let current = exp(step * 7_777_777_777).unwrap();
+            assert!(current >= previous);
+            previous = current;
+        }
+    }
+
+    #[test]
+    fn ln_matches_reference_values() {
+        // (x, ln x) at 12 decimals, reference values rounded down
+        let cases: [(u128, u128); 5] = [
+            (1_000_001_000_000, 999_999),                 // 1.000001
+            (1_050_000_000_000, 48_790_164_169),          // 1.05
+            (2_000_000_000_000, 693_147_180_559),         // 2
+            (2_718_281_828_459, 999_999_999_999),         // e
+            (1_000_000_000_000_000, 6_907_755_278_982),   // 1000
+        ];
+
+        for (x, expected) in cases {
+            let actual = ln(x).unwrap();
+            assert!(
+                actual.abs_diff(expected) <= 2,
+                "ln({}) = {}, expected {}",
+                x,
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn ln_of_one_is_zero() {
+        assert_eq!(ln(SCALE), Some(0));
+        assert_eq!(ln(SCALE - 1), None);
+    }
+
+    #[test]
+    fn ln_inverts_exp() {
+        for x in [1_000_000u128, 50_000_000_000, 3_000_000_000_000, EXP_MAX_INPUT] {
+            let round_trip = ln(exp(x).unwrap()).unwrap();
+            assert!(round_trip.abs_diff(x) <= 2, "ln(exp({})) = {}", x, round_trip);
+        }
+    }
+}
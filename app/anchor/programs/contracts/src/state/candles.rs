@@ -0,0 +1,166 @@
+use anchor_lang::prelude::*;
+
+/// One OHLCV bucket of a candle series
+#[zero_copy]
+#[derive(Default)]
+pub struct Candle {
+    /// Start of the bucket (unix timestamp, multiple of the series interval)
+    pub start_time: i64,
+
+    /// First trade price in the bucket
+    pub open: u64,
+
+    /// Highest trade price in the bucket
+    pub high: u64,
+
+    /// Lowest trade price in the bucket
+    pub low: u64,
+
+    /// Last trade price in the bucket
+    pub close: u64,
+
+    /// Yield tokens traded in the bucket
+    pub volume: u64,
+}
+
+#[account(zero_copy)]
+pub struct CandleSeries {
+    /// Marketplace the candles belong to
+    pub marketplace: Pubkey,
+
+    /// Bucket length in seconds
+    pub interval: i64,
+
+    /// Slot in `candles` holding the most recent bucket
+    pub head: u32,
+
+    /// Number of buckets written so far (capped at MAX_CANDLES)
+    pub count: u32,
+
+    /// Ring buffer of buckets, oldest overwritten first
+    pub candles: [Candle; CandleSeries::MAX_CANDLES],
+}
+
+impl CandleSeries {
+    pub const INIT_SPACE: usize = 8 + // discriminator
+        32 + // marketplace
+        8 + // interval
+        4 + // head
+        4 + // count
+        Self::CANDLE_SIZE * Self::MAX_CANDLES; // candles
+
+    const CANDLE_SIZE: usize = 8 + 8 * 5;
+
+    /// Number of buckets kept per series
+    pub const MAX_CANDLES: usize = 200;
+
+    /// One-minute buckets
+    pub const MINUTE: i64 = 60;
+    /// One-hour buckets
+    pub const HOUR: i64 = 3_600;
+    /// One-day buckets
+    pub const DAY: i64 = 86_400;
+
+    /// Supported bucket lengths, every marketplace keeps one series of each
+    pub const INTERVALS: [i64; 3] = [Self::MINUTE, Self::HOUR, Self::DAY];
+
+    /// Fold a trade into the bucket containing `timestamp`
+    pub fn record_trade(&mut self, price: u64, volume: u64, timestamp: i64) {
+        let start_time = timestamp - timestamp.rem_euclid(self.interval);
+
+        if self.count > 0 {
+            let candle = &mut self.candles[self.head as usize];
+            if candle.start_time == start_time {
+                candle.high = candle.high.max(price);
+                candle.low = candle.low.min(price);
+                candle.close = price;
+                candle.volume += volume;
+                return;
+            }
+            if candle.start_time > start_time {
+                return; // Never rewrite closed buckets
+            }
+            self.head = ((self.head as usize + 1) % Self::MAX_CANDLES) as u32;
+        }
+
+        // Buckets without trades are skipped, readers treat gaps as no activity
+        self.candles[self.head as usize] = Candle {
+            start_time,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume,
+        };
+        self.count = (self.count + 1).min(Self::MAX_CANDLES as u32);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series(interval: i64) -> Box<CandleSeries> {
+        let mut series: Box<CandleSeries> = Box::new(bytemuck::Zeroable::zeroed());
+        series.interval = interval;
+        series
+    }
+
+    fn head(series: &CandleSeries) -> Candle {
+        series.candles[series.head as usize]
+    }
+
+    #[test]
+    fn trades_in_one_bucket_fold_into_one_candle() {
+        let mut series = series(CandleSeries::MINUTE);
+        series.record_trade(1_000_000, 10, 6_005);
+        series.record_trade(1_300_000, 5, 6_020);
+        series.record_trade(900_000, 7, 6_040);
+        series.record_trade(1_100_000, 3, 6_059);
+
+        let candle = head(&series);
+        assert_eq!(series.count, 1);
+        assert_eq!(candle.start_time, 6_000);
+        assert_eq!(
+            (candle.open, candle.high, candle.low, candle.close, candle.volume),
+            (1_000_000, 1_300_000, 900_000, 1_100_000, 25)
+        );
+    }
+
+    #[test]
+    fn a_later_bucket_opens_a_new_candle_and_skips_gaps() {
+        let mut series = series(CandleSeries::MINUTE);
+        series.record_trade(1_000_000, 10, 6_030);
+        series.record_trade(1_200_000, 4, 6_330);
+
+        assert_eq!(series.count, 2);
+        assert_eq!(series.head, 1);
+        assert_eq!(series.candles[0].close, 1_000_000);
+        let candle = head(&series);
+        assert_eq!((candle.start_time, candle.open, candle.volume), (6_300, 1_200_000, 4));
+    }
+
+    #[test]
+    fn closed_buckets_are_never_rewritten() {
+        let mut series = series(CandleSeries::HOUR);
+        series.record_trade(1_000_000, 10, 7_200);
+        series.record_trade(2_000_000, 10, 7_100);
+
+        assert_eq!(series.count, 1);
+        let candle = head(&series);
+        assert_eq!((candle.start_time, candle.high, candle.volume), (7_200, 1_000_000, 10));
+    }
+
+    #[test]
+    fn the_oldest_bucket_is_overwritten_once_full() {
+        let mut series = series(CandleSeries::MINUTE);
+        for bucket in 0..=CandleSeries::MAX_CANDLES as i64 {
+            series.record_trade(1_000_000 + bucket as u64, 1, bucket * 60);
+        }
+
+        assert_eq!(series.count, CandleSeries::MAX_CANDLES as u32);
+        assert_eq!(series.head, 0);
+        assert_eq!(head(&series).start_time, CandleSeries::MAX_CANDLES as i64 * 60);
+        assert_eq!(series.candles[1].start_time, 60);
+    }
+}
@@ -11,8 +11,8 @@ pub struct Marketplace {
     /// Yield token being traded
     pub yield_token_mint: Pubkey,
     
-    /// Underlying token for pricing
-    pub underlying_token_mint: Pubkey,
+    /// Quote token used for pricing and settlement
+    pub quote_mint: Pubkey,
     
     /// Total trading volume
     pub total_volume: u64,
@@ -20,11 +20,11 @@ pub struct Marketplace {
     /// Total number of trades executed
     pub total_trades: u64,
     
-    /// Current best bid price (in underlying tokens per yield token)
+    /// Current best bid price (in quote tokens per yield token)
     /// Price format: fixed-point with 6 decimals (1000000 = 1.0)
     pub best_bid_price: u64,
     
-    /// Current best ask price (in underlying tokens per yield token)
+    /// Current best ask price (in quote tokens per yield token)
     pub best_ask_price: u64,
     
     /// Trading fee in basis points (100 = 1%)
@@ -38,6 +38,42 @@ pub struct Marketplace {
     
     /// Marketplace ID
     pub marketplace_id: u64,
+    
+    /// Number of orders that still hold escrowed funds
+    pub open_orders: u64,
+    
+    /// Length of a batch auction epoch in seconds (0 = continuous matching)
+    pub auction_interval: i64,
+    
+    /// Current batch auction epoch number
+    pub auction_epoch: u64,
+    
+    /// When the current batch auction epoch started
+    pub auction_epoch_start: i64,
+    
+    /// What execute_trade does when both orders belong to the same user
+    pub self_trade_behavior: u8,
+    
+    /// Order prices must be a multiple of this value
+    pub tick_size: u64,
+    
+    /// Minimum yield token amount per order
+    pub min_order_size: u64,
+    
+    /// Price of the most recent trade (0 = no trade yet)
+    pub last_trade_price: u64,
+    
+    /// When the most recent trade happened
+    pub last_trade_at: i64,
+    
+    /// Sum of last_trade_price * seconds it stood, since the first trade
+    pub cumulative_price: u128,
+    
+    /// Ring buffer of cumulative price snapshots for TWAP queries
+    pub observations: [PriceObservation; Marketplace::MAX_OBSERVATIONS],
+    
+    /// Slot in `observations` holding the most recent snapshot
+    pub observation_index: u8,
 }
 
 impl Marketplace {
@@ -45,7 +81,7 @@ impl Marketplace {
         32 + // admin
         32 + // strategy
         32 + // yield_token_mint
-        32 + // underlying_token_mint
+        32 + // quote_mint
         8 + // total_volume
         8 + // total_trades
         8 + // best_bid_price
@@ -53,7 +89,146 @@ impl Marketplace {
         2 + // trading_fee_bps
         1 + // is_active
         8 + // created_at
-        8; // marketplace_id
+        8 + // marketplace_id
+        8 + // open_orders
+        8 + // auction_interval
+        8 + // auction_epoch
+        8 + // auction_epoch_start
+        1 + // self_trade_behavior
+        8 + // tick_size
+        8 + // min_order_size
+        8 + // last_trade_price
+        8 + // last_trade_at
+        16 + // cumulative_price
+        PriceObservation::SIZE * Marketplace::MAX_OBSERVATIONS + // observations
+        1; // observation_index
+    
+    /// Maximum trading fee in basis points (10%)
+    pub const MAX_TRADING_FEE_BPS: u16 = 1000;
+    
+    /// Self-trade prevention: cancel the more recent order
+    pub const STP_CANCEL_NEWEST: u8 = 0;
+    /// Self-trade prevention: cancel the older order
+    pub const STP_CANCEL_OLDEST: u8 = 1;
+    /// Self-trade prevention: shrink both orders by the matched amount
+    pub const STP_DECREMENT_BOTH: u8 = 2;
+    
+    /// Price source: most recent trade price
+    pub const PRICE_SOURCE_LAST_TRADE: u8 = 0;
+    /// Price source: midpoint of best bid and best ask
+    pub const PRICE_SOURCE_MID: u8 = 1;
+    /// Price source: time-weighted average of trade prices
+    pub const PRICE_SOURCE_TWAP: u8 = 2;
+    
+    /// Number of price observations kept for TWAP queries
+    pub const MAX_OBSERVATIONS: usize = 16;
+    /// Minimum seconds between two stored observations
+    pub const OBSERVATION_PERIOD: i64 = 60;
+    /// Longest TWAP window the observations always reach back to: with a trade in
+    /// every period the oldest one kept is this old (15 minutes)
+    pub const MAX_TWAP_WINDOW: i64 = (Self::MAX_OBSERVATIONS as i64 - 1) * Self::OBSERVATION_PERIOD;
+    
+    /// Check if orders are matched in periodic batch auctions
+    pub fn is_auction_mode(&self) -> bool {
+        self.auction_interval > 0
+    }
+    
+    /// Transfers escrowed funds take to reach the counterparty: one directly, or two
+    /// through the auction vaults
+    pub fn settlement_hops(&self) -> u32 {
+        if self.is_auction_mode() { 2 } else { 1 }
+    }
+    
+    /// When the current batch auction epoch stops collecting orders
+    pub fn auction_epoch_end(&self) -> i64 {
+        self.auction_epoch_start + self.auction_interval
+    }
+    
+    /// Quote token value of `amount` yield tokens at `price`
+    pub fn trade_value(amount: u64, price: u64) -> u64 {
+        (amount as u128 * price as u128 / 1_000_000) as u64
+    }
+    
+    /// Trading fee charged on a payment
+    pub fn trading_fee(&self, payment: u64) -> u64 {
+        (payment as u128 * self.trading_fee_bps as u128 / 10000) as u64
+    }
+    
+    /// Record the price of an executed trade
+    pub fn record_trade(&mut self, price: u64, timestamp: i64) {
+        // The previous price counts for every second it stood, so a price
+        // pushed within one transaction carries no weight
+        self.cumulative_price = self.cumulative_price_at(timestamp);
+        self.last_trade_price = price;
+        self.last_trade_at = timestamp;
+        
+        let latest = self.observations[self.observation_index as usize];
+        if latest.timestamp == 0 {
+            self.observations[self.observation_index as usize] = PriceObservation {
+                timestamp,
+                cumulative_price: self.cumulative_price,
+            };
+        } else if timestamp - latest.timestamp >= Self::OBSERVATION_PERIOD {
+            self.observation_index = ((self.observation_index as usize + 1) % Self::MAX_OBSERVATIONS) as u8;
+            self.observations[self.observation_index as usize] = PriceObservation {
+                timestamp,
+                cumulative_price: self.cumulative_price,
+            };
+        }
+    }
+    
+    /// Cumulative price extrapolated to `timestamp` with the last trade price
+    pub fn cumulative_price_at(&self, timestamp: i64) -> u128 {
+        if self.last_trade_at == 0 || timestamp <= self.last_trade_at {
+            return self.cumulative_price;
+        }
+        let elapsed = (timestamp - self.last_trade_at) as u128;
+        self.cumulative_price + self.last_trade_price as u128 * elapsed
+    }
+    
+    /// Time-weighted average price over at least the last `window` seconds
+    ///
+    /// Uses the newest observation at or before `now - window`, so the
+    /// effective window can be longer than requested. Returns None when the
+    /// history doesn't reach back far enough, which windows up to
+    /// `MAX_TWAP_WINDOW` only hit before the market has traded that long.
+    pub fn twap(&self, now: i64, window: i64) -> Option<u64> {
+        if window <= 0 {
+            return None;
+        }
+        let target = now.checked_sub(window)?;
+        
+        let mut start: Option<PriceObservation> = None;
+        for observation in self.observations.iter() {
+            if observation.timestamp == 0 || observation.timestamp > target {
+                continue;
+            }
+            match start {
+                Some(s) if s.timestamp >= observation.timestamp => {}
+                _ => start = Some(*observation),
+            }
+        }
+        let start = start?;
+        
+        let elapsed = (now - start.timestamp) as u128;
+        let price = (self.cumulative_price_at(now) - start.cumulative_price) / elapsed;
+        u64::try_from(price).ok()
+    }
+}
+
+/// Snapshot of the marketplace cumulative price
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct PriceObservation {
+    /// When the snapshot was taken (0 = empty slot)
+    pub timestamp: i64,
+    
+    /// Marketplace cumulative price at that time
+    pub cumulative_price: u128,
+}
+
+impl PriceObservation {
+    pub const SIZE: usize = 8 + // timestamp
+        16; // cumulative_price
 }
 
 #[account]
@@ -70,7 +245,7 @@ pub struct TradeOrder {
     /// Amount of yield tokens
     pub yield_token_amount: u64,
     
-    /// Price per yield token (in underlying tokens)
+    /// Price per yield token (in quote tokens)
     /// Fixed-point with 6 decimals
     pub price_per_token: u64,
     
@@ -140,4 +315,71 @@ pub struct OrderCounter {
 
 impl OrderCounter {
     pub const INIT_SPACE: usize = 8 + 8; // discriminator + count
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_utils::zeroed;
+
+    const PERIOD: i64 = Marketplace::OBSERVATION_PERIOD;
+
+    /// One trade per observation period at prices 1.0, 2.0, ... from `PERIOD` on
+    fn traded_every_period(trades: i64) -> Marketplace {
+        let mut marketplace: Marketplace = zeroed();
+        for k in 1..=trades {
+            marketplace.record_trade(k as u64 * 1_000_000, k * PERIOD);
+        }
+        marketplace
+    }
+
+    #[test]
+    fn observations_are_kept_once_per_period() {
+        let mut marketplace: Marketplace = zeroed();
+        marketplace.record_trade(1_000_000, 1_000);
+        marketplace.record_trade(3_000_000, 1_030);
+        marketplace.record_trade(2_000_000, 1_060);
+
+        assert_eq!(marketplace.observation_index, 1);
+        assert_eq!(marketplace.observations[0].timestamp, 1_000);
+        assert_eq!(marketplace.observations[1].timestamp, 1_060);
+        // 1.0 for 30 seconds then 3.0 for 30 seconds
+        assert_eq!(marketplace.observations[1].cumulative_price, 120_000_000);
+        assert_eq!(marketplace.last_trade_price, 2_000_000);
+    }
+
+    #[test]
+    fn ring_wraps_over_the_oldest_observations() {
+        let marketplace = traded_every_period(20);
+
+        assert_eq!(marketplace.observation_index as i64, (20 - 1) % Marketplace::MAX_OBSERVATIONS as i64);
+        let oldest = marketplace.observations.iter().map(|o| o.timestamp).min().unwrap();
+        assert_eq!(oldest, 5 * PERIOD);
+
+        // The longest window still served averages prices 5.0 to 19.0
+        let now = 20 * PERIOD;
+        assert_eq!(marketplace.twap(now, Marketplace::MAX_TWAP_WINDOW), Some(12_000_000));
+        // A second more reaches before the overwritten observations
+        assert_eq!(marketplace.twap(now, Marketplace::MAX_TWAP_WINDOW + 1), None);
+    }
+
+    #[test]
+    fn windows_longer_than_the_history_have_no_twap() {
+        let marketplace = traded_every_period(3);
+        let now = 3 * PERIOD + 30;
+
+        assert_eq!(marketplace.twap(now, now - PERIOD), Some(1_800_000));
+        assert_eq!(marketplace.twap(now, now - PERIOD + 1), None);
+        assert_eq!(marketplace.twap(now, 0), None);
+    }
+
+    #[test]
+    fn twap_extends_the_last_price_to_now() {
+        let marketplace = traded_every_period(1);
+
+        // No trade since, 1.0 has stood for the whole window
+        assert_eq!(marketplace.twap(10 * PERIOD, 9 * PERIOD), Some(1_000_000));
+        assert_eq!(marketplace.cumulative_price_at(10 * PERIOD), 9 * PERIOD as u128 * 1_000_000);
+    }
+}
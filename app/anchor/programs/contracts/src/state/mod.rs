@@ -1,3 +1,9 @@
 pub mod user_position;
 pub mod strategy;
 pub mod marketplace;
+pub mod signed_order;
+pub mod trading_delegate;
+pub mod trigger_order;
+pub mod candles;
+pub mod rate_feed;
+pub mod withdrawal_ticket;
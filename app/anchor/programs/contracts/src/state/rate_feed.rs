@@ -0,0 +1,202 @@
+use anchor_lang::prelude::*;
+
+use crate::math;
+
+#[account]
+pub struct RateFeed {
+    /// Who created the feed and manages its updater and limits
+    pub authority: Pubkey,
+
+    /// Key allowed to push new rates
+    pub updater: Pubkey,
+
+    /// Feed ID, unique per authority
+    pub feed_id: u64,
+
+    /// Latest annual rate in basis points (1000 = 10.00%)
+    pub rate_bps: u64,
+
+    /// When the rate was last pushed
+    pub last_update: i64,
+
+    /// Seconds after `last_update` during which the rate is considered fresh
+    pub max_staleness: i64,
+
+    /// Maximum relative change of the rate per update, in basis points of the current rate
+    pub max_deviation_bps: u64,
+
+    /// Sum of rate * fresh seconds up to `last_update`, at math::SCALE (1.0 = 100% for a year).
+    /// Strategies index against it, so a rate counts exactly for the time it stood.
+    pub cumulative_rate: u128,
+}
+
+impl RateFeed {
+    pub const INIT_SPACE: usize = 8 + // discriminator
+        32 + // authority
+        32 + // updater
+        8 + // feed_id
+        8 + // rate_bps
+        8 + // last_update
+        8 + // max_staleness
+        8 + // max_deviation_bps
+        16; // cumulative_rate
+
+    /// Highest rate a feed may report (500%)
+    pub const MAX_RATE_BPS: u64 = 50000;
+
+    /// Whether the rate can still be relied on at `now`
+    pub fn is_fresh(&self, now: i64) -> bool {
+        now - self.last_update <= self.max_staleness
+    }
+
+    /// Last moment the current rate may be accrued at
+    pub fn fresh_until(&self) -> i64 {
+        self.last_update.saturating_add(self.max_staleness)
+    }
+
+    /// Cumulative rate extrapolated to `now` with the current rate
+    pub fn cumulative_rate_at(&self, now: i64) -> u128 {
+        let elapsed = now.min(self.fresh_until()) - self.last_update;
+        if self.last_update == 0 || elapsed <= 0 {
+            return self.cumulative_rate;
+        }
+        self.cumulative_rate + math::rate_exponent(self.rate_bps, elapsed)
+    }
+
+    /// Push a new rate, folding the outgoing one into the accumulator first
+    pub fn update(&mut self, rate_bps: u64, now: i64) {
+        self.cumulative_rate = self.cumulative_rate_at(now);
+        self.rate_bps = rate_bps;
+        self.last_update = now;
+    }
+
+    /// Whether moving to `new_rate_bps` stays within the allowed deviation
+    pub fn within_deviation(&self, new_rate_bps: u64) -> bool {
+        // The first push and pushes onto a zero rate have nothing to deviate from
+        if self.last_update == 0 || self.rate_bps == 0 {
+            return true;
+        }
+
+        let change = self.rate_bps.abs_diff(new_rate_bps) as u128;
+        change * 10000 <= self.rate_bps as u128 * self.max_deviation_bps as u128
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::state::strategy::Strategy;
+    use crate::test_utils::zeroed;
+
+    const DAY: i64 = 24 * 60 * 60;
+
+    fn feed(max_staleness: i64) -> RateFeed {
+        let mut feed: RateFeed = zeroed();
+        feed.feed_id = 1;
+        feed.max_staleness = max_staleness;
+        feed.max_deviation_bps = 10000;
+        feed
+    }
+
+    fn oracle_strategy(accrual_mode: u8, cumulative_rate_snapshot: u128, now: i64) -> Strategy {
+        let mut strategy: Strategy = zeroed();
+        strategy.is_active = true;
+        strategy.strategy_id = 1;
+        strategy.rate_mode = Strategy::RATE_MODE_ORACLE;
+        strategy.last_index_update = now;
+        strategy.cumulative_rate_snapshot = cumulative_rate_snapshot;
+        strategy.accrual_mode = accrual_mode;
+        strategy
+    }
+
+    #[test]
+    fn accumulator_counts_each_rate_for_the_time_it_stood() {
+        let mut feed = feed(10 * DAY);
+        feed.update(1000, DAY);
+        feed.update(2000, 3 * DAY);
+
+        let expected = math::rate_exponent(1000, 2 * DAY) + math::rate_exponent(2000, DAY);
+        assert_eq!(feed.cumulative_rate_at(4 * DAY), expected);
+    }
+
+    #[test]
+    fn accumulator_skips_stale_time() {
+        let mut feed = feed(DAY);
+        feed.update(1000, DAY);
+        // The rate goes stale after one day and nothing accrues until the next push
+        assert_eq!(feed.cumulative_rate_at(10 * DAY), math::rate_exponent(1000, DAY));
+
+        feed.update(1000, 10 * DAY);
+        assert_eq!(feed.cumulative_rate_at(11 * DAY), 2 * math::rate_exponent(1000, DAY));
+    }
+
+    #[test]
+    fn strategies_accrue_the_same_whenever_they_catch_up() {
+        for accrual_mode in [Strategy::ACCRUAL_SIMPLE, Strategy::ACCRUAL_COMPOUND] {
+            let mut feed = feed(10 * DAY);
+            feed.update(1000, DAY);
+
+            let mut eager = oracle_strategy(accrual_mode, feed.cumulative_rate_at(DAY), DAY);
+            let mut lazy = oracle_strategy(accrual_mode, feed.cumulative_rate_at(DAY), DAY);
+
+            // Only `eager` is brought up to date when the rate moves
+            eager.accrue_reward_index(&feed, 5 * DAY);
+            feed.update(3000, 5 * DAY);
+
+            eager.accrue_reward_index(&feed, 6 * DAY);
+            lazy.accrue_reward_index(&feed, 6 * DAY);
+
+            let expected = math::rate_exponent(1000, 4 * DAY) + math::rate_exponent(3000, DAY);
+            if accrual_mode == Strategy::ACCRUAL_SIMPLE {
+                assert_eq!(lazy.reward_index, expected);
+                assert_eq!(eager.reward_index, expected);
+            } else {
+                // Compounding in one or two steps differs only by rounding
+                assert_eq!(lazy.reward_index, math::exp(expected).unwrap() - math::SCALE);
+                assert!(eager.reward_index.abs_diff(lazy.reward_index) <= 2);
+            }
+        }
+    }
+
+    #[test]
+    fn strategy_joining_late_only_accrues_from_its_snapshot() {
+        let mut feed = feed(10 * DAY);
+        feed.update(1000, DAY);
+
+        let mut strategy = oracle_strategy(Strategy::ACCRUAL_SIMPLE, feed.cumulative_rate_at(3 * DAY), 3 * DAY);
+        strategy.accrue_reward_index(&feed, 4 * DAY);
+
+        let expected = math::rate_exponent(1000, 3 * DAY) - math::rate_exponent(1000, 2 * DAY);
+        assert_eq!(strategy.reward_index, expected);
+        assert_eq!(strategy.last_index_update, 4 * DAY);
+    }
+
+    #[test]
+    fn deviation_is_relative_to_the_current_rate() {
+        let mut feed = feed(DAY);
+        feed.max_deviation_bps = 2000;
+        // Nothing to deviate from before the first push
+        assert!(feed.within_deviation(RateFeed::MAX_RATE_BPS));
+
+        feed.update(1000, DAY);
+        assert!(feed.within_deviation(1200));
+        assert!(feed.within_deviation(800));
+        assert!(!feed.within_deviation(1201));
+        assert!(!feed.within_deviation(799));
+
+        // A zero rate can move anywhere
+        feed.update(0, 2 * DAY);
+        assert!(feed.within_deviation(5000));
+    }
+
+    #[test]
+    fn rate_is_fresh_for_the_staleness_window() {
+        let mut feed = feed(DAY);
+        feed.update(1000, DAY);
+
+        assert!(feed.is_fresh(2 * DAY));
+        assert!(!feed.is_fresh(2 * DAY + 1));
+        assert_eq!(feed.fresh_until(), 2 * DAY);
+    }
+}
@@ -0,0 +1,240 @@
+use anchor_lang::prelude::*;
+
+/// Order signed off-chain by a maker and settled by `fill_signed_order`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct SignedOrder {
+    /// Marketplace the order is valid on
+    pub marketplace: Pubkey,
+
+    /// Maker who signed the order
+    pub maker: Pubkey,
+
+    /// Order type: 0 = Buy, 1 = Sell (from the maker's side)
+    pub order_type: u8,
+
+    /// Maximum amount of yield tokens to trade
+    pub yield_token_amount: u64,
+
+    /// Price per yield token (in quote tokens)
+    /// Fixed-point with 6 decimals
+    pub price_per_token: u64,
+
+    /// Maker nonce, each nonce can be filled once
+    pub nonce: u64,
+
+    /// Unix timestamp after which the order can no longer be filled
+    pub expires_at: i64,
+}
+
+impl SignedOrder {
+    /// Domain prefix so a maker signature can't be replayed as another message
+    pub const DOMAIN: &'static [u8] = b"yieldos:signed_order:v1";
+
+    /// Bytes the maker signs: domain prefix followed by the Borsh-encoded order
+    pub fn message(&self) -> Result<Vec<u8>> {
+        let mut message = Self::DOMAIN.to_vec();
+        self.serialize(&mut message)?;
+        Ok(message)
+    }
+}
+
+#[account]
+pub struct MakerState {
+    /// Maker this state belongs to
+    pub maker: Pubkey,
+
+    /// Every nonce below this value is used or cancelled
+    pub nonce_floor: u64,
+
+    /// Bitmap of used nonces in [nonce_floor, nonce_floor + NONCE_WINDOW)
+    pub used_nonces: [u64; 4],
+
+    /// Number of signed orders filled for this maker
+    pub total_fills: u64,
+
+    /// PDA bump, the maker state signs as SPL delegate of the maker's token accounts
+    pub bump: u8,
+}
+
+impl MakerState {
+    pub const INIT_SPACE: usize = 8 + // discriminator
+        32 + // maker
+        8 + // nonce_floor
+        8 * 4 + // used_nonces
+        8 + // total_fills
+        1; // bump
+
+    /// Number of nonces tracked above the floor
+    pub const NONCE_WINDOW: u64 = 256;
+
+    /// Check if a nonce can still be filled
+    pub fn is_nonce_usable(&self, nonce: u64) -> bool {
+        if nonce < self.nonce_floor || nonce - self.nonce_floor >= Self::NONCE_WINDOW {
+            return false;
+        }
+        let bit = nonce - self.nonce_floor;
+        self.used_nonces[(bit / 64) as usize] & (1 << (bit % 64)) == 0
+    }
+
+    /// Mark a nonce as used, returns false if it was not usable
+    pub fn use_nonce(&mut self, nonce: u64) -> bool {
+        if !self.is_nonce_usable(nonce) {
+            return false;
+        }
+        let bit = nonce - self.nonce_floor;
+        self.used_nonces[(bit / 64) as usize] |= 1 << (bit % 64);
+
+        // Slide the window past the run of used nonces at the floor
+        let run = self.used_nonces[0].trailing_ones() as u64;
+        if run > 0 {
+            self.raise_floor(self.nonce_floor + run);
+        }
+        true
+    }
+
+    /// Invalidate every nonce below `new_floor`
+    pub fn raise_floor(&mut self, new_floor: u64) {
+        if new_floor <= self.nonce_floor {
+            return;
+        }
+        let shift = new_floor - self.nonce_floor;
+        self.nonce_floor = new_floor;
+
+        if shift >= Self::NONCE_WINDOW {
+            self.used_nonces = [0; 4];
+            return;
+        }
+
+        let word_shift = (shift / 64) as usize;
+        let bit_shift = (shift % 64) as u32;
+        let old = self.used_nonces;
+        let mut shifted = [0u64; 4];
+        for (i, word) in shifted.iter_mut().enumerate() {
+            let src = i + word_shift;
+            if src >= old.len() {
+                break;
+            }
+            *word = old[src] >> bit_shift;
+            if bit_shift > 0 && src + 1 < old.len() {
+                *word |= old[src + 1] << (64 - bit_shift);
+            }
+        }
+        self.used_nonces = shifted;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn maker_state() -> MakerState {
+        MakerState {
+            maker: Pubkey::new_unique(),
+            nonce_floor: 0,
+            used_nonces: [0; 4],
+            total_fills: 0,
+            bump: 255,
+        }
+    }
+
+    #[test]
+    fn nonce_cannot_be_replayed() {
+        let mut state = maker_state();
+        assert!(state.use_nonce(5));
+        assert!(!state.is_nonce_usable(5));
+        assert!(!state.use_nonce(5));
+        // Neighbours are unaffected
+        assert!(state.is_nonce_usable(4));
+        assert!(state.is_nonce_usable(6));
+    }
+
+    #[test]
+    fn nonces_can_be_used_out_of_order() {
+        let mut state = maker_state();
+        assert!(state.use_nonce(200));
+        assert!(state.use_nonce(70));
+        assert!(state.use_nonce(3));
+        assert_eq!(state.nonce_floor, 0);
+        assert!(!state.use_nonce(70));
+        assert!(!state.use_nonce(200));
+    }
+
+    #[test]
+    fn floor_slides_past_the_used_run() {
+        let mut state = maker_state();
+        assert!(state.use_nonce(1));
+        assert!(state.use_nonce(2));
+        assert_eq!(state.nonce_floor, 0);
+
+        // Using the floor itself slides the window past every used nonce above it
+        assert!(state.use_nonce(0));
+        assert_eq!(state.nonce_floor, 3);
+        assert_eq!(state.used_nonces, [0; 4]);
+        for nonce in 0..3 {
+            assert!(!state.is_nonce_usable(nonce));
+        }
+
+        // The window now reaches 256 nonces past the new floor
+        assert!(state.is_nonce_usable(3 + MakerState::NONCE_WINDOW - 1));
+        assert!(!state.is_nonce_usable(3 + MakerState::NONCE_WINDOW));
+    }
+
+    #[test]
+    fn nonces_outside_the_window_are_rejected() {
+        let mut state = maker_state();
+        assert!(!state.use_nonce(MakerState::NONCE_WINDOW));
+        assert!(state.use_nonce(MakerState::NONCE_WINDOW - 1));
+    }
+
+    #[test]
+    fn raising_the_floor_cancels_lower_nonces_and_keeps_used_ones_above() {
+        let mut state = maker_state();
+        assert!(state.use_nonce(10));
+        assert!(state.use_nonce(100));
+        assert!(state.use_nonce(250));
+
+        state.raise_floor(70);
+        assert_eq!(state.nonce_floor, 70);
+        for nonce in [0, 10, 69] {
+            assert!(!state.is_nonce_usable(nonce));
+        }
+        // Used nonces above the new floor stay used across the word shift
+        assert!(!state.is_nonce_usable(100));
+        assert!(!state.is_nonce_usable(250));
+        assert!(state.is_nonce_usable(70));
+        assert!(state.is_nonce_usable(101));
+        assert!(state.is_nonce_usable(70 + MakerState::NONCE_WINDOW - 1));
+
+        // Lowering is a no-op
+        state.raise_floor(20);
+        assert_eq!(state.nonce_floor, 70);
+    }
+
+    #[test]
+    fn raising_the_floor_past_the_window_clears_the_bitmap() {
+        let mut state = maker_state();
+        assert!(state.use_nonce(5));
+        state.raise_floor(1000);
+        assert_eq!(state.used_nonces, [0; 4]);
+        assert!(state.is_nonce_usable(1000));
+        assert!(!state.is_nonce_usable(999));
+    }
+
+    #[test]
+    fn message_is_domain_separated_and_covers_the_nonce() {
+        let order = SignedOrder {
+            marketplace: Pubkey::new_unique(),
+            maker: Pubkey::new_unique(),
+            order_type: 1,
+            yield_token_amount: 1_000_000,
+            price_per_token: 950_000,
+            nonce: 7,
+            expires_at: 1_700_000_000,
+        };
+        let message = order.message().unwrap();
+        assert!(message.starts_with(SignedOrder::DOMAIN));
+
+        let other = SignedOrder { nonce: 8, ..order };
+        assert_ne!(message, other.message().unwrap());
+    }
+}
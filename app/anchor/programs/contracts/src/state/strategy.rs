@@ -1,5 +1,8 @@
 use anchor_lang::prelude::*;
 
+use crate::math;
+use crate::state::rate_feed::RateFeed;
+
 #[account]
 pub struct Strategy {
     /// Admin who created and manages this strategy
@@ -31,6 +34,63 @@ pub struct Strategy {
     
     /// Strategy ID for easy identification
     pub strategy_id: u64,
+
+    /// Yield source the vault is deployed into (Strategy::ADAPTER_*)
+    pub adapter_kind: u8,
+
+    /// External program the adapter CPIs into
+    pub adapter_program: Pubkey,
+
+    /// Accounts of the yield source, in the order the adapter expects them
+    pub adapter_targets: [Pubkey; Strategy::MAX_ADAPTER_TARGETS],
+
+    /// Principal currently deployed into the yield source
+    pub adapter_deposits: u64,
+
+    /// Total yield harvested from the yield source into the reward reserve
+    pub total_harvested: u64,
+
+    /// Where the rate comes from (Strategy::RATE_MODE_*)
+    pub rate_mode: u8,
+
+    /// Rate feed of oracle-rate strategies
+    pub rate_feed: Pubkey,
+
+    /// Yield accrued per unit of principal since the oracle rate was enabled,
+    /// scaled by Strategy::REWARD_INDEX_SCALE
+    pub reward_index: u128,
+
+    /// When the reward index was last brought up to date
+    pub last_index_update: i64,
+
+    /// Rate feed `cumulative_rate` the reward index was last accrued to
+    pub cumulative_rate_snapshot: u128,
+
+    /// How yield accrues over time (Strategy::ACCRUAL_*)
+    pub accrual_mode: u8,
+
+    /// Cap on `total_deposits`, 0 for no cap
+    pub max_total_deposits: u64,
+
+    /// Cap on a single user's deposited principal, 0 for no cap
+    pub max_deposit_per_user: u64,
+
+    /// Seconds a deposit stays locked, 0 for no lock
+    pub lock_duration: i64,
+
+    /// Penalty on principal leaving before the lock ends, in basis points.
+    /// 0 rejects early exits instead.
+    pub early_exit_penalty_bps: u16,
+
+    /// Seconds between requesting and completing a withdrawal, 0 for instant withdrawals
+    pub withdrawal_cooldown: i64,
+
+    /// Principal requested for withdrawal and not yet paid out, held back in the
+    /// vault for the tickets
+    pub pending_withdrawals: u64,
+
+    /// Share of compounded yield paid to keepers, in basis points
+    pub keeper_fee_bps: u16,
 }
 
 impl Strategy {
@@ -44,7 +104,55 @@ impl Strategy {
         1 + // is_active
         8 + // created_at
         8 + // total_yield_tokens_minted
-        8; // strategy_id
+        8 + // strategy_id
+        1 + // adapter_kind
+        32 + // adapter_program
+        32 * Strategy::MAX_ADAPTER_TARGETS + // adapter_targets
+        8 + // adapter_deposits
+        8 + // total_harvested
+        1 + // rate_mode
+        32 + // rate_feed
+        16 + // reward_index
+        8 + // last_index_update
+        16 + // cumulative_rate_snapshot
+        1 + // accrual_mode
+        8 + // max_total_deposits
+        8 + // max_deposit_per_user
+        8 + // lock_duration
+        2 + // early_exit_penalty_bps
+        8 + // withdrawal_cooldown
+        8 + // pending_withdrawals
+        2; // keeper_fee_bps
+
+    /// Deposits stay idle in the strategy vault
+    pub const ADAPTER_NONE: u8 = 0;
+    /// Deposits are lent to the bundled mock lending program
+    pub const ADAPTER_MOCK_LENDING: u8 = 1;
+    /// Number of yield source accounts a strategy can reference
+    pub const MAX_ADAPTER_TARGETS: usize = 3;
+
+    /// Yield accrues at the fixed `apy`
+    pub const RATE_MODE_FIXED: u8 = 0;
+    /// Yield accrues through the reward index at the rate of `rate_feed`
+    pub const RATE_MODE_ORACLE: u8 = 1;
+    /// Fixed-point scale of the reward index
+    pub const REWARD_INDEX_SCALE: u128 = math::SCALE;
+    
+    /// Simple interest: principal * rate * t
+    pub const ACCRUAL_SIMPLE: u8 = 0;
+    /// Continuous compounding: principal * (e^(rate * t) - 1)
+    pub const ACCRUAL_COMPOUND: u8 = 1;
+
+    /// Highest early-exit penalty (50%)
+    pub const MAX_EARLY_EXIT_PENALTY_BPS: u16 = 5000;
+
+    /// Highest keeper fee on compounded yield (10%)
+    pub const MAX_KEEPER_FEE_BPS: u16 = 1000;
+
+    /// Max length of the yield token symbol stored in its mint metadata
+    pub const MAX_SYMBOL_LEN: usize = 10;
+    /// Max length of the yield token metadata URI
+    pub const MAX_URI_LEN: usize = 200;
     
     /// Calculate the accumulated yield for a given amount and time period
     pub fn calculate_yield(&self, principal: u64, time_elapsed_seconds: i64) -> u64 {
@@ -52,6 +160,12 @@ impl Strategy {
             return 0;
         }
         
+        if self.accrual_mode == Self::ACCRUAL_COMPOUND {
+            let growth = Self::compound_growth(self.apy, time_elapsed_seconds);
+            let accrued_yield = (principal as u128).saturating_mul(growth) / math::SCALE;
+            return u64::try_from(accrued_yield).unwrap_or(u64::MAX);
+        }
+        
         // Annual yield = principal * APY / 10000
         let annual_yield = (principal as u128 * self.apy as u128) / 10000;
         
@@ -62,6 +176,130 @@ impl Strategy {
         accrued_yield as u64
     }
     
+    /// Implied annual rate in basis points of buying a yield token at `price`
+    /// (fixed-point with 6 decimals) and holding it for the holding period, while
+    /// its redemption value accrues at `rate_bps` the way the strategy's yield does.
+    /// Negative when the price exceeds the redemption value.
+    pub fn implied_apy_bps(&self, price: u64, rate_bps: u64) -> Option<i64> {
+        if price == 0 {
+            return None;
+        }
+        
+        let seconds_per_year: i128 = 365 * 24 * 60 * 60;
+        let period = self.holding_period();
+        
+        // Redemption value per unit paid, at math::SCALE
+        let redemption = math::SCALE + self.growth_over(rate_bps, period);
+        let ratio = redemption.checked_mul(1_000_000)? / price as u128;
+        if ratio == 0 {
+            return None;
+        }
+        
+        let exponent = if self.accrual_mode == Self::ACCRUAL_COMPOUND {
+            // price * e^(implied * T) = redemption
+            if ratio >= math::SCALE {
+                math::ln(ratio)? as i128
+            } else {
+                -(math::ln(math::SCALE * math::SCALE / ratio)? as i128)
+            }
+        } else {
+            // price * (1 + implied * T) = redemption
+            ratio as i128 - math::SCALE as i128
+        };
+        let implied = exponent * 10000 * seconds_per_year / (math::SCALE as i128 * period as i128);
+        
+        i64::try_from(implied).ok()
+    }
+    
+    /// Seconds a yield token is valued over: the deposit lock, or a year when
+    /// deposits aren't locked
+    pub fn holding_period(&self) -> i64 {
+        if self.lock_duration > 0 {
+            self.lock_duration
+        } else {
+            365 * 24 * 60 * 60
+        }
+    }
+    
+    /// Yield per unit of principal over `seconds` at `rate_bps`, at math::SCALE
+    fn growth_over(&self, rate_bps: u64, seconds: i64) -> u128 {
+        let exponent = math::rate_exponent(rate_bps, seconds);
+        if self.accrual_mode == Self::ACCRUAL_COMPOUND {
+            Self::growth(exponent)
+        } else {
+            exponent
+        }
+    }
+    
+    /// Yield tokens minted for a deposit
+    pub fn yield_tokens_for_deposit(&self, amount: u64) -> u64 {
+        // For simplicity, we start with 1:1 ratio, but this could be more sophisticated
+        amount
+    }
+    
+    /// Whether deposits are deployed into an external yield source
+    pub fn has_adapter(&self) -> bool {
+        self.adapter_kind != Self::ADAPTER_NONE
+    }
+    
+    /// e^(rate * t) - 1 at math::SCALE
+    fn compound_growth(rate_bps: u64, seconds: i64) -> u128 {
+        Self::growth(math::rate_exponent(rate_bps, seconds))
+    }
+    
+    /// e^x - 1 at math::SCALE, saturating past the range of math::exp
+    fn growth(exponent: u128) -> u128 {
+        math::exp(exponent).map_or(u64::MAX as u128 * math::SCALE, |growth| growth - math::SCALE)
+    }
+    
+    /// Keeper's share of `yield_amount` compounded on behalf of a position
+    pub fn keeper_fee(&self, yield_amount: u64) -> u64 {
+        (yield_amount as u128 * self.keeper_fee_bps as u128 / 10000) as u64
+    }
+    
+    /// Deposits the strategy can still take before reaching its cap
+    pub fn remaining_capacity(&self) -> u64 {
+        match self.max_total_deposits {
+            0 => u64::MAX,
+            cap => cap.saturating_sub(self.total_deposits),
+        }
+    }
+    
+    /// Deposits a user holding `deposited_amount` can still make
+    pub fn remaining_user_capacity(&self, deposited_amount: u64) -> u64 {
+        match self.max_deposit_per_user {
+            0 => u64::MAX,
+            cap => cap.saturating_sub(deposited_amount),
+        }
+    }
+    
+    /// Whether yield follows the rate feed instead of the fixed APY
+    pub fn uses_rate_feed(&self) -> bool {
+        self.rate_mode == Self::RATE_MODE_ORACLE
+    }
+    
+    /// Integrate the feed's rate into the reward index up to `now`. The feed's
+    /// accumulator already holds every past rate for the time it stood, and time
+    /// past the staleness window earns nothing until a fresh rate is pushed.
+    pub fn accrue_reward_index(&mut self, rate_feed: &RateFeed, now: i64) {
+        let cumulative_rate = rate_feed.cumulative_rate_at(now);
+        let exponent = cumulative_rate.saturating_sub(self.cumulative_rate_snapshot);
+        if exponent > 0 {
+            if self.accrual_mode == Self::ACCRUAL_COMPOUND {
+                // The index tracks growth - 1, so compounding multiplies the growth factor
+                let growth = Self::growth(exponent);
+                let compounded = (Self::REWARD_INDEX_SCALE + self.reward_index).saturating_mul(growth)
+                    / Self::REWARD_INDEX_SCALE;
+                self.reward_index = self.reward_index.saturating_add(compounded);
+            } else {
+                self.reward_index += exponent;
+            }
+        }
+        
+        self.cumulative_rate_snapshot = cumulative_rate;
+        self.last_index_update = self.last_index_update.max(now);
+    }
+    
     /// Check if strategy is valid for operations
     pub fn is_operational(&self) -> bool {
         self.is_active
@@ -76,4 +314,78 @@ pub struct StrategyCounter {
 
 impl StrategyCounter {
     pub const INIT_SPACE: usize = 8 + 8; // discriminator + count
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_utils::zeroed;
+
+    const YEAR: i64 = 365 * 24 * 60 * 60;
+
+    fn strategy(accrual_mode: u8, lock_duration: i64) -> Strategy {
+        let mut strategy: Strategy = zeroed();
+        strategy.apy = 1000;
+        strategy.accrual_mode = accrual_mode;
+        strategy.lock_duration = lock_duration;
+        strategy
+    }
+
+    #[test]
+    fn simple_yield_tokens_imply_the_rate_over_a_year() {
+        let strategy = strategy(Strategy::ACCRUAL_SIMPLE, 0);
+
+        assert_eq!(strategy.holding_period(), YEAR);
+        assert_eq!(strategy.implied_apy_bps(1_000_000, 1000), Some(1000));
+        // 1.1 redeemed for 1.05 paid
+        assert_eq!(strategy.implied_apy_bps(1_050_000, 1000), Some(476));
+        // Paying more than the redemption value loses money
+        assert_eq!(strategy.implied_apy_bps(1_200_000, 1000), Some(-833));
+        assert_eq!(strategy.implied_apy_bps(0, 1000), None);
+    }
+
+    #[test]
+    fn locked_yield_tokens_are_valued_over_the_lock() {
+        let strategy = strategy(Strategy::ACCRUAL_SIMPLE, YEAR / 2);
+
+        assert_eq!(strategy.holding_period(), YEAR / 2);
+        // 1.05 after half a year at 10%, bought at par
+        assert_eq!(strategy.implied_apy_bps(1_000_000, 1000), Some(1000));
+        assert_eq!(strategy.implied_apy_bps(1_025_000, 1000), Some(487));
+    }
+
+    #[test]
+    fn compounding_yield_tokens_imply_a_continuous_rate() {
+        let strategy = strategy(Strategy::ACCRUAL_COMPOUND, 0);
+
+        // e^0.1 redeemed for 1.0 paid
+        let at_par = strategy.implied_apy_bps(1_000_000, 1000).unwrap();
+        assert!((999..=1000).contains(&at_par), "implied {}", at_par);
+        // ln(e^0.1 / 1.05) and ln(e^0.1 / 1.2), a simple rate would give 525 and -790
+        assert_eq!(strategy.implied_apy_bps(1_050_000, 1000), Some(512));
+        assert_eq!(strategy.implied_apy_bps(1_200_000, 1000), Some(-823));
+    }
+
+    #[test]
+    fn implied_rate_follows_the_accruing_rate() {
+        let strategy = strategy(Strategy::ACCRUAL_SIMPLE, 0);
+
+        // The same price means less when the rate it accrues at is lower
+        assert_eq!(strategy.implied_apy_bps(1_000_000, 400), Some(400));
+        assert_eq!(strategy.implied_apy_bps(1_050_000, 400), Some(-95));
+    }
+
+    #[test]
+    fn compound_accrual_earns_the_continuous_rate() {
+        let simple = strategy(Strategy::ACCRUAL_SIMPLE, 0);
+        let compound = strategy(Strategy::ACCRUAL_COMPOUND, 0);
+
+        assert_eq!(simple.calculate_yield(1_000_000, YEAR), 100_000);
+        // e^0.1 - 1 = 0.1051709...
+        assert_eq!(compound.calculate_yield(1_000_000, YEAR), 105_170);
+        // e^0.05 - 1 = 0.0512710...
+        assert_eq!(compound.calculate_yield(1_000_000, YEAR / 2), 51_271);
+        assert_eq!(compound.calculate_yield(1_000_000, 0), 0);
+    }
+}
+
@@ -0,0 +1,47 @@
+use anchor_lang::prelude::*;
+
+#[account]
+pub struct TradingDelegate {
+    /// User whose orders the session key manages
+    pub user: Pubkey,
+
+    /// Session key allowed to place, modify and cancel orders for the user
+    pub delegate: Pubkey,
+
+    /// When the authorisation stops being valid
+    pub expires_at: i64,
+
+    /// Maximum total order value (in quote tokens) the session key may commit
+    pub notional_limit: u64,
+
+    /// Order value committed so far by the session key
+    pub notional_used: u64,
+
+    /// Lowest price the session key may sell yield tokens at, 0 keeps it to buy orders
+    pub min_sell_price: u64,
+
+    /// PDA bump, the delegate account signs as SPL delegate of the user's token accounts
+    pub bump: u8,
+}
+
+impl TradingDelegate {
+    pub const INIT_SPACE: usize = 8 + // discriminator
+        32 + // user
+        32 + // delegate
+        8 + // expires_at
+        8 + // notional_limit
+        8 + // notional_used
+        8 + // min_sell_price
+        1; // bump
+
+    /// Order value the session key can still commit
+    pub fn remaining_notional(&self) -> u64 {
+        self.notional_limit.saturating_sub(self.notional_used)
+    }
+
+    /// Whether the session key may sell at `price_per_token`. Sells are charged at
+    /// their quote value, so the notional limit alone lets a key sell for almost nothing.
+    pub fn allows_sell_price(&self, price_per_token: u64) -> bool {
+        self.min_sell_price > 0 && price_per_token >= self.min_sell_price
+    }
+}
@@ -0,0 +1,162 @@
+use anchor_lang::prelude::*;
+
+use crate::state::marketplace::Marketplace;
+
+#[account]
+pub struct TriggerOrder {
+    /// User who placed the trigger order
+    pub user: Pubkey,
+
+    /// Marketplace whose price triggers the order
+    pub marketplace: Pubkey,
+
+    /// Order type of the resulting order: 0 = Buy, 1 = Sell
+    pub order_type: u8,
+
+    /// Amount of yield tokens
+    pub yield_token_amount: u64,
+
+    /// Limit price of the resulting order (in quote tokens)
+    /// Fixed-point with 6 decimals
+    pub price_per_token: u64,
+
+    /// Marketplace price that activates the order
+    pub trigger_price: u64,
+
+    /// Trigger condition: 0 = price at or above, 1 = price at or below
+    pub trigger_condition: u8,
+
+    /// Price compared to the trigger price (Marketplace::PRICE_SOURCE_LAST_TRADE or PRICE_SOURCE_TWAP)
+    pub price_source: u8,
+
+    /// TWAP window in seconds when `price_source` is PRICE_SOURCE_TWAP
+    pub twap_window: i64,
+
+    /// Lamports paid to the keeper that converts the order
+    pub keeper_bounty: u64,
+
+    /// Order ID the resulting TradeOrder is created with
+    pub order_id: u64,
+
+    /// When the trigger order was created
+    pub created_at: i64,
+
+    /// Trigger order ID for tracking
+    pub trigger_id: u64,
+}
+
+impl TriggerOrder {
+    pub const INIT_SPACE: usize = 8 + // discriminator
+        32 + // user
+        32 + // marketplace
+        1 + // order_type
+        8 + // yield_token_amount
+        8 + // price_per_token
+        8 + // trigger_price
+        1 + // trigger_condition
+        1 + // price_source
+        8 + // twap_window
+        8 + // keeper_bounty
+        8 + // order_id
+        8 + // created_at
+        8; // trigger_id
+
+    /// Take-profit for sells / breakout for buys
+    pub const TRIGGER_AT_OR_ABOVE: u8 = 0;
+    /// Stop-loss for sells / dip-buy for buys
+    pub const TRIGGER_AT_OR_BELOW: u8 = 1;
+
+    /// Marketplace price this order is checked against, 0 while there is none
+    pub fn observed_price(&self, marketplace: &Marketplace, now: i64) -> u64 {
+        if self.price_source == Marketplace::PRICE_SOURCE_TWAP {
+            marketplace.twap(now, self.twap_window).unwrap_or(0)
+        } else {
+            marketplace.last_trade_price
+        }
+    }
+
+    /// Check if a marketplace price activates this order
+    pub fn is_triggered(&self, price: u64) -> bool {
+        if price == 0 {
+            return false;
+        }
+        if self.trigger_condition == Self::TRIGGER_AT_OR_ABOVE {
+            price >= self.trigger_price
+        } else {
+            price <= self.trigger_price
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_utils::zeroed;
+
+    fn trigger(trigger_price: u64, trigger_condition: u8, price_source: u8, twap_window: i64) -> TriggerOrder {
+        let mut order: TriggerOrder = zeroed();
+        order.trigger_price = trigger_price;
+        order.trigger_condition = trigger_condition;
+        order.price_source = price_source;
+        order.twap_window = twap_window;
+        order
+    }
+
+    /// 1.0 for a minute, 2.0 for a minute, then a one second spike to 3.0
+    fn traded_marketplace() -> Marketplace {
+        let mut marketplace: Marketplace = zeroed();
+        marketplace.record_trade(1_000_000, 1_000);
+        marketplace.record_trade(2_000_000, 1_060);
+        marketplace.record_trade(3_000_000, 1_119);
+        marketplace
+    }
+
+    #[test]
+    fn conditions_include_the_trigger_price() {
+        let above = trigger(1_000_000, TriggerOrder::TRIGGER_AT_OR_ABOVE, Marketplace::PRICE_SOURCE_LAST_TRADE, 0);
+        assert!(above.is_triggered(1_000_000));
+        assert!(above.is_triggered(1_000_001));
+        assert!(!above.is_triggered(999_999));
+
+        let below = trigger(1_000_000, TriggerOrder::TRIGGER_AT_OR_BELOW, Marketplace::PRICE_SOURCE_LAST_TRADE, 0);
+        assert!(below.is_triggered(1_000_000));
+        assert!(below.is_triggered(1));
+        assert!(!below.is_triggered(1_000_001));
+    }
+
+    #[test]
+    fn no_price_never_triggers() {
+        let below = trigger(1_000_000, TriggerOrder::TRIGGER_AT_OR_BELOW, Marketplace::PRICE_SOURCE_LAST_TRADE, 0);
+        assert!(!below.is_triggered(0));
+
+        let marketplace: Marketplace = zeroed();
+        assert_eq!(below.observed_price(&marketplace, 1_000), 0);
+    }
+
+    #[test]
+    fn last_trade_source_follows_the_latest_trade() {
+        let order = trigger(2_500_000, TriggerOrder::TRIGGER_AT_OR_ABOVE, Marketplace::PRICE_SOURCE_LAST_TRADE, 0);
+        let price = order.observed_price(&traded_marketplace(), 1_120);
+
+        assert_eq!(price, 3_000_000);
+        assert!(order.is_triggered(price));
+    }
+
+    #[test]
+    fn twap_source_smooths_out_a_spike() {
+        let order = trigger(2_500_000, TriggerOrder::TRIGGER_AT_OR_ABOVE, Marketplace::PRICE_SOURCE_TWAP, 120);
+        let price = order.observed_price(&traded_marketplace(), 1_120);
+
+        // (1.0 * 60 + 2.0 * 59 + 3.0 * 1) / 120
+        assert_eq!(price, 1_508_333);
+        assert!(!order.is_triggered(price));
+    }
+
+    #[test]
+    fn twap_source_waits_for_enough_history() {
+        let order = trigger(1_000_000, TriggerOrder::TRIGGER_AT_OR_ABOVE, Marketplace::PRICE_SOURCE_TWAP, 600);
+
+        assert_eq!(order.observed_price(&traded_marketplace(), 1_120), 0);
+    }
+}
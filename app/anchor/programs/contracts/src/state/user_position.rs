@@ -1,5 +1,7 @@
 use anchor_lang::prelude::*;
 
+use crate::state::strategy::Strategy;
+
 #[account]
 pub struct UserPosition {
     /// The user who owns this position
@@ -25,6 +27,18 @@ pub struct UserPosition {
     
     /// Position ID for easy tracking
     pub position_id: u64,
+
+    /// Strategy reward index the pending yield was last settled at (oracle-rate strategies)
+    pub reward_index_snapshot: u128,
+
+    /// Yield settled but not yet claimed, from the reward index or from a top-up
+    pub pending_yield: u64,
+
+    /// When the lock ends, `deposit_time` plus the strategy's lock duration at deposit
+    pub lock_until: i64,
+
+    /// Whether keepers may compound this position's yield into its principal
+    pub auto_compound: bool,
 }
 
 impl UserPosition {
@@ -36,5 +50,147 @@ impl UserPosition {
         8 + // deposit_time
         8 + // last_yield_claim
         8 + // total_yield_claimed
-        8; // position_id
+        8 + // position_id
+        16 + // reward_index_snapshot
+        8 + // pending_yield
+        8 + // lock_until
+        1; // auto_compound
+    
+    /// Whether principal can leave without the early-exit terms applying
+    pub fn is_unlocked(&self, current_time: i64) -> bool {
+        current_time >= self.lock_until
+    }
+    
+    /// Yield accrued since the last claim
+    pub fn claimable_yield(&self, strategy: &Strategy, current_time: i64) -> u64 {
+        if strategy.uses_rate_feed() {
+            return self.pending_yield + self.index_yield(strategy);
+        }
+        
+        let time_elapsed = current_time - self.last_yield_claim;
+        self.pending_yield + strategy.calculate_yield(self.deposited_amount, time_elapsed)
+    }
+    
+    /// Move the yield accrued through the reward index into `pending_yield`, must run
+    /// before the principal changes. The strategy index has to be accrued first.
+    pub fn settle_yield(&mut self, strategy: &Strategy) {
+        if !strategy.uses_rate_feed() {
+            return;
+        }
+        
+        self.pending_yield += self.index_yield(strategy);
+        self.reward_index_snapshot = strategy.reward_index;
+    }
+    
+    /// Move all yield accrued so far into `pending_yield`, for either rate source. Runs
+    /// before a top-up so the earlier principal keeps what it earned.
+    pub fn settle_all_yield(&mut self, strategy: &Strategy, current_time: i64) {
+        if strategy.uses_rate_feed() {
+            self.settle_yield(strategy);
+            return;
+        }
+        
+        let time_elapsed = current_time - self.last_yield_claim;
+        self.pending_yield += strategy.calculate_yield(self.deposited_amount, time_elapsed);
+        self.last_yield_claim = current_time;
+    }
+    
+    /// Yield earned by the principal since the last settlement
+    fn index_yield(&self, strategy: &Strategy) -> u64 {
+        let index_delta = strategy.reward_index.saturating_sub(self.reward_index_snapshot);
+        
+        // Under compounding the index is growth - 1, yield compounds from the snapshot's growth
+        let base = if strategy.accrual_mode == Strategy::ACCRUAL_COMPOUND {
+            Strategy::REWARD_INDEX_SCALE + self.reward_index_snapshot
+        } else {
+            Strategy::REWARD_INDEX_SCALE
+        };
+        (self.deposited_amount as u128 * index_delta / base) as u64
+    }
+    
+    /// Principal and yield returned for burning `yield_token_amount` yield tokens,
+    /// proportional to the share of the position being redeemed
+    pub fn redemption_amounts(
+        &self,
+        strategy: &Strategy,
+        yield_token_amount: u64,
+        current_time: i64,
+    ) -> Option<(u64, u64)> {
+        let total_yield_tokens = self.yield_tokens_minted;
+        if total_yield_tokens == 0 {
+            return None;
+        }
+        
+        // Calculate principal to return (proportional to yield tokens being redeemed)
+        let principal_to_return = (self.deposited_amount as u128 * yield_token_amount as u128)
+            / total_yield_tokens as u128;
+        
+        // Calculate accumulated yield for this portion, yield settled by earlier top-ups
+        // and withdrawals is already in `pending_yield`
+        let total_yield_for_position = self.claimable_yield(strategy, current_time);
+        
+        // Proportional yield for the tokens being redeemed
+        let yield_to_return = (total_yield_for_position as u128 * yield_token_amount as u128)
+            / total_yield_tokens as u128;
+        
+        Some((principal_to_return as u64, yield_to_return as u64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::math;
+    use crate::test_utils::zeroed;
+
+    fn oracle_strategy(accrual_mode: u8, reward_index: u128) -> Strategy {
+        let mut strategy: Strategy = zeroed();
+        strategy.rate_mode = Strategy::RATE_MODE_ORACLE;
+        strategy.accrual_mode = accrual_mode;
+        strategy.reward_index = reward_index;
+        strategy
+    }
+
+    fn position(deposited_amount: u64, reward_index_snapshot: u128) -> UserPosition {
+        let mut position: UserPosition = zeroed();
+        position.deposited_amount = deposited_amount;
+        position.yield_tokens_minted = deposited_amount;
+        position.reward_index_snapshot = reward_index_snapshot;
+        position
+    }
+
+    #[test]
+    fn index_yield_is_earned_from_the_snapshot() {
+        // The index moved from 5% to 15% while the position held 1000 tokens
+        let strategy = oracle_strategy(Strategy::ACCRUAL_SIMPLE, math::SCALE * 15 / 100);
+        let mut position = position(1_000_000, math::SCALE * 5 / 100);
+
+        assert_eq!(position.claimable_yield(&strategy, 0), 100_000);
+
+        position.settle_yield(&strategy);
+        assert_eq!(position.pending_yield, 100_000);
+        assert_eq!(position.reward_index_snapshot, strategy.reward_index);
+        assert_eq!(position.claimable_yield(&strategy, 0), 100_000);
+    }
+
+    #[test]
+    fn compounding_index_grows_on_the_snapshot_growth() {
+        // Growth went from 1.1 to 1.21: another 10% on top of what the principal already earned
+        let strategy = oracle_strategy(Strategy::ACCRUAL_COMPOUND, math::SCALE * 21 / 100);
+        let position = position(1_000_000, math::SCALE / 10);
+
+        assert_eq!(position.claimable_yield(&strategy, 0), 100_000);
+    }
+
+    #[test]
+    fn fixed_rate_positions_ignore_the_index() {
+        let mut strategy = oracle_strategy(Strategy::ACCRUAL_SIMPLE, math::SCALE);
+        strategy.rate_mode = Strategy::RATE_MODE_FIXED;
+        let mut position = position(1_000_000, 0);
+
+        position.settle_yield(&strategy);
+        assert_eq!(position.pending_yield, 0);
+        assert_eq!(position.reward_index_snapshot, 0);
+    }
 }
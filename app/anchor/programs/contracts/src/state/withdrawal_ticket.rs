@@ -0,0 +1,63 @@
+use anchor_lang::prelude::*;
+
+#[account]
+pub struct WithdrawalTicket {
+    /// User who requested the withdrawal
+    pub user: Pubkey,
+
+    /// Strategy the principal is withdrawn from
+    pub strategy: Pubkey,
+
+    /// Position the principal was taken out of
+    pub user_position: Pubkey,
+
+    /// Principal leaving the strategy, no longer accruing yield
+    pub amount: u64,
+
+    /// Early-exit penalty withheld from `amount` when the request was made during the lock
+    pub penalty: u64,
+
+    /// When the withdrawal was requested
+    pub requested_at: i64,
+
+    /// When the withdrawal can be completed
+    pub unlock_at: i64,
+
+    /// Ticket ID for tracking
+    pub ticket_id: u64,
+}
+
+impl WithdrawalTicket {
+    pub const INIT_SPACE: usize = 8 + // discriminator
+        32 + // user
+        32 + // strategy
+        32 + // user_position
+        8 + // amount
+        8 + // penalty
+        8 + // requested_at
+        8 + // unlock_at
+        8; // ticket_id
+
+    /// Check if the cooldown is over
+    pub fn is_claimable(&self, current_time: i64) -> bool {
+        current_time >= self.unlock_at
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_utils::zeroed;
+
+    #[test]
+    fn ticket_is_claimable_from_the_unlock_time() {
+        let mut ticket: WithdrawalTicket = zeroed();
+        ticket.requested_at = 1_000;
+        ticket.unlock_at = 1_000 + 86_400;
+
+        assert!(!ticket.is_claimable(1_000));
+        assert!(!ticket.is_claimable(87_399));
+        assert!(ticket.is_claimable(87_400));
+    }
+}
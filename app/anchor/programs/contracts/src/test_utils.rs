@@ -0,0 +1,437 @@
+//! Account fixtures for unit tests. Accounts are leaked so they can be handed to code
+//! expecting `'info` lifetimes.
+
+use std::cell::{Cell, RefCell};
+use std::sync::Once;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::entrypoint::{ProgramResult, MAX_PERMITTED_DATA_INCREASE, SUCCESS};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program_option::COption;
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_lang::solana_program::program_stubs;
+use anchor_lang::solana_program::sysvar;
+use anchor_spl::token::spl_token;
+use anchor_spl::token::spl_token::native_mint;
+use anchor_spl::token_2022::spl_token_2022;
+use anchor_spl::token_2022::spl_token_2022::extension::transfer_fee::{TransferFee, TransferFeeAmount, TransferFeeConfig};
+use anchor_spl::token_2022::spl_token_2022::extension::{
+    BaseStateWithExtensionsMut, ExtensionType, StateWithExtensions, StateWithExtensionsMut,
+};
+use anchor_spl::token_interface::{Mint, TokenAccount};
+use mock_lending::{Pool, Position};
+
+use crate::adapters::{self, AdapterAccounts};
+use crate::state::{candles::CandleSeries, strategy::Strategy, trading_delegate::TradingDelegate};
+
+/// Lamports every fixture account starts with
+pub(crate) const ACCOUNT_LAMPORTS: u64 = 1_000_000_000;
+
+/// Account info over `data`, owned by `owner`
+pub(crate) fn account_info(key: Pubkey, owner: Pubkey, data: Vec<u8>) -> &'static AccountInfo<'static> {
+    leaked_info(key, owner, data, false, false)
+}
+
+/// Account info of a transaction signer
+pub(crate) fn signer_info(key: Pubkey) -> &'static AccountInfo<'static> {
+    leaked_info(key, System::id(), Vec::new(), true, false)
+}
+
+/// Account info of the executable program `id`
+pub(crate) fn program_info(id: Pubkey) -> &'static AccountInfo<'static> {
+    leaked_info(id, Pubkey::default(), Vec::new(), false, true)
+}
+
+fn leaked_info(
+    key: Pubkey,
+    owner: Pubkey,
+    data: Vec<u8>,
+    is_signer: bool,
+    executable: bool,
+) -> &'static AccountInfo<'static> {
+    Box::leak(Box::new(AccountInfo::new(
+        Box::leak(Box::new(key)),
+        is_signer,
+        true,
+        Box::leak(Box::new(ACCOUNT_LAMPORTS)),
+        runtime_data(data),
+        Box::leak(Box::new(owner)),
+        executable,
+        0,
+    )))
+}
+
+/// Account data laid out as the runtime serializes it: `AccountInfo::realloc` writes
+/// the new length just before the data and may grow it up to the permitted increase
+fn runtime_data(data: Vec<u8>) -> &'static mut [u8] {
+    let len = data.len();
+    let buffer = Box::leak(vec![0u8; 8 + len + MAX_PERMITTED_DATA_INCREASE].into_boxed_slice());
+    buffer[..8].copy_from_slice(&(len as u64).to_le_bytes());
+    buffer[8..8 + len].copy_from_slice(&data);
+    &mut buffer[8..8 + len]
+}
+
+/// Program account holding `value`
+pub(crate) fn program_account<T>(value: &T) -> Account<'static, T>
+where
+    T: AccountSerialize + AccountDeserialize + Owner + Clone,
+{
+    program_account_at(Pubkey::new_unique(), value)
+}
+
+/// Program account holding `value` at `key`, for accounts checked against their PDA
+pub(crate) fn program_account_at<T>(key: Pubkey, value: &T) -> Account<'static, T>
+where
+    T: AccountSerialize + AccountDeserialize + Owner + Clone,
+{
+    let mut data = Vec::new();
+    value.try_serialize(&mut data).unwrap();
+    Account::try_from(account_info(key, T::owner(), data)).unwrap()
+}
+
+/// SPL token account of `mint` owned by `owner`, holding `amount`
+pub(crate) fn token_account(mint: Pubkey, owner: Pubkey, amount: u64) -> InterfaceAccount<'static, TokenAccount> {
+    token_account_at(Pubkey::new_unique(), mint, owner, amount)
+}
+
+/// SPL token account at `key`, for escrows and vaults checked against their PDA.
+/// Accounts of the native mint hold `amount` as wrapped lamports.
+pub(crate) fn token_account_at(
+    key: Pubkey,
+    mint: Pubkey,
+    owner: Pubkey,
+    amount: u64,
+) -> InterfaceAccount<'static, TokenAccount> {
+    let is_native = (mint == native_mint::ID).then(|| ACCOUNT_LAMPORTS - amount);
+    let state = spl_token::state::Account {
+        mint,
+        owner,
+        amount,
+        state: spl_token::state::AccountState::Initialized,
+        is_native: is_native.into(),
+        ..Default::default()
+    };
+    let mut data = vec![0; spl_token::state::Account::LEN];
+    spl_token::state::Account::pack(state, &mut data).unwrap();
+    InterfaceAccount::try_from(account_info(key, spl_token::ID, data)).unwrap()
+}
+
+/// Token-2022 account at `key` holding `amount` of a transfer-fee `mint`
+pub(crate) fn fee_token_account_at(
+    key: Pubkey,
+    mint: Pubkey,
+    owner: Pubkey,
+    amount: u64,
+) -> InterfaceAccount<'static, TokenAccount> {
+    type AccountState = spl_token_2022::state::Account;
+    let space = ExtensionType::try_calculate_account_len::<AccountState>(&[ExtensionType::TransferFeeAmount])
+        .unwrap();
+    let mut data = vec![0; space];
+    let mut state = StateWithExtensionsMut::<AccountState>::unpack_uninitialized(&mut data).unwrap();
+    state.init_extension::<TransferFeeAmount>(true).unwrap();
+    state.base = AccountState {
+        mint,
+        owner,
+        amount,
+        state: spl_token_2022::state::AccountState::Initialized,
+        ..Default::default()
+    };
+    state.pack_base();
+    state.init_account_type().unwrap();
+    InterfaceAccount::try_from(account_info(key, spl_token_2022::ID, data)).unwrap()
+}
+
+/// Current balance of a token account of either token program, including changes
+/// made through CPIs
+pub(crate) fn balance(account: &InterfaceAccount<TokenAccount>) -> u64 {
+    let info = account.to_account_info();
+    let data = info.data.borrow();
+    StateWithExtensions::<spl_token_2022::state::Account>::unpack(&data).unwrap().base.amount
+}
+
+/// Let `delegate` move up to `amount` out of a token account, as an SPL `approve` would
+pub(crate) fn approve(account: &InterfaceAccount<TokenAccount>, delegate: Pubkey, amount: u64) {
+    let info = account.to_account_info();
+    let mut state = spl_token::state::Account::unpack(&info.data.borrow()).unwrap();
+    state.delegate = Some(delegate).into();
+    state.delegated_amount = amount;
+    spl_token::state::Account::pack(state, &mut info.data.borrow_mut()).unwrap();
+}
+
+/// Session key authorisation of `authority` for `user` at its PDA, valid until
+/// time 10_000 with `notional_limit` and no sell floor
+pub(crate) fn session_key(user: Pubkey, authority: Pubkey, notional_limit: u64) -> Account<'static, TradingDelegate> {
+    let (address, bump) = Pubkey::find_program_address(
+        &[b"trading_delegate", user.as_ref(), authority.as_ref()],
+        &crate::ID,
+    );
+    program_account_at(address, &TradingDelegate {
+        user,
+        delegate: authority,
+        expires_at: 10_000,
+        notional_limit,
+        notional_used: 0,
+        min_sell_price: 0,
+        bump,
+    })
+}
+
+/// SPL mint at `key` with `decimals`, no supply and no authority
+pub(crate) fn mint_account(key: Pubkey, decimals: u8) -> InterfaceAccount<'static, Mint> {
+    let state = spl_token::state::Mint { decimals, is_initialized: true, ..Default::default() };
+    let mut data = vec![0; spl_token::state::Mint::LEN];
+    spl_token::state::Mint::pack(state, &mut data).unwrap();
+    InterfaceAccount::try_from(account_info(key, spl_token::ID, data)).unwrap()
+}
+
+/// SPL mint at `key` of the yield tokens of `strategy`, with `supply` outstanding
+pub(crate) fn yield_mint(key: Pubkey, strategy: Pubkey, supply: u64, decimals: u8) -> InterfaceAccount<'static, Mint> {
+    let state = spl_token::state::Mint {
+        mint_authority: COption::Some(strategy),
+        supply,
+        decimals,
+        is_initialized: true,
+        ..Default::default()
+    };
+    let mut data = vec![0; spl_token::state::Mint::LEN];
+    spl_token::state::Mint::pack(state, &mut data).unwrap();
+    InterfaceAccount::try_from(account_info(key, spl_token::ID, data)).unwrap()
+}
+
+/// Token-2022 mint at `key` charging `basis_points` per transfer, capped at `maximum_fee`
+pub(crate) fn fee_mint(key: Pubkey, basis_points: u16, maximum_fee: u64) -> InterfaceAccount<'static, Mint> {
+    type MintState = spl_token_2022::state::Mint;
+    let space = ExtensionType::try_calculate_account_len::<MintState>(&[ExtensionType::TransferFeeConfig]).unwrap();
+    let mut data = vec![0; space];
+    let mut state = StateWithExtensionsMut::<MintState>::unpack_uninitialized(&mut data).unwrap();
+    let fee = TransferFee {
+        epoch: 0.into(),
+        maximum_fee: maximum_fee.into(),
+        transfer_fee_basis_points: basis_points.into(),
+    };
+    let config = state.init_extension::<TransferFeeConfig>(true).unwrap();
+    config.older_transfer_fee = fee;
+    config.newer_transfer_fee = fee;
+    state.base = MintState { decimals: 6, is_initialized: true, ..Default::default() };
+    state.pack_base();
+    state.init_account_type().unwrap();
+
+    // Fee lookups read the epoch from the clock
+    stub_syscalls();
+    InterfaceAccount::try_from(account_info(key, spl_token_2022::ID, data)).unwrap()
+}
+
+/// Rent sysvar account with the default rent
+pub(crate) fn rent_sysvar() -> Sysvar<'static, Rent> {
+    let rent = Rent::default();
+    let mut data = rent.lamports_per_byte_year.to_le_bytes().to_vec();
+    data.extend(rent.exemption_threshold.to_le_bytes());
+    data.push(rent.burn_percent);
+    Sysvar::from_account_info(account_info(sysvar::rent::ID, sysvar::ID, data)).unwrap()
+}
+
+/// Empty candle series of `interval`, for handlers recording trades
+pub(crate) fn candle_series(interval: i64) -> AccountLoader<'static, CandleSeries> {
+    let mut data = CandleSeries::DISCRIMINATOR.to_vec();
+    data.resize(8 + std::mem::size_of::<CandleSeries>(), 0);
+    let series = AccountLoader::<CandleSeries>::try_from(account_info(Pubkey::new_unique(), crate::ID, data)).unwrap();
+    series.load_mut().unwrap().interval = interval;
+    series
+}
+
+/// Account state with every field zeroed, for tests to fill in what they need
+pub(crate) fn zeroed<T: AccountDeserialize>() -> T {
+    T::try_deserialize_unchecked(&mut [0u8; 10_240].as_slice()).unwrap()
+}
+
+thread_local! {
+    /// Unix time `Clock::get()` reports on this test thread
+    static CLOCK_TIME: Cell<i64> = const { Cell::new(0) };
+
+    /// Programs currently executing a CPI on this test thread, innermost last
+    static INVOKED_PROGRAMS: RefCell<Vec<Pubkey>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Have `Clock::get()` report `unix_timestamp` on this test thread
+pub(crate) fn set_clock(unix_timestamp: i64) {
+    stub_syscalls();
+    CLOCK_TIME.set(unix_timestamp);
+}
+
+/// Serve syscalls off-chain: `Clock::get()` at the thread's clock time, the default rent, a full compute budget, and CPIs into the
+/// token programs, system transfers and mock lending run in process
+pub(crate) fn stub_syscalls() {
+    struct SyscallStub;
+
+    impl program_stubs::SyscallStubs for SyscallStub {
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            let clock = Clock { unix_timestamp: CLOCK_TIME.get(), ..Clock::default() };
+            // SAFETY: the sysvar getter hands over a pointer to a Clock
+            unsafe { *(var_addr as *mut Clock) = clock };
+            SUCCESS
+        }
+
+        fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+            // SAFETY: the sysvar getter hands over a pointer to a Rent
+            unsafe { *(var_addr as *mut Rent) = Rent::default() };
+            SUCCESS
+        }
+
+        fn sol_remaining_compute_units(&self) -> u64 {
+            200_000
+        }
+
+        fn sol_invoke_signed(
+            &self,
+            instruction: &Instruction,
+            account_infos: &[AccountInfo],
+            signers_seeds: &[&[&[u8]]],
+        ) -> ProgramResult {
+            invoke(instruction, account_infos, signers_seeds)
+        }
+    }
+
+    // Installed once: the stubs stay read-locked through a CPI, replacing them from
+    // another test thread meanwhile would deadlock nested syscalls
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        program_stubs::set_syscall_stubs(Box::new(SyscallStub));
+    });
+}
+
+/// Run a CPI the way the runtime would: accounts in instruction order, signer
+/// privileges only for real signers and PDAs of the calling program
+fn invoke(instruction: &Instruction, account_infos: &[AccountInfo], signers_seeds: &[&[&[u8]]]) -> ProgramResult {
+    let caller = INVOKED_PROGRAMS.with_borrow(|programs| programs.last().copied()).unwrap_or(crate::ID);
+    let pdas = signers_seeds
+        .iter()
+        .map(|seeds| Pubkey::create_program_address(seeds, &caller))
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|_| ProgramError::InvalidSeeds)?;
+
+    let mut accounts = Vec::with_capacity(instruction.accounts.len());
+    for meta in &instruction.accounts {
+        let mut info = account_infos
+            .iter()
+            .find(|info| *info.key == meta.pubkey)
+            .ok_or(ProgramError::NotEnoughAccountKeys)?
+            .clone();
+        if meta.is_signer && !info.is_signer {
+            if !pdas.contains(info.key) {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            info.is_signer = true;
+        }
+        accounts.push(info);
+    }
+
+    let program_id = instruction.program_id;
+    let data = instruction.data.as_slice();
+    INVOKED_PROGRAMS.with_borrow_mut(|programs| programs.push(program_id));
+    let result = if program_id == spl_token::ID {
+        spl_token::processor::Processor::process(&program_id, &accounts, data)
+    } else if program_id == spl_token_2022::ID {
+        spl_token_2022::processor::Processor::process(&program_id, &accounts, data)
+    } else if program_id == System::id() {
+        system_transfer(&accounts, data)
+    } else if program_id == mock_lending::ID {
+        mock_lending::entry(&program_id, accounts.leak(), data)
+    } else {
+        Err(ProgramError::IncorrectProgramId)
+    };
+    INVOKED_PROGRAMS.with_borrow_mut(|programs| programs.pop());
+
+    result
+}
+
+/// The system program's `Transfer`, the only system instruction handlers invoke
+/// on accounts that already exist
+fn system_transfer(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    const TRANSFER: u32 = 2;
+    let (tag, lamports) = data.split_at(4);
+    if u32::from_le_bytes(tag.try_into().unwrap()) != TRANSFER {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let lamports = u64::from_le_bytes(lamports[..8].try_into().unwrap());
+
+    let (from, to) = (&accounts[0], &accounts[1]);
+    if !from.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    **from.try_borrow_mut_lamports()? = from.lamports().checked_sub(lamports).ok_or(ProgramError::InsufficientFunds)?;
+    **to.try_borrow_mut_lamports()? += lamports;
+
+    Ok(())
+}
+
+/// Strategy at its PDA lending into a mock lending pool: its vault holds `vault`,
+/// `deployed` has been lent at `rate_bps` since time 0 and the pool holds a year
+/// of interest on top
+pub(crate) struct LendingStrategy {
+    pub strategy: Account<'static, Strategy>,
+    pub mint: InterfaceAccount<'static, Mint>,
+    pub vault: InterfaceAccount<'static, TokenAccount>,
+    pub pool_vault: InterfaceAccount<'static, TokenAccount>,
+    /// Adapter program followed by the strategy's targets
+    pub remaining_accounts: &'static [AccountInfo<'static>],
+    pub bump: u8,
+}
+
+impl LendingStrategy {
+    pub const STRATEGY_ID: u64 = 7;
+
+    pub fn new(vault: u64, deployed: u64, rate_bps: u16) -> Self {
+        Self::of_mint(Pubkey::new_unique(), vault, deployed, rate_bps)
+    }
+
+    /// Lending strategy over `mint`, such as the native mint
+    pub fn of_mint(mint: Pubkey, vault: u64, deployed: u64, rate_bps: u16) -> Self {
+        stub_syscalls();
+        let (strategy_key, bump) =
+            Pubkey::find_program_address(&[b"strategy", Self::STRATEGY_ID.to_le_bytes().as_ref()], &crate::ID);
+        let (pool_key, pool_bump) = Pubkey::find_program_address(&[b"pool", mint.as_ref()], &mock_lending::ID);
+        let targets = adapters::mock_lending::targets(&pool_key, &strategy_key);
+
+        let pool = Pool { mint, vault: targets[1], rate_bps, total_deposits: deployed, bump: pool_bump };
+        let position = Position { owner: strategy_key, pool: pool_key, balance: deployed, last_update: 0 };
+        let interest = deployed * rate_bps as u64 / 10_000;
+        let pool_vault = token_account_at(targets[1], mint, pool_key, deployed + interest);
+        let remaining_accounts = vec![
+            program_info(mock_lending::ID).clone(),
+            program_account_at(pool_key, &pool).to_account_info(),
+            pool_vault.to_account_info(),
+            program_account_at(targets[2], &position).to_account_info(),
+        ];
+
+        let mut state: Strategy = zeroed();
+        state.underlying_token = mint;
+        state.adapter_kind = Strategy::ADAPTER_MOCK_LENDING;
+        state.adapter_program = mock_lending::ID;
+        state.adapter_targets = targets;
+        state.adapter_deposits = deployed;
+
+        Self {
+            strategy: program_account_at(strategy_key, &state),
+            mint: mint_account(mint, if mint == native_mint::ID { native_mint::DECIMALS } else { 6 }),
+            vault: token_account_at(Pubkey::new_unique(), mint, strategy_key, vault),
+            pool_vault,
+            remaining_accounts: remaining_accounts.leak(),
+            bump,
+        }
+    }
+
+    /// Seeds the strategy signs its CPIs with
+    pub fn signer_seeds(&self) -> [Vec<u8>; 3] {
+        [b"strategy".to_vec(), Self::STRATEGY_ID.to_le_bytes().to_vec(), vec![self.bump]]
+    }
+
+    /// Adapter accounts moving funds in and out of the strategy vault
+    pub fn adapter_accounts(&self) -> AdapterAccounts<'static, 'static> {
+        AdapterAccounts {
+            vault: self.vault.to_account_info(),
+            mint: self.mint.to_account_info(),
+            token_program: program_info(spl_token::ID).clone(),
+            remaining_accounts: self.remaining_accounts,
+        }
+    }
+}
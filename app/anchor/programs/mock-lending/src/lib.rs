@@ -0,0 +1,244 @@
+#![allow(deprecated)]
+
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{
+    Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked,
+};
+
+declare_id!("FZQgozf5S3caWv6knxLAcwKUdgy93xXU7iDeziShWfXr");
+
+/// Minimal lending pool used as a yield source for Yieldos strategies in local tests.
+/// Positions grow at the pool's fixed rate, interest is paid out of whatever
+/// liquidity the pool vault holds beyond deposits, so tests fund it directly.
+#[program]
+pub mod mock_lending {
+    use super::*;
+
+    /// Create a lending pool for a mint with a fixed annual rate in basis points
+    pub fn initialize_pool(ctx: Context<InitializePool>, rate_bps: u16) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        pool.mint = ctx.accounts.mint.key();
+        pool.vault = ctx.accounts.pool_vault.key();
+        pool.rate_bps = rate_bps;
+        pool.total_deposits = 0;
+        pool.bump = ctx.bumps.pool;
+
+        msg!("Lending pool created for mint {} at {}%", pool.mint, rate_bps as f64 / 100.0);
+        Ok(())
+    }
+
+    /// Open a position in a pool, the owner may be a PDA signing through CPI
+    pub fn open_position(ctx: Context<OpenPosition>) -> Result<()> {
+        let position = &mut ctx.accounts.position;
+        position.owner = ctx.accounts.owner.key();
+        position.pool = ctx.accounts.pool.key();
+        position.balance = 0;
+        position.last_update = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
+
+    /// Lend tokens to the pool
+    pub fn deposit(ctx: Context<Deposit>, amount: u64) -> Result<()> {
+        require!(amount > 0, MockLendingError::InvalidAmount);
+
+        let now = Clock::get()?.unix_timestamp;
+        let position = &mut ctx.accounts.position;
+        position.accrue(ctx.accounts.pool.rate_bps, now);
+
+        let cpi_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.source.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                to: ctx.accounts.pool_vault.to_account_info(),
+                authority: ctx.accounts.owner.to_account_info(),
+            },
+        );
+        transfer_checked(cpi_ctx, amount, ctx.accounts.mint.decimals)?;
+
+        position.balance += amount;
+        ctx.accounts.pool.total_deposits += amount;
+        Ok(())
+    }
+
+    /// Withdraw principal or accrued interest from the pool
+    pub fn withdraw(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
+        require!(amount > 0, MockLendingError::InvalidAmount);
+
+        let now = Clock::get()?.unix_timestamp;
+        let pool = &mut ctx.accounts.pool;
+        let position = &mut ctx.accounts.position;
+        position.accrue(pool.rate_bps, now);
+        require!(position.balance >= amount, MockLendingError::InsufficientBalance);
+        require!(ctx.accounts.pool_vault.amount >= amount, MockLendingError::InsufficientLiquidity);
+
+        let mint_key = pool.mint;
+        let signer_seeds: &[&[u8]] = &[b"pool", mint_key.as_ref(), &[pool.bump]];
+        let seeds: &[&[&[u8]]] = &[signer_seeds];
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.pool_vault.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                to: ctx.accounts.destination.to_account_info(),
+                authority: pool.to_account_info(),
+            },
+            seeds,
+        );
+        transfer_checked(cpi_ctx, amount, ctx.accounts.mint.decimals)?;
+
+        position.balance -= amount;
+        pool.total_deposits = pool.total_deposits.saturating_sub(amount);
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitializePool<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"pool", mint.key().as_ref()],
+        bump,
+        space = 8 + Pool::INIT_SPACE
+    )]
+    pub pool: Account<'info, Pool>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"pool_vault", pool.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = pool,
+        token::token_program = token_program,
+    )]
+    pub pool_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct OpenPosition<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub owner: Signer<'info>,
+
+    pub pool: Account<'info, Pool>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"position", pool.key().as_ref(), owner.key().as_ref()],
+        bump,
+        space = 8 + Position::INIT_SPACE
+    )]
+    pub position: Account<'info, Position>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Deposit<'info> {
+    pub owner: Signer<'info>,
+
+    #[account(mut, has_one = mint)]
+    pub pool: Account<'info, Pool>,
+
+    #[account(mut, address = pool.vault)]
+    pub pool_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"position", pool.key().as_ref(), owner.key().as_ref()],
+        bump
+    )]
+    pub position: Account<'info, Position>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut)]
+    pub source: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    pub owner: Signer<'info>,
+
+    #[account(mut, has_one = mint)]
+    pub pool: Account<'info, Pool>,
+
+    #[account(mut, address = pool.vault)]
+    pub pool_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"position", pool.key().as_ref(), owner.key().as_ref()],
+        bump
+    )]
+    pub position: Account<'info, Position>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut)]
+    pub destination: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Pool {
+    pub mint: Pubkey,
+    pub vault: Pubkey,
+    /// Fixed annual lending rate in basis points
+    pub rate_bps: u16,
+    pub total_deposits: u64,
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Position {
+    pub owner: Pubkey,
+    pub pool: Pubkey,
+    /// Principal plus interest accrued up to `last_update`
+    pub balance: u64,
+    pub last_update: i64,
+}
+
+impl Position {
+    const SECONDS_PER_YEAR: u128 = 365 * 24 * 60 * 60;
+
+    /// Balance including interest accrued since the last update
+    pub fn balance_at(&self, rate_bps: u16, now: i64) -> u64 {
+        let elapsed = now.saturating_sub(self.last_update).max(0) as u128;
+        let interest =
+            self.balance as u128 * rate_bps as u128 * elapsed / 10000 / Self::SECONDS_PER_YEAR;
+        self.balance.saturating_add(interest as u64)
+    }
+
+    fn accrue(&mut self, rate_bps: u16, now: i64) {
+        self.balance = self.balance_at(rate_bps, now);
+        self.last_update = now;
+    }
+}
+
+#[error_code]
+pub enum MockLendingError {
+    #[msg("Invalid amount")]
+    InvalidAmount,
+    #[msg("Position balance is too low")]
+    InsufficientBalance,
+    #[msg("Pool does not hold enough liquidity")]
+    InsufficientLiquidity,
+}
@@ -66,7 +66,7 @@ async function createStrategy() {
         console.log("\n🚀 Création de la stratégie on-chain...");
 
         const tx = await program.methods
-            .createStrategy(strategyName, apyBasisPoints, new anchor.BN(strategyId))
+            .createStrategy(strategyName, apyBasisPoints, new anchor.BN(strategyId), "yUSDC", "")
             .accountsPartial({
                 admin: wallet.publicKey,
                 strategy: strategyPda,
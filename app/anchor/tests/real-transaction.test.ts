@@ -61,7 +61,7 @@ describe("Real Yieldos Transactions", () => {
 
         // 3. Créer la stratégie
         const tx = await program.methods
-            .createStrategy(strategyName, apyBasisPoints, new anchor.BN(strategyId))
+            .createStrategy(strategyName, apyBasisPoints, new anchor.BN(strategyId), "yUSDC", "")
             .accounts({
                 admin: provider.wallet.publicKey,
                 strategy: strategyPda,
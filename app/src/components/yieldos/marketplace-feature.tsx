@@ -32,10 +32,10 @@ export function MarketplaceFeature() {
     const [userYieldTokenBalance, setUserYieldTokenBalance] = useState<number>(0)
     const [loadingBalance, setLoadingBalance] = useState(false)
 
-    // État pour le balance des underlying tokens de l'utilisateur (pour buy orders)
-    const [userUnderlyingTokenBalance, setUserUnderlyingTokenBalance] = useState<number>(0)
-    const [loadingUnderlyingBalance, setLoadingUnderlyingBalance] = useState(false)
-    const [underlyingTokenInfo, setUnderlyingTokenInfo] = useState<{
+    // État pour le balance du quote token de l'utilisateur (pour buy orders)
+    const [userQuoteTokenBalance, setUserQuoteTokenBalance] = useState<number>(0)
+    const [loadingQuoteBalance, setLoadingQuoteBalance] = useState(false)
+    const [quoteTokenInfo, setQuoteTokenInfo] = useState<{
         mint: string,
         symbol: string,
         decimals: number
@@ -72,43 +72,27 @@ export function MarketplaceFeature() {
         }
     }
 
-    // Fonction pour récupérer le balance des underlying tokens
-    const fetchUserUnderlyingTokenBalance = async () => {
-        if (!wallet.publicKey || !selectedStrategy) {
-            setUserUnderlyingTokenBalance(0)
+    // Fonction pour récupérer le balance du quote token du marketplace (pour buy orders)
+    const fetchUserQuoteTokenBalance = async () => {
+        if (!wallet.publicKey || !finalMarketplace) {
+            setUserQuoteTokenBalance(0)
             return
         }
 
-        setLoadingUnderlyingBalance(true)
+        setLoadingQuoteBalance(true)
         try {
-            // Calculer le PDA de la stratégie pour récupérer l'underlying token
-            const [strategyPda] = getPDAs.getStrategyPda(selectedStrategy)
-            const strategyAccount = await connection.getAccountInfo(strategyPda)
-
-            if (!strategyAccount) {
-                setUserUnderlyingTokenBalance(0)
-                return
-            }
-
-            // Récupérer l'underlying token depuis la stratégie
-            let underlyingTokenMint: PublicKey
-            try {
-                const underlyingTokenBytes = strategyAccount.data.subarray(40, 72)
-                underlyingTokenMint = new PublicKey(underlyingTokenBytes)
-            } catch (parseError) {
-                // Fallback vers WSOL
-                underlyingTokenMint = new PublicKey('So11111111111111111111111111111111111111112')
-            }
+            // Les buy orders sont payés dans le quote mint du marketplace
+            const quoteMint = new PublicKey(finalMarketplace.quoteMint)
 
             // Récupérer les informations du token
             let tokenInfo = {
-                mint: underlyingTokenMint.toString(),
+                mint: quoteMint.toString(),
                 symbol: 'Unknown',
                 decimals: 9
             }
 
             // Si c'est WSOL, on peut utiliser le balance SOL natif + WSOL
-            if (underlyingTokenMint.toString() === 'So11111111111111111111111111111111111111112') {
+            if (quoteMint.toString() === 'So11111111111111111111111111111111111111112') {
                 tokenInfo.symbol = 'SOL'
                 tokenInfo.decimals = 9
 
@@ -116,21 +100,20 @@ export function MarketplaceFeature() {
 
                 // Aussi récupérer le balance WSOL s'il existe
                 try {
-                    const userWsolAccount = await getAssociatedTokenAddress(underlyingTokenMint, wallet.publicKey)
+                    const userWsolAccount = await getAssociatedTokenAddress(quoteMint, wallet.publicKey)
                     const wsolAccount = await connection.getTokenAccountBalance(userWsolAccount)
                     const wsolBalance = wsolAccount.value ? Number(wsolAccount.value.amount) : 0
 
                     const totalBalance = (solBalance + wsolBalance) / LAMPORTS_PER_SOL
-                    setUserUnderlyingTokenBalance(totalBalance)
+                    setUserQuoteTokenBalance(totalBalance)
                 } catch (wsolError) {
-                    setUserUnderlyingTokenBalance(solBalance / LAMPORTS_PER_SOL)
+                    setUserQuoteTokenBalance(solBalance / LAMPORTS_PER_SOL)
                 }
             } else {
-                console.log('❌ Strategy uses non-SOL token:', underlyingTokenMint.toString())
-                console.log('❌ This strategy should be deleted or reconfigured to use SOL/WSOL')
+                console.log('Marketplace quotes in non-SOL token:', quoteMint.toString())
 
                 // Pour debug: afficher quand même le balance du token
-                const mintStr = underlyingTokenMint.toString()
+                const mintStr = quoteMint.toString()
                 if (mintStr.startsWith('EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v')) {
                     tokenInfo.symbol = 'USDC'
                     tokenInfo.decimals = 6
@@ -142,36 +125,28 @@ export function MarketplaceFeature() {
                 }
 
                 try {
-                    const userTokenAccount = await getAssociatedTokenAddress(underlyingTokenMint, wallet.publicKey)
+                    const userTokenAccount = await getAssociatedTokenAddress(quoteMint, wallet.publicKey)
                     const tokenAccount = await connection.getTokenAccountBalance(userTokenAccount)
                     if (tokenAccount.value) {
-                        setUserUnderlyingTokenBalance(Number(tokenAccount.value.amount) / Math.pow(10, tokenInfo.decimals))
+                        setUserQuoteTokenBalance(Number(tokenAccount.value.amount) / Math.pow(10, tokenInfo.decimals))
                     } else {
-                        setUserUnderlyingTokenBalance(0)
+                        setUserQuoteTokenBalance(0)
                     }
                 } catch (error) {
-                    setUserUnderlyingTokenBalance(0)
+                    setUserQuoteTokenBalance(0)
                 }
             }
 
-            setUnderlyingTokenInfo(tokenInfo)
+            setQuoteTokenInfo(tokenInfo)
         } catch (error) {
-            console.error('Error fetching underlying token balance:', error)
-            setUserUnderlyingTokenBalance(0)
-            setUnderlyingTokenInfo(null)
+            console.error('Error fetching quote token balance:', error)
+            setUserQuoteTokenBalance(0)
+            setQuoteTokenInfo(null)
         } finally {
-            setLoadingUnderlyingBalance(false)
+            setLoadingQuoteBalance(false)
         }
     }
 
-    // Charger les balances quand la stratégie change
-    useEffect(() => {
-        // Réinitialiser les infos du token
-        setUnderlyingTokenInfo(null)
-        fetchUserYieldTokenBalance()
-        fetchUserUnderlyingTokenBalance()
-    }, [wallet.publicKey, selectedStrategy])
-
     // Get marketplace for selected strategy
     const currentMarketplace = useMemo(() => {
         if (!selectedStrategy || !marketplacesQuery.data || !strategiesQuery.data) {
@@ -199,10 +174,18 @@ export function MarketplaceFeature() {
     // Final marketplace (from global search or direct lookup)
     const finalMarketplace = currentMarketplace || (selectedStrategy ? directMarketplaceCache[selectedStrategy] : null)
 
+    // Charger les balances quand la stratégie ou le marketplace change
+    useEffect(() => {
+        // Réinitialiser les infos du token
+        setQuoteTokenInfo(null)
+        fetchUserYieldTokenBalance()
+        fetchUserQuoteTokenBalance()
+    }, [wallet.publicKey, selectedStrategy, finalMarketplace?.quoteMint.toString()])
+
     // Get orders for current marketplace
     const ordersQuery = getOrdersQuery(
         finalMarketplace?.strategy
-            ? getPDAs.getMarketplacePda(new PublicKey(finalMarketplace.strategy))[0]
+            ? getPDAs.getMarketplacePda(new PublicKey(finalMarketplace.strategy), new PublicKey(finalMarketplace.quoteMint))[0]
             : null
     )
 
@@ -294,7 +277,7 @@ export function MarketplaceFeature() {
             const pricePerToken = Math.floor(Number(sellPrice) * 1000000) // 6 decimals for price
 
             // Calculer le vrai PDA du marketplace
-            const [marketplacePda] = getPDAs.getMarketplacePda(new PublicKey(finalMarketplace.strategy))
+            const [marketplacePda] = getPDAs.getMarketplacePda(new PublicKey(finalMarketplace.strategy), new PublicKey(finalMarketplace.quoteMint))
 
             await placeOrderMutation.mutateAsync({
                 marketplacePda,
@@ -321,7 +304,7 @@ export function MarketplaceFeature() {
             const pricePerToken = Math.floor(Number(buyPrice) * 1000000) // 6 decimals for price
 
             // Calculer le vrai PDA du marketplace
-            const [marketplacePda] = getPDAs.getMarketplacePda(new PublicKey(finalMarketplace.strategy))
+            const [marketplacePda] = getPDAs.getMarketplacePda(new PublicKey(finalMarketplace.strategy), new PublicKey(finalMarketplace.quoteMint))
 
             await placeOrderMutation.mutateAsync({
                 marketplacePda,
@@ -334,7 +317,7 @@ export function MarketplaceFeature() {
             setBuyAmount('')
             setBuyPrice('')
             ordersQuery.refetch()
-            fetchUserUnderlyingTokenBalance() // Refresh balance after buy order
+            fetchUserQuoteTokenBalance() // Refresh balance after buy order
         } catch (error) {
             console.error('Error creating buy order:', error)
         }
@@ -345,7 +328,7 @@ export function MarketplaceFeature() {
 
         try {
             // Calculer le vrai PDA du marketplace
-            const [marketplacePda] = getPDAs.getMarketplacePda(new PublicKey(finalMarketplace.strategy))
+            const [marketplacePda] = getPDAs.getMarketplacePda(new PublicKey(finalMarketplace.strategy), new PublicKey(finalMarketplace.quoteMint))
 
             await cancelOrderMutation.mutateAsync({
                 orderId,
@@ -354,7 +337,7 @@ export function MarketplaceFeature() {
             })
             ordersQuery.refetch()
             fetchUserYieldTokenBalance() // Refresh balance after cancel order
-            fetchUserUnderlyingTokenBalance() // Refresh underlying balance too
+            fetchUserQuoteTokenBalance() // Refresh quote balance too
         } catch (error) {
             console.error('Error canceling order:', error)
         }
@@ -460,7 +443,7 @@ export function MarketplaceFeature() {
                             const [sellOrderPda] = getPDAs.getOrderPda(sellOrder.user, sellOrder.orderId)
 
                             // Calculer le vrai PDA du marketplace
-                            const [marketplacePda] = getPDAs.getMarketplacePda(new PublicKey(finalMarketplace.strategy))
+                            const [marketplacePda] = getPDAs.getMarketplacePda(new PublicKey(finalMarketplace.strategy), new PublicKey(finalMarketplace.quoteMint))
 
                             await executeTradesMutation.mutateAsync({
                                 buyOrderPda,
@@ -714,7 +697,7 @@ export function MarketplaceFeature() {
                                         <div className="space-y-2">
                                             <div className="flex justify-between text-sm">
                                                 <span>Total Value:</span>
-                                                <span>{(Number(sellAmount || 0) * Number(sellPrice || 0)).toFixed(underlyingTokenInfo?.decimals || 6)} {underlyingTokenInfo?.symbol || 'tokens'}</span>
+                                                <span>{(Number(sellAmount || 0) * Number(sellPrice || 0)).toFixed(quoteTokenInfo?.decimals || 6)} {quoteTokenInfo?.symbol || 'tokens'}</span>
                                             </div>
                                             {Number(sellAmount || 0) > userYieldTokenBalance && (
                                                 <div className="text-xs text-red-500">
@@ -747,14 +730,14 @@ export function MarketplaceFeature() {
                                             <div className="flex justify-between items-center">
                                                 <Label htmlFor="buy-amount">Amount (YLD tokens)</Label>
                                                 <div className="text-sm text-gray-500">
-                                                    {loadingUnderlyingBalance ? 'Loading...' : `Balance: ${userUnderlyingTokenBalance.toFixed(underlyingTokenInfo?.decimals || 6)} ${underlyingTokenInfo?.symbol || 'tokens'}`}
-                                                    {userUnderlyingTokenBalance > 0 && buyPrice && Number(buyPrice) > 0 && (
+                                                    {loadingQuoteBalance ? 'Loading...' : `Balance: ${userQuoteTokenBalance.toFixed(quoteTokenInfo?.decimals || 6)} ${quoteTokenInfo?.symbol || 'tokens'}`}
+                                                    {userQuoteTokenBalance > 0 && buyPrice && Number(buyPrice) > 0 && (
                                                         <Button
                                                             variant="link"
                                                             size="sm"
                                                             className="ml-2 h-auto p-0 text-xs"
                                                             onClick={() => {
-                                                                const maxAmount = userUnderlyingTokenBalance / Number(buyPrice)
+                                                                const maxAmount = userQuoteTokenBalance / Number(buyPrice)
                                                                 setBuyAmount(maxAmount.toFixed(6))
                                                             }}
                                                         >
@@ -786,11 +769,11 @@ export function MarketplaceFeature() {
                                         <div className="space-y-2">
                                             <div className="flex justify-between text-sm">
                                                 <span>Total Cost:</span>
-                                                <span>{(Number(buyAmount || 0) * Number(buyPrice || 0)).toFixed(underlyingTokenInfo?.decimals || 6)} {underlyingTokenInfo?.symbol || 'tokens'}</span>
+                                                <span>{(Number(buyAmount || 0) * Number(buyPrice || 0)).toFixed(quoteTokenInfo?.decimals || 6)} {quoteTokenInfo?.symbol || 'tokens'}</span>
                                             </div>
-                                            {(Number(buyAmount || 0) * Number(buyPrice || 0)) > userUnderlyingTokenBalance && (
+                                            {(Number(buyAmount || 0) * Number(buyPrice || 0)) > userQuoteTokenBalance && (
                                                 <div className="text-xs text-red-500">
-                                                    Insufficient balance. You need {(Number(buyAmount || 0) * Number(buyPrice || 0)).toFixed(underlyingTokenInfo?.decimals || 6)} {underlyingTokenInfo?.symbol || 'tokens'} but only have {userUnderlyingTokenBalance.toFixed(underlyingTokenInfo?.decimals || 6)} {underlyingTokenInfo?.symbol || 'tokens'}.
+                                                    Insufficient balance. You need {(Number(buyAmount || 0) * Number(buyPrice || 0)).toFixed(quoteTokenInfo?.decimals || 6)} {quoteTokenInfo?.symbol || 'tokens'} but only have {userQuoteTokenBalance.toFixed(quoteTokenInfo?.decimals || 6)} {quoteTokenInfo?.symbol || 'tokens'}.
                                                 </div>
                                             )}
                                         </div>
@@ -800,7 +783,7 @@ export function MarketplaceFeature() {
                                                 !buyAmount ||
                                                 !buyPrice ||
                                                 placeOrderMutation.isPending ||
-                                                (Number(buyAmount || 0) * Number(buyPrice || 0)) > userUnderlyingTokenBalance
+                                                (Number(buyAmount || 0) * Number(buyPrice || 0)) > userQuoteTokenBalance
                                             }
                                             variant="outline"
                                             className="w-full"
@@ -135,9 +135,9 @@ export function useYieldosProgram() {
             )
         },
 
-        getMarketplacePda: (strategyPda: PublicKey) => {
+        getMarketplacePda: (strategyPda: PublicKey, quoteMint: PublicKey) => {
             return PublicKey.findProgramAddressSync(
-                [Buffer.from("marketplace"), strategyPda.toBuffer()],
+                [Buffer.from("marketplace"), strategyPda.toBuffer(), quoteMint.toBuffer()],
                 YIELDOS_PROGRAM_ID
             )
         },
@@ -168,6 +168,13 @@ export function useYieldosProgram() {
                 [Buffer.from("escrow"), orderPda.toBuffer()],
                 YIELDOS_PROGRAM_ID
             )
+        },
+
+        getCandleSeriesPda: (marketplacePda: PublicKey, interval: number) => {
+            return PublicKey.findProgramAddressSync(
+                [Buffer.from("candles"), marketplacePda.toBuffer(), new anchor.BN(interval).toArrayLike(Buffer, "le", 8)],
+                YIELDOS_PROGRAM_ID
+            )
         }
     }), [])
 
@@ -458,9 +465,11 @@ export function useYieldosProgram() {
     // Mutations pour les instructions principales
     const createStrategyMutation = useMutation({
         mutationKey: ['yieldos', 'createStrategy'],
-        mutationFn: async ({ name, apyBasisPoints }: {
+        mutationFn: async ({ name, apyBasisPoints, symbol = 'yWSOL', uri = '' }: {
             name: string,
-            apyBasisPoints: number
+            apyBasisPoints: number,
+            symbol?: string, // Symbole du yield token (10 caractères max)
+            uri?: string // URI des métadonnées du yield token (200 caractères max)
         }) => {
             if (!connection || !wallet.publicKey || !provider || !program) {
                 throw new Error('Wallet or program not available')
@@ -481,13 +490,15 @@ export function useYieldosProgram() {
                     name,
                     apyBasisPoints,
                     strategyId,
+                    symbol,
+                    uri,
                     strategyPda: strategyPda.toString(),
                     strategyCounterPda: strategyCounterPda.toString(),
                     yieldTokenMintPda: yieldTokenMintPda.toString()
                 })
 
                 const tx = await program.methods
-                    .createStrategy(name, apyBasisPoints, new anchor.BN(strategyId))
+                    .createStrategy(name, apyBasisPoints, new anchor.BN(strategyId), symbol, uri)
                     .accounts({
                         admin: wallet.publicKey,
                         strategy: strategyPda,
@@ -1068,7 +1079,7 @@ export interface MarketplaceData {
     admin: PublicKey
     strategy: PublicKey
     yieldTokenMint: PublicKey
-    underlyingTokenMint: PublicKey
+    quoteMint: PublicKey
     totalVolume: number
     totalTrades: number
     bestBidPrice: number
@@ -1162,7 +1173,7 @@ export function useMarketplace() {
                         offset += 32
                         const yieldTokenMint = new PublicKey(data.subarray(offset, offset + 32))
                         offset += 32
-                        const underlyingTokenMint = new PublicKey(data.subarray(offset, offset + 32))
+                        const quoteMint = new PublicKey(data.subarray(offset, offset + 32))
                         offset += 32
 
                         // Vérifier qu'on a encore assez de données
@@ -1192,7 +1203,7 @@ export function useMarketplace() {
                             admin.toString() !== '11111111111111111111111111111111' && // Pas le system program
                             strategy.toString() !== '11111111111111111111111111111111' &&
                             yieldTokenMint.toString() !== '11111111111111111111111111111111' &&
-                            underlyingTokenMint.toString() !== '11111111111111111111111111111111' &&
+                            quoteMint.toString() !== '11111111111111111111111111111111' &&
                             tradingFeeBps <= 10000 && // Fee ne peut pas être > 100%
                             (isActive === true || isActive === false) // Boolean valide
                         )
@@ -1207,7 +1218,7 @@ export function useMarketplace() {
                             admin,
                             strategy,
                             yieldTokenMint,
-                            underlyingTokenMint,
+                            quoteMint,
                             totalVolume,
                             totalTrades,
                             bestBidPrice,
@@ -1335,28 +1346,28 @@ export function useMarketplace() {
 
     // Mutation pour créer un marketplace
     const createMarketplaceMutation = useMutation({
-        mutationFn: async ({ strategyId, tradingFeeBps }: { strategyId: number, tradingFeeBps: number }) => {
+        mutationFn: async ({ strategyId, tradingFeeBps, quoteMint }: { strategyId: number, tradingFeeBps: number, quoteMint?: PublicKey }) => {
             if (!program || !wallet.publicKey) {
                 throw new Error('Program or wallet not connected')
             }
 
             const [strategyPda] = getPDAs.getStrategyPda(strategyId)
-            const [marketplacePda] = getPDAs.getMarketplacePda(strategyPda)
             const [marketplaceCounterPda] = getPDAs.getMarketplaceCounterPda()
             const [yieldTokenMintPda] = getPDAs.getYieldTokenMintPda(strategyId)
 
+            // Get strategy to find underlying token, the default quote mint
+            const strategyAccount = await connection.getAccountInfo(strategyPda)
+            if (!strategyAccount) throw new Error('Strategy not found')
+
+            const quoteToken = quoteMint ?? new PublicKey(strategyAccount.data.subarray(40, 72))
+            const [marketplacePda] = getPDAs.getMarketplacePda(strategyPda, quoteToken)
+
             // Check if marketplace already exists
             const existingMarketplace = await connection.getAccountInfo(marketplacePda)
             if (existingMarketplace) {
-                throw new Error(`Marketplace already exists for strategy ${strategyId}`)
+                throw new Error(`Marketplace already exists for strategy ${strategyId} and quote mint ${quoteToken.toString()}`)
             }
 
-            // Get strategy to find underlying token
-            const strategyAccount = await connection.getAccountInfo(strategyPda)
-            if (!strategyAccount) throw new Error('Strategy not found')
-
-            const underlyingToken = new PublicKey(strategyAccount.data.subarray(40, 72))
-
             // Get marketplace counter to determine ID
             let marketplaceId = 1
             try {
@@ -1376,7 +1387,7 @@ export function useMarketplace() {
                     marketplace: marketplacePda,
                     marketplaceCounter: marketplaceCounterPda,
                     yieldTokenMint: yieldTokenMintPda,
-                    underlyingTokenMint: underlyingToken,
+                    quoteMint: quoteToken,
                     systemProgram: SystemProgram.programId,
                     rent: SYSVAR_RENT_PUBKEY,
                 })
@@ -1437,30 +1448,33 @@ export function useMarketplace() {
             const marketplaceAccount = await connection.getAccountInfo(marketplacePda)
             if (!marketplaceAccount) throw new Error('Marketplace not found')
 
-            // Parser les token mints depuis le marketplace (structure: admin + strategy + yieldTokenMint + underlyingTokenMint)
+            // Parser les token mints depuis le marketplace (structure: admin + strategy + yieldTokenMint + quoteMint)
             let offset = 40 // Skip discriminator(8) + admin(32)
             offset += 32 // Skip strategy
             const yieldTokenMint = new PublicKey(marketplaceAccount.data.subarray(offset, offset + 32))
             offset += 32
-            const underlyingTokenMint = new PublicKey(marketplaceAccount.data.subarray(offset, offset + 32))
+            const quoteMint = new PublicKey(marketplaceAccount.data.subarray(offset, offset + 32))
 
             const [escrowPda] = getPDAs.getEscrowPda(orderPda)
 
             // Get user token accounts
             const userYieldTokenAccount = await getAssociatedTokenAddress(yieldTokenMint, wallet.publicKey)
-            const userUnderlyingTokenAccount = await getAssociatedTokenAddress(underlyingTokenMint, wallet.publicKey)
+            const userQuoteTokenAccount = await getAssociatedTokenAddress(quoteMint, wallet.publicKey)
 
             const transaction = await program.methods
                 .placeOrder(new anchor.BN(orderId), orderType, new anchor.BN(yieldTokenAmount), new anchor.BN(pricePerToken))
                 .accounts({
+                    authority: wallet.publicKey,
                     user: wallet.publicKey,
+                    tradingDelegate: null,
                     marketplace: marketplacePda,
                     order: orderPda,
                     orderCounter: orderCounterPda,
                     yieldTokenMint,
-                    underlyingTokenMint,
+                    quoteMint,
                     userYieldTokenAccount,
-                    userUnderlyingTokenAccount,
+                    userQuoteTokenAccount,
+                    escrowMint: orderType === 1 ? yieldTokenMint : quoteMint, // 1 = sell order
                     escrowAccount: escrowPda,
                     tokenProgram: TOKEN_PROGRAM_ID,
                     systemProgram: SystemProgram.programId,
@@ -1503,35 +1517,28 @@ export function useMarketplace() {
             const orderType = orderAccount.data.readUInt8(72) // orderType field offset
 
             // Calculer les PDAs pour les token mints
-            const [strategyPda] = getPDAs.getStrategyPda(strategyId)
             const [yieldTokenMint] = getPDAs.getYieldTokenMintPda(strategyId)
 
-            // Récupérer l'underlying token depuis la stratégie
-            let underlyingTokenMint: PublicKey
-            try {
-                const strategyAccount = await connection.getAccountInfo(strategyPda)
-                if (!strategyAccount) {
-                    throw new Error(`Strategy ${strategyId} not found`)
-                }
-                // L'underlying token est à l'offset 40 (discriminator 8 + admin 32)
-                const underlyingTokenBytes = strategyAccount.data.subarray(40, 72)
-                underlyingTokenMint = new PublicKey(underlyingTokenBytes)
-            } catch (parseError) {
-                console.warn('Failed to parse underlying token, using WSOL:', parseError)
-                underlyingTokenMint = new PublicKey('So11111111111111111111111111111111111111112')
-            }
+            // Les ordres d'achat sont en quote token, à lire depuis le marketplace
+            const marketplaceAccount = await connection.getAccountInfo(marketplacePda)
+            if (!marketplaceAccount) throw new Error('Marketplace not found')
+            // Le quote mint est à l'offset 104 (discriminator 8 + admin 32 + strategy 32 + yieldTokenMint 32)
+            const quoteMint = new PublicKey(marketplaceAccount.data.subarray(104, 136))
 
             // Determine user token account based on order type
-            const tokenMint = orderType === 1 ? yieldTokenMint : underlyingTokenMint // 1 = sell order
+            const tokenMint = orderType === 1 ? yieldTokenMint : quoteMint // 1 = sell order
             const userTokenAccount = await getAssociatedTokenAddress(tokenMint, wallet.publicKey)
 
             const transaction = await program.methods
                 .cancelOrder(new anchor.BN(orderId))
                 .accounts({
+                    authority: wallet.publicKey,
                     user: wallet.publicKey,
+                    tradingDelegate: null,
                     marketplace: marketplacePda,
                     order: orderPda,
                     escrowAccount: escrowPda,
+                    escrowMint: tokenMint,
                     userTokenAccount,
                     tokenProgram: TOKEN_PROGRAM_ID,
                 })
@@ -1578,23 +1585,13 @@ export function useMarketplace() {
             const [sellOrderEscrowPda] = getPDAs.getEscrowPda(sellOrderPda)
 
             // Calculer les PDAs pour les token mints
-            const [strategyPda] = getPDAs.getStrategyPda(strategyId)
             const [yieldTokenMint] = getPDAs.getYieldTokenMintPda(strategyId)
 
-            // Récupérer l'underlying token depuis la stratégie
-            let underlyingTokenMint: PublicKey
-            try {
-                const strategyAccount = await connection.getAccountInfo(strategyPda)
-                if (!strategyAccount) {
-                    throw new Error(`Strategy ${strategyId} not found`)
-                }
-                // L'underlying token est à l'offset 40 (discriminator 8 + admin 32)
-                const underlyingTokenBytes = strategyAccount.data.subarray(40, 72)
-                underlyingTokenMint = new PublicKey(underlyingTokenBytes)
-            } catch (parseError) {
-                console.warn('Failed to parse underlying token, using WSOL:', parseError)
-                underlyingTokenMint = new PublicKey('So11111111111111111111111111111111111111112')
-            }
+            // Le paiement se fait dans le quote token du marketplace
+            const marketplaceAccount = await connection.getAccountInfo(marketplacePda)
+            if (!marketplaceAccount) throw new Error('Marketplace not found')
+            // Le quote mint est à l'offset 104 (discriminator 8 + admin 32 + strategy 32 + yieldTokenMint 32)
+            const quoteMint = new PublicKey(marketplaceAccount.data.subarray(104, 136))
 
             // Get order data to find users
             const buyOrderAccount = await connection.getAccountInfo(buyOrderPda)
@@ -1606,15 +1603,13 @@ export function useMarketplace() {
 
             // Get user token accounts
             const buyerYieldTokenAccount = await getAssociatedTokenAddress(yieldTokenMint, buyerPubkey)
-            const buyerUnderlyingTokenAccount = await getAssociatedTokenAddress(underlyingTokenMint, buyerPubkey)
-            const sellerUnderlyingTokenAccount = await getAssociatedTokenAddress(underlyingTokenMint, sellerPubkey)
+            const buyerQuoteTokenAccount = await getAssociatedTokenAddress(quoteMint, buyerPubkey)
+            const sellerQuoteTokenAccount = await getAssociatedTokenAddress(quoteMint, sellerPubkey)
 
             // Fee collection account (marketplace admin)
             // Récupérer l'admin depuis les données du marketplace
-            const marketplaceAccount = await connection.getAccountInfo(marketplacePda)
-            if (!marketplaceAccount) throw new Error('Marketplace not found')
             const marketplaceAdmin = new PublicKey(marketplaceAccount.data.subarray(8, 40))
-            const feeCollectionAccount = await getAssociatedTokenAddress(underlyingTokenMint, marketplaceAdmin)
+            const feeCollectionAccount = await getAssociatedTokenAddress(quoteMint, marketplaceAdmin)
 
             const transaction = await program.methods
                 .executeTrade(new anchor.BN(tradeAmount))
@@ -1626,10 +1621,16 @@ export function useMarketplace() {
                     buyOrderEscrow: buyOrderEscrowPda,
                     sellOrderEscrow: sellOrderEscrowPda,
                     buyerYieldTokenAccount,
-                    buyerUnderlyingTokenAccount,
-                    sellerUnderlyingTokenAccount,
+                    buyerQuoteTokenAccount,
+                    sellerQuoteTokenAccount,
                     feeCollectionAccount,
+                    yieldTokenMint,
+                    quoteMint,
+                    minuteCandles: getPDAs.getCandleSeriesPda(marketplacePda, 60)[0],
+                    hourCandles: getPDAs.getCandleSeriesPda(marketplacePda, 3600)[0],
+                    dayCandles: getPDAs.getCandleSeriesPda(marketplacePda, 86400)[0],
                     tokenProgram: TOKEN_PROGRAM_ID,
+                    yieldTokenProgram: TOKEN_PROGRAM_ID,
                 })
                 .transaction()
 
@@ -1651,7 +1652,7 @@ export function useMarketplace() {
     })
 
     // Fonction utilitaire pour récupérer un marketplace spécifique
-    const getMarketplaceByStrategy = async (strategyId: number) => {
+    const getMarketplaceByStrategy = async (strategyId: number, quoteMint?: PublicKey) => {
         if (!connection) throw new Error('Connection not available')
 
         try {
@@ -1661,8 +1662,17 @@ export function useMarketplace() {
             const [strategyPda] = getPDAs.getStrategyPda(strategyId)
             console.log('Strategy PDA:', strategyPda.toString())
 
-            // Calculer la PDA du marketplace
-            const [marketplacePda] = getPDAs.getMarketplacePda(strategyPda)
+            // Calculer la PDA du marketplace, par défaut coté dans l'underlying token de la stratégie
+            let quoteToken = quoteMint
+            if (!quoteToken) {
+                const strategyAccount = await connection.getAccountInfo(strategyPda)
+                if (!strategyAccount) {
+                    console.log('❌ No strategy account found at this PDA')
+                    return null
+                }
+                quoteToken = new PublicKey(strategyAccount.data.subarray(40, 72))
+            }
+            const [marketplacePda] = getPDAs.getMarketplacePda(strategyPda, quoteToken)
             console.log('Expected marketplace PDA:', marketplacePda.toString())
 
             // Récupérer directement le compte marketplace
@@ -1689,7 +1699,7 @@ export function useMarketplace() {
             offset += 32
             const yieldTokenMint = new PublicKey(data.subarray(offset, offset + 32))
             offset += 32
-            const underlyingTokenMint = new PublicKey(data.subarray(offset, offset + 32))
+            const quoteMint = new PublicKey(data.subarray(offset, offset + 32))
             offset += 32
             const totalVolume = Number(data.readBigUInt64LE(offset))
             offset += 8
@@ -1710,8 +1720,9 @@ export function useMarketplace() {
             const marketplaceData = {
                 admin,
                 strategy,
+                pubkey: marketplacePda,
                 yieldTokenMint,
-                underlyingTokenMint,
+                quoteMint,
                 totalVolume,
                 totalTrades,
                 bestBidPrice,